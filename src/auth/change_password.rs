@@ -0,0 +1,53 @@
+use axum::{extract::State, http::HeaderMap};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{extractor::AuthUser, password::verify_password, utils::device_label_from_headers},
+    error::{ApiError, ErrorResponse},
+    extract::Json,
+    server::AppState,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/change_password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed, all previous sessions revoked. Returns a new session token", body = String),
+        (status = 401, description = "Invalid API key or old password", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn change_password(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    headers: HeaderMap,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<String, ApiError> {
+    let user = app_state.db.get_user(auth.user_id).await?;
+    if !verify_password(&payload.old_password, &user.password) {
+        return Err(ApiError::Unauthorized("Invalid credentials".to_string()));
+    }
+
+    app_state
+        .db
+        .update_user_password(auth.user_id, &payload.new_password)
+        .await?;
+    let device_label = device_label_from_headers(&headers);
+    let token = app_state
+        .db
+        .create_api_token(
+            auth.user_id,
+            device_label.as_deref(),
+            app_state.config.token_ttl(),
+        )
+        .await?;
+    Ok(token)
+}
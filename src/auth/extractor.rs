@@ -0,0 +1,38 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use crate::{auth::utils::extract_token_from_headers, error::ApiError, server::AppState};
+
+/// The authenticated caller of a protected route.
+///
+/// Accepts the session token either in the legacy `todo_apikey` header or as
+/// `Authorization: Bearer <token>`, and rejects the request with a 401 JSON
+/// error when it is missing, unknown or expired.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: u32,
+    pub token: String,
+}
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app_state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = extract_token_from_headers(&parts.headers)
+            .map_err(ApiError::Unauthorized)?
+            .to_string();
+        let user_id = match app_state.db.get_user_id_by_token(&token).await {
+            Ok(user_id) => user_id,
+            Err(sqlx::Error::RowNotFound) => {
+                return Err(ApiError::Unauthorized(
+                    "Invalid or expired API key".to_string(),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        tracing::Span::current().record("user_id", user_id);
+        Ok(AuthUser { user_id, token })
+    }
+}
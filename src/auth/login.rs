@@ -1,31 +1,79 @@
-use axum::{extract::State, response::Response, Json};
-use base64::{engine::general_purpose, Engine as _};
-use sha2::{Digest, Sha256};
+use axum::{extract::State, http::HeaderMap};
+use serde::Deserialize;
+use utoipa::ToSchema;
 
-use crate::{models::user::User, server::AppState};
+use crate::{
+    auth::{
+        password::{is_legacy_password, verify_legacy_password, verify_password, DUMMY_HASH},
+        utils::device_label_from_headers,
+    },
+    error::{ApiError, ErrorResponse},
+    extract::Json,
+    server::AppState,
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+    /// Human readable name of the device, shown in the session list.
+    /// Defaults to the `User-Agent` header.
+    #[serde(default)]
+    pub device_label: Option<String>,
+}
 
 #[utoipa::path(
     post,
     path = "/login",
-    request_body = User,
+    request_body = LoginRequest,
     responses(
-        (status = 200, description = "User found"),
-        (status = 404, description = "User not found")
+        (status = 200, description = "Logged in, returns a new session token", body = String),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse)
     )
 )]
-pub async fn login(State(app_state): State<AppState>, Json(user): Json<User>) -> Response<String> {
-    // Generate token the same way as in register
-    let mut hasher = Sha256::new();
-    hasher.update(user.email.as_bytes());
-    hasher.update(user.password.as_bytes());
-    let result = hasher.finalize();
-    let token = general_purpose::STANDARD.encode(result);
+pub async fn login(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(credentials): Json<LoginRequest>,
+) -> Result<String, ApiError> {
+    let invalid = || ApiError::Unauthorized("Invalid credentials".to_string());
 
-    let user_id = app_state.db.get_user_id_by_token(&token).await;
-    // Check if token exists in api_tokens
-    if user_id.is_ok() {
-        Response::new(token)
-    } else {
-        Response::new("Invalid credentials".to_string())
+    let user = match app_state.db.get_user_by_email(&credentials.email).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => {
+            // Spend as long as a wrong password would, so that response times
+            // do not tell which emails have an account.
+            verify_password(&credentials.password, DUMMY_HASH);
+            return Err(invalid());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let user_id = user.id.ok_or_else(invalid)?;
+
+    if is_legacy_password(&user.password) {
+        // Plaintext password from before hashing was introduced: accept it once,
+        // then store a proper hash and drop the old deterministic tokens.
+        if !verify_legacy_password(&credentials.password, &user.password) {
+            return Err(invalid());
+        }
+        app_state
+            .db
+            .update_user_password(user_id, &credentials.password)
+            .await?;
+    } else if !verify_password(&credentials.password, &user.password) {
+        return Err(invalid());
     }
+
+    let device_label = credentials
+        .device_label
+        .or_else(|| device_label_from_headers(&headers));
+    let token = app_state
+        .db
+        .create_api_token(
+            user_id,
+            device_label.as_deref(),
+            app_state.config.token_ttl(),
+        )
+        .await?;
+    Ok(token)
 }
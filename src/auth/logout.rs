@@ -0,0 +1,42 @@
+use axum::extract::State;
+
+use crate::{
+    auth::extractor::AuthUser,
+    error::{ApiError, ErrorResponse},
+    extract::Json,
+    server::AppState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/logout",
+    responses(
+        (status = 200, description = "Current session revoked", body = bool),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn logout(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<bool>, ApiError> {
+    app_state.db.delete_api_token(&auth.token).await?;
+    Ok(Json(true))
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout_all",
+    responses(
+        (status = 200, description = "Every session of the user revoked", body = bool),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn logout_all(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<bool>, ApiError> {
+    app_state.db.delete_user_tokens(auth.user_id).await?;
+    Ok(Json(true))
+}
@@ -1,4 +1,7 @@
-use axum::{routing::post, Router};
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
 use utoipa::OpenApi;
 
 pub mod login;
@@ -8,16 +11,47 @@ use login::login;
 pub mod register;
 use register::__path_register;
 use register::register;
+
+pub mod change_password;
+use change_password::__path_change_password;
+use change_password::change_password;
+
+pub mod logout;
+use logout::__path_logout;
+use logout::__path_logout_all;
+use logout::{logout, logout_all};
+
+pub mod sessions;
+use sessions::__path_get_sessions;
+use sessions::__path_revoke_session;
+use sessions::{get_sessions, revoke_session};
+
+pub mod extractor;
+pub mod password;
+pub mod policy;
 pub mod utils;
 use crate::server::AppState;
 
 #[derive(OpenApi)]
-#[openapi(paths(register, login))]
+#[openapi(paths(
+    register,
+    login,
+    change_password,
+    logout,
+    logout_all,
+    get_sessions,
+    revoke_session
+))]
 pub struct AuthApi;
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
         .route("/register", post(register))
         .route("/login", post(login))
+        .route("/change_password", post(change_password))
+        .route("/logout", post(logout))
+        .route("/logout_all", post(logout_all))
+        .route("/sessions", get(get_sessions))
+        .route("/sessions/{id}", delete(revoke_session))
         .with_state(app_state)
 }
@@ -0,0 +1,74 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use subtle::ConstantTimeEq;
+
+/// Hash of a password no account has. Logins for unknown emails are checked
+/// against it so that they take as long as logins with a wrong password.
+pub const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$7mMWiKhhfAoBf4nuJwPpLQ$g1r4rvkmGJbcnFNB6o/HAgFZAw8ebGLqJfICToZH8Iw";
+
+/// Hashes a plaintext password with argon2id and a random salt, returning a PHC string.
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Checks a plaintext password against a stored PHC hash.
+pub fn verify_password(password: &str, stored_hash: &str) -> bool {
+    match PasswordHash::new(stored_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Databases created before password hashing store the password as plaintext.
+/// Those rows are recognised by not being a PHC string and get rehashed on login.
+pub fn is_legacy_password(stored: &str) -> bool {
+    PasswordHash::new(stored).is_err()
+}
+
+/// Compares a password with a legacy plaintext one in constant time.
+pub fn verify_legacy_password(password: &str, stored: &str) -> bool {
+    password.as_bytes().ct_eq(stored.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_password("secret").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("secret", &hash));
+        assert!(!verify_password("wrong", &hash));
+        assert!(!is_legacy_password(&hash));
+    }
+
+    #[test]
+    fn test_hash_is_salted() {
+        let first = hash_password("secret").unwrap();
+        let second = hash_password("secret").unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_legacy_password() {
+        assert!(is_legacy_password("password123"));
+        assert!(!verify_password("password123", "password123"));
+        assert!(verify_legacy_password("password123", "password123"));
+        assert!(!verify_legacy_password("password124", "password123"));
+        assert!(!verify_legacy_password("password", "password123"));
+    }
+
+    #[test]
+    fn test_dummy_hash_is_a_real_hash() {
+        assert!(!is_legacy_password(DUMMY_HASH));
+        assert!(!verify_password("", DUMMY_HASH));
+    }
+}
@@ -0,0 +1,732 @@
+//! Authorization rules shared by every group-scoped endpoint.
+//!
+//! Handlers authenticate the caller with [`AuthUser`](super::extractor::AuthUser)
+//! and then call these checks before touching group data, so that a missing group
+//! is always reported as 404 and a group the caller cannot access as 403.
+
+use crate::{
+    db::Database,
+    error::ApiError,
+    models::{
+        expenses::{Expense, Transaction},
+        group::{Group, MemberRemoval},
+        guest::{is_guest_email, Guest},
+        invitation::JoinOutcome,
+        role::{GroupRole, Permission},
+    },
+};
+
+/// Loads a group, or 404 if it does not exist.
+pub async fn find_group(db: &Database, group_id: u32) -> Result<Group, ApiError> {
+    match db.get_group(group_id).await {
+        Ok(group) => Ok(group),
+        Err(sqlx::Error::RowNotFound) => {
+            Err(ApiError::NotFound(format!("Group {} not found", group_id)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Loads the unclaimed guest a claim token belongs to, or 404. 409 if the
+/// group of the guest is archived, as claiming it would change its balances.
+pub async fn find_guest(db: &Database, claim_token: &str) -> Result<Guest, ApiError> {
+    let guest = match db.get_guest_by_claim_token(claim_token).await {
+        Ok(guest) => guest,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(ApiError::NotFound(
+                "Unknown or already claimed guest".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    ensure_not_archived(&find_group(db, guest.group_id).await?)?;
+    Ok(guest)
+}
+
+/// Returns the members of `group_id`, or 404 if the group does not exist.
+async fn group_members(db: &Database, group_id: u32) -> Result<Vec<u32>, ApiError> {
+    find_group(db, group_id).await?;
+    Ok(db.get_group_members(group_id).await?)
+}
+
+/// Ensures the caller is a member of the group.
+pub async fn ensure_group_member(
+    db: &Database,
+    group_id: u32,
+    user_id: u32,
+) -> Result<(), ApiError> {
+    let members = group_members(db, group_id).await?;
+    if !members.contains(&user_id) {
+        return Err(ApiError::Forbidden(format!(
+            "You are not a member of group {}",
+            group_id
+        )));
+    }
+    Ok(())
+}
+
+/// Ensures the caller is a member of the group whose role allows `permission`
+/// and that the group is not archived, and returns the role.
+pub async fn ensure_permission(
+    db: &Database,
+    group_id: u32,
+    user_id: u32,
+    permission: Permission,
+) -> Result<GroupRole, ApiError> {
+    let (group, role) = member_role(db, group_id, user_id, permission).await?;
+    ensure_not_archived(&group)?;
+    Ok(role)
+}
+
+/// Like [`ensure_permission`], for reading what only some roles may see; the
+/// group may be archived.
+pub async fn ensure_role(
+    db: &Database,
+    group_id: u32,
+    user_id: u32,
+    permission: Permission,
+) -> Result<GroupRole, ApiError> {
+    let (_, role) = member_role(db, group_id, user_id, permission).await?;
+    Ok(role)
+}
+
+/// Loads the group and the caller's role in it, or 403 unless the role allows
+/// `permission`.
+async fn member_role(
+    db: &Database,
+    group_id: u32,
+    user_id: u32,
+    permission: Permission,
+) -> Result<(Group, GroupRole), ApiError> {
+    let group = find_group(db, group_id).await?;
+    let role = match db.get_member_role(group_id, user_id).await {
+        Ok(role) => role,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(ApiError::Forbidden(format!(
+                "You are not a member of group {}",
+                group_id
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !role.allows(permission) {
+        return Err(ApiError::Forbidden(format!(
+            "As {} of group {} you cannot {}",
+            role, group_id, permission
+        )));
+    }
+    Ok((group, role))
+}
+
+/// Ensures nothing changes in an archived group until it is reopened.
+pub fn ensure_not_archived(group: &Group) -> Result<(), ApiError> {
+    if group.archived_at.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "Group {} is archived",
+            group.id.unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// Lets the caller into the group with an invitation code, or files a join
+/// request if the invitation requires approval. 404 if the group does not exist,
+/// 409 if it is archived or the caller already is a member and 403 if the code
+/// is not valid for it.
+pub async fn join_with_code(
+    db: &Database,
+    group_id: u32,
+    code: &str,
+    user_id: u32,
+) -> Result<JoinOutcome, ApiError> {
+    let group = find_group(db, group_id).await?;
+    ensure_not_archived(&group)?;
+    let members = db.get_group_members(group_id).await?;
+    if members.contains(&user_id) {
+        return Err(ApiError::Conflict(format!(
+            "You are already a member of group {}",
+            group_id
+        )));
+    }
+    match db.redeem_invitation(group_id, code, user_id).await {
+        Ok(outcome) => Ok(outcome),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::Forbidden(
+            "Invalid or expired invitation code".to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Ensures the caller owns the group and returns it.
+pub async fn ensure_group_owner(
+    db: &Database,
+    group_id: u32,
+    user_id: u32,
+) -> Result<Group, ApiError> {
+    let group = find_group(db, group_id).await?;
+    if group.owner_id != user_id {
+        return Err(ApiError::Forbidden(format!(
+            "Only the owner of group {} can do this",
+            group_id
+        )));
+    }
+    Ok(group)
+}
+
+/// Removes `user_id` from the group: the owner and admins may remove members
+/// (only the owner may remove admins), and any member may leave. A member whose
+/// balance is not zero can only go if the owner or an admin hands their
+/// expenses and payments over to `reassign_to`; the owner has to transfer
+/// ownership first, and members the settlement constraint names have to be
+/// taken out of it.
+pub async fn remove_member(
+    db: &Database,
+    group_id: u32,
+    caller_id: u32,
+    user_id: u32,
+    reassign_to: Option<u32>,
+) -> Result<(), ApiError> {
+    let group = find_group(db, group_id).await?;
+    let members = db.get_group_members(group_id).await?;
+    if !members.contains(&caller_id) {
+        return Err(ApiError::Forbidden(format!(
+            "You are not a member of group {}",
+            group_id
+        )));
+    }
+    if caller_id != user_id || reassign_to.is_some() {
+        ensure_permission(db, group_id, caller_id, Permission::ManageMembers).await?;
+    }
+    if !members.contains(&user_id) {
+        return Err(ApiError::NotFound(format!(
+            "User {} is not a member of group {}",
+            user_id, group_id
+        )));
+    }
+    if user_id == group.owner_id {
+        return Err(ApiError::Conflict(
+            "The owner has to transfer ownership before leaving".to_string(),
+        ));
+    }
+    if caller_id != user_id
+        && caller_id != group.owner_id
+        && db.get_member_role(group_id, user_id).await? == GroupRole::Admin
+    {
+        return Err(ApiError::Forbidden(
+            "Only the owner can remove an admin".to_string(),
+        ));
+    }
+    if let Some(to) = reassign_to {
+        if to == user_id || !members.contains(&to) {
+            return Err(ApiError::unprocessable(format!(
+                "Expenses can only be reassigned to another member of group {}",
+                group_id
+            )));
+        }
+    }
+    if let Some(constraint) = db.get_settlement_constraint(group_id).await? {
+        if constraint.user_ids().contains(&user_id) {
+            return Err(ApiError::Conflict(format!(
+                "User {} is part of the settlement constraint of the group",
+                user_id
+            )));
+        }
+    }
+    match db
+        .remove_group_member(group_id, user_id, reassign_to)
+        .await?
+    {
+        MemberRemoval::Removed => Ok(()),
+        MemberRemoval::OutstandingBalance(balance) => Err(ApiError::Conflict(format!(
+            "User {} still has a balance of {}; settle it or reassign it first",
+            user_id, balance
+        ))),
+    }
+}
+
+/// Makes another member the owner of the group; only the owner can, and not
+/// once the group is archived. Guests cannot own a group since nobody can log
+/// in as them.
+pub async fn transfer_ownership(
+    db: &Database,
+    group_id: u32,
+    caller_id: u32,
+    new_owner_id: u32,
+) -> Result<Group, ApiError> {
+    ensure_not_archived(&ensure_group_owner(db, group_id, caller_id).await?)?;
+    let members = db.get_group_members(group_id).await?;
+    if !members.contains(&new_owner_id) {
+        return Err(ApiError::unprocessable(format!(
+            "User {} is not a member of group {}",
+            new_owner_id, group_id
+        )));
+    }
+    if is_guest_email(&db.get_user(new_owner_id).await?.email) {
+        return Err(ApiError::unprocessable("A guest cannot own a group"));
+    }
+    db.update_group_owner(group_id, new_owner_id).await?;
+    Ok(db.get_group(group_id).await?)
+}
+
+/// Gives a member other than the owner a new role; only the owner can, and not
+/// once the group is archived. The owner role itself only changes hands with
+/// [`transfer_ownership`].
+pub async fn change_member_role(
+    db: &Database,
+    group_id: u32,
+    caller_id: u32,
+    user_id: u32,
+    role: GroupRole,
+) -> Result<(), ApiError> {
+    ensure_not_archived(&ensure_group_owner(db, group_id, caller_id).await?)?;
+    if role == GroupRole::Owner || user_id == caller_id {
+        return Err(ApiError::unprocessable(
+            "Transfer ownership to change who owns the group",
+        ));
+    }
+    match db.set_member_role(group_id, user_id, role).await {
+        Ok(()) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::NotFound(format!(
+            "User {} is not a member of group {}",
+            user_id, group_id
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Ensures the caller, the payer and every participant of an expense belong to
+/// the group, and that the caller's role lets them add it: only owners and
+/// admins can add expenses someone else paid.
+pub async fn ensure_expense_members(
+    db: &Database,
+    group_id: u32,
+    caller_id: u32,
+    payer_id: u32,
+    participants_ids: &[u32],
+) -> Result<(), ApiError> {
+    let members = group_members(db, group_id).await?;
+    if !members.contains(&caller_id) {
+        return Err(ApiError::Forbidden(format!(
+            "You are not a member of group {}",
+            group_id
+        )));
+    }
+    if !members.contains(&payer_id) {
+        return Err(ApiError::Forbidden(format!(
+            "Payer {} is not a member of group {}",
+            payer_id, group_id
+        )));
+    }
+    let outsiders: Vec<u32> = participants_ids
+        .iter()
+        .filter(|id| !members.contains(id))
+        .copied()
+        .collect();
+    if !outsiders.is_empty() {
+        return Err(ApiError::Forbidden(format!(
+            "Participants {:?} are not members of group {}",
+            outsiders, group_id
+        )));
+    }
+    let permission = if payer_id == caller_id {
+        Permission::AddExpenses
+    } else {
+        Permission::AddExpensesForOthers
+    };
+    ensure_permission(db, group_id, caller_id, permission).await?;
+    Ok(())
+}
+
+/// Ensures the caller may edit or delete an expense: its payer can unless they
+/// are a viewer, anyone else needs a role that may edit the expenses of others.
+pub async fn ensure_expense_editor(
+    db: &Database,
+    expense: &Expense,
+    caller_id: u32,
+) -> Result<(), ApiError> {
+    let permission = if expense.payer_id == caller_id {
+        Permission::AddExpenses
+    } else {
+        Permission::EditOthersExpenses
+    };
+    ensure_permission(db, expense.group_id, caller_id, permission).await?;
+    Ok(())
+}
+
+/// Ensures the caller is the receiver of a transaction, the only one who can
+/// confirm that its money arrived.
+pub fn ensure_transaction_receiver(
+    transaction: &Transaction,
+    caller_id: u32,
+) -> Result<(), ApiError> {
+    if transaction.receiver_id != caller_id {
+        return Err(ApiError::Forbidden(
+            "Only the receiver can confirm this payment".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Ensures a user only reads data that belongs to them.
+pub fn ensure_self(caller_id: u32, user_id: u32) -> Result<(), ApiError> {
+    if caller_id != user_id {
+        return Err(ApiError::Forbidden(
+            "You can only access your own data".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        db::tests::IN_MEMORY_DB,
+        models::{
+            expenses::{SplitType, Status},
+            money::Money,
+            user::User,
+        },
+    };
+
+    async fn setup_test_env() -> (Database, u32, u32, u32) {
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let member = db
+            .create_user(&User::new("Member", "member@example.com", "password"))
+            .await
+            .unwrap();
+        let outsider = db
+            .create_user(&User::new("Outsider", "outsider@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            member,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, member).await.unwrap();
+        (db, group_id, member, outsider)
+    }
+
+    #[tokio::test]
+    async fn test_ensure_group_member() {
+        let (db, group_id, member, outsider) = setup_test_env().await;
+
+        assert!(ensure_group_member(&db, group_id, member).await.is_ok());
+        let err = ensure_group_member(&db, group_id, outsider)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = ensure_group_member(&db, 999, member).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_group_owner() {
+        let (db, group_id, member, outsider) = setup_test_env().await;
+
+        assert!(ensure_group_owner(&db, group_id, member).await.is_ok());
+        let err = ensure_group_owner(&db, group_id, outsider)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = ensure_group_owner(&db, 999, member).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_expense_members() {
+        let (db, group_id, member, outsider) = setup_test_env().await;
+
+        assert!(
+            ensure_expense_members(&db, group_id, member, member, &[member])
+                .await
+                .is_ok()
+        );
+        let err = ensure_expense_members(&db, group_id, member, outsider, &[member])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = ensure_expense_members(&db, group_id, member, member, &[member, outsider])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = ensure_expense_members(&db, group_id, outsider, member, &[member])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_expense_editor() {
+        let (db, group_id, owner, other) = setup_test_env().await;
+        let third = db
+            .create_user(&User::new("Third", "third@example.com", "password"))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, other).await.unwrap();
+        db.add_user_to_group(group_id, third).await.unwrap();
+        let expense = Expense {
+            id: None,
+            description: "Dinner".to_string(),
+            amount: Money::new(1000, "EUR"),
+            payer_id: other,
+            group_id,
+            date: "2024-01-01".to_string(),
+            split_type: SplitType::Equal,
+        };
+
+        assert!(ensure_expense_editor(&db, &expense, other).await.is_ok());
+        assert!(ensure_expense_editor(&db, &expense, owner).await.is_ok());
+        let err = ensure_expense_editor(&db, &expense, third)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        db.set_member_role(group_id, third, GroupRole::Admin)
+            .await
+            .unwrap();
+        assert!(ensure_expense_editor(&db, &expense, third).await.is_ok());
+        // A payer who became a viewer cannot touch the expense any more
+        db.set_member_role(group_id, other, GroupRole::Viewer)
+            .await
+            .unwrap();
+        let err = ensure_expense_editor(&db, &expense, other)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_remove_member() {
+        let (db, group_id, owner, other) = setup_test_env().await;
+        let third = db
+            .create_user(&User::new("Third", "third@example.com", "password"))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, other).await.unwrap();
+        db.add_user_to_group(group_id, third).await.unwrap();
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Dinner".to_string(),
+                amount: Money::new(1000, "EUR"),
+                payer_id: owner,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type: SplitType::Equal,
+            })
+            .await
+            .unwrap();
+        db.add_participants_to_expense(expense_id, vec![owner, other])
+            .await
+            .unwrap();
+
+        let status = |result: Result<(), ApiError>| result.unwrap_err().status();
+        // Members can only remove themselves, and the owner cannot leave
+        assert_eq!(
+            status(remove_member(&db, group_id, third, other, None).await),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(remove_member(&db, group_id, owner, owner, None).await),
+            StatusCode::CONFLICT
+        );
+        // Other still owes 5.00
+        assert_eq!(
+            status(remove_member(&db, group_id, other, other, None).await),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            status(remove_member(&db, group_id, other, other, Some(third)).await),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(remove_member(&db, group_id, owner, other, Some(other)).await),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        assert!(remove_member(&db, group_id, third, third, None)
+            .await
+            .is_ok());
+        assert_eq!(
+            status(remove_member(&db, group_id, owner, third, None).await),
+            StatusCode::NOT_FOUND
+        );
+        assert!(remove_member(&db, group_id, owner, other, Some(owner))
+            .await
+            .is_ok());
+        assert_eq!(db.get_group_members(group_id).await.unwrap(), vec![owner]);
+        let balances = db.get_group_balances(group_id).await.unwrap();
+        assert!(balances.iter().all(|b| b.net_balance.minor_units == 0));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_ownership() {
+        let (db, group_id, owner, other) = setup_test_env().await;
+        let err = transfer_ownership(&db, group_id, owner, other)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let guest = db.create_guest(group_id, "Guest", owner).await.unwrap();
+        let err = transfer_ownership(&db, group_id, owner, guest.guest.user_id)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        db.add_user_to_group(group_id, other).await.unwrap();
+        let group = transfer_ownership(&db, group_id, owner, other)
+            .await
+            .unwrap();
+        assert_eq!(group.owner_id, other);
+        let err = transfer_ownership(&db, group_id, owner, owner)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        // The previous owner is a regular member now and may leave
+        assert!(remove_member(&db, group_id, owner, owner, None)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ensure_permission() {
+        let (db, group_id, owner, other) = setup_test_env().await;
+        db.add_user_to_group(group_id, other).await.unwrap();
+
+        for permission in Permission::ALL {
+            assert_eq!(
+                ensure_permission(&db, group_id, owner, permission)
+                    .await
+                    .unwrap(),
+                GroupRole::Owner
+            );
+        }
+        assert!(
+            ensure_permission(&db, group_id, other, Permission::AddExpenses)
+                .await
+                .is_ok()
+        );
+        let err = ensure_permission(&db, group_id, other, Permission::ChangeSettings)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = ensure_permission(&db, 999, other, Permission::AddExpenses)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+
+        change_member_role(&db, group_id, owner, other, GroupRole::Viewer)
+            .await
+            .unwrap();
+        let err = ensure_expense_members(&db, group_id, other, other, &[other])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = change_member_role(&db, group_id, owner, other, GroupRole::Owner)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let err = change_member_role(&db, group_id, other, owner, GroupRole::Viewer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_members_add_only_their_own_expenses() {
+        let (db, group_id, owner, other) = setup_test_env().await;
+        db.add_user_to_group(group_id, other).await.unwrap();
+
+        assert!(
+            ensure_expense_members(&db, group_id, owner, other, &[owner, other])
+                .await
+                .is_ok()
+        );
+        let err = ensure_expense_members(&db, group_id, other, owner, &[owner, other])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+
+        change_member_role(&db, group_id, owner, other, GroupRole::Admin)
+            .await
+            .unwrap();
+        assert!(
+            ensure_expense_members(&db, group_id, other, owner, &[owner, other])
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_archived_group_is_read_only() {
+        let (db, group_id, owner, other) = setup_test_env().await;
+        db.add_user_to_group(group_id, other).await.unwrap();
+        db.set_group_archived(group_id, Some(Utc::now()))
+            .await
+            .unwrap();
+
+        assert!(ensure_group_member(&db, group_id, owner).await.is_ok());
+        assert!(ensure_role(&db, group_id, owner, Permission::ManageMembers)
+            .await
+            .is_ok());
+        let err = ensure_permission(&db, group_id, owner, Permission::AddExpenses)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        let err = change_member_role(&db, group_id, owner, other, GroupRole::Admin)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        let err = transfer_ownership(&db, group_id, owner, other)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        assert_eq!(db.get_group(group_id).await.unwrap().owner_id, owner);
+        let third = db
+            .create_user(&User::new("Third", "third@example.com", "password"))
+            .await
+            .unwrap();
+        let err = join_with_code(&db, group_id, "code", third)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_ensure_transaction_receiver() {
+        let transaction = Transaction {
+            id: Some(1),
+            payer_id: 1,
+            receiver_id: 2,
+            amount: Money::new(1000, "EUR"),
+            date: "2024-01-01".to_string(),
+            status: Status::Paid,
+            group_id: 1,
+            plan_id: None,
+        };
+        assert!(ensure_transaction_receiver(&transaction, 2).is_ok());
+        assert_eq!(
+            ensure_transaction_receiver(&transaction, 1)
+                .unwrap_err()
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn test_ensure_self() {
+        assert!(ensure_self(1, 1).is_ok());
+        assert_eq!(
+            ensure_self(1, 2).unwrap_err().status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+}
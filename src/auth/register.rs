@@ -1,31 +1,63 @@
-use axum::{extract::State, response::Response, Json};
-use base64::{engine::general_purpose, Engine as _};
-use sha2::{Digest, Sha256};
+use axum::{extract::State, http::HeaderMap};
+use serde::Deserialize;
+use utoipa::IntoParams;
 
-use crate::{models::user::User, server::AppState};
+use crate::{
+    auth::{policy::find_guest, utils::device_label_from_headers},
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Query},
+    models::{guest::is_guest_email, user::User},
+    server::AppState,
+};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RegisterQuery {
+    /// Claim token of a guest; the new user takes over its groups, expenses
+    /// and transactions.
+    pub claim: Option<String>,
+}
 
 #[utoipa::path(
     post,
     path = "/register",
+    params(RegisterQuery),
     request_body = User,
     responses(
-        (status = 200, description = "User created successfully", body = String)
+        (status = 200, description = "User created successfully, returns a session token", body = String),
+        (status = 404, description = "Unknown or already claimed guest", body = ErrorResponse),
+        (status = 409, description = "Email already registered or group of the guest archived", body = ErrorResponse),
+        (status = 422, description = "Email address reserved for guests", body = ErrorResponse)
     )
 )]
 pub async fn register(
     State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<RegisterQuery>,
     Json(user): Json<User>,
-) -> Response<String> {
-    let mut hasher = Sha256::new();
-    hasher.update(user.email.as_bytes());
-    hasher.update(user.password.as_bytes());
-    let result = hasher.finalize();
-    let token = general_purpose::STANDARD.encode(result);
-
-    match app_state.db.create_user(&user, &token).await {
-        Ok(_) => (),
-        Err(e) => return Response::new(e.to_string()),
+) -> Result<String, ApiError> {
+    if is_guest_email(&user.email) {
+        return Err(ApiError::unprocessable(
+            "This email address is reserved for guests",
+        ));
+    }
+    let guest = match &query.claim {
+        Some(claim_token) => Some(find_guest(&app_state.db, claim_token).await?),
+        None => None,
     };
+    let user_id = app_state
+        .db
+        .register_user(&user, guest.map(|guest| guest.user_id))
+        .await?;
 
-    Response::new(token)
+    let device_label = device_label_from_headers(&headers);
+    let token = app_state
+        .db
+        .create_api_token(
+            user_id,
+            device_label.as_deref(),
+            app_state.config.token_ttl(),
+        )
+        .await?;
+    Ok(token)
 }
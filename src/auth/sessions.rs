@@ -0,0 +1,58 @@
+use axum::extract::State;
+
+use crate::{
+    auth::extractor::AuthUser,
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    models::user::Session,
+    server::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    responses(
+        (status = 200, description = "Active sessions of the user", body = Vec<Session>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_sessions(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<Vec<Session>>, ApiError> {
+    let sessions = app_state
+        .db
+        .get_user_sessions(auth.user_id, &auth.token)
+        .await?;
+    Ok(Json(sessions))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/sessions/{id}",
+    params(
+        ("id" = u32, Path, description = "Session ID to revoke")
+    ),
+    responses(
+        (status = 200, description = "Session revoked", body = bool),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn revoke_session(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(session_id): Path<u32>,
+) -> Result<Json<bool>, ApiError> {
+    app_state
+        .db
+        .delete_user_session(auth.user_id, session_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => ApiError::NotFound("Session not found".to_string()),
+            e => e.into(),
+        })?;
+    Ok(Json(true))
+}
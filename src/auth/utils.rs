@@ -1,16 +1,88 @@
-use axum::http::HeaderMap;
+use axum::http::{header, HeaderMap};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::TimeDelta;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
 
-use crate::server::AppState;
+/// How long a session token stays valid after login, unless configured otherwise.
+pub const TOKEN_TTL: TimeDelta = TimeDelta::days(30);
 
-pub async fn extract_user_id_from_headers(
-    headers: &HeaderMap,
-    app_state: &AppState,
-) -> Result<u32, String> {
-    let token = headers
+/// Reads the session token from `Authorization: Bearer` or the legacy `todo_apikey` header.
+pub fn extract_token_from_headers(headers: &HeaderMap) -> Result<&str, String> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        let value = value
+            .to_str()
+            .map_err(|_| "Invalid Authorization header format".to_string())?;
+        return value
+            .strip_prefix("Bearer ")
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| "Authorization header must use the Bearer scheme".to_string());
+    }
+    headers
         .get("todo_apikey")
         .ok_or_else(|| "Missing API key header".to_string())?
         .to_str()
-        .map_err(|_| "Invalid API key header format".to_string())?;
-    let user_id = app_state.db.get_user_id_by_token(token).await.unwrap();
-    Ok(user_id)
+        .map_err(|_| "Invalid API key header format".to_string())
+}
+
+/// Label for a new session when the client does not provide one.
+pub fn device_label_from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Creates a new random session token handed out to the client.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Only the SHA-256 digest of a session token is stored, so a database leak
+/// does not hand out usable tokens.
+pub fn hash_token(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    general_purpose::STANDARD.encode(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_token_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(extract_token_from_headers(&headers).is_err());
+
+        headers.insert("todo_apikey", HeaderValue::from_static("legacy"));
+        assert_eq!(extract_token_from_headers(&headers).unwrap(), "legacy");
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer abc"),
+        );
+        assert_eq!(extract_token_from_headers(&headers).unwrap(), "abc");
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert!(extract_token_from_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn test_generate_token_is_random() {
+        let first = generate_token();
+        let second = generate_token();
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 43);
+    }
+
+    #[test]
+    fn test_hash_token_is_stable() {
+        assert_eq!(hash_token("token"), hash_token("token"));
+        assert_ne!(hash_token("token"), "token");
+    }
 }
@@ -0,0 +1,439 @@
+use std::{
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use axum::http::HeaderValue;
+use chrono::TimeDelta;
+use clap::{Args, ValueEnum};
+use serde::Deserialize;
+
+use crate::{auth::utils::TOKEN_TTL, db::DEFAULT_MAX_CONNECTIONS};
+
+/// Config file read at startup if it exists and no other file is named.
+pub const DEFAULT_CONFIG_FILE: &str = "trip_split.toml";
+
+/// Longest `token_ttl_days` accepted; far larger values would push expiry
+/// dates out of the range timestamps can hold.
+pub const MAX_TOKEN_TTL_DAYS: u32 = 3650;
+
+/// Everything the server can be configured with. Each setting is taken from
+/// the first of its command line flag, its environment variable, the config
+/// file and the default that sets it.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the API is served on.
+    pub bind_address: SocketAddr,
+    /// A `postgres://` URL, or a SQLite URL or file path.
+    pub database_url: String,
+    /// Size of the database connection pool.
+    pub max_connections: u32,
+    /// Origins browsers may call the API from; `*` allows any.
+    pub cors_allowed_origins: Vec<String>,
+    /// Days a session token stays valid after login, at most
+    /// [`MAX_TOKEN_TTL_DAYS`].
+    pub token_ttl_days: u32,
+    pub log_level: LogLevel,
+    pub log_format: LogFormat,
+    /// CSV file of exchange rates loaded at startup, if it exists.
+    pub exchange_rates_file: PathBuf,
+    pub features: Features,
+}
+
+/// Parts of the API that can be switched off.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// Serve the Swagger UI at `/swagger-ui` and the OpenAPI document.
+    pub swagger_ui: bool,
+    /// Serve the RPC-style routes superseded by `/api/v1`.
+    pub legacy_routes: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// How log lines are written to standard output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line, for log aggregators.
+    Json,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+        write!(f, "{}", level)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind_address: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            database_url: "sqlite.db".to_string(),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            cors_allowed_origins: vec!["*".to_string()],
+            token_ttl_days: TOKEN_TTL.num_days() as u32,
+            log_level: LogLevel::Info,
+            log_format: LogFormat::Text,
+            exchange_rates_file: PathBuf::from("exchange_rates.csv"),
+            features: Features::default(),
+        }
+    }
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            swagger_ui: true,
+            legacy_routes: true,
+        }
+    }
+}
+
+/// Command line flags and environment variables overriding the config file.
+#[derive(Args, Debug, Default)]
+pub struct ConfigArgs {
+    /// TOML config file [default: trip_split.toml, if it exists]
+    #[arg(long, env = "CONFIG_FILE", global = true)]
+    pub config: Option<PathBuf>,
+    /// Address to serve the API on [default: 0.0.0.0:3000]
+    #[arg(long, env = "BIND_ADDRESS", global = true)]
+    pub bind_address: Option<SocketAddr>,
+    /// postgres:// URL, or SQLite URL or file path [default: sqlite.db]
+    #[arg(long, env = "DATABASE_URL", global = true)]
+    pub database_url: Option<String>,
+    /// Size of the database connection pool [default: 50]
+    #[arg(long, env = "DATABASE_MAX_CONNECTIONS", global = true)]
+    pub max_connections: Option<u32>,
+    /// Comma separated origins allowed by CORS, or * for any [default: *]
+    #[arg(
+        long,
+        env = "CORS_ALLOWED_ORIGINS",
+        value_delimiter = ',',
+        global = true
+    )]
+    pub cors_allowed_origins: Option<Vec<String>>,
+    /// Days a session token stays valid, at most 3650 [default: 30]
+    #[arg(long, env = "TOKEN_TTL_DAYS", global = true)]
+    pub token_ttl_days: Option<u32>,
+    /// Verbosity of the server output [default: info]
+    #[arg(long, env = "LOG_LEVEL", global = true)]
+    pub log_level: Option<LogLevel>,
+    /// Format of the log lines [default: text]
+    #[arg(long, env = "LOG_FORMAT", global = true)]
+    pub log_format: Option<LogFormat>,
+    /// CSV file of exchange rates [default: exchange_rates.csv]
+    #[arg(long, env = "EXCHANGE_RATES_FILE", global = true)]
+    pub exchange_rates_file: Option<PathBuf>,
+    /// Serve the Swagger UI [default: true]
+    #[arg(long, env = "FEATURE_SWAGGER_UI", global = true)]
+    pub swagger_ui: Option<bool>,
+    /// Serve the legacy RPC-style routes [default: true]
+    #[arg(long, env = "FEATURE_LEGACY_ROUTES", global = true)]
+    pub legacy_routes: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+    /// A setting has a value the server cannot run with.
+    Invalid {
+        setting: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => {
+                write!(f, "Cannot read config file {}: {}", path.display(), error)
+            }
+            ConfigError::Parse { path, error } => {
+                write!(f, "Invalid config file {}: {}", path.display(), error)
+            }
+            ConfigError::Invalid { setting, reason } => {
+                write!(f, "Invalid {}: {}", setting, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the config file named by `args`, or [`DEFAULT_CONFIG_FILE`] if it
+    /// exists, applies `args` on top of it and validates the result.
+    pub fn load(args: ConfigArgs) -> Result<Config, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => Config::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|error| ConfigError::Read {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        toml::from_str(&contents).map_err(|error| ConfigError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    fn apply(&mut self, args: ConfigArgs) {
+        let ConfigArgs {
+            config: _,
+            bind_address,
+            database_url,
+            max_connections,
+            cors_allowed_origins,
+            token_ttl_days,
+            log_level,
+            log_format,
+            exchange_rates_file,
+            swagger_ui,
+            legacy_routes,
+        } = args;
+        self.bind_address = bind_address.unwrap_or(self.bind_address);
+        if let Some(url) = database_url {
+            self.database_url = url;
+        }
+        self.max_connections = max_connections.unwrap_or(self.max_connections);
+        if let Some(origins) = cors_allowed_origins {
+            self.cors_allowed_origins = origins;
+        }
+        self.token_ttl_days = token_ttl_days.unwrap_or(self.token_ttl_days);
+        self.log_level = log_level.unwrap_or(self.log_level);
+        self.log_format = log_format.unwrap_or(self.log_format);
+        if let Some(path) = exchange_rates_file {
+            self.exchange_rates_file = path;
+        }
+        self.features.swagger_ui = swagger_ui.unwrap_or(self.features.swagger_ui);
+        self.features.legacy_routes = legacy_routes.unwrap_or(self.features.legacy_routes);
+    }
+
+    /// Checks what the types alone do not, so that a bad setting stops the
+    /// server at startup instead of failing requests later.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |setting, reason: &str| {
+            Err(ConfigError::Invalid {
+                setting,
+                reason: reason.to_string(),
+            })
+        };
+        if self.database_url.trim().is_empty() {
+            return invalid("database_url", "must not be empty");
+        }
+        if self.max_connections == 0 {
+            return invalid("max_connections", "must be at least 1");
+        }
+        if self.token_ttl_days == 0 {
+            return invalid("token_ttl_days", "must be at least 1");
+        }
+        if self.token_ttl_days > MAX_TOKEN_TTL_DAYS {
+            return invalid("token_ttl_days", "must be at most 3650");
+        }
+        if self.cors_allowed_origins.is_empty() {
+            return invalid("cors_allowed_origins", "must list at least one origin");
+        }
+        if self.cors_allowed_origins.len() > 1 && self.allows_any_origin() {
+            return invalid("cors_allowed_origins", "* cannot be combined with origins");
+        }
+        if !self.allows_any_origin() {
+            for origin in &self.cors_allowed_origins {
+                let is_url = origin.starts_with("http://") || origin.starts_with("https://");
+                if !is_url || origin.ends_with('/') || HeaderValue::from_str(origin).is_err() {
+                    return Err(ConfigError::Invalid {
+                        setting: "cors_allowed_origins",
+                        reason: format!("{} is not an origin such as https://example.com", origin),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows_any_origin(&self) -> bool {
+        self.cors_allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    pub fn token_ttl(&self) -> TimeDelta {
+        TimeDelta::days(self.token_ttl_days.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        config: ConfigArgs,
+    }
+
+    fn args(flags: &[&str]) -> ConfigArgs {
+        Cli::try_parse_from(std::iter::once("trip_split").chain(flags.iter().copied()))
+            .unwrap()
+            .config
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = Config::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.bind_address.to_string(), "0.0.0.0:3000");
+        assert_eq!(config.token_ttl(), TOKEN_TTL);
+        assert!(config.allows_any_origin());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let config: Config = toml::from_str(
+            r#"
+            bind_address = "127.0.0.1:8080"
+            database_url = "postgres://localhost/tripsplit"
+            max_connections = 10
+            cors_allowed_origins = ["https://tripsplit.example.com"]
+            log_level = "debug"
+            log_format = "json"
+
+            [features]
+            swagger_ui = false
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.bind_address.port(), 8080);
+        assert_eq!(config.max_connections, 10);
+        assert_eq!(config.log_level, LogLevel::Debug);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert!(!config.allows_any_origin());
+        assert!(!config.features.swagger_ui);
+        // Unset settings keep their defaults
+        assert!(config.features.legacy_routes);
+        assert_eq!(config.token_ttl_days, 30);
+    }
+
+    #[test]
+    fn test_config_file_typos_are_rejected() {
+        assert!(toml::from_str::<Config>("bind_adress = \"127.0.0.1:8080\"").is_err());
+        assert!(toml::from_str::<Config>("log_level = \"loud\"").is_err());
+        assert!(toml::from_str::<Config>("bind_address = \"localhost\"").is_err());
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let mut config: Config =
+            toml::from_str("max_connections = 10\ntoken_ttl_days = 7").unwrap();
+        config.apply(args(&[
+            "--max-connections",
+            "5",
+            "--cors-allowed-origins",
+            "https://a.example.com,https://b.example.com",
+            "--swagger-ui",
+            "false",
+        ]));
+        assert_eq!(config.max_connections, 5);
+        assert_eq!(config.token_ttl_days, 7);
+        assert_eq!(
+            config.cors_allowed_origins,
+            ["https://a.example.com", "https://b.example.com"]
+        );
+        assert!(!config.features.swagger_ui);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_missing_config_file() {
+        let result = Config::load(ConfigArgs {
+            config: Some(PathBuf::from("does_not_exist.toml")),
+            ..ConfigArgs::default()
+        });
+        assert!(matches!(result, Err(ConfigError::Read { .. })));
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let invalid = |config: Config| match config.validate() {
+            Err(ConfigError::Invalid { setting, .. }) => setting,
+            Ok(()) => panic!("{:?} should be invalid", config),
+            Err(e) => panic!("{}", e),
+        };
+        let base = Config::default;
+        assert_eq!(
+            invalid(Config {
+                max_connections: 0,
+                ..base()
+            }),
+            "max_connections"
+        );
+        for token_ttl_days in [0, MAX_TOKEN_TTL_DAYS + 1, u32::MAX] {
+            assert_eq!(
+                invalid(Config {
+                    token_ttl_days,
+                    ..base()
+                }),
+                "token_ttl_days"
+            );
+        }
+        assert_eq!(
+            invalid(Config {
+                database_url: String::new(),
+                ..base()
+            }),
+            "database_url"
+        );
+        for origins in [
+            vec![],
+            vec!["*", "https://example.com"],
+            vec!["example.com"],
+            vec!["https://example.com/"],
+        ] {
+            assert_eq!(
+                invalid(Config {
+                    cors_allowed_origins: origins.into_iter().map(String::from).collect(),
+                    ..base()
+                }),
+                "cors_allowed_origins"
+            );
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+use tracing::instrument;
+
+use crate::{
+    db::{repository::ExchangeRateRepository, SqliteRepository},
+    models::{exchange_rate::ExchangeRate, money::RATE_SCALE},
+};
+
+fn exchange_rate_from_row(row: SqliteRow) -> ExchangeRate {
+    ExchangeRate {
+        id: Some(row.get("id")),
+        group_id: row.get("group_id"),
+        from_currency: row.get("from_currency"),
+        to_currency: row.get("to_currency"),
+        rate_micros: row.get("rate_micros"),
+        effective_date: row.get("effective_date"),
+    }
+}
+
+#[async_trait]
+impl ExchangeRateRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_exchange_rate(&self, rate: &ExchangeRate) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO exchange_rates (group_id, from_currency, to_currency, rate_micros, effective_date) VALUES (?, ?, ?, ?, ?) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(rate.group_id)
+            .bind(rate.from_currency.clone())
+            .bind(rate.to_currency.clone())
+            .bind(rate.rate_micros)
+            .bind(rate.effective_date)
+            .fetch_one(&self.pool)
+            .await?;
+        let id = row.get("id");
+        Ok(id)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_exchange_rates(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<ExchangeRate>, sqlx::Error> {
+        let query =
+            "SELECT * FROM exchange_rates WHERE group_id = ? ORDER BY effective_date DESC, id DESC";
+        let rows = sqlx::query(query)
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(exchange_rate_from_row).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn replace_global_exchange_rates(
+        &self,
+        rates: &[ExchangeRate],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM exchange_rates WHERE group_id IS NULL")
+            .execute(&mut *tx)
+            .await?;
+        let query = "INSERT INTO exchange_rates (group_id, from_currency, to_currency, rate_micros, effective_date) VALUES (NULL, ?, ?, ?, ?)";
+        for rate in rates {
+            sqlx::query(query)
+                .bind(rate.from_currency.clone())
+                .bind(rate.to_currency.clone())
+                .bind(rate.rate_micros)
+                .bind(rate.effective_date)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_exchange_rate(
+        &self,
+        group_id: u32,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        Self::fetch_exchange_rate(&mut *self.pool.acquire().await?, group_id, from, to, date).await
+    }
+}
+
+impl SqliteRepository {
+    /// `find_exchange_rate` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_exchange_rate(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        if from == to {
+            return Ok(Some(RATE_SCALE));
+        }
+        let query = "SELECT * FROM exchange_rates
+            WHERE (group_id = ? OR group_id IS NULL)
+              AND ((from_currency = ? AND to_currency = ?) OR (from_currency = ? AND to_currency = ?))
+              AND effective_date <= ?
+            ORDER BY group_id IS NULL, effective_date DESC, from_currency = ? DESC, id DESC
+            LIMIT 1";
+        let rate = sqlx::query(query)
+            .bind(group_id)
+            .bind(from)
+            .bind(to)
+            .bind(to)
+            .bind(from)
+            .bind(date)
+            .bind(from)
+            .fetch_optional(conn)
+            .await?
+            .map(exchange_rate_from_row);
+        Ok(rate.map(|rate| rate.micros_from(from)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::{
+        db::tests::backend_tests,
+        models::{group::Group, user::User},
+    };
+    use chrono::Utc;
+
+    fn rate(
+        group_id: Option<u32>,
+        from: &str,
+        to: &str,
+        rate_micros: i64,
+        day: &str,
+    ) -> ExchangeRate {
+        ExchangeRate {
+            id: None,
+            group_id,
+            from_currency: from.to_string(),
+            to_currency: to.to_string(),
+            rate_micros,
+            effective_date: day.parse().unwrap(),
+        }
+    }
+
+    async fn setup_group(db: Database) -> (Database, u32) {
+        let owner = db
+            .create_user(&User::new("Owner", "owner@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            owner,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        (db, group_id)
+    }
+
+    async fn test_find_exchange_rate_by_date(db: Database) {
+        let (db, group_id) = setup_group(db).await;
+        db.replace_global_exchange_rates(&[
+            rate(None, "USD", "EUR", 900_000, "2024-01-01"),
+            rate(None, "USD", "EUR", 950_000, "2024-02-01"),
+        ])
+        .await
+        .unwrap();
+        let day = |day: &str| day.parse::<NaiveDate>().unwrap();
+
+        let found = db.find_exchange_rate(group_id, "USD", "EUR", day("2024-01-15"));
+        assert_eq!(found.await.unwrap(), Some(900_000));
+        let found = db.find_exchange_rate(group_id, "USD", "EUR", day("2024-02-01"));
+        assert_eq!(found.await.unwrap(), Some(950_000));
+        let found = db.find_exchange_rate(group_id, "USD", "EUR", day("2023-12-31"));
+        assert_eq!(found.await.unwrap(), None);
+        let found = db.find_exchange_rate(group_id, "EUR", "EUR", day("2023-12-31"));
+        assert_eq!(found.await.unwrap(), Some(RATE_SCALE));
+        // The opposite direction is inverted: 1 / 0.95
+        let found = db.find_exchange_rate(group_id, "EUR", "USD", day("2024-03-01"));
+        assert_eq!(found.await.unwrap(), Some(1_052_632));
+    }
+
+    async fn test_group_rate_overrides_rates_file(db: Database) {
+        let (db, group_id) = setup_group(db).await;
+        db.replace_global_exchange_rates(&[rate(None, "USD", "EUR", 900_000, "2024-01-01")])
+            .await
+            .unwrap();
+        db.create_exchange_rate(&rate(Some(group_id), "USD", "EUR", 910_000, "2024-01-01"))
+            .await
+            .unwrap();
+        let day = "2024-01-10".parse().unwrap();
+        let found = db.find_exchange_rate(group_id, "USD", "EUR", day);
+        assert_eq!(found.await.unwrap(), Some(910_000));
+        assert_eq!(
+            db.get_group_exchange_rates(group_id).await.unwrap().len(),
+            1
+        );
+
+        // Reloading the rates file keeps the group's own rates
+        db.replace_global_exchange_rates(&[]).await.unwrap();
+        let found = db.find_exchange_rate(group_id, "USD", "EUR", day);
+        assert_eq!(found.await.unwrap(), Some(910_000));
+        let found = db.find_exchange_rate(group_id + 1, "USD", "EUR", day);
+        assert_eq!(found.await.unwrap(), None);
+    }
+
+    backend_tests!(
+        test_find_exchange_rate_by_date,
+        test_group_rate_overrides_rates_file
+    );
+}
@@ -1,84 +1,83 @@
-use crate::{db::Database, models::expenses::Expense};
-use sqlx::Row;
+use crate::{
+    db::{repository::ExpenseRepository, SqliteRepository},
+    expense::split::merge_participant,
+    models::{
+        expenses::{Expense, ParticipantSplit, SplitType},
+        money::Money,
+    },
+};
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+use tracing::instrument;
+
+fn expense_from_row(row: SqliteRow) -> Result<Expense, sqlx::Error> {
+    Ok(Expense {
+        id: Some(row.get("id")),
+        description: row.get("description"),
+        amount: Money::new(row.get("amount"), row.get("currency")),
+        payer_id: row.get("payer_id"),
+        group_id: row.get("group_id"),
+        date: row.get("date"),
+        split_type: split_type_from_row(&row)?,
+    })
+}
 
-impl Database {
-    pub async fn create_expense(&self, expense: &Expense) -> Result<u32, sqlx::Error> {
-        let query = "INSERT INTO expenses (description, amount, payer_id, group_id, date) VALUES (?, ?, ?, ?, ?) RETURNING id";
-        let row = sqlx::query(query)
-            .bind(expense.description.clone())
-            .bind(expense.amount)
-            .bind(expense.payer_id)
-            .bind(expense.group_id)
-            .bind(expense.date.clone())
-            .fetch_one(&self.pool)
-            .await?;
-        let id = row.get("id");
-        Ok(id)
+fn split_type_from_row(row: &SqliteRow) -> Result<SplitType, sqlx::Error> {
+    row.get::<String, _>("split_type")
+        .parse()
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+#[async_trait]
+impl ExpenseRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_expense(&self, expense: &Expense) -> Result<u32, sqlx::Error> {
+        Self::insert_expense(&mut *self.pool.acquire().await?, expense).await
     }
 
-    pub async fn get_expenses_by_group_id(
+    #[instrument(level = "debug", skip_all)]
+    async fn create_expense_with_splits(
         &self,
-        group_id: u32,
-    ) -> Result<Vec<Expense>, sqlx::Error> {
-        let query = "SELECT * FROM expenses WHERE group_id = ?";
-        let rows = sqlx::query(query)
-            .bind(group_id)
-            .fetch_all(&self.pool)
-            .await?;
-        let expenses = rows
-            .into_iter()
-            .map(|row| Expense {
-                id: Some(row.get("id")),
-                description: row.get("description"),
-                amount: row.get("amount"),
-                payer_id: row.get("payer_id"),
-                group_id: row.get("group_id"),
-                date: row.get("date"),
-            })
-            .collect();
-        Ok(expenses)
+        expense: &Expense,
+        splits: &[ParticipantSplit],
+    ) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::insert_expense(&mut tx, expense).await?;
+        Self::insert_expense_splits(&mut tx, id, splits).await?;
+        tx.commit().await?;
+        Ok(id)
     }
 
-    pub async fn get_expenses_by_payer_id(
-        &self,
-        payer_id: u32,
-    ) -> Result<Vec<Expense>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expenses_by_group_id(&self, group_id: u32) -> Result<Vec<Expense>, sqlx::Error> {
+        Self::fetch_group_expenses(&mut *self.pool.acquire().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expenses_by_payer_id(&self, payer_id: u32) -> Result<Vec<Expense>, sqlx::Error> {
         let query = "SELECT * FROM expenses WHERE payer_id = ?";
         let rows = sqlx::query(query)
             .bind(payer_id)
             .fetch_all(&self.pool)
             .await?;
-        let expenses = rows
-            .into_iter()
-            .map(|row| Expense {
-                id: Some(row.get("id")),
-                description: row.get("description"),
-                amount: row.get("amount"),
-                payer_id: row.get("payer_id"),
-                group_id: row.get("group_id"),
-                date: row.get("date"),
-            })
-            .collect();
-        Ok(expenses)
+        rows.into_iter().map(expense_from_row).collect()
     }
 
-    pub async fn add_participants_to_expense(
+    #[instrument(level = "debug", skip_all)]
+    async fn add_participants_to_expense(
         &self,
         expense_id: u32,
         users_ids: Vec<u32>,
     ) -> Result<(), sqlx::Error> {
-        for user_id in users_ids {
-            let query = "INSERT INTO expense_participants (expense_id, user_id) VALUES (?, ?)";
-            sqlx::query(query)
-                .bind(expense_id)
-                .bind(user_id)
-                .execute(&self.pool)
-                .await?;
-        }
-        Ok(())
+        let splits: Vec<ParticipantSplit> = users_ids
+            .into_iter()
+            .map(|user_id| ParticipantSplit { user_id, value: 0 })
+            .collect();
+        self.add_expense_splits(expense_id, &splits).await
     }
 
-    pub async fn get_expense_participants(&self, expense_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expense_participants(&self, expense_id: u32) -> Result<Vec<u32>, sqlx::Error> {
         let query = "SELECT user_id FROM expense_participants WHERE expense_id = ?";
         let rows = sqlx::query(query)
             .bind(expense_id)
@@ -88,7 +87,27 @@ impl Database {
         Ok(participants)
     }
 
-    pub async fn get_all_user_expenses(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn add_expense_splits(
+        &self,
+        expense_id: u32,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_expense_splits(&mut tx, expense_id, splits).await?;
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expense_splits(
+        &self,
+        expense_id: u32,
+    ) -> Result<Vec<ParticipantSplit>, sqlx::Error> {
+        Self::fetch_expense_splits(&mut *self.pool.acquire().await?, expense_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_all_user_expenses(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error> {
         let query = "SELECT expense_id FROM expense_participants WHERE user_id = ?";
         let rows = sqlx::query(query)
             .bind(user_id)
@@ -98,24 +117,19 @@ impl Database {
         Ok(expenses_id)
     }
 
-    pub async fn get_expense_by_id(&self, expense_id: u32) -> Result<Expense, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expense_by_id(&self, expense_id: u32) -> Result<Expense, sqlx::Error> {
         let query = "SELECT * FROM expenses WHERE id = ?";
         let row = sqlx::query(query)
             .bind(expense_id)
             .fetch_one(&self.pool)
             .await?;
-        let expense = Expense {
-            id: Some(row.get("id")),
-            description: row.get("description"),
-            amount: row.get("amount"),
-            payer_id: row.get("payer_id"),
-            group_id: row.get("group_id"),
-            date: row.get("date"),
-        };
+        let expense = expense_from_row(row)?;
         Ok(expense)
     }
 
-    pub async fn get_expenses_by_ids(
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expenses_by_ids(
         &self,
         expenses_ids: Vec<u32>,
     ) -> Result<Vec<Expense>, sqlx::Error> {
@@ -126,37 +140,195 @@ impl Database {
                 .bind(expense_id)
                 .fetch_one(&self.pool)
                 .await?;
-            let expense = Expense {
-                id: Some(row.get("id")),
-                description: row.get("description"),
-                amount: row.get("amount"),
-                payer_id: row.get("payer_id"),
-                group_id: row.get("group_id"),
-                date: row.get("date"),
-            };
+            let expense = expense_from_row(row)?;
             expenses.push(expense);
         }
         Ok(expenses)
     }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_expense(
+        &self,
+        expense_id: u32,
+        expense: &Expense,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE expenses SET description = ?, amount = ?, currency = ?, payer_id = ?, date = ?, split_type = ? WHERE id = ?";
+        let result = sqlx::query(query)
+            .bind(expense.description.clone())
+            .bind(expense.amount.minor_units)
+            .bind(expense.amount.currency.clone())
+            .bind(expense.payer_id)
+            .bind(expense.date.clone())
+            .bind(expense.split_type.to_string())
+            .bind(expense_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        sqlx::query("DELETE FROM expense_participants WHERE expense_id = ?")
+            .bind(expense_id)
+            .execute(&mut *tx)
+            .await?;
+        Self::insert_expense_splits(&mut tx, expense_id, splits).await?;
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_expense(&self, expense_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM expense_participants WHERE expense_id = ?")
+            .bind(expense_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM expenses WHERE id = ?")
+            .bind(expense_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+}
+
+impl SqliteRepository {
+    /// Inserts an expense on `conn`, which may be a transaction of the caller.
+    pub async fn insert_expense(
+        conn: &mut SqliteConnection,
+        expense: &Expense,
+    ) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO expenses (description, amount, currency, payer_id, group_id, date, split_type) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(expense.description.clone())
+            .bind(expense.amount.minor_units)
+            .bind(expense.amount.currency.clone())
+            .bind(expense.payer_id)
+            .bind(expense.group_id)
+            .bind(expense.date.clone())
+            .bind(expense.split_type.to_string())
+            .fetch_one(conn)
+            .await?;
+        let id = row.get("id");
+        Ok(id)
+    }
+
+    /// Inserts the participants of an expense with their split values on `conn`,
+    /// which may be a transaction of the caller.
+    pub async fn insert_expense_splits(
+        conn: &mut SqliteConnection,
+        expense_id: u32,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error> {
+        for split in splits {
+            let query = "INSERT INTO expense_participants (expense_id, user_id, split_value) VALUES (?, ?, ?)";
+            sqlx::query(query)
+                .bind(expense_id)
+                .bind(split.user_id)
+                .bind(split.value)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// `get_expenses_by_group_id` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_group_expenses(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+    ) -> Result<Vec<Expense>, sqlx::Error> {
+        let query = "SELECT * FROM expenses WHERE group_id = ?";
+        let rows = sqlx::query(query).bind(group_id).fetch_all(conn).await?;
+        rows.into_iter().map(expense_from_row).collect()
+    }
+
+    /// `get_expense_splits` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_expense_splits(
+        conn: &mut SqliteConnection,
+        expense_id: u32,
+    ) -> Result<Vec<ParticipantSplit>, sqlx::Error> {
+        let query = "SELECT user_id, split_value FROM expense_participants WHERE expense_id = ? ORDER BY user_id";
+        let rows = sqlx::query(query).bind(expense_id).fetch_all(conn).await?;
+        let splits = rows
+            .into_iter()
+            .map(|row| ParticipantSplit {
+                user_id: row.get("user_id"),
+                value: row.get("split_value"),
+            })
+            .collect();
+        Ok(splits)
+    }
+
+    /// Hands the part `from_user_id` has in the expenses of `group_id`, or of
+    /// every group, over to `to_user_id` on `conn`, which may be a transaction
+    /// of the caller. Expenses both take part in become exact splits so that
+    /// what everyone else owes stays the same; see [`merge_participant`].
+    pub async fn merge_expense_participant(
+        conn: &mut SqliteConnection,
+        from_user_id: u32,
+        to_user_id: u32,
+        group_id: Option<u32>,
+    ) -> Result<(), sqlx::Error> {
+        let query = "SELECT id, amount, currency, split_type FROM expenses
+            WHERE (?3 IS NULL OR group_id = ?3)
+              AND id IN (SELECT expense_id FROM expense_participants WHERE user_id = ?1)
+              AND id IN (SELECT expense_id FROM expense_participants WHERE user_id = ?2)";
+        let shared = sqlx::query(query)
+            .bind(from_user_id)
+            .bind(to_user_id)
+            .bind(group_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        for row in shared {
+            let expense_id: u32 = row.get("id");
+            let amount = Money::new(row.get("amount"), row.get("currency"));
+            let split_type = split_type_from_row(&row)?;
+            let splits = Self::fetch_expense_splits(conn, expense_id).await?;
+            let merged = merge_participant(&amount, &split_type, &splits, from_user_id, to_user_id)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            sqlx::query("UPDATE expenses SET split_type = ? WHERE id = ?")
+                .bind(SplitType::Exact.to_string())
+                .bind(expense_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("DELETE FROM expense_participants WHERE expense_id = ?")
+                .bind(expense_id)
+                .execute(&mut *conn)
+                .await?;
+            Self::insert_expense_splits(conn, expense_id, &merged).await?;
+        }
+        let query = "UPDATE expense_participants SET user_id = ?2 WHERE user_id = ?1
+            AND expense_id IN (SELECT id FROM expenses WHERE ?3 IS NULL OR group_id = ?3)";
+        sqlx::query(query)
+            .bind(from_user_id)
+            .bind(to_user_id)
+            .bind(group_id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        db::tests::IN_MEMORY_DB,
-        models::{group::Group, user::User},
+        db::tests::backend_tests,
+        models::{
+            group::{Group, SettlementStrategy},
+            user::User,
+        },
     };
     use chrono::Utc;
 
     use super::*;
+    use crate::db::Database;
 
-    async fn setup_test_env() -> (Database, u32, u32) {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
-
+    async fn setup_test_env(db: Database) -> (Database, u32, u32) {
         // Create test user
         let user = User::new("Test User", "test@example.com", "password");
-        let user_id = db.create_user(&user, "token").await.unwrap();
+        let user_id = db.create_user(&user).await.unwrap();
 
         // Create test group
         let group = Group {
@@ -167,24 +339,27 @@ mod tests {
             group_end_date: Utc::now(),
             description: "Test Description".to_string(),
             location: "Test Location".to_string(),
+            base_currency: "EUR".to_string(),
+            settlement_strategy: SettlementStrategy::MinimizeTransfers,
+            archived_at: None,
         };
         let group_id = db.create_group(&group).await.unwrap();
 
         (db, user_id, group_id)
     }
 
-    #[tokio::test]
-    async fn test_create_and_get_expense() {
-        let (db, user_id, group_id) = setup_test_env().await;
+    async fn test_create_and_get_expense(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
         let time = Utc::now().to_string();
         db.add_user_to_group(group_id, user_id).await.unwrap();
         let expense = Expense {
             id: None,
             description: "Test Expense".to_string(),
-            amount: 100.0,
+            amount: Money::new(10000, "EUR"),
             payer_id: user_id,
             group_id,
             date: time.clone(),
+            split_type: SplitType::Equal,
         };
 
         // Test create expense
@@ -194,21 +369,20 @@ mod tests {
         // Test get expense by id
         let fetched_expense = db.get_expense_by_id(expense_id).await.unwrap();
         assert_eq!(fetched_expense.description, "Test Expense");
-        assert_eq!(fetched_expense.amount, 100.0);
+        assert_eq!(fetched_expense.amount, Money::new(10000, "EUR"));
         assert_eq!(fetched_expense.payer_id, user_id);
         assert_eq!(fetched_expense.group_id, group_id);
         assert_eq!(fetched_expense.date, time);
     }
 
-    #[tokio::test]
-    async fn test_expense_participants() {
-        let (db, user_id, group_id) = setup_test_env().await;
+    async fn test_expense_participants(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
 
         // Create additional participants
         let participant1 = User::new("Participant 1", "p1@example.com", "pass1");
         let participant2 = User::new("Participant 2", "p2@example.com", "pass2");
-        let p1_id = db.create_user(&participant1, "token1").await.unwrap();
-        let p2_id = db.create_user(&participant2, "token2").await.unwrap();
+        let p1_id = db.create_user(&participant1).await.unwrap();
+        let p2_id = db.create_user(&participant2).await.unwrap();
         db.add_user_to_group(group_id, p1_id).await.unwrap();
         db.add_user_to_group(group_id, p2_id).await.unwrap();
         db.add_user_to_group(group_id, user_id).await.unwrap();
@@ -217,10 +391,11 @@ mod tests {
         let expense = Expense {
             id: None,
             description: "Group Dinner".to_string(),
-            amount: 150.0,
+            amount: Money::new(15000, "EUR"),
             payer_id: user_id,
             group_id,
             date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
         };
         let expense_id = db.create_expense(&expense).await.unwrap();
 
@@ -241,9 +416,8 @@ mod tests {
         assert_eq!(p1_expenses[0], expense_id);
     }
 
-    #[tokio::test]
-    async fn test_get_expenses_by_group() {
-        let (db, user_id, group_id) = setup_test_env().await;
+    async fn test_get_expenses_by_group(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
 
         db.add_user_to_group(group_id, user_id).await.unwrap();
 
@@ -251,18 +425,20 @@ mod tests {
         let expense1 = Expense {
             id: None,
             description: "Expense 1".to_string(),
-            amount: 50.0,
+            amount: Money::new(5000, "EUR"),
             payer_id: user_id,
             group_id,
             date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
         };
         let expense2 = Expense {
             id: None,
             description: "Expense 2".to_string(),
-            amount: 75.0,
+            amount: Money::new(7500, "EUR"),
             payer_id: user_id,
             group_id,
             date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
         };
 
         db.create_expense(&expense1).await.unwrap();
@@ -271,35 +447,36 @@ mod tests {
         // Test get expenses by group
         let group_expenses = db.get_expenses_by_group_id(group_id).await.unwrap();
         assert_eq!(group_expenses.len(), 2);
-        assert_eq!(group_expenses[0].amount, 50.0);
-        assert_eq!(group_expenses[1].amount, 75.0);
+        assert_eq!(group_expenses[0].amount, Money::new(5000, "EUR"));
+        assert_eq!(group_expenses[1].amount, Money::new(7500, "EUR"));
     }
 
-    #[tokio::test]
-    async fn test_get_expenses_by_payer() {
-        let (db, user_id, group_id) = setup_test_env().await;
+    async fn test_get_expenses_by_payer(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
 
         // Create another user as payer
         let other_payer = User::new("Other Payer", "other@example.com", "pass");
-        let other_id = db.create_user(&other_payer, "token_other").await.unwrap();
+        let other_id = db.create_user(&other_payer).await.unwrap();
         db.add_user_to_group(group_id, other_id).await.unwrap();
         db.add_user_to_group(group_id, user_id).await.unwrap();
         // Create expenses with different payers
         let expense1 = Expense {
             id: None,
             description: "User1 Expense".to_string(),
-            amount: 100.0,
+            amount: Money::new(10000, "EUR"),
             payer_id: user_id,
             group_id,
             date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
         };
         let expense2 = Expense {
             id: None,
             description: "User2 Expense".to_string(),
-            amount: 150.0,
+            amount: Money::new(15000, "EUR"),
             payer_id: other_id,
             group_id,
             date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
         };
 
         db.create_expense(&expense1).await.unwrap();
@@ -311,25 +488,217 @@ mod tests {
 
         assert_eq!(user1_expenses.len(), 1);
         assert_eq!(user2_expenses.len(), 1);
-        assert_eq!(user1_expenses[0].amount, 100.0);
-        assert_eq!(user2_expenses[0].amount, 150.0);
+        assert_eq!(user1_expenses[0].amount, Money::new(10000, "EUR"));
+        assert_eq!(user2_expenses[0].amount, Money::new(15000, "EUR"));
     }
 
-    #[tokio::test]
-    async fn test_expense_not_found() {
-        let (db, _, _) = setup_test_env().await;
+    async fn test_expense_not_found(db: Database) {
+        let (db, _, _) = setup_test_env(db).await;
 
         // Try to get non-existent expense
         let result = db.get_expense_by_id(999).await;
         assert!(result.is_err());
     }
 
-    #[tokio::test]
-    async fn test_add_participant_to_nonexistent_expense() {
-        let (db, user_id, _) = setup_test_env().await;
+    async fn test_add_participant_to_nonexistent_expense(db: Database) {
+        let (db, user_id, _) = setup_test_env(db).await;
 
         // Try to add participant to non-existent expense
         let result = db.add_participants_to_expense(999, vec![user_id]).await;
         assert!(result.is_err());
     }
+
+    async fn test_update_expense(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
+        let other = User::new("Other", "other@example.com", "pass");
+        let other_id = db.create_user(&other).await.unwrap();
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        db.add_user_to_group(group_id, other_id).await.unwrap();
+        let mut expense = Expense {
+            id: None,
+            description: "Dinner".to_string(),
+            amount: Money::new(10000, "EUR"),
+            payer_id: user_id,
+            group_id,
+            date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
+        };
+        let expense_id = db.create_expense(&expense).await.unwrap();
+        db.add_participants_to_expense(expense_id, vec![user_id, other_id])
+            .await
+            .unwrap();
+
+        expense.amount = Money::new(8000, "EUR");
+        expense.payer_id = other_id;
+        let splits = [ParticipantSplit {
+            user_id: other_id,
+            value: 0,
+        }];
+        db.update_expense(expense_id, &expense, &splits)
+            .await
+            .unwrap();
+
+        let fetched = db.get_expense_by_id(expense_id).await.unwrap();
+        assert_eq!(fetched.amount, Money::new(8000, "EUR"));
+        assert_eq!(fetched.payer_id, other_id);
+        let participants = db.get_expense_participants(expense_id).await.unwrap();
+        assert_eq!(participants, vec![other_id]);
+
+        assert!(matches!(
+            db.update_expense(999, &expense, &splits).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    async fn test_update_expense_is_atomic(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        let expense = Expense {
+            id: None,
+            description: "Dinner".to_string(),
+            amount: Money::new(10000, "EUR"),
+            payer_id: user_id,
+            group_id,
+            date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
+        };
+        let expense_id = db.create_expense(&expense).await.unwrap();
+        db.add_participants_to_expense(expense_id, vec![user_id])
+            .await
+            .unwrap();
+
+        // An unknown participant fails the insert and rolls back the whole update
+        let mut changed = expense.clone();
+        changed.amount = Money::new(1000, "EUR");
+        let splits = [
+            ParticipantSplit { user_id, value: 0 },
+            ParticipantSplit {
+                user_id: 999,
+                value: 0,
+            },
+        ];
+        assert!(db
+            .update_expense(expense_id, &changed, &splits)
+            .await
+            .is_err());
+        let fetched = db.get_expense_by_id(expense_id).await.unwrap();
+        assert_eq!(fetched.amount, Money::new(10000, "EUR"));
+        let participants = db.get_expense_participants(expense_id).await.unwrap();
+        assert_eq!(participants, vec![user_id]);
+    }
+
+    async fn test_create_expense_with_splits_is_atomic(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        let expense = Expense {
+            id: None,
+            description: "Dinner".to_string(),
+            amount: Money::new(10000, "EUR"),
+            payer_id: user_id,
+            group_id,
+            date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
+        };
+
+        // The second participant is unknown, so the expense is not kept either
+        let splits = [
+            ParticipantSplit { user_id, value: 0 },
+            ParticipantSplit {
+                user_id: 999,
+                value: 0,
+            },
+        ];
+        assert!(db
+            .create_expense_with_splits(&expense, &splits)
+            .await
+            .is_err());
+        assert!(db
+            .get_expenses_by_group_id(group_id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db.get_all_user_expenses(user_id).await.unwrap().is_empty());
+
+        let expense_id = db
+            .create_expense_with_splits(&expense, &splits[..1])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_expense_participants(expense_id).await.unwrap(),
+            vec![user_id]
+        );
+    }
+
+    async fn test_delete_expense(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        let expense = Expense {
+            id: None,
+            description: "Dinner".to_string(),
+            amount: Money::new(10000, "EUR"),
+            payer_id: user_id,
+            group_id,
+            date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
+        };
+        let expense_id = db.create_expense(&expense).await.unwrap();
+        db.add_participants_to_expense(expense_id, vec![user_id])
+            .await
+            .unwrap();
+
+        db.delete_expense(expense_id).await.unwrap();
+        assert!(db.get_expense_by_id(expense_id).await.is_err());
+        assert!(db.get_all_user_expenses(user_id).await.unwrap().is_empty());
+        assert!(matches!(
+            db.delete_expense(expense_id).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    async fn test_expense_splits(db: Database) {
+        let (db, user_id, group_id) = setup_test_env(db).await;
+        let other = User::new("Other", "other@example.com", "pass");
+        let other_id = db.create_user(&other).await.unwrap();
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        db.add_user_to_group(group_id, other_id).await.unwrap();
+        let expense = Expense {
+            id: None,
+            description: "Hotel".to_string(),
+            amount: Money::new(10000, "EUR"),
+            payer_id: user_id,
+            group_id,
+            date: Utc::now().to_string(),
+            split_type: SplitType::Exact,
+        };
+        let expense_id = db.create_expense(&expense).await.unwrap();
+        let splits = vec![
+            ParticipantSplit {
+                user_id,
+                value: 4000,
+            },
+            ParticipantSplit {
+                user_id: other_id,
+                value: 6000,
+            },
+        ];
+        db.add_expense_splits(expense_id, &splits).await.unwrap();
+
+        let fetched = db.get_expense_by_id(expense_id).await.unwrap();
+        assert_eq!(fetched.split_type, SplitType::Exact);
+        assert_eq!(db.get_expense_splits(expense_id).await.unwrap(), splits);
+    }
+
+    backend_tests!(
+        test_create_and_get_expense,
+        test_expense_participants,
+        test_get_expenses_by_group,
+        test_get_expenses_by_payer,
+        test_expense_not_found,
+        test_add_participant_to_nonexistent_expense,
+        test_update_expense,
+        test_update_expense_is_atomic,
+        test_create_expense_with_splits_is_atomic,
+        test_delete_expense,
+        test_expense_splits
+    );
 }
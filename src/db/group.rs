@@ -1,86 +1,190 @@
-use crate::models::group::Group;
+use crate::models::{
+    expenses::Status,
+    group::{Group, GroupLedger, LedgerExpense, MemberRemoval, SettlementStrategy},
+    money::RATE_SCALE,
+    role::GroupRole,
+};
+use crate::summary::group_balances;
 
-use super::Database;
-use sqlx::Row;
+use super::{repository::GroupRepository, SqliteRepository};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+use tracing::instrument;
 
-impl Database {
-    pub async fn create_group(&self, group: &Group) -> Result<u32, sqlx::Error> {
-        let query = "INSERT INTO groups (name, description, owner_id, group_start_date, group_end_date, location) VALUES (?, ?, ?, ?, ?, ?) RETURNING id";
-        let id = sqlx::query(query)
-            .bind(group.name.clone())
-            .bind(group.description.clone())
-            .bind(group.owner_id)
-            .bind(group.group_start_date)
-            .bind(group.group_end_date)
-            .bind(group.location.clone())
-            .fetch_one(&self.pool)
-            .await?;
-        let id = id.get("id");
+/// Current members of a group with their role; the owner's comes from the group.
+const MEMBER_ROLES: &str = "SELECT group_members.user_id, CASE WHEN groups.owner_id = group_members.user_id THEN 'owner' ELSE group_members.role END AS role
+    FROM group_members JOIN groups ON groups.id = group_members.group_id
+    WHERE group_members.group_id = ? AND group_members.left_at IS NULL";
+
+fn group_from_row(row: SqliteRow) -> Result<Group, sqlx::Error> {
+    Ok(Group {
+        id: Some(row.get("id")),
+        name: row.get("name"),
+        owner_id: row.get("owner_id"),
+        group_start_date: row.get("group_start_date"),
+        group_end_date: row.get("group_end_date"),
+        description: row.get("description"),
+        location: row.get("location"),
+        base_currency: row.get("base_currency"),
+        settlement_strategy: row
+            .get::<String, _>("settlement_strategy")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        archived_at: row.get("archived_at"),
+    })
+}
+
+fn role_from_row(row: &SqliteRow) -> Result<GroupRole, sqlx::Error> {
+    row.get::<String, _>("role")
+        .parse()
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+#[async_trait]
+impl GroupRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_group(&self, group: &Group) -> Result<u32, sqlx::Error> {
+        Self::insert_group(&mut *self.pool.acquire().await?, group).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_group_with_owner(&self, group: &Group) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::insert_group(&mut tx, group).await?;
+        Self::insert_group_member(&mut tx, id, group.owner_id).await?;
+        tx.commit().await?;
         Ok(id)
     }
 
-    pub async fn get_group(&self, group_id: u32) -> Result<Group, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group(&self, group_id: u32) -> Result<Group, sqlx::Error> {
         let query = "SELECT * FROM groups WHERE id = ?";
         let row = sqlx::query(query)
             .bind(group_id)
             .fetch_one(&self.pool)
             .await?;
-
-        let group = Group {
-            id: Some(row.get("id")),
-            name: row.get("name"),
-            owner_id: row.get("owner_id"),
-            group_start_date: row.get("group_start_date"),
-            group_end_date: row.get("group_end_date"),
-            description: row.get("description"),
-            location: row.get("location"),
-        };
-        Ok(group)
+        group_from_row(row)
     }
 
-    pub async fn get_groups_by_owner_id(&self, owner_id: u32) -> Result<Vec<Group>, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_groups_by_owner_id(&self, owner_id: u32) -> Result<Vec<Group>, sqlx::Error> {
         let query = "SELECT * FROM groups WHERE owner_id = ?";
         let rows = sqlx::query(query)
             .bind(owner_id)
             .fetch_all(&self.pool)
             .await?;
-        let groups = rows
-            .into_iter()
-            .map(|row| Group {
-                id: Some(row.get("id")),
-                name: row.get("name"),
-                owner_id: row.get("owner_id"),
-                group_start_date: row.get("group_start_date"),
-                group_end_date: row.get("group_end_date"),
-                description: row.get("description"),
-                location: row.get("location"),
-            })
-            .collect();
+        rows.into_iter().map(group_from_row).collect()
+    }
 
-        Ok(groups)
+    #[instrument(level = "debug", skip_all)]
+    async fn update_settlement_strategy(
+        &self,
+        group_id: u32,
+        strategy: SettlementStrategy,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE groups SET settlement_strategy = ? WHERE id = ?";
+        let result = sqlx::query(query)
+            .bind(strategy.to_string())
+            .bind(group_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
     }
 
-    pub async fn add_user_to_group(&self, group_id: u32, user_id: u32) -> Result<(), sqlx::Error> {
-        let query = "INSERT INTO group_members (group_id, user_id) VALUES (?, ?)";
-        sqlx::query(query)
+    #[instrument(level = "debug", skip_all)]
+    async fn update_group(&self, group: &Group) -> Result<(), sqlx::Error> {
+        let query = "UPDATE groups SET name = ?, description = ?, location = ?, group_start_date = ?, group_end_date = ? WHERE id = ?";
+        let result = sqlx::query(query)
+            .bind(&group.name)
+            .bind(&group.description)
+            .bind(&group.location)
+            .bind(group.group_start_date)
+            .bind(group.group_end_date)
+            .bind(group.id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_group_archived(
+        &self,
+        group_id: u32,
+        archived_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE groups SET archived_at = ? WHERE id = ?";
+        let result = sqlx::query(query)
+            .bind(archived_at)
             .bind(group_id)
-            .bind(user_id)
             .execute(&self.pool)
             .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
         Ok(())
     }
 
-    pub async fn get_group_members(&self, group_id: u32) -> Result<Vec<u32>, sqlx::Error> {
-        let query = "SELECT user_id FROM group_members WHERE group_id = ?";
-        let rows = sqlx::query(query)
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_group(&self, group_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let guest_ids: Vec<u32> = sqlx::query("SELECT user_id FROM guests WHERE group_id = ?")
             .bind(group_id)
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|row| row.get("user_id"))
+            .collect();
+        for query in [
+            "DELETE FROM expense_participants WHERE expense_id IN (SELECT id FROM expenses WHERE group_id = ?)",
+            "DELETE FROM expenses WHERE group_id = ?",
+            "DELETE FROM transactions WHERE group_id = ?",
+            "DELETE FROM settlement_plans WHERE group_id = ?",
+            "DELETE FROM exchange_rates WHERE group_id = ?",
+            "DELETE FROM settlement_constraint_pairs WHERE group_id = ?",
+            "DELETE FROM settlement_constraints WHERE group_id = ?",
+            "DELETE FROM join_requests WHERE group_id = ?",
+            "DELETE FROM group_invitations WHERE group_id = ?",
+            "DELETE FROM guests WHERE group_id = ?",
+            "DELETE FROM group_members WHERE group_id = ?",
+        ] {
+            sqlx::query(query).bind(group_id).execute(&mut *tx).await?;
+        }
+        for guest_id in guest_ids {
+            sqlx::query("DELETE FROM users WHERE id = ?")
+                .bind(guest_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let result = sqlx::query("DELETE FROM groups WHERE id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
             .await?;
-        let members = rows.into_iter().map(|row| row.get("user_id")).collect();
-        Ok(members)
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn add_user_to_group(&self, group_id: u32, user_id: u32) -> Result<(), sqlx::Error> {
+        Self::insert_group_member(&mut *self.pool.acquire().await?, group_id, user_id).await
     }
-    pub async fn get_user_groups(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error> {
-        let query = "SELECT group_id FROM group_members WHERE user_id = ?";
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_members(&self, group_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+        Self::fetch_group_members(&mut *self.pool.acquire().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_groups(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+        let query = "SELECT group_id FROM group_members WHERE user_id = ? AND left_at IS NULL";
         let rows = sqlx::query(query)
             .bind(user_id)
             .fetch_all(&self.pool)
@@ -88,29 +192,284 @@ impl Database {
         let groups = rows.into_iter().map(|row| row.get("group_id")).collect();
         Ok(groups)
     }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_ledger(&self, group_id: u32) -> Result<GroupLedger, sqlx::Error> {
+        Self::fetch_group_ledger(&mut *self.pool.begin().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn remove_group_member(
+        &self,
+        group_id: u32,
+        user_id: u32,
+        reassign_to: Option<u32>,
+    ) -> Result<MemberRemoval, sqlx::Error> {
+        // Nothing can be added to the group between reading the balance and
+        // leaving: SQLite fails one of two transactions writing on what the
+        // other read
+        let mut tx = self.pool.begin().await?;
+        match reassign_to {
+            Some(to_user_id) => {
+                Self::reassign_member_to(&mut tx, group_id, user_id, to_user_id).await?
+            }
+            None => {
+                let ledger = Self::fetch_group_ledger(&mut tx, group_id).await?;
+                let balance = group_balances(&ledger)?
+                    .into_iter()
+                    .find(|balance| balance.user_id == user_id);
+                if let Some(balance) = balance.filter(|b| b.net_balance.minor_units != 0) {
+                    return Ok(MemberRemoval::OutstandingBalance(balance.net_balance));
+                }
+            }
+        }
+        let query = "UPDATE group_members SET left_at = ? WHERE group_id = ? AND user_id = ? AND left_at IS NULL";
+        let result = sqlx::query(query)
+            .bind(Utc::now())
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        let query = "UPDATE transactions SET status = ? WHERE group_id = ? AND status = ? AND (payer_id = ?4 OR receiver_id = ?4)";
+        sqlx::query(query)
+            .bind(Status::Superseded.to_string())
+            .bind(group_id)
+            .bind(Status::Pending.to_string())
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(MemberRemoval::Removed)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_group_owner(&self, group_id: u32, owner_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE group_members SET role = ? WHERE group_id = ?2 AND user_id = (SELECT owner_id FROM groups WHERE id = ?2)";
+        sqlx::query(query)
+            .bind(GroupRole::Admin.to_string())
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        let query = "UPDATE groups SET owner_id = ? WHERE id = ?";
+        let result = sqlx::query(query)
+            .bind(owner_id)
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_member_role(&self, group_id: u32, user_id: u32) -> Result<GroupRole, sqlx::Error> {
+        let query = format!("{MEMBER_ROLES} AND group_members.user_id = ?");
+        let row = sqlx::query(&query)
+            .bind(group_id)
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+        role_from_row(&row)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_member_roles(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<(u32, GroupRole)>, sqlx::Error> {
+        let query = format!("{MEMBER_ROLES} ORDER BY group_members.user_id");
+        let rows = sqlx::query(&query)
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter()
+            .map(|row| Ok((row.get("user_id"), role_from_row(&row)?)))
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_member_role(
+        &self,
+        group_id: u32,
+        user_id: u32,
+        role: GroupRole,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE group_members SET role = ? WHERE group_id = ? AND user_id = ? AND left_at IS NULL";
+        let result = sqlx::query(query)
+            .bind(role.to_string())
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl SqliteRepository {
+    /// Inserts a group on `conn`, which may be a transaction of the caller.
+    pub async fn insert_group(
+        conn: &mut SqliteConnection,
+        group: &Group,
+    ) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO groups (name, description, owner_id, group_start_date, group_end_date, location, base_currency, settlement_strategy) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id";
+        let id = sqlx::query(query)
+            .bind(group.name.clone())
+            .bind(group.description.clone())
+            .bind(group.owner_id)
+            .bind(group.group_start_date)
+            .bind(group.group_end_date)
+            .bind(group.location.clone())
+            .bind(group.base_currency.clone())
+            .bind(group.settlement_strategy.to_string())
+            .fetch_one(conn)
+            .await?;
+        let id = id.get("id");
+        Ok(id)
+    }
+
+    /// `add_user_to_group` on `conn`, which may be a transaction of the caller.
+    pub async fn insert_group_member(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+        user_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE group_members SET left_at = NULL, role = 'member' WHERE group_id = ? AND user_id = ? AND left_at IS NOT NULL";
+        let result = sqlx::query(query)
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() > 0 {
+            return Ok(());
+        }
+        let query = "INSERT INTO group_members (group_id, user_id) VALUES (?, ?)";
+        sqlx::query(query)
+            .bind(group_id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Hands everything `from_user_id` has in the group over to `to_user_id`
+    /// on `conn`, which may be a transaction of the caller; see
+    /// `remove_group_member`.
+    pub async fn reassign_member_to(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+        from_user_id: u32,
+        to_user_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE expenses SET payer_id = ? WHERE payer_id = ? AND group_id = ?")
+            .bind(to_user_id)
+            .bind(from_user_id)
+            .bind(group_id)
+            .execute(&mut *conn)
+            .await?;
+        Self::merge_expense_participant(conn, from_user_id, to_user_id, Some(group_id)).await?;
+        for query in [
+            "UPDATE transactions SET payer_id = ?2 WHERE payer_id = ?1 AND group_id = ?3",
+            "UPDATE transactions SET receiver_id = ?2 WHERE receiver_id = ?1 AND group_id = ?3",
+            // Payments between the two would now go from the new member to themselves
+            "DELETE FROM transactions WHERE payer_id = ?2 AND receiver_id = ?2 AND group_id = ?3",
+        ] {
+            sqlx::query(query)
+                .bind(from_user_id)
+                .bind(to_user_id)
+                .bind(group_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// `get_group_ledger` on `conn`, which should be a transaction so that
+    /// everything is read at the same point in time.
+    pub async fn fetch_group_ledger(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+    ) -> Result<GroupLedger, sqlx::Error> {
+        let group = sqlx::query("SELECT * FROM groups WHERE id = ?")
+            .bind(group_id)
+            .fetch_one(&mut *conn)
+            .await
+            .and_then(group_from_row)?;
+        let base_currency = &group.base_currency;
+        let members = Self::fetch_group_members(conn, group_id).await?;
+        let mut expenses = Vec::new();
+        for expense in Self::fetch_group_expenses(conn, group_id).await? {
+            let currency = &expense.amount.currency;
+            let rate_micros = match expense.day() {
+                Some(day) => {
+                    Self::fetch_exchange_rate(conn, group_id, currency, base_currency, day).await?
+                }
+                None if currency == base_currency => Some(RATE_SCALE),
+                None => None,
+            };
+            let splits = Self::fetch_expense_splits(conn, expense.id.unwrap()).await?;
+            expenses.push(LedgerExpense {
+                expense,
+                rate_micros,
+                splits,
+            });
+        }
+        let payments =
+            Self::fetch_group_transactions_by_status(conn, group_id, Status::Completed).await?;
+        let constraint = Self::fetch_settlement_constraint(conn, group_id).await?;
+        Ok(GroupLedger {
+            group,
+            members,
+            expenses,
+            payments,
+            constraint,
+        })
+    }
+
+    /// `get_group_members` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_group_members(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+    ) -> Result<Vec<u32>, sqlx::Error> {
+        let query = "SELECT user_id FROM group_members WHERE group_id = ? AND left_at IS NULL";
+        let rows = sqlx::query(query).bind(group_id).fetch_all(conn).await?;
+        let members = rows.into_iter().map(|row| row.get("user_id")).collect();
+        Ok(members)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::Utc;
-
-    use crate::{db::tests::IN_MEMORY_DB, models::user::User};
+    use crate::{
+        db::tests::{backend_tests, fail_on},
+        models::{
+            expenses::{Expense, ParticipantSplit, SplitType, Status, Transaction},
+            money::Money,
+            user::User,
+        },
+    };
 
     use super::*;
+    use crate::db::Database;
+    use chrono::SubsecRound;
 
-    async fn setup_test_env() -> (Database, u32) {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
+    async fn setup_test_env(db: Database) -> (Database, u32) {
         let user = User::new("Test User", "test@example.com", "password");
-        let user_id = db.create_user(&user, "token").await.unwrap();
+        let user_id = db.create_user(&user).await.unwrap();
         (db, user_id)
     }
 
-    #[tokio::test]
-    async fn test_create_group() {
-        let (db, user_id) = setup_test_env().await;
+    async fn test_create_group(db: Database) {
+        let (db, user_id) = setup_test_env(db).await;
 
-        let time = Utc::now();
+        let time = Utc::now().trunc_subsecs(6);
         let group = Group {
             id: None,
             name: "Test Group".to_string(),
@@ -119,6 +478,9 @@ mod tests {
             group_end_date: time,
             description: "Test Description".to_string(),
             location: "Test Location".to_string(),
+            base_currency: "EUR".to_string(),
+            settlement_strategy: SettlementStrategy::MinimizeTransfers,
+            archived_at: None,
         };
         db.create_group(&group).await.unwrap();
         db.create_group(&group).await.unwrap();
@@ -136,10 +498,25 @@ mod tests {
         assert_eq!(group.location, "Test Location");
     }
 
-    #[tokio::test]
-    async fn test_no_existing_group() {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
+    async fn test_create_group_with_owner_is_atomic(db: Database) {
+        let (db, user_id) = setup_test_env(db).await;
+        let group = Group::new(
+            "Trip",
+            user_id,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group_with_owner(&group).await.unwrap();
+        assert_eq!(db.get_group_members(group_id).await.unwrap(), vec![user_id]);
+
+        fail_on(&db, "INSERT", "group_members").await;
+        assert!(db.create_group_with_owner(&group).await.is_err());
+        assert_eq!(db.get_groups_by_owner_id(user_id).await.unwrap().len(), 1);
+    }
+
+    async fn test_no_existing_group(db: Database) {
         let group = Group {
             id: None,
             name: "Test Group".to_string(),
@@ -148,15 +525,17 @@ mod tests {
             group_end_date: Utc::now(),
             description: "Test Description".to_string(),
             location: "Test Location".to_string(),
+            base_currency: "EUR".to_string(),
+            settlement_strategy: SettlementStrategy::MinimizeTransfers,
+            archived_at: None,
         };
         assert!(db.create_group(&group).await.is_err());
     }
 
-    #[tokio::test]
-    async fn test_add_user_to_group() {
-        let (db, user_id) = setup_test_env().await;
+    async fn test_add_user_to_group(db: Database) {
+        let (db, user_id) = setup_test_env(db).await;
 
-        let time = Utc::now();
+        let time = Utc::now().trunc_subsecs(6);
         let group = Group {
             id: None,
             name: "Test Group".to_string(),
@@ -165,6 +544,9 @@ mod tests {
             group_end_date: time,
             description: "Test Description".to_string(),
             location: "Test Location".to_string(),
+            base_currency: "EUR".to_string(),
+            settlement_strategy: SettlementStrategy::MinimizeTransfers,
+            archived_at: None,
         };
         let group_id = db.create_group(&group).await.unwrap();
 
@@ -186,27 +568,24 @@ mod tests {
         assert!(groups.contains(&group_id));
     }
 
-    #[tokio::test]
-    async fn test_get_nonexistent_group() {
-        let (db, _) = setup_test_env().await;
+    async fn test_get_nonexistent_group(db: Database) {
+        let (db, _) = setup_test_env(db).await;
 
         // Try to get non-existent group
         let result = db.get_group(999).await;
         assert!(result.is_err());
     }
 
-    #[tokio::test]
-    async fn test_add_user_to_nonexistent_group() {
-        let (db, user_id) = setup_test_env().await;
+    async fn test_add_user_to_nonexistent_group(db: Database) {
+        let (db, user_id) = setup_test_env(db).await;
 
         // Try to add user to non-existent group
         let result = db.add_user_to_group(999, user_id).await;
         assert!(result.is_err());
     }
 
-    #[tokio::test]
-    async fn test_get_members_nonexistent_group() {
-        let (db, _) = setup_test_env().await;
+    async fn test_get_members_nonexistent_group(db: Database) {
+        let (db, _) = setup_test_env(db).await;
 
         // Try to get members of non-existent group
         let result = db.get_group_members(999).await;
@@ -214,15 +593,14 @@ mod tests {
         assert_eq!(result.unwrap().len(), 0);
     }
 
-    #[tokio::test]
-    async fn test_multiple_users_in_group() {
-        let (db, owner_id) = setup_test_env().await;
+    async fn test_multiple_users_in_group(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
 
         // Create additional users
         let user1 = User::new("User 1", "user1@example.com", "pass1");
         let user2 = User::new("User 2", "user2@example.com", "pass2");
-        let user1_id = db.create_user(&user1, "token1").await.unwrap();
-        let user2_id = db.create_user(&user2, "token2").await.unwrap();
+        let user1_id = db.create_user(&user1).await.unwrap();
+        let user2_id = db.create_user(&user2).await.unwrap();
 
         // Create group
         let group = Group {
@@ -233,6 +611,9 @@ mod tests {
             group_end_date: Utc::now(),
             description: "Test Group".to_string(),
             location: "Test Location".to_string(),
+            base_currency: "EUR".to_string(),
+            settlement_strategy: SettlementStrategy::MinimizeTransfers,
+            archived_at: None,
         };
         let group_id = db.create_group(&group).await.unwrap();
 
@@ -254,4 +635,465 @@ mod tests {
         assert_eq!(user1_groups[0], group_id);
         assert_eq!(user2_groups[0], group_id);
     }
+
+    async fn test_remove_and_rejoin_group_member(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let user_id = db
+            .create_user(&User::new("Member", "member@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, owner_id).await.unwrap();
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+
+        assert_eq!(
+            db.remove_group_member(group_id, user_id, None)
+                .await
+                .unwrap(),
+            MemberRemoval::Removed
+        );
+        assert_eq!(
+            db.get_group_members(group_id).await.unwrap(),
+            vec![owner_id]
+        );
+        assert!(db.get_user_groups(user_id).await.unwrap().is_empty());
+        assert!(matches!(
+            db.remove_group_member(group_id, user_id, None).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+
+        // A former member can be added again, but only once
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        assert!(db.add_user_to_group(group_id, user_id).await.is_err());
+        assert_eq!(db.get_user_groups(user_id).await.unwrap(), vec![group_id]);
+    }
+
+    /// The owner pays 90.00 split between them, Bob and Carol, and Carol has
+    /// paid Bob back. Carol's part then goes to Bob. Returns the type and splits
+    /// of the expense afterwards.
+    async fn reassign_shared_expense(
+        db: Database,
+        split_type: SplitType,
+        values: [i64; 3],
+    ) -> (SplitType, Vec<(u32, i64)>, u32, u32) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        let carol = db
+            .create_user(&User::new("Carol", "carol@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        for user_id in [owner_id, bob, carol] {
+            db.add_user_to_group(group_id, user_id).await.unwrap();
+        }
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Tickets".to_string(),
+                amount: Money::new(9000, "EUR"),
+                payer_id: owner_id,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type,
+            })
+            .await
+            .unwrap();
+        let splits: Vec<_> = [owner_id, bob, carol]
+            .into_iter()
+            .zip(values)
+            .map(|(user_id, value)| ParticipantSplit { user_id, value })
+            .collect();
+        db.add_expense_splits(expense_id, &splits).await.unwrap();
+        db.create_transaction(&Transaction {
+            id: None,
+            payer_id: carol,
+            receiver_id: bob,
+            amount: Money::new(500, "EUR"),
+            date: "2024-01-02".to_string(),
+            status: Status::Completed,
+            group_id,
+            plan_id: None,
+        })
+        .await
+        .unwrap();
+
+        db.remove_group_member(group_id, carol, Some(bob))
+            .await
+            .unwrap();
+        assert!(db
+            .get_group_transactions(group_id)
+            .await
+            .unwrap()
+            .is_empty());
+        let split_type = db.get_expense_by_id(expense_id).await.unwrap().split_type;
+        let splits = db
+            .get_expense_splits(expense_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|split| (split.user_id, split.value))
+            .collect();
+        (split_type, splits, owner_id, bob)
+    }
+
+    async fn test_remove_member_with_balance(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let user_id = db
+            .create_user(&User::new("Member", "member@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, owner_id).await.unwrap();
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Tickets".to_string(),
+                amount: Money::new(3000, "EUR"),
+                payer_id: user_id,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type: SplitType::Shares,
+            })
+            .await
+            .unwrap();
+        let split = |user_id, value| ParticipantSplit { user_id, value };
+        db.add_expense_splits(expense_id, &[split(owner_id, 1), split(user_id, 2)])
+            .await
+            .unwrap();
+
+        // The member paid 30.00 and owes 20.00 of it, so cannot just leave
+        assert_eq!(
+            db.remove_group_member(group_id, user_id, None)
+                .await
+                .unwrap(),
+            MemberRemoval::OutstandingBalance(Money::new(1000, "EUR"))
+        );
+        assert_eq!(db.get_group_members(group_id).await.unwrap().len(), 2);
+
+        assert_eq!(
+            db.remove_group_member(group_id, user_id, Some(owner_id))
+                .await
+                .unwrap(),
+            MemberRemoval::Removed
+        );
+        assert_eq!(
+            db.get_group_members(group_id).await.unwrap(),
+            vec![owner_id]
+        );
+        assert_eq!(
+            db.get_expense_by_id(expense_id).await.unwrap().payer_id,
+            owner_id
+        );
+        let splits = db.get_expense_splits(expense_id).await.unwrap();
+        assert_eq!(splits.len(), 1);
+        assert_eq!(splits[0].user_id, owner_id);
+        assert_eq!(splits[0].value, 3000);
+    }
+
+    async fn test_failed_removal_rolls_back_reassignment(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let user_id = db
+            .create_user(&User::new("Member", "member@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group_with_owner(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+        let split = |user_id, value| ParticipantSplit { user_id, value };
+        let expense_id = db
+            .create_expense_with_splits(
+                &Expense {
+                    id: None,
+                    description: "Tickets".to_string(),
+                    amount: Money::new(3000, "EUR"),
+                    payer_id: user_id,
+                    group_id,
+                    date: "2024-01-01".to_string(),
+                    split_type: SplitType::Equal,
+                },
+                &[split(owner_id, 0), split(user_id, 0)],
+            )
+            .await
+            .unwrap();
+
+        // The expenses are handed over before leaving fails
+        fail_on(&db, "UPDATE", "group_members").await;
+        assert!(db
+            .remove_group_member(group_id, user_id, Some(owner_id))
+            .await
+            .is_err());
+        let expense = db.get_expense_by_id(expense_id).await.unwrap();
+        assert_eq!(expense.payer_id, user_id);
+        assert_eq!(expense.split_type, SplitType::Equal);
+        assert_eq!(
+            db.get_expense_splits(expense_id).await.unwrap(),
+            vec![split(owner_id, 0), split(user_id, 0)]
+        );
+        assert_eq!(db.get_group_members(group_id).await.unwrap().len(), 2);
+    }
+
+    async fn test_reassign_keeps_equal_split(db: Database) {
+        let (split_type, splits, owner_id, bob) =
+            reassign_shared_expense(db, SplitType::Equal, [0, 0, 0]).await;
+        // Bob now owes 60.00, not half of it
+        assert_eq!(split_type, SplitType::Exact);
+        assert_eq!(splits, vec![(owner_id, 3000), (bob, 6000)]);
+    }
+
+    async fn test_reassign_keeps_adjustment_split(db: Database) {
+        let (split_type, splits, owner_id, bob) =
+            reassign_shared_expense(db, SplitType::Adjustment, [1500, 0, 0]).await;
+        assert_eq!(split_type, SplitType::Exact);
+        assert_eq!(splits, vec![(owner_id, 4000), (bob, 5000)]);
+    }
+
+    async fn test_update_group_owner(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let user_id = db
+            .create_user(&User::new("Member", "member@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        db.update_group_owner(group_id, user_id).await.unwrap();
+        assert_eq!(db.get_group(group_id).await.unwrap().owner_id, user_id);
+        assert!(matches!(
+            db.update_group_owner(999, user_id).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    async fn test_member_roles(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let user_id = db
+            .create_user(&User::new("Member", "member@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, owner_id).await.unwrap();
+        db.add_user_to_group(group_id, user_id).await.unwrap();
+
+        assert_eq!(
+            db.get_group_member_roles(group_id).await.unwrap(),
+            vec![(owner_id, GroupRole::Owner), (user_id, GroupRole::Member)]
+        );
+        db.set_member_role(group_id, user_id, GroupRole::Viewer)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_member_role(group_id, user_id).await.unwrap(),
+            GroupRole::Viewer
+        );
+        assert!(matches!(
+            db.get_member_role(group_id, 999).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+
+        // The previous owner stays on as an admin
+        db.update_group_owner(group_id, user_id).await.unwrap();
+        assert_eq!(
+            db.get_group_member_roles(group_id).await.unwrap(),
+            vec![(owner_id, GroupRole::Admin), (user_id, GroupRole::Owner)]
+        );
+
+        // Former members come back as regular members
+        db.remove_group_member(group_id, owner_id, None)
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, owner_id).await.unwrap();
+        assert_eq!(
+            db.get_member_role(group_id, owner_id).await.unwrap(),
+            GroupRole::Member
+        );
+    }
+
+    async fn test_update_and_archive_group(db: Database) {
+        let (db, owner_id) = setup_test_env(db).await;
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        let mut group = db.get_group(group_id).await.unwrap();
+        group.name = "Road trip".to_string();
+        group.location = "Lisbon".to_string();
+        db.update_group(&group).await.unwrap();
+        let updated = db.get_group(group_id).await.unwrap();
+        assert_eq!(updated.name, "Road trip");
+        assert_eq!(updated.location, "Lisbon");
+        assert!(updated.archived_at.is_none());
+
+        db.set_group_archived(group_id, Some(Utc::now()))
+            .await
+            .unwrap();
+        assert!(db.get_group(group_id).await.unwrap().archived_at.is_some());
+        db.set_group_archived(group_id, None).await.unwrap();
+        assert!(db.get_group(group_id).await.unwrap().archived_at.is_none());
+    }
+
+    async fn test_delete_group_with_everything_in_it(db: Database) {
+        use crate::models::{
+            expenses::{Expense, SplitType, Transaction},
+            money::Money,
+        };
+
+        let (db, owner_id) = setup_test_env(db).await;
+        let group_id = db
+            .create_group(&Group::new(
+                "Trip",
+                owner_id,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, owner_id).await.unwrap();
+        let guest_id = db
+            .create_guest(group_id, "Guest", owner_id)
+            .await
+            .unwrap()
+            .guest
+            .user_id;
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Dinner".to_string(),
+                amount: Money::new(2000, "EUR"),
+                payer_id: owner_id,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type: SplitType::Equal,
+            })
+            .await
+            .unwrap();
+        db.add_participants_to_expense(expense_id, vec![owner_id, guest_id])
+            .await
+            .unwrap();
+        db.create_transaction(&Transaction {
+            id: None,
+            payer_id: guest_id,
+            receiver_id: owner_id,
+            amount: Money::new(1000, "EUR"),
+            date: "2024-01-02".to_string(),
+            status: Status::Completed,
+            group_id,
+            plan_id: None,
+        })
+        .await
+        .unwrap();
+        db.generate_settlement_plan(group_id, owner_id)
+            .await
+            .unwrap();
+
+        db.delete_group(group_id).await.unwrap();
+        assert!(matches!(
+            db.get_group(group_id).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        assert!(db.get_expense_by_id(expense_id).await.is_err());
+        assert!(db
+            .get_group_transactions(group_id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db.get_user_groups(owner_id).await.unwrap().is_empty());
+        assert!(db.get_user(guest_id).await.is_err());
+        assert!(db.get_user(owner_id).await.is_ok());
+        assert!(matches!(
+            db.delete_group(group_id).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    backend_tests!(
+        test_create_group,
+        test_create_group_with_owner_is_atomic,
+        test_no_existing_group,
+        test_add_user_to_group,
+        test_get_nonexistent_group,
+        test_add_user_to_nonexistent_group,
+        test_get_members_nonexistent_group,
+        test_multiple_users_in_group,
+        test_remove_and_rejoin_group_member,
+        test_remove_member_with_balance,
+        test_failed_removal_rolls_back_reassignment,
+        test_reassign_keeps_equal_split,
+        test_reassign_keeps_adjustment_split,
+        test_update_group_owner,
+        test_member_roles,
+        test_update_and_archive_group,
+        test_delete_group_with_everything_in_it
+    );
 }
@@ -0,0 +1,464 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+use tracing::instrument;
+
+use crate::{
+    auth::{
+        password::hash_password,
+        utils::{generate_token, hash_token},
+    },
+    db::{repository::GuestRepository, SqliteRepository},
+    models::guest::{Guest, GuestInvitation, GUEST_EMAIL_DOMAIN},
+};
+
+fn guest_from_row(row: SqliteRow) -> Guest {
+    Guest {
+        user_id: row.get("user_id"),
+        group_id: row.get("group_id"),
+        name: row.get("name"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[async_trait]
+impl GuestRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_guest(
+        &self,
+        group_id: u32,
+        name: &str,
+        created_by: u32,
+    ) -> Result<GuestInvitation, sqlx::Error> {
+        let claim_token = generate_token();
+        // Nobody knows the password, and the reserved domain keeps the address
+        // out of the way of real users.
+        let email = format!("{}@{}", generate_token(), GUEST_EMAIL_DOMAIN);
+        let password_hash =
+            hash_password(&generate_token()).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let created_at = Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+        let query = "INSERT INTO users (name, email, password) VALUES (?, ?, ?) RETURNING id";
+        let user_id: u32 = sqlx::query(query)
+            .bind(name)
+            .bind(email)
+            .bind(password_hash)
+            .fetch_one(&mut *tx)
+            .await?
+            .get("id");
+        sqlx::query("INSERT INTO group_members (group_id, user_id) VALUES (?, ?)")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        let query = "INSERT INTO guests (user_id, group_id, claim_token, created_by, created_at) VALUES (?, ?, ?, ?, ?)";
+        sqlx::query(query)
+            .bind(user_id)
+            .bind(group_id)
+            .bind(hash_token(&claim_token))
+            .bind(created_by)
+            .bind(created_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(GuestInvitation {
+            guest: Guest {
+                user_id,
+                group_id,
+                name: name.to_string(),
+                created_by,
+                created_at,
+            },
+            claim_token,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_guests(&self, group_id: u32) -> Result<Vec<Guest>, sqlx::Error> {
+        let query = "SELECT guests.*, users.name FROM guests JOIN users ON users.id = guests.user_id WHERE guests.group_id = ? ORDER BY guests.user_id";
+        let rows = sqlx::query(query)
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(guest_from_row).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_guest_by_claim_token(&self, claim_token: &str) -> Result<Guest, sqlx::Error> {
+        let query = "SELECT guests.*, users.name FROM guests JOIN users ON users.id = guests.user_id WHERE guests.claim_token = ?";
+        let row = sqlx::query(query)
+            .bind(hash_token(claim_token))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(guest_from_row(row))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn merge_guest(&self, guest_id: u32, user_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::merge_guest_into(&mut tx, guest_id, user_id).await?;
+        tx.commit().await
+    }
+}
+
+impl SqliteRepository {
+    /// `merge_guest` on `conn`, which may be a transaction of the caller.
+    pub async fn merge_guest_into(
+        conn: &mut SqliteConnection,
+        guest_id: u32,
+        user_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        // Memberships first: expenses and transactions reference them. Groups the
+        // guest was removed from are only kept for their history.
+        sqlx::query(
+            "INSERT INTO group_members (group_id, user_id, left_at, role) SELECT group_id, ?, left_at, role FROM group_members WHERE user_id = ?
+            ON CONFLICT (group_id, user_id) DO UPDATE SET left_at = CASE WHEN excluded.left_at IS NULL THEN NULL ELSE left_at END",
+        )
+        .bind(user_id)
+        .bind(guest_id)
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query("UPDATE expenses SET payer_id = ? WHERE payer_id = ?")
+            .bind(user_id)
+            .bind(guest_id)
+            .execute(&mut *conn)
+            .await?;
+        Self::merge_expense_participant(conn, guest_id, user_id, None).await?;
+        for query in [
+            "UPDATE transactions SET payer_id = ? WHERE payer_id = ?",
+            "UPDATE transactions SET receiver_id = ? WHERE receiver_id = ?",
+            "UPDATE settlement_constraints SET hub_user_id = ? WHERE hub_user_id = ?",
+            // Pairs are stored lowest id first, which the user may not keep
+            "INSERT OR IGNORE INTO settlement_constraint_pairs (group_id, first_user_id, second_user_id)
+            SELECT group_id, MIN(other_user_id, ?1), MAX(other_user_id, ?1) FROM (
+                SELECT group_id, second_user_id AS other_user_id FROM settlement_constraint_pairs WHERE first_user_id = ?2
+                UNION ALL
+                SELECT group_id, first_user_id FROM settlement_constraint_pairs WHERE second_user_id = ?2)",
+        ] {
+            sqlx::query(query)
+                .bind(user_id)
+                .bind(guest_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        for query in [
+            "DELETE FROM settlement_constraint_pairs WHERE first_user_id = ?1 OR second_user_id = ?1",
+            "DELETE FROM group_members WHERE user_id = ?",
+            "DELETE FROM guests WHERE user_id = ?",
+            "DELETE FROM users WHERE id = ?",
+        ] {
+            sqlx::query(query).bind(guest_id).execute(&mut *conn).await?;
+        }
+        // Payments between the guest and the user are now the user paying
+        // themselves, and a pair of the two no longer constrains anything
+        for query in [
+            "DELETE FROM transactions WHERE payer_id = ?1 AND receiver_id = ?1",
+            "DELETE FROM settlement_constraint_pairs WHERE first_user_id = ?1 AND second_user_id = ?1",
+        ] {
+            sqlx::query(query).bind(user_id).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::{
+        db::tests::{backend_tests, fail_on},
+        models::{
+            expenses::{Expense, ParticipantSplit, SplitType, Status, Transaction},
+            group::Group,
+            money::Money,
+            settlement_constraint::{MemberPair, SettlementConstraint},
+            user::User,
+        },
+    };
+
+    async fn test_merge_guest_into_user(db: Database) {
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            alice,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, alice).await.unwrap();
+
+        let invitation = db.create_guest(group_id, "Bob", alice).await.unwrap();
+        let guest = invitation.guest.user_id;
+        assert_eq!(db.get_group_members(group_id).await.unwrap().len(), 2);
+        assert_eq!(
+            db.get_guest_by_claim_token(&invitation.claim_token)
+                .await
+                .unwrap(),
+            invitation.guest
+        );
+        assert!(db.get_guest_by_claim_token("wrong").await.is_err());
+
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Boat".to_string(),
+                amount: Money::new(3000, "EUR"),
+                payer_id: guest,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type: SplitType::Shares,
+            })
+            .await
+            .unwrap();
+        db.add_expense_splits(
+            expense_id,
+            &[
+                ParticipantSplit {
+                    user_id: alice,
+                    value: 1,
+                },
+                ParticipantSplit {
+                    user_id: guest,
+                    value: 2,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_transaction(&Transaction {
+            id: None,
+            payer_id: alice,
+            receiver_id: guest,
+            amount: Money::new(1000, "EUR"),
+            date: "2024-01-02".to_string(),
+            status: Status::Completed,
+            group_id,
+            plan_id: None,
+        })
+        .await
+        .unwrap();
+
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        db.merge_guest(guest, bob).await.unwrap();
+
+        let mut members = db.get_group_members(group_id).await.unwrap();
+        members.sort();
+        assert_eq!(members, vec![alice, bob]);
+        assert_eq!(
+            db.get_expense_by_id(expense_id).await.unwrap().payer_id,
+            bob
+        );
+        let splits = db.get_expense_splits(expense_id).await.unwrap();
+        assert_eq!(splits[1].user_id, bob);
+        assert_eq!(splits[1].value, 2);
+        let received = db.get_group_transactions(group_id).await.unwrap();
+        assert_eq!(received[0].receiver_id, bob);
+        assert!(db.get_user(guest).await.is_err());
+        assert!(db.get_group_guests(group_id).await.unwrap().is_empty());
+    }
+
+    async fn test_failed_claim_leaves_no_user(db: Database) {
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group_with_owner(&Group::new(
+                "Trip",
+                alice,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        let invitation = db.create_guest(group_id, "Bob", alice).await.unwrap();
+        let bob = User::new("Bob", "bob@example.com", "password");
+
+        // The merge fails after the user and their memberships were written
+        fail_on(&db, "DELETE", "guests").await;
+        assert!(db
+            .register_user(&bob, Some(invitation.guest.user_id))
+            .await
+            .is_err());
+        assert!(db.get_user_by_email("bob@example.com").await.is_err());
+        assert_eq!(db.get_group_guests(group_id).await.unwrap().len(), 1);
+        assert_eq!(db.get_group_members(group_id).await.unwrap().len(), 2);
+
+        let bob = db.register_user(&bob, None).await.unwrap();
+        assert_eq!(db.get_user(bob).await.unwrap().name, "Bob");
+    }
+
+    /// Alice pays 90.00 split between her, Bob and a guest, then Bob claims the
+    /// guest. Returns the type and splits of the expense afterwards.
+    async fn claim_shared_expense(
+        db: &Database,
+        split_type: SplitType,
+        values: [i64; 3],
+    ) -> (SplitType, Vec<(u32, i64)>, u32, u32) {
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            alice,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, alice).await.unwrap();
+        db.add_user_to_group(group_id, bob).await.unwrap();
+        let guest = db
+            .create_guest(group_id, "Bob's phone", alice)
+            .await
+            .unwrap()
+            .guest
+            .user_id;
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Fuel".to_string(),
+                amount: Money::new(9000, "EUR"),
+                payer_id: alice,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type,
+            })
+            .await
+            .unwrap();
+        let splits: Vec<_> = [alice, bob, guest]
+            .into_iter()
+            .zip(values)
+            .map(|(user_id, value)| ParticipantSplit { user_id, value })
+            .collect();
+        db.add_expense_splits(expense_id, &splits).await.unwrap();
+
+        db.merge_guest(guest, bob).await.unwrap();
+        let split_type = db.get_expense_by_id(expense_id).await.unwrap().split_type;
+        let splits = db
+            .get_expense_splits(expense_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|split| (split.user_id, split.value))
+            .collect();
+        (split_type, splits, alice, bob)
+    }
+
+    async fn test_merge_guest_keeps_equal_split(db: Database) {
+        let (split_type, splits, alice, bob) =
+            claim_shared_expense(&db, SplitType::Equal, [0, 0, 0]).await;
+        // Bob still owes two thirds, not half
+        assert_eq!(split_type, SplitType::Exact);
+        assert_eq!(splits, vec![(alice, 3000), (bob, 6000)]);
+    }
+
+    async fn test_merge_guest_keeps_adjustment_split(db: Database) {
+        let (split_type, splits, alice, bob) =
+            claim_shared_expense(&db, SplitType::Adjustment, [1500, 0, 0]).await;
+        assert_eq!(split_type, SplitType::Exact);
+        assert_eq!(splits, vec![(alice, 4000), (bob, 5000)]);
+    }
+
+    async fn test_merge_guest_adds_up_shares(db: Database) {
+        let (split_type, splits, alice, bob) =
+            claim_shared_expense(&db, SplitType::Shares, [1, 1, 1]).await;
+        assert_eq!(split_type, SplitType::Exact);
+        assert_eq!(splits, vec![(alice, 3000), (bob, 6000)]);
+    }
+
+    async fn test_merge_guest_drops_transfers_between_them(db: Database) {
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        let group_id = db
+            .create_group_with_owner(&Group::new(
+                "Trip",
+                alice,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            ))
+            .await
+            .unwrap();
+        db.add_user_to_group(group_id, bob).await.unwrap();
+        let guest = db
+            .create_guest(group_id, "Alice's phone", alice)
+            .await
+            .unwrap()
+            .guest
+            .user_id;
+        db.create_transaction(&Transaction {
+            id: None,
+            payer_id: guest,
+            receiver_id: alice,
+            amount: Money::new(1000, "EUR"),
+            date: "2024-01-02".to_string(),
+            status: Status::Completed,
+            group_id,
+            plan_id: None,
+        })
+        .await
+        .unwrap();
+        let pair = |first_user_id, second_user_id| MemberPair {
+            first_user_id,
+            second_user_id,
+        };
+        db.set_settlement_constraint(
+            group_id,
+            &SettlementConstraint::AllowedPairs {
+                pairs: vec![pair(alice, guest), pair(bob, guest)],
+            },
+        )
+        .await
+        .unwrap();
+
+        // The owner claims the guest themselves
+        db.merge_guest(guest, alice).await.unwrap();
+        assert!(db
+            .get_group_transactions(group_id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.get_settlement_constraint(group_id).await.unwrap(),
+            Some(SettlementConstraint::AllowedPairs {
+                pairs: vec![pair(alice, bob)],
+            })
+        );
+    }
+
+    backend_tests!(
+        test_merge_guest_into_user,
+        test_failed_claim_leaves_no_user,
+        test_merge_guest_keeps_equal_split,
+        test_merge_guest_keeps_adjustment_split,
+        test_merge_guest_adds_up_shares,
+        test_merge_guest_drops_transfers_between_them
+    );
+}
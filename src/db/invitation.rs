@@ -0,0 +1,359 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{sqlite::SqliteRow, Row};
+use tracing::instrument;
+
+use crate::{
+    auth::utils::{generate_token, hash_token},
+    db::{repository::InvitationRepository, SqliteRepository},
+    models::invitation::{Invitation, JoinOutcome, JoinRequest, JoinRequestStatus, NewInvitation},
+};
+
+/// Adds a member, letting a former member back in as a regular member rather
+/// than failing.
+const REJOIN_GROUP: &str = "INSERT INTO group_members (group_id, user_id) VALUES (?, ?)
+    ON CONFLICT (group_id, user_id) DO UPDATE SET left_at = NULL, role = CASE WHEN left_at IS NULL THEN role ELSE 'member' END";
+
+fn invitation_from_row(row: SqliteRow) -> Invitation {
+    Invitation {
+        id: row.get("id"),
+        group_id: row.get("group_id"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        max_uses: row.get("max_uses"),
+        uses: row.get("uses"),
+        require_approval: row.get("require_approval"),
+        revoked: row.get("revoked"),
+    }
+}
+
+fn join_request_from_row(row: SqliteRow) -> Result<JoinRequest, sqlx::Error> {
+    Ok(JoinRequest {
+        id: row.get("id"),
+        group_id: row.get("group_id"),
+        user_id: row.get("user_id"),
+        invitation_id: row.get("invitation_id"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        created_at: row.get("created_at"),
+        decided_at: row.get("decided_at"),
+    })
+}
+
+#[async_trait]
+impl InvitationRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_invitation(
+        &self,
+        group_id: u32,
+        created_by: u32,
+        expires_at: DateTime<Utc>,
+        max_uses: Option<u32>,
+        require_approval: bool,
+    ) -> Result<NewInvitation, sqlx::Error> {
+        let code = generate_token();
+        let created_at = Utc::now();
+        let query = "INSERT INTO group_invitations (group_id, code, created_by, created_at, expires_at, max_uses, require_approval) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *";
+        let row = sqlx::query(query)
+            .bind(group_id)
+            .bind(hash_token(&code))
+            .bind(created_by)
+            .bind(created_at)
+            .bind(expires_at)
+            .bind(max_uses)
+            .bind(require_approval)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(NewInvitation {
+            invitation: invitation_from_row(row),
+            code,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_invitations(&self, group_id: u32) -> Result<Vec<Invitation>, sqlx::Error> {
+        let query = "SELECT * FROM group_invitations WHERE group_id = ? ORDER BY id DESC";
+        let rows = sqlx::query(query)
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(invitation_from_row).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn revoke_invitation(
+        &self,
+        group_id: u32,
+        invitation_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE group_invitations SET revoked = 1 WHERE id = ? AND group_id = ?";
+        let result = sqlx::query(query)
+            .bind(invitation_id)
+            .bind(group_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn redeem_invitation(
+        &self,
+        group_id: u32,
+        code: &str,
+        user_id: u32,
+    ) -> Result<JoinOutcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let query = "SELECT * FROM group_invitations WHERE code = ? AND group_id = ?";
+        let invitation = sqlx::query(query)
+            .bind(hash_token(code))
+            .bind(group_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map(invitation_from_row)?;
+        let now = Utc::now();
+        if invitation.expires_at <= now {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        // Counting the use in the same statement as the checks keeps two people
+        // from taking the last use of a code at once
+        let query = "UPDATE group_invitations SET uses = uses + 1 WHERE id = ? AND revoked = 0 AND (max_uses IS NULL OR uses < max_uses)";
+        let result = sqlx::query(query)
+            .bind(invitation.id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let outcome = if invitation.require_approval {
+            let query = "INSERT INTO join_requests (group_id, user_id, invitation_id, status, created_at) VALUES (?, ?, ?, ?, ?) RETURNING *";
+            let row = sqlx::query(query)
+                .bind(group_id)
+                .bind(user_id)
+                .bind(invitation.id)
+                .bind(JoinRequestStatus::Pending.to_string())
+                .bind(now)
+                .fetch_one(&mut *tx)
+                .await?;
+            JoinOutcome::Requested(join_request_from_row(row)?)
+        } else {
+            sqlx::query(REJOIN_GROUP)
+                .bind(group_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            JoinOutcome::Joined
+        };
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_pending_join_requests(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<JoinRequest>, sqlx::Error> {
+        let query = "SELECT * FROM join_requests WHERE group_id = ? AND status = ? ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(group_id)
+            .bind(JoinRequestStatus::Pending.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(join_request_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn decide_join_request(
+        &self,
+        group_id: u32,
+        request_id: u32,
+        approve: bool,
+    ) -> Result<JoinRequest, sqlx::Error> {
+        let status = if approve {
+            JoinRequestStatus::Approved
+        } else {
+            JoinRequestStatus::Rejected
+        };
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE join_requests SET status = ?, decided_at = ? WHERE id = ? AND group_id = ? AND status = ? RETURNING *";
+        let request = sqlx::query(query)
+            .bind(status.to_string())
+            .bind(Utc::now())
+            .bind(request_id)
+            .bind(group_id)
+            .bind(JoinRequestStatus::Pending.to_string())
+            .fetch_one(&mut *tx)
+            .await
+            .and_then(join_request_from_row)?;
+        if approve {
+            sqlx::query(REJOIN_GROUP)
+                .bind(group_id)
+                .bind(request.user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::{
+        db::tests::backend_tests,
+        models::{group::Group, user::User},
+    };
+    use chrono::TimeDelta;
+
+    async fn setup_group(db: Database) -> (Database, u32, Vec<u32>) {
+        let mut users = Vec::new();
+        for name in ["Owner", "Alice", "Bob"] {
+            let email = format!("{}@example.com", name.to_lowercase());
+            users.push(
+                db.create_user(&User::new(name, &email, "password"))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let group = Group::new(
+            "Trip",
+            users[0],
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, users[0]).await.unwrap();
+        (db, group_id, users)
+    }
+
+    async fn test_single_use_invitation(db: Database) {
+        let (db, group_id, users) = setup_group(db).await;
+        let expires_at = Utc::now() + TimeDelta::hours(1);
+        let new = db
+            .create_invitation(group_id, users[0], expires_at, Some(1), false)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            db.redeem_invitation(group_id, "wrong", users[1]).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        assert!(matches!(
+            db.redeem_invitation(group_id + 1, &new.code, users[1])
+                .await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        let outcome = db.redeem_invitation(group_id, &new.code, users[1]).await;
+        assert_eq!(outcome.unwrap(), JoinOutcome::Joined);
+        assert!(db
+            .get_group_members(group_id)
+            .await
+            .unwrap()
+            .contains(&users[1]));
+        // The only use is gone
+        assert!(matches!(
+            db.redeem_invitation(group_id, &new.code, users[2]).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        assert_eq!(db.get_group_invitations(group_id).await.unwrap()[0].uses, 1);
+    }
+
+    async fn test_expired_and_revoked_invitations(db: Database) {
+        let (db, group_id, users) = setup_group(db).await;
+        let expired = db
+            .create_invitation(group_id, users[0], Utc::now(), None, false)
+            .await
+            .unwrap();
+        assert!(db
+            .redeem_invitation(group_id, &expired.code, users[1])
+            .await
+            .is_err());
+
+        let revoked = db
+            .create_invitation(
+                group_id,
+                users[0],
+                Utc::now() + TimeDelta::hours(1),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        db.revoke_invitation(group_id, revoked.invitation.id)
+            .await
+            .unwrap();
+        assert!(db
+            .redeem_invitation(group_id, &revoked.code, users[1])
+            .await
+            .is_err());
+        assert!(db.get_group_invitations(group_id).await.unwrap()[0].revoked);
+        assert_eq!(db.get_group_members(group_id).await.unwrap().len(), 1);
+    }
+
+    async fn test_join_requests_need_approval(db: Database) {
+        let (db, group_id, users) = setup_group(db).await;
+        let new = db
+            .create_invitation(
+                group_id,
+                users[0],
+                Utc::now() + TimeDelta::hours(1),
+                None,
+                true,
+            )
+            .await
+            .unwrap();
+        let JoinOutcome::Requested(alice) = db
+            .redeem_invitation(group_id, &new.code, users[1])
+            .await
+            .unwrap()
+        else {
+            panic!("approval was required");
+        };
+        let JoinOutcome::Requested(bob) = db
+            .redeem_invitation(group_id, &new.code, users[2])
+            .await
+            .unwrap()
+        else {
+            panic!("approval was required");
+        };
+        assert_eq!(alice.status, JoinRequestStatus::Pending);
+        assert_eq!(db.get_group_members(group_id).await.unwrap().len(), 1);
+        assert_eq!(
+            db.get_pending_join_requests(group_id).await.unwrap().len(),
+            2
+        );
+
+        let approved = db
+            .decide_join_request(group_id, alice.id, true)
+            .await
+            .unwrap();
+        assert_eq!(approved.status, JoinRequestStatus::Approved);
+        assert!(approved.decided_at.is_some());
+        db.decide_join_request(group_id, bob.id, false)
+            .await
+            .unwrap();
+        let mut members = db.get_group_members(group_id).await.unwrap();
+        members.sort();
+        assert_eq!(members, vec![users[0], users[1]]);
+        assert!(matches!(
+            db.decide_join_request(group_id, bob.id, true).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    backend_tests!(
+        test_single_use_invitation,
+        test_expired_and_revoked_invitations,
+        test_join_requests_need_approval
+    );
+}
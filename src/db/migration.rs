@@ -0,0 +1,536 @@
+use core::fmt;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+use super::{repository::Repository, SqliteRepository};
+
+/// A versioned schema change. Applied migrations are recorded in the
+/// `schema_migrations` table and never edited afterwards; a later change gets a
+/// new migration.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    /// Undoes `up`; `None` if the migration cannot be rolled back.
+    pub down: Option<&'static str>,
+}
+
+/// All migrations in version order. The first one is the schema of the
+/// original `database.sql`; none of the current ones can be rolled back. A
+/// later migration ships an `.up.sql` and, where it can be undone, a
+/// `.down.sql` script in `migrations/`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        up: include_str!("migrations/001_initial_schema.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 2,
+        name: "token_lifecycle",
+        up: include_str!("migrations/002_token_lifecycle.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 3,
+        name: "split_modes",
+        up: include_str!("migrations/003_split_modes.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 4,
+        name: "integer_money",
+        up: include_str!("migrations/004_integer_money.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 5,
+        name: "exchange_rates",
+        up: include_str!("migrations/005_exchange_rates.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 6,
+        name: "settlement_plans",
+        up: include_str!("migrations/006_settlement_plans.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 7,
+        name: "payments",
+        up: include_str!("migrations/007_payments.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 8,
+        name: "settlement_strategy",
+        up: include_str!("migrations/008_settlement_strategy.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 9,
+        name: "settlement_constraints",
+        up: include_str!("migrations/009_settlement_constraints.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 10,
+        name: "guests",
+        up: include_str!("migrations/010_guests.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 11,
+        name: "invitations",
+        up: include_str!("migrations/011_invitations.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 12,
+        name: "member_removal",
+        up: include_str!("migrations/012_member_removal.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 13,
+        name: "member_roles",
+        up: include_str!("migrations/013_member_roles.up.sql"),
+        down: None,
+    },
+    Migration {
+        version: 14,
+        name: "group_archive",
+        up: include_str!("migrations/014_group_archive.up.sql"),
+        down: None,
+    },
+];
+
+const CREATE_SCHEMA_MIGRATIONS: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+  version INTEGER PRIMARY KEY,
+  name TEXT NOT NULL,
+  applied_at TEXT NOT NULL
+)";
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Database(sqlx::Error),
+    /// The migration has no down script; nothing was rolled back.
+    Irreversible {
+        version: i64,
+        name: &'static str,
+    },
+    /// The database has a migration this build does not know, so it was
+    /// migrated by a newer version of the server.
+    Unknown(i64),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Database(e) => write!(f, "{}", e),
+            MigrationError::Irreversible { version, name } => {
+                write!(
+                    f,
+                    "Migration {} ({}) cannot be rolled back; migrations are roll-forward only",
+                    version, name
+                )
+            }
+            MigrationError::Unknown(version) => write!(
+                f,
+                "Migration {} is applied to the database but unknown to this build",
+                version
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+/// A migration and when it was applied, if it was.
+#[derive(Debug, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+impl Repository for SqliteRepository {
+    /// A database created before migrations were tracked is recorded as having
+    /// the migrations it already has, so its data is kept.
+    #[instrument(level = "debug", skip_all)]
+    async fn migrate(&self) -> Result<Vec<&'static Migration>, MigrationError> {
+        self.migrate_with(MIGRATIONS).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn rollback(&self, steps: usize) -> Result<Vec<&'static Migration>, MigrationError> {
+        self.rollback_with(MIGRATIONS, steps).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, MigrationError> {
+        self.status_with(MIGRATIONS).await
+    }
+}
+
+impl SqliteRepository {
+    async fn migrate_with<'a>(
+        &self,
+        migrations: &'a [Migration],
+    ) -> Result<Vec<&'a Migration>, MigrationError> {
+        if !self.table_exists("schema_migrations").await? {
+            self.adopt_untracked_schema(migrations).await?;
+        }
+
+        let applied = self.applied_migrations().await?;
+        if let Some((version, _, _)) = applied
+            .iter()
+            .find(|(version, _, _)| !migrations.iter().any(|m| m.version == *version))
+        {
+            return Err(MigrationError::Unknown(*version));
+        }
+
+        let mut done = Vec::new();
+        for migration in migrations {
+            if applied
+                .iter()
+                .any(|(version, _, _)| *version == migration.version)
+            {
+                continue;
+            }
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(migration.up).execute(&mut *tx).await?;
+            record_migration(&mut tx, migration).await?;
+            tx.commit().await?;
+            done.push(migration);
+        }
+        Ok(done)
+    }
+
+    async fn rollback_with<'a>(
+        &self,
+        migrations: &'a [Migration],
+        steps: usize,
+    ) -> Result<Vec<&'a Migration>, MigrationError> {
+        let mut done = Vec::new();
+        if !self.table_exists("schema_migrations").await? {
+            return Ok(done);
+        }
+        let applied = self.applied_migrations().await?;
+        let versions = applied
+            .iter()
+            .rev()
+            .take(steps)
+            .map(|(version, _, _)| *version);
+        for (migration, down) in reversible(migrations, versions)? {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(down).execute(&mut *tx).await?;
+            sqlx::query("DELETE FROM schema_migrations WHERE version = ?")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            done.push(migration);
+        }
+        Ok(done)
+    }
+
+    async fn status_with(
+        &self,
+        migrations: &[Migration],
+    ) -> Result<Vec<MigrationStatus>, MigrationError> {
+        let applied = if self.table_exists("schema_migrations").await? {
+            self.applied_migrations().await?
+        } else {
+            Vec::new()
+        };
+        let mut status: Vec<MigrationStatus> = migrations
+            .iter()
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_string(),
+                applied_at: applied
+                    .iter()
+                    .find(|(version, _, _)| *version == migration.version)
+                    .map(|(_, _, applied_at)| *applied_at),
+            })
+            .collect();
+        status.extend(
+            applied
+                .into_iter()
+                .filter(|(version, _, _)| !migrations.iter().any(|m| m.version == *version))
+                .map(|(version, name, applied_at)| MigrationStatus {
+                    version,
+                    name,
+                    applied_at: Some(applied_at),
+                }),
+        );
+        Ok(status)
+    }
+
+    /// Applied migrations as `(version, name, applied_at)`, oldest first.
+    async fn applied_migrations(&self) -> Result<Vec<(i64, String, DateTime<Utc>)>, sqlx::Error> {
+        sqlx::query_as("SELECT version, name, applied_at FROM schema_migrations ORDER BY version")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn table_exists(&self, name: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+        )
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+
+    /// Creates the `schema_migrations` table. A database created from
+    /// `database.sql` before migrations were tracked counts the upgrades it got
+    /// since in the `user_version` pragma; it is recorded as having the initial
+    /// schema and that many migrations after it, and the pragma is not read again.
+    async fn adopt_untracked_schema(&self, migrations: &[Migration]) -> Result<(), sqlx::Error> {
+        let untracked = if self.table_exists("users").await? {
+            let upgrades: i64 = sqlx::query_scalar("PRAGMA user_version")
+                .fetch_one(&self.pool)
+                .await?;
+            upgrades as usize + 1
+        } else {
+            0
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(CREATE_SCHEMA_MIGRATIONS)
+            .execute(&mut *tx)
+            .await?;
+        for migration in migrations.iter().take(untracked) {
+            record_migration(&mut tx, migration).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// The migrations with the given applied `versions` and their down scripts, in
+/// the same order. Fails before anything is rolled back if one of them is
+/// unknown or has no down script.
+pub(crate) fn reversible(
+    migrations: &[Migration],
+    versions: impl Iterator<Item = i64>,
+) -> Result<Vec<(&Migration, &'static str)>, MigrationError> {
+    versions
+        .map(|version| {
+            let migration = migrations
+                .iter()
+                .find(|m| m.version == version)
+                .ok_or(MigrationError::Unknown(version))?;
+            let down = migration.down.ok_or(MigrationError::Irreversible {
+                version: migration.version,
+                name: migration.name,
+            })?;
+            Ok((migration, down))
+        })
+        .collect()
+}
+
+async fn record_migration(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    migration: &Migration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO schema_migrations (version, name, applied_at) VALUES (?, ?, ?)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(Utc::now())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        repository::{
+            ExchangeRateRepository, ExpenseRepository, GroupRepository, TransactionRepository,
+            UserRepository,
+        },
+        tests::IN_MEMORY_DB,
+        DEFAULT_MAX_CONNECTIONS,
+    };
+    use crate::models::{expenses::SplitType, money::Money};
+
+    /// [`MIGRATIONS`] followed by one that can be rolled back.
+    fn extended() -> Vec<Migration> {
+        let mut migrations: Vec<Migration> = MIGRATIONS
+            .iter()
+            .map(|m| Migration {
+                version: m.version,
+                name: m.name,
+                up: m.up,
+                down: m.down,
+            })
+            .collect();
+        migrations.push(Migration {
+            version: 15,
+            name: "nicknames",
+            up: "ALTER TABLE users ADD COLUMN nickname TEXT",
+            down: Some("ALTER TABLE users DROP COLUMN nickname"),
+        });
+        migrations
+    }
+
+    fn all_versions() -> Vec<i64> {
+        MIGRATIONS.iter().map(|m| m.version).collect()
+    }
+
+    fn versions(migrations: &[&Migration]) -> Vec<i64> {
+        migrations.iter().map(|m| m.version).collect()
+    }
+
+    #[tokio::test]
+    async fn test_migrate_fresh_database() {
+        let db = SqliteRepository::new(IN_MEMORY_DB, DEFAULT_MAX_CONNECTIONS)
+            .await
+            .unwrap();
+        assert_eq!(versions(&db.migrate().await.unwrap()), all_versions());
+        assert!(db.migrate().await.unwrap().is_empty());
+
+        let status = db.migration_status().await.unwrap();
+        assert_eq!(status.len(), MIGRATIONS.len());
+        assert_eq!(status[0].name, "initial_schema");
+        assert!(status.iter().all(|m| m.applied_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_and_roll_back() {
+        let db = SqliteRepository::new(IN_MEMORY_DB, DEFAULT_MAX_CONNECTIONS)
+            .await
+            .unwrap();
+        let extended = extended();
+        let mut expected = all_versions();
+        expected.push(15);
+        assert_eq!(
+            versions(&db.migrate_with(&extended).await.unwrap()),
+            expected
+        );
+        sqlx::query("INSERT INTO users (name, email, password, nickname) VALUES ('A', 'a@example.com', 'x', 'Al')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        // An older build refuses a database it does not fully understand
+        assert!(matches!(
+            db.migrate().await,
+            Err(MigrationError::Unknown(15))
+        ));
+        let status = db.status_with(MIGRATIONS).await.unwrap();
+        assert_eq!(status[MIGRATIONS.len()].name, "nicknames");
+
+        // Nothing is rolled back when one of the steps cannot be
+        assert!(matches!(
+            db.rollback_with(&extended, 2).await,
+            Err(MigrationError::Irreversible { version: 14, .. })
+        ));
+        assert!(db.status_with(&extended).await.unwrap()[14]
+            .applied_at
+            .is_some());
+
+        assert_eq!(
+            versions(&db.rollback_with(&extended, 1).await.unwrap()),
+            vec![15]
+        );
+        let status = db.status_with(&extended).await.unwrap();
+        assert!(status[13].applied_at.is_some());
+        assert!(status[14].applied_at.is_none());
+        assert!(matches!(
+            db.rollback_with(&extended, 1).await,
+            Err(MigrationError::Irreversible { version: 14, .. })
+        ));
+        assert_eq!(
+            versions(&db.migrate_with(&extended).await.unwrap()),
+            vec![15]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_adopt_untracked_database() {
+        // Created from the last database.sql, before migrations were tracked
+        let db = SqliteRepository::new(IN_MEMORY_DB, DEFAULT_MAX_CONNECTIONS)
+            .await
+            .unwrap();
+        for migration in MIGRATIONS {
+            sqlx::query(migration.up).execute(&db.pool).await.unwrap();
+        }
+        sqlx::query(&format!("PRAGMA user_version = {}", MIGRATIONS.len() - 1))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (name, email, password) VALUES ('A', 'a@example.com', 'x')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        assert!(db.migrate().await.unwrap().is_empty());
+        assert!(db
+            .migration_status()
+            .await
+            .unwrap()
+            .iter()
+            .all(|m| m.applied_at.is_some()));
+        assert!(db.get_user(1).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_legacy_schema() {
+        let db = SqliteRepository::new(IN_MEMORY_DB, DEFAULT_MAX_CONNECTIONS)
+            .await
+            .unwrap();
+        // Schema as it was before upgrades were tracked
+        sqlx::query(MIGRATIONS[0].up)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let legacy_data = "INSERT INTO users (name, email, password) VALUES ('Old', 'old@example.com', 'plain');
+            INSERT INTO api_tokens (token, user_id) VALUES ('legacy', 1);
+            INSERT INTO groups (name, description, owner_id, group_start_date, group_end_date, location) VALUES ('Trip', '', 1, '2024-01-01T00:00:00Z', '2024-01-02T00:00:00Z', '');
+            INSERT INTO group_members (group_id, user_id) VALUES (1, 1);
+            INSERT INTO expenses (description, amount, payer_id, group_id, date) VALUES ('Dinner', 12.5, 1, 1, '2024-01-01');
+            INSERT INTO expense_participants (expense_id, user_id) VALUES (1, 1);
+            INSERT INTO transactions (payer_id, receiver_id, amount, date, status, group_id) VALUES (1, 1, 0.1, '2024-01-01', 'pending', 1);";
+        sqlx::query(legacy_data).execute(&db.pool).await.unwrap();
+
+        assert_eq!(versions(&db.migrate().await.unwrap()), all_versions()[1..]);
+        let sessions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM api_tokens")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        // Legacy tokens are derived from the credentials and must not survive
+        assert_eq!(sessions, 0);
+        let expense = db.get_expense_by_id(1).await.unwrap();
+        assert_eq!(expense.split_type, SplitType::Equal);
+        assert_eq!(expense.amount, Money::new(1250, "EUR"));
+        assert_eq!(db.get_expense_participants(1).await.unwrap(), vec![1]);
+        let transaction = db.get_transaction(1).await.unwrap();
+        assert_eq!(transaction.amount, Money::new(10, "EUR"));
+        assert_eq!(transaction.plan_id, None);
+        let group = db.get_group(1).await.unwrap();
+        assert_eq!(group.base_currency, "EUR");
+        assert_eq!(
+            group.settlement_strategy,
+            crate::models::group::SettlementStrategy::MinimizeTransfers
+        );
+        assert!(db.get_group_exchange_rates(1).await.unwrap().is_empty());
+
+        // Running it again is a no-op
+        assert!(db.migrate().await.unwrap().is_empty());
+    }
+}
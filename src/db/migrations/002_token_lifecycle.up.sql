@@ -0,0 +1,16 @@
+CREATE TABLE api_tokens_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  token TEXT UNIQUE NOT NULL,
+  user_id INTEGER NOT NULL,
+  device_label TEXT,
+  created_at TEXT NOT NULL,
+  last_used_at TEXT NOT NULL,
+  expires_at TEXT NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+-- The old tokens are derived from the email and password, so they are not
+-- carried over; everyone logs in again to get a random one.
+DROP TABLE api_tokens;
+
+ALTER TABLE api_tokens_new RENAME TO api_tokens;
@@ -0,0 +1,4 @@
+ALTER TABLE expenses ADD COLUMN split_type TEXT NOT NULL DEFAULT 'equal'
+  CHECK (split_type IN ('equal', 'exact', 'percentage', 'shares', 'adjustment'));
+
+ALTER TABLE expense_participants ADD COLUMN split_value REAL NOT NULL DEFAULT 0;
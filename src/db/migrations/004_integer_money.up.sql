@@ -0,0 +1,68 @@
+-- Amounts move from REAL to INTEGER minor units. Existing data is in euros.
+-- SQLite cannot change a column type, so the tables are rebuilt; the new child
+-- table references expenses_new, which the rename below turns into expenses.
+
+CREATE TABLE expenses_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  description TEXT,
+  amount INTEGER NOT NULL,
+  currency TEXT NOT NULL,
+  payer_id INTEGER NOT NULL,
+  group_id INTEGER NOT NULL,
+  date TEXT NOT NULL,
+  split_type TEXT NOT NULL DEFAULT 'equal' CHECK (split_type IN ('equal', 'exact', 'percentage', 'shares', 'adjustment')),
+  FOREIGN KEY (payer_id) REFERENCES users(id),
+  FOREIGN KEY (group_id, payer_id) REFERENCES group_members(group_id, user_id),
+  FOREIGN KEY (group_id) REFERENCES groups(id)
+);
+
+INSERT INTO expenses_new (id, description, amount, currency, payer_id, group_id, date, split_type)
+SELECT id, description, CAST(ROUND(amount * 100) AS INTEGER), 'EUR', payer_id, group_id, date, split_type
+FROM expenses;
+
+CREATE TABLE expense_participants_new (
+  expense_id INTEGER NOT NULL,
+  user_id INTEGER NOT NULL,
+  split_value INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (expense_id, user_id),
+  FOREIGN KEY (expense_id) REFERENCES expenses_new(id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+-- Exact amounts and adjustments become cents, percentages become basis points
+INSERT INTO expense_participants_new (expense_id, user_id, split_value)
+SELECT p.expense_id, p.user_id,
+  CASE e.split_type
+    WHEN 'shares' THEN CAST(ROUND(p.split_value) AS INTEGER)
+    ELSE CAST(ROUND(p.split_value * 100) AS INTEGER)
+  END
+FROM expense_participants p
+JOIN expenses e ON e.id = p.expense_id;
+
+CREATE TABLE transactions_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  payer_id INTEGER NOT NULL,
+  receiver_id INTEGER NOT NULL,
+  amount INTEGER NOT NULL,
+  currency TEXT NOT NULL,
+  date TEXT NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('pending', 'completed')),
+  group_id INTEGER NOT NULL,
+  FOREIGN KEY (payer_id) REFERENCES users(id),
+  FOREIGN KEY (receiver_id) REFERENCES users(id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (group_id, payer_id) REFERENCES group_members(group_id, user_id),
+  FOREIGN KEY (group_id, receiver_id) REFERENCES group_members(group_id, user_id)
+);
+
+INSERT INTO transactions_new (id, payer_id, receiver_id, amount, currency, date, status, group_id)
+SELECT id, payer_id, receiver_id, CAST(ROUND(amount * 100) AS INTEGER), 'EUR', date, status, group_id
+FROM transactions;
+
+DROP TABLE expense_participants;
+DROP TABLE transactions;
+DROP TABLE expenses;
+
+ALTER TABLE expenses_new RENAME TO expenses;
+ALTER TABLE expense_participants_new RENAME TO expense_participants;
+ALTER TABLE transactions_new RENAME TO transactions;
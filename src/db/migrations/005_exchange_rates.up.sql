@@ -0,0 +1,18 @@
+-- Groups get a base currency, taken from their existing expenses, which share
+-- a single currency up to this version.
+ALTER TABLE groups ADD COLUMN base_currency TEXT NOT NULL DEFAULT 'EUR';
+
+UPDATE groups SET base_currency = (
+  SELECT currency FROM expenses WHERE expenses.group_id = groups.id ORDER BY id LIMIT 1
+)
+WHERE EXISTS (SELECT 1 FROM expenses WHERE expenses.group_id = groups.id);
+
+CREATE TABLE exchange_rates (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  group_id INTEGER,
+  from_currency TEXT NOT NULL,
+  to_currency TEXT NOT NULL,
+  rate_micros INTEGER NOT NULL CHECK (rate_micros > 0),
+  effective_date TEXT NOT NULL,
+  FOREIGN KEY (group_id) REFERENCES groups(id)
+);
@@ -0,0 +1,38 @@
+-- Settlement transfers belong to a versioned plan and can be superseded by a
+-- newer one. The status CHECK constraint changes, so transactions is rebuilt.
+
+CREATE TABLE settlement_plans (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  group_id INTEGER NOT NULL,
+  version INTEGER NOT NULL,
+  created_by INTEGER NOT NULL,
+  created_at TEXT NOT NULL,
+  UNIQUE (group_id, version),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE TABLE transactions_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  payer_id INTEGER NOT NULL,
+  receiver_id INTEGER NOT NULL,
+  amount INTEGER NOT NULL,
+  currency TEXT NOT NULL,
+  date TEXT NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('pending', 'completed', 'superseded')),
+  group_id INTEGER NOT NULL,
+  plan_id INTEGER,
+  FOREIGN KEY (plan_id) REFERENCES settlement_plans(id),
+  FOREIGN KEY (payer_id) REFERENCES users(id),
+  FOREIGN KEY (receiver_id) REFERENCES users(id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (group_id, payer_id) REFERENCES group_members(group_id, user_id),
+  FOREIGN KEY (group_id, receiver_id) REFERENCES group_members(group_id, user_id)
+);
+
+INSERT INTO transactions_new (id, payer_id, receiver_id, amount, currency, date, status, group_id)
+SELECT id, payer_id, receiver_id, amount, currency, date, status, group_id
+FROM transactions;
+
+DROP TABLE transactions;
+ALTER TABLE transactions_new RENAME TO transactions;
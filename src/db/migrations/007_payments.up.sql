@@ -0,0 +1,27 @@
+-- Payments recorded by the payer wait for the receiver's confirmation in the
+-- new 'paid' status. The status CHECK constraint changes, so transactions is rebuilt.
+
+CREATE TABLE transactions_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  payer_id INTEGER NOT NULL,
+  receiver_id INTEGER NOT NULL,
+  amount INTEGER NOT NULL,
+  currency TEXT NOT NULL,
+  date TEXT NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('pending', 'paid', 'completed', 'superseded')),
+  group_id INTEGER NOT NULL,
+  plan_id INTEGER,
+  FOREIGN KEY (plan_id) REFERENCES settlement_plans(id),
+  FOREIGN KEY (payer_id) REFERENCES users(id),
+  FOREIGN KEY (receiver_id) REFERENCES users(id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (group_id, payer_id) REFERENCES group_members(group_id, user_id),
+  FOREIGN KEY (group_id, receiver_id) REFERENCES group_members(group_id, user_id)
+);
+
+INSERT INTO transactions_new (id, payer_id, receiver_id, amount, currency, date, status, group_id, plan_id)
+SELECT id, payer_id, receiver_id, amount, currency, date, status, group_id, plan_id
+FROM transactions;
+
+DROP TABLE transactions;
+ALTER TABLE transactions_new RENAME TO transactions;
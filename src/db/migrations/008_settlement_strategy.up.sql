@@ -0,0 +1,2 @@
+ALTER TABLE groups ADD COLUMN settlement_strategy TEXT NOT NULL DEFAULT 'minimize_transfers'
+  CHECK (settlement_strategy IN ('minimize_transfers', 'direct', 'minimize_amount'));
@@ -0,0 +1,17 @@
+CREATE TABLE settlement_constraints (
+  group_id INTEGER PRIMARY KEY,
+  kind TEXT NOT NULL CHECK (kind IN ('allowed_pairs', 'forbidden_pairs', 'hub', 'through_owner')),
+  hub_user_id INTEGER,
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (hub_user_id) REFERENCES users(id)
+);
+
+CREATE TABLE settlement_constraint_pairs (
+  group_id INTEGER NOT NULL,
+  first_user_id INTEGER NOT NULL,
+  second_user_id INTEGER NOT NULL,
+  PRIMARY KEY (group_id, first_user_id, second_user_id),
+  FOREIGN KEY (group_id) REFERENCES settlement_constraints(group_id),
+  FOREIGN KEY (first_user_id) REFERENCES users(id),
+  FOREIGN KEY (second_user_id) REFERENCES users(id)
+);
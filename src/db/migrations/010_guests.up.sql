@@ -0,0 +1,10 @@
+CREATE TABLE guests (
+  user_id INTEGER PRIMARY KEY,
+  group_id INTEGER NOT NULL,
+  claim_token TEXT UNIQUE NOT NULL,
+  created_by INTEGER NOT NULL,
+  created_at TEXT NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (created_by) REFERENCES users(id)
+);
@@ -0,0 +1,3 @@
+-- Members who leave or are removed keep their row, because expenses and
+-- transactions reference it; left_at marks them as former members.
+ALTER TABLE group_members ADD COLUMN left_at TEXT;
@@ -0,0 +1,4 @@
+-- The owner keeps being whoever groups.owner_id names; every other member
+-- gets a role, starting out as a regular member.
+ALTER TABLE group_members ADD COLUMN role TEXT NOT NULL DEFAULT 'member'
+  CHECK (role IN ('admin', 'member', 'viewer'));
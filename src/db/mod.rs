@@ -1,42 +1,332 @@
-use std::time::Duration;
+use std::{ops::Deref, str::FromStr, time::Duration};
 
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Pool, Sqlite,
+};
 
+use self::{postgres::PostgresRepository, repository::Repository};
+
+pub mod exchange_rate;
 pub mod expense;
 pub mod group;
+pub mod guest;
+pub mod invitation;
+pub mod migration;
+pub mod postgres;
+pub mod repository;
+pub mod settlement_constraint;
+pub mod settlement_plan;
 pub mod transaction;
 pub mod user;
+
+/// Size of the connection pool unless configured otherwise.
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 50;
+
+/// The storage of the server. Every repository method is available on it
+/// through [`Repository`], whichever backend it is connected to.
 #[derive(Clone)]
-pub struct Database {
-    pub pool: Pool<Sqlite>,
+pub enum Database {
+    Sqlite(SqliteRepository),
+    Postgres(PostgresRepository),
 }
 
 impl Database {
+    /// Opens the SQLite database at `path`, creating it if needed.
     pub async fn new(path: &str) -> Result<Self, sqlx::Error> {
+        Self::connect(path, DEFAULT_MAX_CONNECTIONS).await
+    }
+
+    /// Connects to the database at `url` with a pool of up to `max_connections`.
+    /// `postgres://` and `postgresql://` URLs select PostgreSQL; anything else is
+    /// a SQLite URL or file path.
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            let repository = PostgresRepository::new(url, max_connections).await?;
+            return Ok(Database::Postgres(repository));
+        }
+        let path = url.strip_prefix("sqlite:").unwrap_or(url);
+        Ok(Database::Sqlite(
+            SqliteRepository::new(path, max_connections).await?,
+        ))
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Repository;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Database::Sqlite(repository) => repository,
+            Database::Postgres(repository) => repository,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SqliteRepository {
+    pub pool: Pool<Sqlite>,
+}
+
+impl SqliteRepository {
+    pub async fn new(path: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
         let pool = SqlitePoolOptions::new()
             .acquire_timeout(Duration::from_secs(10))
-            .max_connections(50)
-            .connect(&format!("sqlite:{}", path))
+            .max_connections(max_connections)
+            .connect_with(
+                SqliteConnectOptions::from_str(&format!("sqlite:{}", path))?
+                    .create_if_missing(true),
+            )
             .await?;
 
         Ok(Self { pool })
     }
-    pub async fn init(&self) -> Result<(), sqlx::Error> {
-        let schema = include_str!("../db/database.sql");
-        sqlx::query(schema).execute(&self.pool).await?;
-        Ok(())
-    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     pub const IN_MEMORY_DB: &str = ":memory:";
 
+    use std::{
+        path::PathBuf,
+        process::{Command, Stdio},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            OnceLock,
+        },
+    };
+
+    use sqlx::{Connection, PgConnection};
+
     use super::*;
 
-    #[tokio::test]
-    async fn test_database_new() {
+    /// Runs each of the given `async fn(Database)` tests against a fresh, migrated
+    /// database of every backend, as `sqlite::<name>` and `postgres::<name>`.
+    /// Without a PostgreSQL server the `postgres` ones pass without doing anything.
+    macro_rules! backend_tests {
+        ($($name:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        super::$name($crate::db::tests::sqlite().await).await;
+                    }
+                )*
+            }
+
+            mod postgres {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        $crate::db::tests::with_postgres(super::$name).await;
+                    }
+                )*
+            }
+        };
+    }
+    pub(crate) use backend_tests;
+
+    pub async fn sqlite() -> Database {
         let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
+        db.migrate().await.unwrap();
+        db
     }
+
+    /// Runs `test` on a migrated database of its own on the test PostgreSQL
+    /// server, which is dropped afterwards.
+    pub async fn with_postgres<F, Fut>(test: F)
+    where
+        F: FnOnce(Database) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        static DATABASES: AtomicUsize = AtomicUsize::new(0);
+        let Some(server) = postgres_server() else {
+            return;
+        };
+        let name = format!(
+            "trip_split_test_{}_{}",
+            std::process::id(),
+            DATABASES.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut admin = PgConnection::connect(server).await.unwrap();
+        sqlx::query(&format!("CREATE DATABASE {name}"))
+            .execute(&mut admin)
+            .await
+            .unwrap();
+        let url = database_url(server, &name);
+        let db = Database::connect(&url, DEFAULT_MAX_CONNECTIONS)
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+
+        // Spawned so that the database is dropped even if the test fails
+        let result = tokio::spawn(test(db.clone())).await;
+        if let Database::Postgres(repository) = db {
+            repository.pool.close().await;
+        }
+        sqlx::query(&format!("DROP DATABASE {name} WITH (FORCE)"))
+            .execute(&mut admin)
+            .await
+            .unwrap();
+        if let Err(e) = result {
+            std::panic::resume_unwind(e.into_panic());
+        }
+    }
+
+    /// `url` with its database replaced by `name`.
+    fn database_url(url: &str, name: &str) -> String {
+        let (base, params) = url.split_once('?').unwrap_or((url, ""));
+        let (server, _) = base.rsplit_once('/').unwrap();
+        match params {
+            "" => format!("{server}/{name}"),
+            params => format!("{server}/{name}?{params}"),
+        }
+    }
+
+    /// URL of the maintenance database of the PostgreSQL server to test against.
+    ///
+    /// `TEST_POSTGRES_URL` names an existing server. Otherwise one is created in
+    /// the temporary directory with `initdb` and started with `pg_ctl`, listening
+    /// on a unix socket only; later test runs reuse it. `None` if neither works.
+    fn postgres_server() -> Option<&'static str> {
+        static SERVER: OnceLock<Option<String>> = OnceLock::new();
+        SERVER
+            .get_or_init(|| {
+                std::env::var("TEST_POSTGRES_URL")
+                    .ok()
+                    .or_else(start_postgres_server)
+            })
+            .as_deref()
+    }
+
+    fn start_postgres_server() -> Option<String> {
+        let dir = std::env::temp_dir().join("trip_split_postgres");
+        let run = |command: &mut Command| {
+            command
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        if !dir.join("PG_VERSION").exists()
+            && !run(Command::new("initdb").arg("-D").arg(&dir).args([
+                "-U",
+                "postgres",
+                "--auth=trust",
+                "--no-sync",
+            ]))
+        {
+            return None;
+        }
+        let socket_dir: PathBuf = dir.join("socket");
+        std::fs::create_dir_all(&socket_dir).ok()?;
+        let running = run(Command::new("pg_ctl").arg("status").arg("-D").arg(&dir));
+        if !running
+            && !run(Command::new("pg_ctl")
+                .arg("start")
+                .arg("-w")
+                .arg("-D")
+                .arg(&dir)
+                .arg("-l")
+                .arg(dir.join("server.log"))
+                .arg("-o")
+                .arg(format!(
+                    "-c listen_addresses='' -c unix_socket_directories='{}' -c max_connections=200",
+                    socket_dir.display()
+                )))
+        {
+            return None;
+        }
+        Some(format!(
+            "postgres://postgres@localhost/postgres?host={}",
+            socket_dir.display()
+        ))
+    }
+
+    /// Runs a statement without parameters, whichever the backend.
+    pub async fn execute(db: &Database, statement: &str) {
+        match db {
+            Database::Sqlite(repository) => {
+                sqlx::raw_sql(statement)
+                    .execute(&repository.pool)
+                    .await
+                    .unwrap();
+            }
+            Database::Postgres(repository) => {
+                sqlx::raw_sql(statement)
+                    .execute(&repository.pool)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Runs a query without parameters returning a single value, whichever the
+    /// backend.
+    pub async fn fetch_scalar<T>(db: &Database, query: &str) -> T
+    where
+        T: Send + Unpin,
+        (T,): for<'r> sqlx::FromRow<'r, sqlx::sqlite::SqliteRow>
+            + for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow>,
+    {
+        match db {
+            Database::Sqlite(repository) => sqlx::query_scalar(query)
+                .fetch_one(&repository.pool)
+                .await
+                .unwrap(),
+            Database::Postgres(repository) => sqlx::query_scalar(query)
+                .fetch_one(&repository.pool)
+                .await
+                .unwrap(),
+        }
+    }
+
+    /// Makes every `event` (`INSERT`, `UPDATE` or `DELETE`) on `table` fail, to
+    /// check that the write around it is rolled back as a whole.
+    pub async fn fail_on(db: &Database, event: &str, table: &str) {
+        let trigger = match db {
+            Database::Sqlite(_) => format!(
+                "CREATE TRIGGER fail_{event}_{table} BEFORE {event} ON {table} BEGIN SELECT RAISE(ABORT, 'injected failure'); END"
+            ),
+            Database::Postgres(_) => format!(
+                "CREATE FUNCTION fail_{event}_{table}() RETURNS trigger AS $$ BEGIN RAISE EXCEPTION 'injected failure'; END $$ LANGUAGE plpgsql;
+                CREATE TRIGGER fail_{event}_{table} BEFORE {event} ON {table} FOR EACH ROW EXECUTE FUNCTION fail_{event}_{table}()"
+            ),
+        };
+        execute(db, &trigger).await;
+    }
+
+    async fn test_database_new(db: Database) {
+        let status = db.migration_status().await.unwrap();
+        assert!(status
+            .iter()
+            .all(|migration| migration.applied_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_connect_by_url() {
+        assert!(matches!(
+            Database::connect("sqlite::memory:", DEFAULT_MAX_CONNECTIONS)
+                .await
+                .unwrap(),
+            Database::Sqlite(_)
+        ));
+        assert!(matches!(
+            Database::connect(IN_MEMORY_DB, DEFAULT_MAX_CONNECTIONS)
+                .await
+                .unwrap(),
+            Database::Sqlite(_)
+        ));
+    }
+
+    async fn test_rollback_is_roll_forward_only(db: Database) {
+        let latest = db.migration_status().await.unwrap().pop().unwrap();
+        assert!(matches!(
+            db.rollback(1).await,
+            Err(migration::MigrationError::Irreversible { version, .. }) if version == latest.version
+        ));
+        assert!(db.migrate().await.unwrap().is_empty());
+    }
+
+    backend_tests!(test_database_new, test_rollback_is_roll_forward_only);
 }
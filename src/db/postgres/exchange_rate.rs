@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_optional_u32, get_u32, PostgresRepository};
+use crate::{
+    db::repository::ExchangeRateRepository,
+    models::{exchange_rate::ExchangeRate, money::RATE_SCALE},
+};
+
+fn exchange_rate_from_row(row: PgRow) -> ExchangeRate {
+    ExchangeRate {
+        id: Some(get_u32(&row, "id")),
+        group_id: get_optional_u32(&row, "group_id"),
+        from_currency: row.get("from_currency"),
+        to_currency: row.get("to_currency"),
+        rate_micros: row.get("rate_micros"),
+        effective_date: row.get("effective_date"),
+    }
+}
+
+#[async_trait]
+impl ExchangeRateRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_exchange_rate(&self, rate: &ExchangeRate) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO exchange_rates (group_id, from_currency, to_currency, rate_micros, effective_date) VALUES ($1, $2, $3, $4, $5) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(rate.group_id.map(i64::from))
+            .bind(&rate.from_currency)
+            .bind(&rate.to_currency)
+            .bind(rate.rate_micros)
+            .bind(rate.effective_date)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(get_u32(&row, "id"))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_exchange_rates(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<ExchangeRate>, sqlx::Error> {
+        let query =
+            "SELECT * FROM exchange_rates WHERE group_id = $1 ORDER BY effective_date DESC, id DESC";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(exchange_rate_from_row).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn replace_global_exchange_rates(
+        &self,
+        rates: &[ExchangeRate],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM exchange_rates WHERE group_id IS NULL")
+            .execute(&mut *tx)
+            .await?;
+        let query = "INSERT INTO exchange_rates (group_id, from_currency, to_currency, rate_micros, effective_date) VALUES (NULL, $1, $2, $3, $4)";
+        for rate in rates {
+            sqlx::query(query)
+                .bind(&rate.from_currency)
+                .bind(&rate.to_currency)
+                .bind(rate.rate_micros)
+                .bind(rate.effective_date)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_exchange_rate(
+        &self,
+        group_id: u32,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        Self::fetch_exchange_rate(&mut *self.pool.acquire().await?, group_id, from, to, date).await
+    }
+}
+
+impl PostgresRepository {
+    /// `find_exchange_rate` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_exchange_rate(
+        conn: &mut PgConnection,
+        group_id: u32,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        if from == to {
+            return Ok(Some(RATE_SCALE));
+        }
+        let query = "SELECT * FROM exchange_rates
+            WHERE (group_id = $1 OR group_id IS NULL)
+              AND ((from_currency = $2 AND to_currency = $3) OR (from_currency = $3 AND to_currency = $2))
+              AND effective_date <= $4
+            ORDER BY group_id IS NULL, effective_date DESC, from_currency = $2 DESC, id DESC
+            LIMIT 1";
+        let rate = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(from)
+            .bind(to)
+            .bind(date)
+            .fetch_optional(conn)
+            .await?
+            .map(exchange_rate_from_row);
+        Ok(rate.map(|rate| rate.micros_from(from)))
+    }
+}
@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_u32, PostgresRepository};
+use crate::{
+    db::repository::ExpenseRepository,
+    expense::split::merge_participant,
+    models::{
+        expenses::{Expense, ParticipantSplit, SplitType},
+        money::Money,
+    },
+};
+
+fn expense_from_row(row: PgRow) -> Result<Expense, sqlx::Error> {
+    Ok(Expense {
+        id: Some(get_u32(&row, "id")),
+        description: row.get("description"),
+        amount: Money::new(row.get("amount"), row.get("currency")),
+        payer_id: get_u32(&row, "payer_id"),
+        group_id: get_u32(&row, "group_id"),
+        date: row.get("date"),
+        split_type: split_type_from_row(&row)?,
+    })
+}
+
+fn split_type_from_row(row: &PgRow) -> Result<SplitType, sqlx::Error> {
+    row.get::<String, _>("split_type")
+        .parse()
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+#[async_trait]
+impl ExpenseRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_expense(&self, expense: &Expense) -> Result<u32, sqlx::Error> {
+        Self::insert_expense(&mut *self.pool.acquire().await?, expense).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_expense_with_splits(
+        &self,
+        expense: &Expense,
+        splits: &[ParticipantSplit],
+    ) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::insert_expense(&mut tx, expense).await?;
+        Self::insert_expense_splits(&mut tx, id, splits).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expenses_by_group_id(&self, group_id: u32) -> Result<Vec<Expense>, sqlx::Error> {
+        Self::fetch_group_expenses(&mut *self.pool.acquire().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expenses_by_payer_id(&self, payer_id: u32) -> Result<Vec<Expense>, sqlx::Error> {
+        let query = "SELECT * FROM expenses WHERE payer_id = $1 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(payer_id))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(expense_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn add_participants_to_expense(
+        &self,
+        expense_id: u32,
+        users_ids: Vec<u32>,
+    ) -> Result<(), sqlx::Error> {
+        let splits: Vec<ParticipantSplit> = users_ids
+            .into_iter()
+            .map(|user_id| ParticipantSplit { user_id, value: 0 })
+            .collect();
+        self.add_expense_splits(expense_id, &splits).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expense_participants(&self, expense_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+        let query =
+            "SELECT user_id FROM expense_participants WHERE expense_id = $1 ORDER BY user_id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(expense_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| get_u32(row, "user_id")).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn add_expense_splits(
+        &self,
+        expense_id: u32,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_expense_splits(&mut tx, expense_id, splits).await?;
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expense_splits(
+        &self,
+        expense_id: u32,
+    ) -> Result<Vec<ParticipantSplit>, sqlx::Error> {
+        Self::fetch_expense_splits(&mut *self.pool.acquire().await?, expense_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_all_user_expenses(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+        let query =
+            "SELECT expense_id FROM expense_participants WHERE user_id = $1 ORDER BY expense_id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(user_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| get_u32(row, "expense_id")).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expense_by_id(&self, expense_id: u32) -> Result<Expense, sqlx::Error> {
+        let query = "SELECT * FROM expenses WHERE id = $1";
+        let row = sqlx::query(query)
+            .bind(i64::from(expense_id))
+            .fetch_one(&self.pool)
+            .await?;
+        expense_from_row(row)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_expenses_by_ids(
+        &self,
+        expenses_ids: Vec<u32>,
+    ) -> Result<Vec<Expense>, sqlx::Error> {
+        let mut expenses = Vec::new();
+        for expense_id in expenses_ids {
+            expenses.push(self.get_expense_by_id(expense_id).await?);
+        }
+        Ok(expenses)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_expense(
+        &self,
+        expense_id: u32,
+        expense: &Expense,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE expenses SET description = $1, amount = $2, currency = $3, payer_id = $4, date = $5, split_type = $6 WHERE id = $7";
+        let result = sqlx::query(query)
+            .bind(&expense.description)
+            .bind(expense.amount.minor_units)
+            .bind(&expense.amount.currency)
+            .bind(i64::from(expense.payer_id))
+            .bind(&expense.date)
+            .bind(expense.split_type.to_string())
+            .bind(i64::from(expense_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        sqlx::query("DELETE FROM expense_participants WHERE expense_id = $1")
+            .bind(i64::from(expense_id))
+            .execute(&mut *tx)
+            .await?;
+        Self::insert_expense_splits(&mut tx, expense_id, splits).await?;
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_expense(&self, expense_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM expense_participants WHERE expense_id = $1")
+            .bind(i64::from(expense_id))
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM expenses WHERE id = $1")
+            .bind(i64::from(expense_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+}
+
+impl PostgresRepository {
+    /// Inserts an expense on `conn`, which may be a transaction of the caller.
+    pub async fn insert_expense(
+        conn: &mut PgConnection,
+        expense: &Expense,
+    ) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO expenses (description, amount, currency, payer_id, group_id, date, split_type) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(&expense.description)
+            .bind(expense.amount.minor_units)
+            .bind(&expense.amount.currency)
+            .bind(i64::from(expense.payer_id))
+            .bind(i64::from(expense.group_id))
+            .bind(&expense.date)
+            .bind(expense.split_type.to_string())
+            .fetch_one(conn)
+            .await?;
+        Ok(get_u32(&row, "id"))
+    }
+
+    /// Inserts the participants of an expense with their split values on `conn`,
+    /// which may be a transaction of the caller.
+    pub async fn insert_expense_splits(
+        conn: &mut PgConnection,
+        expense_id: u32,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error> {
+        for split in splits {
+            let query = "INSERT INTO expense_participants (expense_id, user_id, split_value) VALUES ($1, $2, $3)";
+            sqlx::query(query)
+                .bind(i64::from(expense_id))
+                .bind(i64::from(split.user_id))
+                .bind(split.value)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// `get_expenses_by_group_id` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_group_expenses(
+        conn: &mut PgConnection,
+        group_id: u32,
+    ) -> Result<Vec<Expense>, sqlx::Error> {
+        let query = "SELECT * FROM expenses WHERE group_id = $1 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(conn)
+            .await?;
+        rows.into_iter().map(expense_from_row).collect()
+    }
+
+    /// `get_expense_splits` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_expense_splits(
+        conn: &mut PgConnection,
+        expense_id: u32,
+    ) -> Result<Vec<ParticipantSplit>, sqlx::Error> {
+        let query = "SELECT user_id, split_value FROM expense_participants WHERE expense_id = $1 ORDER BY user_id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(expense_id))
+            .fetch_all(conn)
+            .await?;
+        let splits = rows
+            .into_iter()
+            .map(|row| ParticipantSplit {
+                user_id: get_u32(&row, "user_id"),
+                value: row.get("split_value"),
+            })
+            .collect();
+        Ok(splits)
+    }
+
+    /// Hands the part `from_user_id` has in the expenses of `group_id`, or of
+    /// every group, over to `to_user_id` on `conn`, which may be a transaction
+    /// of the caller. Expenses both take part in become exact splits so that
+    /// what everyone else owes stays the same; see [`merge_participant`].
+    pub async fn merge_expense_participant(
+        conn: &mut PgConnection,
+        from_user_id: u32,
+        to_user_id: u32,
+        group_id: Option<u32>,
+    ) -> Result<(), sqlx::Error> {
+        let query = "SELECT id, amount, currency, split_type FROM expenses
+            WHERE ($3::BIGINT IS NULL OR group_id = $3)
+              AND id IN (SELECT expense_id FROM expense_participants WHERE user_id = $1)
+              AND id IN (SELECT expense_id FROM expense_participants WHERE user_id = $2)";
+        let shared = sqlx::query(query)
+            .bind(i64::from(from_user_id))
+            .bind(i64::from(to_user_id))
+            .bind(group_id.map(i64::from))
+            .fetch_all(&mut *conn)
+            .await?;
+        for row in shared {
+            let expense_id = get_u32(&row, "id");
+            let amount = Money::new(row.get("amount"), row.get("currency"));
+            let split_type = split_type_from_row(&row)?;
+            let splits = Self::fetch_expense_splits(conn, expense_id).await?;
+            let merged = merge_participant(&amount, &split_type, &splits, from_user_id, to_user_id)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            sqlx::query("UPDATE expenses SET split_type = $1 WHERE id = $2")
+                .bind(SplitType::Exact.to_string())
+                .bind(i64::from(expense_id))
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("DELETE FROM expense_participants WHERE expense_id = $1")
+                .bind(i64::from(expense_id))
+                .execute(&mut *conn)
+                .await?;
+            Self::insert_expense_splits(conn, expense_id, &merged).await?;
+        }
+        let query = "UPDATE expense_participants SET user_id = $2 WHERE user_id = $1
+            AND expense_id IN (SELECT id FROM expenses WHERE $3::BIGINT IS NULL OR group_id = $3)";
+        sqlx::query(query)
+            .bind(i64::from(from_user_id))
+            .bind(i64::from(to_user_id))
+            .bind(group_id.map(i64::from))
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_u32, PostgresRepository};
+use crate::{
+    db::repository::GroupRepository,
+    models::{
+        expenses::Status,
+        group::{Group, GroupLedger, LedgerExpense, MemberRemoval, SettlementStrategy},
+        money::RATE_SCALE,
+        role::GroupRole,
+    },
+    summary::group_balances,
+};
+
+/// Current members of a group with their role; the owner's comes from the group.
+const MEMBER_ROLES: &str = "SELECT group_members.user_id, CASE WHEN groups.owner_id = group_members.user_id THEN 'owner' ELSE group_members.role END AS role
+    FROM group_members JOIN groups ON groups.id = group_members.group_id
+    WHERE group_members.group_id = $1 AND group_members.left_at IS NULL";
+
+fn group_from_row(row: PgRow) -> Result<Group, sqlx::Error> {
+    Ok(Group {
+        id: Some(get_u32(&row, "id")),
+        name: row.get("name"),
+        owner_id: get_u32(&row, "owner_id"),
+        group_start_date: row.get("group_start_date"),
+        group_end_date: row.get("group_end_date"),
+        description: row.get("description"),
+        location: row.get("location"),
+        base_currency: row.get("base_currency"),
+        settlement_strategy: row
+            .get::<String, _>("settlement_strategy")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        archived_at: row.get("archived_at"),
+    })
+}
+
+fn role_from_row(row: &PgRow) -> Result<GroupRole, sqlx::Error> {
+    row.get::<String, _>("role")
+        .parse()
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+#[async_trait]
+impl GroupRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_group(&self, group: &Group) -> Result<u32, sqlx::Error> {
+        Self::insert_group(&mut *self.pool.acquire().await?, group).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_group_with_owner(&self, group: &Group) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::insert_group(&mut tx, group).await?;
+        Self::insert_group_member(&mut tx, id, group.owner_id).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group(&self, group_id: u32) -> Result<Group, sqlx::Error> {
+        let query = "SELECT * FROM groups WHERE id = $1";
+        let row = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_one(&self.pool)
+            .await?;
+        group_from_row(row)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_groups_by_owner_id(&self, owner_id: u32) -> Result<Vec<Group>, sqlx::Error> {
+        let query = "SELECT * FROM groups WHERE owner_id = $1 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(owner_id))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(group_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_settlement_strategy(
+        &self,
+        group_id: u32,
+        strategy: SettlementStrategy,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE groups SET settlement_strategy = $1 WHERE id = $2";
+        let result = sqlx::query(query)
+            .bind(strategy.to_string())
+            .bind(i64::from(group_id))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_group(&self, group: &Group) -> Result<(), sqlx::Error> {
+        let query = "UPDATE groups SET name = $1, description = $2, location = $3, group_start_date = $4, group_end_date = $5 WHERE id = $6";
+        let result = sqlx::query(query)
+            .bind(&group.name)
+            .bind(&group.description)
+            .bind(&group.location)
+            .bind(group.group_start_date)
+            .bind(group.group_end_date)
+            .bind(group.id.map(i64::from))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_group_archived(
+        &self,
+        group_id: u32,
+        archived_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE groups SET archived_at = $1 WHERE id = $2";
+        let result = sqlx::query(query)
+            .bind(archived_at)
+            .bind(i64::from(group_id))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_group(&self, group_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let guest_ids: Vec<i64> =
+            sqlx::query_scalar("SELECT user_id FROM guests WHERE group_id = $1")
+                .bind(i64::from(group_id))
+                .fetch_all(&mut *tx)
+                .await?;
+        for query in [
+            "DELETE FROM expense_participants WHERE expense_id IN (SELECT id FROM expenses WHERE group_id = $1)",
+            "DELETE FROM expenses WHERE group_id = $1",
+            "DELETE FROM transactions WHERE group_id = $1",
+            "DELETE FROM settlement_plans WHERE group_id = $1",
+            "DELETE FROM exchange_rates WHERE group_id = $1",
+            "DELETE FROM settlement_constraint_pairs WHERE group_id = $1",
+            "DELETE FROM settlement_constraints WHERE group_id = $1",
+            "DELETE FROM join_requests WHERE group_id = $1",
+            "DELETE FROM group_invitations WHERE group_id = $1",
+            "DELETE FROM guests WHERE group_id = $1",
+            "DELETE FROM group_members WHERE group_id = $1",
+        ] {
+            sqlx::query(query)
+                .bind(i64::from(group_id))
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(guest_ids)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM groups WHERE id = $1")
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn add_user_to_group(&self, group_id: u32, user_id: u32) -> Result<(), sqlx::Error> {
+        Self::insert_group_member(&mut *self.pool.acquire().await?, group_id, user_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_members(&self, group_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+        Self::fetch_group_members(&mut *self.pool.acquire().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_groups(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error> {
+        let query = "SELECT group_id FROM group_members WHERE user_id = $1 AND left_at IS NULL ORDER BY group_id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(user_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| get_u32(row, "group_id")).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_ledger(&self, group_id: u32) -> Result<GroupLedger, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *tx)
+            .await?;
+        Self::fetch_group_ledger(&mut tx, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn remove_group_member(
+        &self,
+        group_id: u32,
+        user_id: u32,
+        reassign_to: Option<u32>,
+    ) -> Result<MemberRemoval, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // Expenses and payments reference the group, so locking it keeps them
+        // from being added until the user has left
+        sqlx::query("SELECT id FROM groups WHERE id = $1 FOR UPDATE")
+            .bind(i64::from(group_id))
+            .fetch_one(&mut *tx)
+            .await?;
+        match reassign_to {
+            Some(to_user_id) => {
+                Self::reassign_member_to(&mut tx, group_id, user_id, to_user_id).await?
+            }
+            None => {
+                let ledger = Self::fetch_group_ledger(&mut tx, group_id).await?;
+                let balance = group_balances(&ledger)?
+                    .into_iter()
+                    .find(|balance| balance.user_id == user_id);
+                if let Some(balance) = balance.filter(|b| b.net_balance.minor_units != 0) {
+                    return Ok(MemberRemoval::OutstandingBalance(balance.net_balance));
+                }
+            }
+        }
+        let query = "UPDATE group_members SET left_at = $1 WHERE group_id = $2 AND user_id = $3 AND left_at IS NULL";
+        let result = sqlx::query(query)
+            .bind(Utc::now())
+            .bind(i64::from(group_id))
+            .bind(i64::from(user_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        let query = "UPDATE transactions SET status = $1 WHERE group_id = $2 AND status = $3 AND (payer_id = $4 OR receiver_id = $4)";
+        sqlx::query(query)
+            .bind(Status::Superseded.to_string())
+            .bind(i64::from(group_id))
+            .bind(Status::Pending.to_string())
+            .bind(i64::from(user_id))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(MemberRemoval::Removed)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_group_owner(&self, group_id: u32, owner_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE group_members SET role = $1 WHERE group_id = $2 AND user_id = (SELECT owner_id FROM groups WHERE id = $2)";
+        sqlx::query(query)
+            .bind(GroupRole::Admin.to_string())
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        let query = "UPDATE groups SET owner_id = $1 WHERE id = $2";
+        let result = sqlx::query(query)
+            .bind(i64::from(owner_id))
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_member_role(&self, group_id: u32, user_id: u32) -> Result<GroupRole, sqlx::Error> {
+        let query = format!("{MEMBER_ROLES} AND group_members.user_id = $2");
+        let row = sqlx::query(&query)
+            .bind(i64::from(group_id))
+            .bind(i64::from(user_id))
+            .fetch_one(&self.pool)
+            .await?;
+        role_from_row(&row)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_member_roles(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<(u32, GroupRole)>, sqlx::Error> {
+        let query = format!("{MEMBER_ROLES} ORDER BY group_members.user_id");
+        let rows = sqlx::query(&query)
+            .bind(i64::from(group_id))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter()
+            .map(|row| Ok((get_u32(&row, "user_id"), role_from_row(&row)?)))
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_member_role(
+        &self,
+        group_id: u32,
+        user_id: u32,
+        role: GroupRole,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE group_members SET role = $1 WHERE group_id = $2 AND user_id = $3 AND left_at IS NULL";
+        let result = sqlx::query(query)
+            .bind(role.to_string())
+            .bind(i64::from(group_id))
+            .bind(i64::from(user_id))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl PostgresRepository {
+    /// Inserts a group on `conn`, which may be a transaction of the caller.
+    pub async fn insert_group(conn: &mut PgConnection, group: &Group) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO groups (name, description, owner_id, group_start_date, group_end_date, location, base_currency, settlement_strategy) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(&group.name)
+            .bind(&group.description)
+            .bind(i64::from(group.owner_id))
+            .bind(group.group_start_date)
+            .bind(group.group_end_date)
+            .bind(&group.location)
+            .bind(&group.base_currency)
+            .bind(group.settlement_strategy.to_string())
+            .fetch_one(conn)
+            .await?;
+        Ok(get_u32(&row, "id"))
+    }
+
+    /// `add_user_to_group` on `conn`, which may be a transaction of the caller.
+    pub async fn insert_group_member(
+        conn: &mut PgConnection,
+        group_id: u32,
+        user_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE group_members SET left_at = NULL, role = 'member' WHERE group_id = $1 AND user_id = $2 AND left_at IS NOT NULL";
+        let result = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(i64::from(user_id))
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() > 0 {
+            return Ok(());
+        }
+        let query = "INSERT INTO group_members (group_id, user_id) VALUES ($1, $2)";
+        sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(i64::from(user_id))
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Hands everything `from_user_id` has in the group over to `to_user_id`
+    /// on `conn`, which may be a transaction of the caller; see
+    /// `remove_group_member`.
+    pub async fn reassign_member_to(
+        conn: &mut PgConnection,
+        group_id: u32,
+        from_user_id: u32,
+        to_user_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE expenses SET payer_id = $2 WHERE payer_id = $1 AND group_id = $3")
+            .bind(i64::from(from_user_id))
+            .bind(i64::from(to_user_id))
+            .bind(i64::from(group_id))
+            .execute(&mut *conn)
+            .await?;
+        Self::merge_expense_participant(conn, from_user_id, to_user_id, Some(group_id)).await?;
+        for query in [
+            "UPDATE transactions SET payer_id = $2 WHERE payer_id = $1 AND group_id = $3",
+            "UPDATE transactions SET receiver_id = $2 WHERE receiver_id = $1 AND group_id = $3",
+        ] {
+            sqlx::query(query)
+                .bind(i64::from(from_user_id))
+                .bind(i64::from(to_user_id))
+                .bind(i64::from(group_id))
+                .execute(&mut *conn)
+                .await?;
+        }
+        // Payments between the two would now go from the new member to themselves
+        sqlx::query(
+            "DELETE FROM transactions WHERE payer_id = $1 AND receiver_id = $1 AND group_id = $2",
+        )
+        .bind(i64::from(to_user_id))
+        .bind(i64::from(group_id))
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// `get_group_ledger` on `conn`, which should be a transaction so that
+    /// everything is read at the same point in time.
+    pub async fn fetch_group_ledger(
+        conn: &mut PgConnection,
+        group_id: u32,
+    ) -> Result<GroupLedger, sqlx::Error> {
+        let group = sqlx::query("SELECT * FROM groups WHERE id = $1")
+            .bind(i64::from(group_id))
+            .fetch_one(&mut *conn)
+            .await
+            .and_then(group_from_row)?;
+        let base_currency = &group.base_currency;
+        let members = Self::fetch_group_members(conn, group_id).await?;
+        let mut expenses = Vec::new();
+        for expense in Self::fetch_group_expenses(conn, group_id).await? {
+            let currency = &expense.amount.currency;
+            let rate_micros = match expense.day() {
+                Some(day) => {
+                    Self::fetch_exchange_rate(conn, group_id, currency, base_currency, day).await?
+                }
+                None if currency == base_currency => Some(RATE_SCALE),
+                None => None,
+            };
+            let splits = Self::fetch_expense_splits(conn, expense.id.unwrap()).await?;
+            expenses.push(LedgerExpense {
+                expense,
+                rate_micros,
+                splits,
+            });
+        }
+        let payments =
+            Self::fetch_group_transactions_by_status(conn, group_id, Status::Completed).await?;
+        let constraint = Self::fetch_settlement_constraint(conn, group_id).await?;
+        Ok(GroupLedger {
+            group,
+            members,
+            expenses,
+            payments,
+            constraint,
+        })
+    }
+
+    /// `get_group_members` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_group_members(
+        conn: &mut PgConnection,
+        group_id: u32,
+    ) -> Result<Vec<u32>, sqlx::Error> {
+        let query = "SELECT user_id FROM group_members WHERE group_id = $1 AND left_at IS NULL ORDER BY user_id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(conn)
+            .await?;
+        Ok(rows.iter().map(|row| get_u32(row, "user_id")).collect())
+    }
+}
@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use chrono::{SubsecRound, Utc};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_u32, PostgresRepository};
+use crate::{
+    auth::{
+        password::hash_password,
+        utils::{generate_token, hash_token},
+    },
+    db::repository::GuestRepository,
+    models::guest::{Guest, GuestInvitation, GUEST_EMAIL_DOMAIN},
+};
+
+fn guest_from_row(row: PgRow) -> Guest {
+    Guest {
+        user_id: get_u32(&row, "user_id"),
+        group_id: get_u32(&row, "group_id"),
+        name: row.get("name"),
+        created_by: get_u32(&row, "created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[async_trait]
+impl GuestRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_guest(
+        &self,
+        group_id: u32,
+        name: &str,
+        created_by: u32,
+    ) -> Result<GuestInvitation, sqlx::Error> {
+        let claim_token = generate_token();
+        // Nobody knows the password, and the reserved domain keeps the address
+        // out of the way of real users.
+        let email = format!("{}@{}", generate_token(), GUEST_EMAIL_DOMAIN);
+        let password_hash =
+            hash_password(&generate_token()).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        // Stored to the microsecond
+        let created_at = Utc::now().trunc_subsecs(6);
+
+        let mut tx = self.pool.begin().await?;
+        let query = "INSERT INTO users (name, email, password) VALUES ($1, $2, $3) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(name)
+            .bind(email)
+            .bind(password_hash)
+            .fetch_one(&mut *tx)
+            .await?;
+        let user_id = get_u32(&row, "id");
+        sqlx::query("INSERT INTO group_members (group_id, user_id) VALUES ($1, $2)")
+            .bind(i64::from(group_id))
+            .bind(i64::from(user_id))
+            .execute(&mut *tx)
+            .await?;
+        let query = "INSERT INTO guests (user_id, group_id, claim_token, created_by, created_at) VALUES ($1, $2, $3, $4, $5)";
+        sqlx::query(query)
+            .bind(i64::from(user_id))
+            .bind(i64::from(group_id))
+            .bind(hash_token(&claim_token))
+            .bind(i64::from(created_by))
+            .bind(created_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(GuestInvitation {
+            guest: Guest {
+                user_id,
+                group_id,
+                name: name.to_string(),
+                created_by,
+                created_at,
+            },
+            claim_token,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_guests(&self, group_id: u32) -> Result<Vec<Guest>, sqlx::Error> {
+        let query = "SELECT guests.*, users.name FROM guests JOIN users ON users.id = guests.user_id WHERE guests.group_id = $1 ORDER BY guests.user_id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(guest_from_row).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_guest_by_claim_token(&self, claim_token: &str) -> Result<Guest, sqlx::Error> {
+        let query = "SELECT guests.*, users.name FROM guests JOIN users ON users.id = guests.user_id WHERE guests.claim_token = $1";
+        let row = sqlx::query(query)
+            .bind(hash_token(claim_token))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(guest_from_row(row))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn merge_guest(&self, guest_id: u32, user_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::merge_guest_into(&mut tx, guest_id, user_id).await?;
+        tx.commit().await
+    }
+}
+
+impl PostgresRepository {
+    /// `merge_guest` on `conn`, which may be a transaction of the caller.
+    pub async fn merge_guest_into(
+        conn: &mut PgConnection,
+        guest_id: u32,
+        user_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        // Memberships first: expenses and transactions reference them. Groups the
+        // guest was removed from are only kept for their history.
+        for query in [
+            "INSERT INTO group_members (group_id, user_id, left_at, role) SELECT group_id, $1, left_at, role FROM group_members WHERE user_id = $2
+            ON CONFLICT (group_id, user_id) DO UPDATE SET left_at = CASE WHEN excluded.left_at IS NULL THEN NULL ELSE group_members.left_at END",
+            "UPDATE expenses SET payer_id = $1 WHERE payer_id = $2",
+        ] {
+            sqlx::query(query)
+                .bind(i64::from(user_id))
+                .bind(i64::from(guest_id))
+                .execute(&mut *conn)
+                .await?;
+        }
+        Self::merge_expense_participant(conn, guest_id, user_id, None).await?;
+        let queries = [
+            "UPDATE transactions SET payer_id = $1 WHERE payer_id = $2",
+            "UPDATE transactions SET receiver_id = $1 WHERE receiver_id = $2",
+            "UPDATE settlement_constraints SET hub_user_id = $1 WHERE hub_user_id = $2",
+            // Pairs are stored lowest id first, which the user may not keep
+            "INSERT INTO settlement_constraint_pairs (group_id, first_user_id, second_user_id)
+            SELECT group_id, LEAST(other_user_id, $1), GREATEST(other_user_id, $1) FROM (
+                SELECT group_id, second_user_id AS other_user_id FROM settlement_constraint_pairs WHERE first_user_id = $2
+                UNION ALL
+                SELECT group_id, first_user_id FROM settlement_constraint_pairs WHERE second_user_id = $2) guest_pairs
+            ON CONFLICT DO NOTHING",
+            "DELETE FROM settlement_constraint_pairs WHERE first_user_id = $2 OR second_user_id = $2",
+            "DELETE FROM group_members WHERE user_id = $2",
+            "DELETE FROM guests WHERE user_id = $2",
+            "DELETE FROM users WHERE id = $2",
+        ];
+        for query in queries {
+            sqlx::query(query)
+                .bind(i64::from(user_id))
+                .bind(i64::from(guest_id))
+                .execute(&mut *conn)
+                .await?;
+        }
+        // Payments between the guest and the user are now the user paying
+        // themselves, and a pair of the two no longer constrains anything
+        for query in [
+            "DELETE FROM transactions WHERE payer_id = $1 AND receiver_id = $1",
+            "DELETE FROM settlement_constraint_pairs WHERE first_user_id = $1 AND second_user_id = $1",
+        ] {
+            sqlx::query(query)
+                .bind(i64::from(user_id))
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, Row};
+use tracing::instrument;
+
+use super::{get_optional_u32, get_u32, PostgresRepository};
+use crate::{
+    auth::utils::{generate_token, hash_token},
+    db::repository::InvitationRepository,
+    models::invitation::{Invitation, JoinOutcome, JoinRequest, JoinRequestStatus, NewInvitation},
+};
+
+/// Adds a member, letting a former member back in as a regular member rather
+/// than failing.
+const REJOIN_GROUP: &str = "INSERT INTO group_members (group_id, user_id) VALUES ($1, $2)
+    ON CONFLICT (group_id, user_id) DO UPDATE SET left_at = NULL, role = CASE WHEN group_members.left_at IS NULL THEN group_members.role ELSE 'member' END";
+
+fn invitation_from_row(row: PgRow) -> Invitation {
+    Invitation {
+        id: get_u32(&row, "id"),
+        group_id: get_u32(&row, "group_id"),
+        created_by: get_u32(&row, "created_by"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        max_uses: get_optional_u32(&row, "max_uses"),
+        uses: get_u32(&row, "uses"),
+        require_approval: row.get("require_approval"),
+        revoked: row.get("revoked"),
+    }
+}
+
+fn join_request_from_row(row: PgRow) -> Result<JoinRequest, sqlx::Error> {
+    Ok(JoinRequest {
+        id: get_u32(&row, "id"),
+        group_id: get_u32(&row, "group_id"),
+        user_id: get_u32(&row, "user_id"),
+        invitation_id: get_u32(&row, "invitation_id"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        created_at: row.get("created_at"),
+        decided_at: row.get("decided_at"),
+    })
+}
+
+#[async_trait]
+impl InvitationRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_invitation(
+        &self,
+        group_id: u32,
+        created_by: u32,
+        expires_at: DateTime<Utc>,
+        max_uses: Option<u32>,
+        require_approval: bool,
+    ) -> Result<NewInvitation, sqlx::Error> {
+        let code = generate_token();
+        let query = "INSERT INTO group_invitations (group_id, code, created_by, created_at, expires_at, max_uses, require_approval) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *";
+        let row = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(hash_token(&code))
+            .bind(i64::from(created_by))
+            .bind(Utc::now())
+            .bind(expires_at)
+            .bind(max_uses.map(i64::from))
+            .bind(require_approval)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(NewInvitation {
+            invitation: invitation_from_row(row),
+            code,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_invitations(&self, group_id: u32) -> Result<Vec<Invitation>, sqlx::Error> {
+        let query = "SELECT * FROM group_invitations WHERE group_id = $1 ORDER BY id DESC";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(invitation_from_row).collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn revoke_invitation(
+        &self,
+        group_id: u32,
+        invitation_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE group_invitations SET revoked = TRUE WHERE id = $1 AND group_id = $2";
+        let result = sqlx::query(query)
+            .bind(i64::from(invitation_id))
+            .bind(i64::from(group_id))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn redeem_invitation(
+        &self,
+        group_id: u32,
+        code: &str,
+        user_id: u32,
+    ) -> Result<JoinOutcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let query = "SELECT * FROM group_invitations WHERE code = $1 AND group_id = $2";
+        let invitation = sqlx::query(query)
+            .bind(hash_token(code))
+            .bind(i64::from(group_id))
+            .fetch_one(&mut *tx)
+            .await
+            .map(invitation_from_row)?;
+        let now = Utc::now();
+        if invitation.expires_at <= now {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        // Counting the use in the same statement as the checks keeps two people
+        // from taking the last use of a code at once
+        let query = "UPDATE group_invitations SET uses = uses + 1 WHERE id = $1 AND NOT revoked AND (max_uses IS NULL OR uses < max_uses)";
+        let result = sqlx::query(query)
+            .bind(i64::from(invitation.id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let outcome = if invitation.require_approval {
+            let query = "INSERT INTO join_requests (group_id, user_id, invitation_id, status, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *";
+            let row = sqlx::query(query)
+                .bind(i64::from(group_id))
+                .bind(i64::from(user_id))
+                .bind(i64::from(invitation.id))
+                .bind(JoinRequestStatus::Pending.to_string())
+                .bind(now)
+                .fetch_one(&mut *tx)
+                .await?;
+            JoinOutcome::Requested(join_request_from_row(row)?)
+        } else {
+            sqlx::query(REJOIN_GROUP)
+                .bind(i64::from(group_id))
+                .bind(i64::from(user_id))
+                .execute(&mut *tx)
+                .await?;
+            JoinOutcome::Joined
+        };
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_pending_join_requests(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<JoinRequest>, sqlx::Error> {
+        let query = "SELECT * FROM join_requests WHERE group_id = $1 AND status = $2 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(JoinRequestStatus::Pending.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(join_request_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn decide_join_request(
+        &self,
+        group_id: u32,
+        request_id: u32,
+        approve: bool,
+    ) -> Result<JoinRequest, sqlx::Error> {
+        let status = if approve {
+            JoinRequestStatus::Approved
+        } else {
+            JoinRequestStatus::Rejected
+        };
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE join_requests SET status = $1, decided_at = $2 WHERE id = $3 AND group_id = $4 AND status = $5 RETURNING *";
+        let request = sqlx::query(query)
+            .bind(status.to_string())
+            .bind(Utc::now())
+            .bind(i64::from(request_id))
+            .bind(i64::from(group_id))
+            .bind(JoinRequestStatus::Pending.to_string())
+            .fetch_one(&mut *tx)
+            .await
+            .and_then(join_request_from_row)?;
+        if approve {
+            sqlx::query(REJOIN_GROUP)
+                .bind(i64::from(group_id))
+                .bind(i64::from(request.user_id))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(request)
+    }
+}
@@ -0,0 +1,186 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres, Transaction};
+use tracing::instrument;
+
+use super::PostgresRepository;
+use crate::db::{
+    migration::{reversible, Migration, MigrationError, MigrationStatus},
+    repository::Repository,
+};
+
+/// All migrations in version order. PostgreSQL support came after the SQLite
+/// migrations up to version 14, so its baseline creates that schema at once and
+/// takes version 14; later migrations share versions and names with the SQLite
+/// ones in [`crate::db::migration::MIGRATIONS`].
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 14,
+    name: "baseline",
+    up: include_str!("migrations/014_baseline.up.sql"),
+    down: None,
+}];
+
+const CREATE_SCHEMA_MIGRATIONS: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+  version BIGINT PRIMARY KEY,
+  name TEXT NOT NULL,
+  applied_at TIMESTAMPTZ NOT NULL
+)";
+
+/// Key of the advisory lock held while migrating, so that replicas starting at
+/// the same time apply each migration once; "trip_spl" in ASCII.
+const MIGRATION_LOCK: i64 = 0x7472_6970_5f73_706c;
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn migrate(&self) -> Result<Vec<&'static Migration>, MigrationError> {
+        self.migrate_with(MIGRATIONS).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn rollback(&self, steps: usize) -> Result<Vec<&'static Migration>, MigrationError> {
+        self.rollback_with(MIGRATIONS, steps).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, MigrationError> {
+        let mut tx = self.pool.begin().await?;
+        let applied = applied_migrations(&mut tx).await?;
+        let mut status: Vec<MigrationStatus> = MIGRATIONS
+            .iter()
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_string(),
+                applied_at: applied
+                    .iter()
+                    .find(|(version, _, _)| *version == migration.version)
+                    .map(|(_, _, applied_at)| *applied_at),
+            })
+            .collect();
+        status.extend(
+            applied
+                .into_iter()
+                .filter(|(version, _, _)| !MIGRATIONS.iter().any(|m| m.version == *version))
+                .map(|(version, name, applied_at)| MigrationStatus {
+                    version,
+                    name,
+                    applied_at: Some(applied_at),
+                }),
+        );
+        Ok(status)
+    }
+}
+
+impl PostgresRepository {
+    /// Applies the pending `migrations` in a single transaction.
+    async fn migrate_with<'a>(
+        &self,
+        migrations: &'a [Migration],
+    ) -> Result<Vec<&'a Migration>, MigrationError> {
+        let mut tx = self.locked_transaction().await?;
+        let applied = applied_migrations(&mut tx).await?;
+        if let Some((version, _, _)) = applied
+            .iter()
+            .find(|(version, _, _)| !migrations.iter().any(|m| m.version == *version))
+        {
+            return Err(MigrationError::Unknown(*version));
+        }
+
+        let mut done = Vec::new();
+        for migration in migrations {
+            if applied
+                .iter()
+                .any(|(version, _, _)| *version == migration.version)
+            {
+                continue;
+            }
+            tx.execute(sqlx::raw_sql(migration.up)).await?;
+            sqlx::query(
+                "INSERT INTO schema_migrations (version, name, applied_at) VALUES ($1, $2, $3)",
+            )
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+            done.push(migration);
+        }
+        tx.commit().await?;
+        Ok(done)
+    }
+
+    async fn rollback_with<'a>(
+        &self,
+        migrations: &'a [Migration],
+        steps: usize,
+    ) -> Result<Vec<&'a Migration>, MigrationError> {
+        let mut tx = self.locked_transaction().await?;
+        let mut done = Vec::new();
+        let applied = applied_migrations(&mut tx).await?;
+        let versions = applied
+            .iter()
+            .rev()
+            .take(steps)
+            .map(|(version, _, _)| *version);
+        for (migration, down) in reversible(migrations, versions)? {
+            tx.execute(sqlx::raw_sql(down)).await?;
+            sqlx::query("DELETE FROM schema_migrations WHERE version = $1")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+            done.push(migration);
+        }
+        tx.commit().await?;
+        Ok(done)
+    }
+
+    /// A transaction holding the migration lock, with the `schema_migrations`
+    /// table created if needed.
+    async fn locked_transaction(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(MIGRATION_LOCK)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(CREATE_SCHEMA_MIGRATIONS)
+            .execute(&mut *tx)
+            .await?;
+        Ok(tx)
+    }
+}
+
+/// Applied migrations as `(version, name, applied_at)`, oldest first; none if
+/// the `schema_migrations` table does not exist yet.
+async fn applied_migrations(
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<(i64, String, DateTime<Utc>)>, sqlx::Error> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('schema_migrations') IS NOT NULL")
+        .fetch_one(&mut **tx)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+    sqlx::query_as("SELECT version, name, applied_at FROM schema_migrations ORDER BY version")
+        .fetch_all(&mut **tx)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migration;
+
+    #[test]
+    fn test_migrations_match_sqlite() {
+        let names = |migrations: &[Migration]| {
+            migrations
+                .iter()
+                .map(|m| (m.version, m.name, m.down.is_some()))
+                .collect::<Vec<_>>()
+        };
+        let baseline = MIGRATIONS[0].version;
+        let sqlite = migration::MIGRATIONS;
+        let later = sqlite.iter().position(|m| m.version == baseline).unwrap() + 1;
+        assert_eq!(names(&MIGRATIONS[1..]), names(&sqlite[later..]));
+    }
+}
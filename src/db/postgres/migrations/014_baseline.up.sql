@@ -0,0 +1,162 @@
+CREATE TABLE users (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  name TEXT NOT NULL,
+  email TEXT UNIQUE NOT NULL,
+  password TEXT NOT NULL
+);
+
+CREATE TABLE groups (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  name TEXT NOT NULL,
+  description TEXT NOT NULL,
+  owner_id BIGINT NOT NULL,
+  group_start_date TIMESTAMPTZ NOT NULL,
+  group_end_date TIMESTAMPTZ NOT NULL,
+  location TEXT NOT NULL,
+  base_currency TEXT NOT NULL DEFAULT 'EUR',
+  settlement_strategy TEXT NOT NULL DEFAULT 'minimize_transfers' CHECK (settlement_strategy IN ('minimize_transfers', 'direct', 'minimize_amount')),
+  archived_at TIMESTAMPTZ,
+  FOREIGN KEY (owner_id) REFERENCES users(id)
+);
+
+CREATE TABLE group_members (
+  group_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL,
+  left_at TIMESTAMPTZ,
+  role TEXT NOT NULL DEFAULT 'member' CHECK (role IN ('admin', 'member', 'viewer')),
+  PRIMARY KEY (group_id, user_id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE expenses (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  description TEXT,
+  amount BIGINT NOT NULL,
+  currency TEXT NOT NULL,
+  payer_id BIGINT NOT NULL,
+  group_id BIGINT NOT NULL,
+  date TEXT NOT NULL,
+  split_type TEXT NOT NULL DEFAULT 'equal' CHECK (split_type IN ('equal', 'exact', 'percentage', 'shares', 'adjustment')),
+  FOREIGN KEY (payer_id) REFERENCES users(id),
+  FOREIGN KEY (group_id, payer_id) REFERENCES group_members(group_id, user_id),
+  FOREIGN KEY (group_id) REFERENCES groups(id)
+);
+
+CREATE TABLE expense_participants (
+  expense_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL,
+  split_value BIGINT NOT NULL DEFAULT 0,
+  PRIMARY KEY (expense_id, user_id),
+  FOREIGN KEY (expense_id) REFERENCES expenses(id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE settlement_plans (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  group_id BIGINT NOT NULL,
+  version BIGINT NOT NULL,
+  created_by BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  UNIQUE (group_id, version),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE TABLE transactions (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  payer_id BIGINT NOT NULL,
+  receiver_id BIGINT NOT NULL,
+  amount BIGINT NOT NULL,
+  currency TEXT NOT NULL,
+  date TEXT NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('pending', 'paid', 'completed', 'superseded')),
+  group_id BIGINT NOT NULL,
+  plan_id BIGINT,
+  FOREIGN KEY (plan_id) REFERENCES settlement_plans(id),
+  FOREIGN KEY (payer_id) REFERENCES users(id),
+  FOREIGN KEY (receiver_id) REFERENCES users(id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (group_id, payer_id) REFERENCES group_members(group_id, user_id),
+  FOREIGN KEY (group_id, receiver_id) REFERENCES group_members(group_id, user_id)
+);
+
+CREATE TABLE exchange_rates (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  group_id BIGINT,
+  from_currency TEXT NOT NULL,
+  to_currency TEXT NOT NULL,
+  rate_micros BIGINT NOT NULL CHECK (rate_micros > 0),
+  effective_date DATE NOT NULL,
+  FOREIGN KEY (group_id) REFERENCES groups(id)
+);
+
+CREATE TABLE settlement_constraints (
+  group_id BIGINT PRIMARY KEY,
+  kind TEXT NOT NULL CHECK (kind IN ('allowed_pairs', 'forbidden_pairs', 'hub', 'through_owner')),
+  hub_user_id BIGINT,
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (hub_user_id) REFERENCES users(id)
+);
+
+CREATE TABLE settlement_constraint_pairs (
+  group_id BIGINT NOT NULL,
+  first_user_id BIGINT NOT NULL,
+  second_user_id BIGINT NOT NULL,
+  PRIMARY KEY (group_id, first_user_id, second_user_id),
+  FOREIGN KEY (group_id) REFERENCES settlement_constraints(group_id),
+  FOREIGN KEY (first_user_id) REFERENCES users(id),
+  FOREIGN KEY (second_user_id) REFERENCES users(id)
+);
+
+CREATE TABLE guests (
+  user_id BIGINT PRIMARY KEY,
+  group_id BIGINT NOT NULL,
+  claim_token TEXT UNIQUE NOT NULL,
+  created_by BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id),
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE TABLE group_invitations (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  group_id BIGINT NOT NULL,
+  code TEXT UNIQUE NOT NULL,
+  created_by BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ NOT NULL,
+  max_uses BIGINT CHECK (max_uses > 0),
+  uses BIGINT NOT NULL DEFAULT 0,
+  require_approval BOOLEAN NOT NULL DEFAULT FALSE,
+  revoked BOOLEAN NOT NULL DEFAULT FALSE,
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE TABLE join_requests (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  group_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL,
+  invitation_id BIGINT NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('pending', 'approved', 'rejected')),
+  created_at TIMESTAMPTZ NOT NULL,
+  decided_at TIMESTAMPTZ,
+  FOREIGN KEY (group_id) REFERENCES groups(id),
+  FOREIGN KEY (user_id) REFERENCES users(id),
+  FOREIGN KEY (invitation_id) REFERENCES group_invitations(id)
+);
+
+CREATE UNIQUE INDEX join_requests_pending ON join_requests (group_id, user_id) WHERE status = 'pending';
+
+CREATE TABLE api_tokens (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+  token TEXT UNIQUE NOT NULL,
+  user_id BIGINT NOT NULL,
+  device_label TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  last_used_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,48 @@
+//! PostgreSQL implementation of the repositories, for deployments with several
+//! replicas sharing one database.
+//!
+//! PostgreSQL has no unsigned integers: ids and counts are `BIGINT` columns,
+//! bound with `i64::from` and read back with [`get_u32`].
+
+use std::time::Duration;
+
+use sqlx::{
+    postgres::{PgPoolOptions, PgRow},
+    Pool, Postgres, Row,
+};
+
+pub mod exchange_rate;
+pub mod expense;
+pub mod group;
+pub mod guest;
+pub mod invitation;
+pub mod migration;
+pub mod settlement_constraint;
+pub mod settlement_plan;
+pub mod transaction;
+pub mod user;
+
+#[derive(Clone)]
+pub struct PostgresRepository {
+    pub pool: Pool<Postgres>,
+}
+
+impl PostgresRepository {
+    pub async fn new(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_secs(10))
+            .max_connections(max_connections)
+            .connect(url)
+            .await?;
+
+        Ok(Self { pool })
+    }
+}
+
+fn get_u32(row: &PgRow, column: &str) -> u32 {
+    row.get::<i64, _>(column) as u32
+}
+
+fn get_optional_u32(row: &PgRow, column: &str) -> Option<u32> {
+    row.get::<Option<i64>, _>(column).map(|value| value as u32)
+}
@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use sqlx::{PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_u32, PostgresRepository};
+use crate::{
+    db::repository::SettlementConstraintRepository,
+    models::settlement_constraint::{ConstraintKind, MemberPair, SettlementConstraint},
+};
+
+#[async_trait]
+impl SettlementConstraintRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn set_settlement_constraint(
+        &self,
+        group_id: u32,
+        constraint: &SettlementConstraint,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM settlement_constraint_pairs WHERE group_id = $1")
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM settlement_constraints WHERE group_id = $1")
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        let hub_user_id = match constraint {
+            SettlementConstraint::Hub { user_id } => Some(i64::from(*user_id)),
+            _ => None,
+        };
+        sqlx::query(
+            "INSERT INTO settlement_constraints (group_id, kind, hub_user_id) VALUES ($1, $2, $3)",
+        )
+        .bind(i64::from(group_id))
+        .bind(constraint.to_string())
+        .bind(hub_user_id)
+        .execute(&mut *tx)
+        .await?;
+        if let SettlementConstraint::AllowedPairs { pairs }
+        | SettlementConstraint::ForbiddenPairs { pairs } = constraint
+        {
+            let query = "INSERT INTO settlement_constraint_pairs (group_id, first_user_id, second_user_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING";
+            for pair in pairs {
+                // Stored lowest id first so the same pair is only kept once
+                let first = pair.first_user_id.min(pair.second_user_id);
+                let second = pair.first_user_id.max(pair.second_user_id);
+                sqlx::query(query)
+                    .bind(i64::from(group_id))
+                    .bind(i64::from(first))
+                    .bind(i64::from(second))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_settlement_constraint(
+        &self,
+        group_id: u32,
+    ) -> Result<Option<SettlementConstraint>, sqlx::Error> {
+        Self::fetch_settlement_constraint(&mut *self.pool.acquire().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_settlement_constraint(&self, group_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM settlement_constraint_pairs WHERE group_id = $1")
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM settlement_constraints WHERE group_id = $1")
+            .bind(i64::from(group_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+}
+
+impl PostgresRepository {
+    /// `get_settlement_constraint` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_settlement_constraint(
+        conn: &mut PgConnection,
+        group_id: u32,
+    ) -> Result<Option<SettlementConstraint>, sqlx::Error> {
+        let Some(row) = sqlx::query("SELECT * FROM settlement_constraints WHERE group_id = $1")
+            .bind(i64::from(group_id))
+            .fetch_optional(&mut *conn)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let query = "SELECT * FROM settlement_constraint_pairs WHERE group_id = $1 ORDER BY first_user_id, second_user_id";
+        let pairs = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(&mut *conn)
+            .await?
+            .iter()
+            .map(|row| {
+                MemberPair::new(
+                    get_u32(row, "first_user_id"),
+                    get_u32(row, "second_user_id"),
+                )
+            })
+            .collect();
+        let kind: ConstraintKind = row
+            .get::<String, _>("kind")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let constraint = match kind {
+            ConstraintKind::AllowedPairs => SettlementConstraint::AllowedPairs { pairs },
+            ConstraintKind::ForbiddenPairs => SettlementConstraint::ForbiddenPairs { pairs },
+            ConstraintKind::Hub => SettlementConstraint::Hub {
+                user_id: get_u32(&row, "hub_user_id"),
+            },
+            ConstraintKind::ThroughOwner => SettlementConstraint::ThroughOwner,
+        };
+        Ok(Some(constraint))
+    }
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use chrono::{SubsecRound, Utc};
+use sqlx::Row;
+use tracing::instrument;
+
+use super::{
+    get_u32,
+    transaction::{transaction_from_row, INSERT_TRANSACTION},
+    PostgresRepository,
+};
+use crate::{
+    db::repository::SettlementPlanRepository,
+    models::{
+        expenses::{Status, Transaction},
+        settlement_plan::SettlementPlan,
+    },
+};
+
+#[async_trait]
+impl SettlementPlanRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_settlement_plan(
+        &self,
+        group_id: u32,
+        created_by: u32,
+        transfers: &[Transaction],
+    ) -> Result<SettlementPlan, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE transactions SET status = $1 WHERE group_id = $2 AND status = $3")
+            .bind(Status::Superseded.to_string())
+            .bind(i64::from(group_id))
+            .bind(Status::Pending.to_string())
+            .execute(&mut *tx)
+            .await?;
+        // Two plans generated at once both get the same version; the unique
+        // constraint on it then rejects the second one.
+        let version: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(version), 0) + 1 FROM settlement_plans WHERE group_id = $1",
+        )
+        .bind(i64::from(group_id))
+        .fetch_one(&mut *tx)
+        .await?;
+        // Stored to the microsecond
+        let created_at = Utc::now().trunc_subsecs(6);
+        let query = "INSERT INTO settlement_plans (group_id, version, created_by, created_at) VALUES ($1, $2, $3, $4) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(version)
+            .bind(i64::from(created_by))
+            .bind(created_at)
+            .fetch_one(&mut *tx)
+            .await?;
+        let plan_id = get_u32(&row, "id");
+
+        let mut transactions = Vec::new();
+        for transfer in transfers {
+            let mut transaction = Transaction {
+                status: Status::Pending,
+                group_id,
+                plan_id: Some(plan_id),
+                ..transfer.clone()
+            };
+            let row = sqlx::query(INSERT_TRANSACTION)
+                .bind(i64::from(transaction.payer_id))
+                .bind(i64::from(transaction.receiver_id))
+                .bind(transaction.amount.minor_units)
+                .bind(&transaction.amount.currency)
+                .bind(&transaction.date)
+                .bind(transaction.status.to_string())
+                .bind(i64::from(group_id))
+                .bind(i64::from(plan_id))
+                .fetch_one(&mut *tx)
+                .await?;
+            transaction.id = Some(get_u32(&row, "id"));
+            transactions.push(transaction);
+        }
+        tx.commit().await?;
+
+        Ok(SettlementPlan {
+            id: plan_id,
+            group_id,
+            version: version as u32,
+            created_by,
+            created_at,
+            transactions,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_latest_settlement_plan(
+        &self,
+        group_id: u32,
+    ) -> Result<SettlementPlan, sqlx::Error> {
+        let query =
+            "SELECT * FROM settlement_plans WHERE group_id = $1 ORDER BY version DESC LIMIT 1";
+        let row = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_one(&self.pool)
+            .await?;
+        let plan_id = get_u32(&row, "id");
+        let rows = sqlx::query("SELECT * FROM transactions WHERE plan_id = $1 ORDER BY id")
+            .bind(i64::from(plan_id))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(SettlementPlan {
+            id: plan_id,
+            group_id: get_u32(&row, "group_id"),
+            version: get_u32(&row, "version"),
+            created_by: get_u32(&row, "created_by"),
+            created_at: row.get("created_at"),
+            transactions: rows
+                .into_iter()
+                .map(transaction_from_row)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
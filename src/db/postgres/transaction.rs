@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_optional_u32, get_u32, PostgresRepository};
+use crate::{
+    db::repository::TransactionRepository,
+    models::{
+        expenses::{Status, Transaction},
+        money::Money,
+    },
+};
+
+pub(crate) fn transaction_from_row(row: PgRow) -> Result<Transaction, sqlx::Error> {
+    Ok(Transaction {
+        id: Some(get_u32(&row, "id")),
+        payer_id: get_u32(&row, "payer_id"),
+        receiver_id: get_u32(&row, "receiver_id"),
+        amount: Money::new(row.get("amount"), row.get("currency")),
+        date: row.get("date"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        group_id: get_u32(&row, "group_id"),
+        plan_id: get_optional_u32(&row, "plan_id"),
+    })
+}
+
+/// Inserts a transaction with the given status and returns its id.
+pub(crate) const INSERT_TRANSACTION: &str = "INSERT INTO transactions (payer_id, receiver_id, amount, currency, date, status, group_id, plan_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id";
+
+#[async_trait]
+impl TransactionRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_transaction(&self, transaction: &Transaction) -> Result<u32, sqlx::Error> {
+        let row = sqlx::query(INSERT_TRANSACTION)
+            .bind(i64::from(transaction.payer_id))
+            .bind(i64::from(transaction.receiver_id))
+            .bind(transaction.amount.minor_units)
+            .bind(&transaction.amount.currency)
+            .bind(&transaction.date)
+            .bind(transaction.status.to_string())
+            .bind(i64::from(transaction.group_id))
+            .bind(transaction.plan_id.map(i64::from))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(get_u32(&row, "id"))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_transaction(&self, id: u32) -> Result<Transaction, sqlx::Error> {
+        let query = "SELECT * FROM transactions WHERE id = $1";
+        let row = sqlx::query(query)
+            .bind(i64::from(id))
+            .fetch_one(&self.pool)
+            .await?;
+        transaction_from_row(row)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_transactions_by_payer_id(
+        &self,
+        payer_id: u32,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        let query = "SELECT * FROM transactions WHERE payer_id = $1 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(payer_id))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(transaction_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_transactions_by_status(
+        &self,
+        group_id: u32,
+        status: Status,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        Self::fetch_group_transactions_by_status(&mut *self.pool.acquire().await?, group_id, status)
+            .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_transactions(&self, group_id: u32) -> Result<Vec<Transaction>, sqlx::Error> {
+        let query = "SELECT * FROM transactions WHERE group_id = $1 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(transaction_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_payment(
+        &self,
+        payment: &Transaction,
+        planned_id: Option<u32>,
+    ) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(planned_id) = planned_id {
+            let query = "UPDATE transactions SET status = $1, date = $2 WHERE id = $3 AND status = $4 AND amount = $5";
+            let result = sqlx::query(query)
+                .bind(Status::Paid.to_string())
+                .bind(&payment.date)
+                .bind(i64::from(planned_id))
+                .bind(Status::Pending.to_string())
+                .bind(payment.amount.minor_units)
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() == 1 {
+                tx.commit().await?;
+                return Ok(planned_id);
+            }
+            let query = "UPDATE transactions SET amount = amount - $1 WHERE id = $2 AND status = $3 AND amount > $1";
+            let result = sqlx::query(query)
+                .bind(payment.amount.minor_units)
+                .bind(i64::from(planned_id))
+                .bind(Status::Pending.to_string())
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() == 0 {
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+        let row = sqlx::query(INSERT_TRANSACTION)
+            .bind(i64::from(payment.payer_id))
+            .bind(i64::from(payment.receiver_id))
+            .bind(payment.amount.minor_units)
+            .bind(&payment.amount.currency)
+            .bind(&payment.date)
+            .bind(Status::Paid.to_string())
+            .bind(i64::from(payment.group_id))
+            .bind(payment.plan_id.map(i64::from))
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(get_u32(&row, "id"))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn complete_transaction(&self, id: u32) -> Result<(), sqlx::Error> {
+        let query = "UPDATE transactions SET status = $1 WHERE id = $2 AND status IN ($3, $4)";
+        let result = sqlx::query(query)
+            .bind(Status::Completed.to_string())
+            .bind(i64::from(id))
+            .bind(Status::Pending.to_string())
+            .bind(Status::Paid.to_string())
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+}
+
+impl PostgresRepository {
+    /// `get_group_transactions_by_status` on `conn`, which may be a transaction
+    /// of the caller.
+    pub async fn fetch_group_transactions_by_status(
+        conn: &mut PgConnection,
+        group_id: u32,
+        status: Status,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        let query = "SELECT * FROM transactions WHERE group_id = $1 AND status = $2 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(group_id))
+            .bind(status.to_string())
+            .fetch_all(conn)
+            .await?;
+        rows.into_iter().map(transaction_from_row).collect()
+    }
+}
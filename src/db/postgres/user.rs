@@ -0,0 +1,205 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tracing::instrument;
+
+use super::{get_u32, PostgresRepository};
+use crate::{
+    auth::{
+        password::hash_password,
+        utils::{generate_token, hash_token},
+    },
+    db::repository::UserRepository,
+    models::user::{Session, User},
+};
+
+fn user_from_row(row: PgRow) -> User {
+    User {
+        id: Some(get_u32(&row, "id")),
+        name: row.get("name"),
+        email: row.get("email"),
+        password: row.get("password"),
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgresRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_user(&self, user: &User) -> Result<u32, sqlx::Error> {
+        Self::insert_user(&mut *self.pool.acquire().await?, user).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn register_user(&self, user: &User, guest_id: Option<u32>) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let user_id = Self::insert_user(&mut tx, user).await?;
+        if let Some(guest_id) = guest_id {
+            Self::merge_guest_into(&mut tx, guest_id, user_id).await?;
+        }
+        tx.commit().await?;
+        Ok(user_id)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user(&self, user_id: u32) -> Result<User, sqlx::Error> {
+        let query = "SELECT * FROM users WHERE id = $1";
+        let row = sqlx::query(query)
+            .bind(i64::from(user_id))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(user_from_row(row))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_by_email(&self, email: &str) -> Result<User, sqlx::Error> {
+        let query = "SELECT * FROM users WHERE email = $1";
+        let row = sqlx::query(query).bind(email).fetch_one(&self.pool).await?;
+        Ok(user_from_row(row))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_user_password(&self, user_id: u32, password: &str) -> Result<(), sqlx::Error> {
+        let password_hash =
+            hash_password(password).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE users SET password = $1 WHERE id = $2";
+        let result = sqlx::query(query)
+            .bind(password_hash)
+            .bind(i64::from(user_id))
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        sqlx::query("DELETE FROM api_tokens WHERE user_id = $1")
+            .bind(i64::from(user_id))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_api_token(
+        &self,
+        user_id: u32,
+        device_label: Option<&str>,
+        ttl: TimeDelta,
+    ) -> Result<String, sqlx::Error> {
+        let token = generate_token();
+        let now = Utc::now();
+        let query = "INSERT INTO api_tokens (token, user_id, device_label, created_at, last_used_at, expires_at) VALUES ($1, $2, $3, $4, $4, $5)";
+        sqlx::query(query)
+            .bind(hash_token(&token))
+            .bind(i64::from(user_id))
+            .bind(device_label)
+            .bind(now)
+            .bind(now + ttl)
+            .execute(&self.pool)
+            .await?;
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_id_by_token(&self, token: &str) -> Result<u32, sqlx::Error> {
+        let token_hash = hash_token(token);
+        let query = "SELECT user_id, expires_at FROM api_tokens WHERE token = $1";
+        let row = sqlx::query(query)
+            .bind(&token_hash)
+            .fetch_one(&self.pool)
+            .await?;
+        let user_id = get_u32(&row, "user_id");
+        let expires_at: DateTime<Utc> = row.get("expires_at");
+
+        let now = Utc::now();
+        if expires_at <= now {
+            self.delete_api_token(token).await?;
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let query = "UPDATE api_tokens SET last_used_at = $1 WHERE token = $2";
+        sqlx::query(query)
+            .bind(now)
+            .bind(&token_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(user_id)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_api_token(&self, token: &str) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM api_tokens WHERE token = $1";
+        sqlx::query(query)
+            .bind(hash_token(token))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_sessions(
+        &self,
+        user_id: u32,
+        current_token: &str,
+    ) -> Result<Vec<Session>, sqlx::Error> {
+        let current_hash = hash_token(current_token);
+        let query = "SELECT * FROM api_tokens WHERE user_id = $1 AND expires_at > $2 ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(i64::from(user_id))
+            .bind(Utc::now())
+            .fetch_all(&self.pool)
+            .await?;
+        let sessions = rows
+            .into_iter()
+            .map(|row| Session {
+                id: get_u32(&row, "id"),
+                device_label: row.get("device_label"),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+                expires_at: row.get("expires_at"),
+                current: row.get::<String, _>("token") == current_hash,
+            })
+            .collect();
+        Ok(sessions)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user_session(&self, user_id: u32, session_id: u32) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2";
+        let result = sqlx::query(query)
+            .bind(i64::from(session_id))
+            .bind(i64::from(user_id))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user_tokens(&self, user_id: u32) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM api_tokens WHERE user_id = $1 OR expires_at <= $2";
+        sqlx::query(query)
+            .bind(i64::from(user_id))
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+impl PostgresRepository {
+    /// `create_user` on `conn`, which may be a transaction of the caller.
+    pub async fn insert_user(conn: &mut PgConnection, user: &User) -> Result<u32, sqlx::Error> {
+        let password_hash =
+            hash_password(&user.password).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let query = "INSERT INTO users (name, email, password) VALUES ($1, $2, $3) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(&user.name)
+            .bind(&user.email)
+            .bind(password_hash)
+            .fetch_one(conn)
+            .await?;
+        Ok(get_u32(&row, "id"))
+    }
+}
@@ -0,0 +1,419 @@
+//! What the server needs from its storage, one trait per area. Every backend
+//! implements all of them; see [`Repository`].
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::{
+    db::migration::{Migration, MigrationError, MigrationStatus},
+    models::{
+        exchange_rate::ExchangeRate,
+        expenses::{Expense, ParticipantSplit, Status, Transaction},
+        group::{Group, GroupLedger, MemberRemoval, SettlementStrategy},
+        guest::{Guest, GuestInvitation},
+        invitation::{Invitation, JoinOutcome, JoinRequest, NewInvitation},
+        role::GroupRole,
+        settlement_constraint::SettlementConstraint,
+        settlement_plan::SettlementPlan,
+        user::{Session, User},
+    },
+};
+
+/// A storage backend: every repository plus its schema migrations.
+#[async_trait]
+pub trait Repository:
+    UserRepository
+    + GroupRepository
+    + GuestRepository
+    + InvitationRepository
+    + ExpenseRepository
+    + ExchangeRateRepository
+    + TransactionRepository
+    + SettlementPlanRepository
+    + SettlementConstraintRepository
+{
+    /// Applies the pending migrations and returns them.
+    async fn migrate(&self) -> Result<Vec<&'static Migration>, MigrationError>;
+
+    /// Rolls back the `steps` most recently applied migrations and returns them.
+    async fn rollback(&self, steps: usize) -> Result<Vec<&'static Migration>, MigrationError>;
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, MigrationError>;
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Inserts a user, storing an argon2 hash of `user.password`.
+    async fn create_user(&self, user: &User) -> Result<u32, sqlx::Error>;
+
+    /// Creates the user and, if they claim the guest `guest_id`, merges the
+    /// guest into them. A failed merge leaves no user behind.
+    async fn register_user(&self, user: &User, guest_id: Option<u32>) -> Result<u32, sqlx::Error>;
+
+    async fn get_user(&self, user_id: u32) -> Result<User, sqlx::Error>;
+
+    async fn get_user_by_email(&self, email: &str) -> Result<User, sqlx::Error>;
+
+    /// Replaces the stored password hash and revokes every session of the user.
+    async fn update_user_password(&self, user_id: u32, password: &str) -> Result<(), sqlx::Error>;
+
+    /// Mints a new random session token for the user, valid for `ttl`, and
+    /// returns it. Only its hash is persisted.
+    async fn create_api_token(
+        &self,
+        user_id: u32,
+        device_label: Option<&str>,
+        ttl: TimeDelta,
+    ) -> Result<String, sqlx::Error>;
+
+    /// Resolves a session token to its user. Expired tokens are deleted and
+    /// rejected with `RowNotFound`; valid ones get their last-used time refreshed.
+    async fn get_user_id_by_token(&self, token: &str) -> Result<u32, sqlx::Error>;
+
+    async fn delete_api_token(&self, token: &str) -> Result<(), sqlx::Error>;
+
+    /// Lists the unexpired sessions of a user, flagging the one `current_token` belongs to.
+    async fn get_user_sessions(
+        &self,
+        user_id: u32,
+        current_token: &str,
+    ) -> Result<Vec<Session>, sqlx::Error>;
+
+    /// Revokes a single session of a user. Fails with `RowNotFound` if the session
+    /// does not exist or belongs to someone else.
+    async fn delete_user_session(&self, user_id: u32, session_id: u32) -> Result<(), sqlx::Error>;
+
+    /// Revokes every session of a user, clearing out the expired sessions of
+    /// all users along the way.
+    async fn delete_user_tokens(&self, user_id: u32) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait GroupRepository: Send + Sync {
+    async fn create_group(&self, group: &Group) -> Result<u32, sqlx::Error>;
+
+    /// Creates the group with its owner as the first member, or neither.
+    async fn create_group_with_owner(&self, group: &Group) -> Result<u32, sqlx::Error>;
+
+    async fn get_group(&self, group_id: u32) -> Result<Group, sqlx::Error>;
+
+    async fn get_groups_by_owner_id(&self, owner_id: u32) -> Result<Vec<Group>, sqlx::Error>;
+
+    async fn update_settlement_strategy(
+        &self,
+        group_id: u32,
+        strategy: SettlementStrategy,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Saves the name, description, location and dates of the group.
+    async fn update_group(&self, group: &Group) -> Result<(), sqlx::Error>;
+
+    /// Archives the group as of `archived_at`, or reopens it with `None`.
+    async fn set_group_archived(
+        &self,
+        group_id: u32,
+        archived_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Deletes the group with everything in it: expenses and their participants,
+    /// transactions and settlement plans, exchange rates, the settlement
+    /// constraint, invitations and join requests, memberships and the guests
+    /// that only existed for the group. Fails with `RowNotFound` if there is no
+    /// such group.
+    async fn delete_group(&self, group_id: u32) -> Result<(), sqlx::Error>;
+
+    /// Adds the user to the group as a regular member, or lets a former member
+    /// back in as one. Fails with a unique violation if the user already is a
+    /// member.
+    async fn add_user_to_group(&self, group_id: u32, user_id: u32) -> Result<(), sqlx::Error>;
+
+    /// Current members of the group; former members are left out.
+    async fn get_group_members(&self, group_id: u32) -> Result<Vec<u32>, sqlx::Error>;
+
+    async fn get_user_groups(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error>;
+
+    /// Everything the balances of the group are computed from, read in one
+    /// transaction. `RowNotFound` if there is no such group.
+    async fn get_group_ledger(&self, group_id: u32) -> Result<GroupLedger, sqlx::Error>;
+
+    /// Makes the user a former member of the group and supersedes the pending
+    /// transfers proposed to or from them, all in one transaction. Fails with
+    /// `RowNotFound` unless the user is a current member.
+    ///
+    /// With `reassign_to`, everything the user has in the group is handed over
+    /// to that member first: the expenses they paid, their shares and their
+    /// payments, so that their balance moves with it. Expenses both took part
+    /// in become exact splits of what each owed, added up, and payments between
+    /// the two are dropped. Without it, the user is only removed if their
+    /// balance is zero, so nobody is left owing a former member.
+    async fn remove_group_member(
+        &self,
+        group_id: u32,
+        user_id: u32,
+        reassign_to: Option<u32>,
+    ) -> Result<MemberRemoval, sqlx::Error>;
+
+    /// Makes `owner_id` the owner of the group. The previous owner stays as an
+    /// admin.
+    async fn update_group_owner(&self, group_id: u32, owner_id: u32) -> Result<(), sqlx::Error>;
+
+    /// Role of a current member of the group, or `RowNotFound` for anyone else.
+    async fn get_member_role(&self, group_id: u32, user_id: u32) -> Result<GroupRole, sqlx::Error>;
+
+    /// Current members of the group with their roles.
+    async fn get_group_member_roles(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<(u32, GroupRole)>, sqlx::Error>;
+
+    /// Changes the role of a current member other than the owner, who is named
+    /// by the group instead. Fails with `RowNotFound` for anyone else.
+    async fn set_member_role(
+        &self,
+        group_id: u32,
+        user_id: u32,
+        role: GroupRole,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait GuestRepository: Send + Sync {
+    /// Adds a member without an account to the group and returns it with the
+    /// token to claim it. Only the hash of the token is stored.
+    async fn create_guest(
+        &self,
+        group_id: u32,
+        name: &str,
+        created_by: u32,
+    ) -> Result<GuestInvitation, sqlx::Error>;
+
+    /// Guests of the group that were not claimed yet.
+    async fn get_group_guests(&self, group_id: u32) -> Result<Vec<Guest>, sqlx::Error>;
+
+    /// The unclaimed guest `claim_token` belongs to, or `RowNotFound`.
+    async fn get_guest_by_claim_token(&self, claim_token: &str) -> Result<Guest, sqlx::Error>;
+
+    /// Merges the guest into the real user `user_id` and deletes it.
+    ///
+    /// The user joins every group of the guest and takes over the expenses it
+    /// paid, its shares and its transactions. Expenses both took part in become
+    /// exact splits of what each owed, added up. Payments between the two are
+    /// dropped.
+    async fn merge_guest(&self, guest_id: u32, user_id: u32) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait InvitationRepository: Send + Sync {
+    /// Creates an invitation to the group with a new random code. Only the hash
+    /// of the code is stored.
+    async fn create_invitation(
+        &self,
+        group_id: u32,
+        created_by: u32,
+        expires_at: DateTime<Utc>,
+        max_uses: Option<u32>,
+        require_approval: bool,
+    ) -> Result<NewInvitation, sqlx::Error>;
+
+    /// Every invitation of the group, newest first.
+    async fn get_group_invitations(&self, group_id: u32) -> Result<Vec<Invitation>, sqlx::Error>;
+
+    /// Makes the code of an invitation unusable. Fails with `RowNotFound` if the
+    /// group has no such invitation.
+    async fn revoke_invitation(&self, group_id: u32, invitation_id: u32)
+        -> Result<(), sqlx::Error>;
+
+    /// Uses `code` to let the user into the group, or to file a join request if
+    /// the invitation requires approval. Unknown, expired, revoked and used up
+    /// codes, as well as codes of another group, fail with `RowNotFound`.
+    async fn redeem_invitation(
+        &self,
+        group_id: u32,
+        code: &str,
+        user_id: u32,
+    ) -> Result<JoinOutcome, sqlx::Error>;
+
+    /// Join requests of the group still waiting for the owner, oldest first.
+    async fn get_pending_join_requests(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<JoinRequest>, sqlx::Error>;
+
+    /// Approves or rejects a pending join request; approving adds the user to
+    /// the group. Fails with `RowNotFound` unless the request is pending.
+    async fn decide_join_request(
+        &self,
+        group_id: u32,
+        request_id: u32,
+        approve: bool,
+    ) -> Result<JoinRequest, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ExpenseRepository: Send + Sync {
+    async fn create_expense(&self, expense: &Expense) -> Result<u32, sqlx::Error>;
+
+    /// Creates an expense together with its participants and their split values.
+    /// If any participant is rejected, the expense is not stored either.
+    async fn create_expense_with_splits(
+        &self,
+        expense: &Expense,
+        splits: &[ParticipantSplit],
+    ) -> Result<u32, sqlx::Error>;
+
+    async fn get_expenses_by_group_id(&self, group_id: u32) -> Result<Vec<Expense>, sqlx::Error>;
+
+    async fn get_expenses_by_payer_id(&self, payer_id: u32) -> Result<Vec<Expense>, sqlx::Error>;
+
+    async fn add_participants_to_expense(
+        &self,
+        expense_id: u32,
+        users_ids: Vec<u32>,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_expense_participants(&self, expense_id: u32) -> Result<Vec<u32>, sqlx::Error>;
+
+    /// Adds participants together with their split values; all of them or none.
+    async fn add_expense_splits(
+        &self,
+        expense_id: u32,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error>;
+
+    async fn get_expense_splits(
+        &self,
+        expense_id: u32,
+    ) -> Result<Vec<ParticipantSplit>, sqlx::Error>;
+
+    async fn get_all_user_expenses(&self, user_id: u32) -> Result<Vec<u32>, sqlx::Error>;
+
+    async fn get_expense_by_id(&self, expense_id: u32) -> Result<Expense, sqlx::Error>;
+
+    async fn get_expenses_by_ids(
+        &self,
+        expenses_ids: Vec<u32>,
+    ) -> Result<Vec<Expense>, sqlx::Error>;
+
+    /// Rewrites an expense and replaces its participants in a single transaction.
+    /// The group of an expense cannot be changed.
+    async fn update_expense(
+        &self,
+        expense_id: u32,
+        expense: &Expense,
+        splits: &[ParticipantSplit],
+    ) -> Result<(), sqlx::Error>;
+
+    /// Deletes an expense together with its participants.
+    async fn delete_expense(&self, expense_id: u32) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ExchangeRateRepository: Send + Sync {
+    async fn create_exchange_rate(&self, rate: &ExchangeRate) -> Result<u32, sqlx::Error>;
+
+    /// Rates entered for the group, newest first.
+    async fn get_group_exchange_rates(
+        &self,
+        group_id: u32,
+    ) -> Result<Vec<ExchangeRate>, sqlx::Error>;
+
+    /// Replaces every rate loaded from the rates file with `rates`. Rates entered
+    /// for a group are kept.
+    async fn replace_global_exchange_rates(
+        &self,
+        rates: &[ExchangeRate],
+    ) -> Result<(), sqlx::Error>;
+
+    /// Rate converting `from` into `to` on `date`, in millionths.
+    ///
+    /// The latest rate effective on or before `date` wins. Rates entered for the
+    /// group take precedence over the ones from the rates file, and a rate for the
+    /// opposite direction is inverted when no direct one exists.
+    async fn find_exchange_rate(
+        &self,
+        group_id: u32,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> Result<Option<i64>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait TransactionRepository: Send + Sync {
+    async fn create_transaction(&self, transaction: &Transaction) -> Result<u32, sqlx::Error>;
+
+    async fn get_transaction(&self, id: u32) -> Result<Transaction, sqlx::Error>;
+
+    async fn get_transactions_by_payer_id(
+        &self,
+        payer_id: u32,
+    ) -> Result<Vec<Transaction>, sqlx::Error>;
+
+    /// Transactions of the group with the given status, oldest first.
+    async fn get_group_transactions_by_status(
+        &self,
+        group_id: u32,
+        status: Status,
+    ) -> Result<Vec<Transaction>, sqlx::Error>;
+
+    /// Every transaction of the group, oldest first.
+    async fn get_group_transactions(&self, group_id: u32) -> Result<Vec<Transaction>, sqlx::Error>;
+
+    /// Records `payment` with the `paid` status and returns its id.
+    ///
+    /// When it pays off the pending transfer `planned_id`, paying the full
+    /// amount moves that transfer to `paid`; a partial payment is stored as a
+    /// separate transaction of the same plan and the planned amount is reduced
+    /// accordingly. Returns `RowNotFound` if the transfer is no longer pending
+    /// or smaller than the payment.
+    async fn record_payment(
+        &self,
+        payment: &Transaction,
+        planned_id: Option<u32>,
+    ) -> Result<u32, sqlx::Error>;
+
+    /// Marks a pending or paid transaction as completed. Returns `RowNotFound`
+    /// if it does not exist or is already completed or superseded.
+    async fn complete_transaction(&self, id: u32) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SettlementPlanRepository: Send + Sync {
+    /// Stores a new version of the group's settlement plan proposing `transfers`.
+    ///
+    /// Pending transfers of earlier plans are marked superseded in the same
+    /// database transaction; completed ones are left untouched.
+    async fn create_settlement_plan(
+        &self,
+        group_id: u32,
+        created_by: u32,
+        transfers: &[Transaction],
+    ) -> Result<SettlementPlan, sqlx::Error>;
+
+    /// The most recent settlement plan of the group with all its transfers,
+    /// or `RowNotFound` if none was generated yet.
+    async fn get_latest_settlement_plan(
+        &self,
+        group_id: u32,
+    ) -> Result<SettlementPlan, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SettlementConstraintRepository: Send + Sync {
+    /// Replaces the settlement constraint of the group with `constraint`.
+    async fn set_settlement_constraint(
+        &self,
+        group_id: u32,
+        constraint: &SettlementConstraint,
+    ) -> Result<(), sqlx::Error>;
+
+    /// The settlement constraint of the group, or `None` if anyone may pay anyone.
+    async fn get_settlement_constraint(
+        &self,
+        group_id: u32,
+    ) -> Result<Option<SettlementConstraint>, sqlx::Error>;
+
+    /// Removes the settlement constraint of the group, returning `RowNotFound`
+    /// if it had none.
+    async fn delete_settlement_constraint(&self, group_id: u32) -> Result<(), sqlx::Error>;
+}
@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use sqlx::{Row, SqliteConnection};
+use tracing::instrument;
+
+use crate::{
+    db::{repository::SettlementConstraintRepository, SqliteRepository},
+    models::settlement_constraint::{ConstraintKind, MemberPair, SettlementConstraint},
+};
+
+#[async_trait]
+impl SettlementConstraintRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn set_settlement_constraint(
+        &self,
+        group_id: u32,
+        constraint: &SettlementConstraint,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM settlement_constraint_pairs WHERE group_id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM settlement_constraints WHERE group_id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        let hub_user_id = match constraint {
+            SettlementConstraint::Hub { user_id } => Some(*user_id),
+            _ => None,
+        };
+        sqlx::query(
+            "INSERT INTO settlement_constraints (group_id, kind, hub_user_id) VALUES (?, ?, ?)",
+        )
+        .bind(group_id)
+        .bind(constraint.to_string())
+        .bind(hub_user_id)
+        .execute(&mut *tx)
+        .await?;
+        if let SettlementConstraint::AllowedPairs { pairs }
+        | SettlementConstraint::ForbiddenPairs { pairs } = constraint
+        {
+            let query = "INSERT OR IGNORE INTO settlement_constraint_pairs (group_id, first_user_id, second_user_id) VALUES (?, ?, ?)";
+            for pair in pairs {
+                // Stored lowest id first so the same pair is only kept once
+                let first = pair.first_user_id.min(pair.second_user_id);
+                let second = pair.first_user_id.max(pair.second_user_id);
+                sqlx::query(query)
+                    .bind(group_id)
+                    .bind(first)
+                    .bind(second)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_settlement_constraint(
+        &self,
+        group_id: u32,
+    ) -> Result<Option<SettlementConstraint>, sqlx::Error> {
+        Self::fetch_settlement_constraint(&mut *self.pool.acquire().await?, group_id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_settlement_constraint(&self, group_id: u32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM settlement_constraint_pairs WHERE group_id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM settlement_constraints WHERE group_id = ?")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await
+    }
+}
+
+impl SqliteRepository {
+    /// `get_settlement_constraint` on `conn`, which may be a transaction of the caller.
+    pub async fn fetch_settlement_constraint(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+    ) -> Result<Option<SettlementConstraint>, sqlx::Error> {
+        let Some(row) = sqlx::query("SELECT * FROM settlement_constraints WHERE group_id = ?")
+            .bind(group_id)
+            .fetch_optional(&mut *conn)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let query = "SELECT * FROM settlement_constraint_pairs WHERE group_id = ? ORDER BY first_user_id, second_user_id";
+        let pairs = sqlx::query(query)
+            .bind(group_id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| MemberPair::new(row.get("first_user_id"), row.get("second_user_id")))
+            .collect();
+        let kind: ConstraintKind = row
+            .get::<String, _>("kind")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let constraint = match kind {
+            ConstraintKind::AllowedPairs => SettlementConstraint::AllowedPairs { pairs },
+            ConstraintKind::ForbiddenPairs => SettlementConstraint::ForbiddenPairs { pairs },
+            ConstraintKind::Hub => SettlementConstraint::Hub {
+                user_id: row.get("hub_user_id"),
+            },
+            ConstraintKind::ThroughOwner => SettlementConstraint::ThroughOwner,
+        };
+        Ok(Some(constraint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::{
+        db::tests::backend_tests,
+        models::{group::Group, user::User},
+    };
+    use chrono::Utc;
+
+    async fn test_settlement_constraint_round_trip(db: Database) {
+        let mut users = Vec::new();
+        for name in ["Alice", "Bob", "Carol"] {
+            let email = format!("{}@example.com", name.to_lowercase());
+            users.push(
+                db.create_user(&User::new(name, &email, "password"))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let group = Group::new(
+            "Trip",
+            users[0],
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        assert_eq!(db.get_settlement_constraint(group_id).await.unwrap(), None);
+
+        let pairs = SettlementConstraint::AllowedPairs {
+            pairs: vec![
+                MemberPair::new(users[2], users[0]),
+                MemberPair::new(users[0], users[2]),
+                MemberPair::new(users[0], users[1]),
+            ],
+        };
+        db.set_settlement_constraint(group_id, &pairs)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_settlement_constraint(group_id).await.unwrap(),
+            Some(SettlementConstraint::AllowedPairs {
+                pairs: vec![
+                    MemberPair::new(users[0], users[1]),
+                    MemberPair::new(users[0], users[2]),
+                ],
+            })
+        );
+
+        let hub = SettlementConstraint::Hub { user_id: users[1] };
+        db.set_settlement_constraint(group_id, &hub).await.unwrap();
+        assert_eq!(
+            db.get_settlement_constraint(group_id).await.unwrap(),
+            Some(hub)
+        );
+
+        db.delete_settlement_constraint(group_id).await.unwrap();
+        assert_eq!(db.get_settlement_constraint(group_id).await.unwrap(), None);
+        assert!(matches!(
+            db.delete_settlement_constraint(group_id).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    backend_tests!(test_settlement_constraint_round_trip);
+}
@@ -0,0 +1,216 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{sqlite::SqliteRow, Row};
+use tracing::instrument;
+
+use crate::{
+    db::{
+        repository::SettlementPlanRepository, transaction::transaction_from_row, SqliteRepository,
+    },
+    models::{
+        expenses::{Status, Transaction},
+        settlement_plan::SettlementPlan,
+    },
+};
+
+fn settlement_plan_from_row(row: SqliteRow, transactions: Vec<Transaction>) -> SettlementPlan {
+    SettlementPlan {
+        id: row.get("id"),
+        group_id: row.get("group_id"),
+        version: row.get("version"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        transactions,
+    }
+}
+
+#[async_trait]
+impl SettlementPlanRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_settlement_plan(
+        &self,
+        group_id: u32,
+        created_by: u32,
+        transfers: &[Transaction],
+    ) -> Result<SettlementPlan, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE transactions SET status = ? WHERE group_id = ? AND status = ?")
+            .bind(Status::Superseded.to_string())
+            .bind(group_id)
+            .bind(Status::Pending.to_string())
+            .execute(&mut *tx)
+            .await?;
+        let version: u32 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(version), 0) + 1 FROM settlement_plans WHERE group_id = ?",
+        )
+        .bind(group_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let created_at = Utc::now();
+        let query = "INSERT INTO settlement_plans (group_id, version, created_by, created_at) VALUES (?, ?, ?, ?) RETURNING id";
+        let plan_id: u32 = sqlx::query(query)
+            .bind(group_id)
+            .bind(version)
+            .bind(created_by)
+            .bind(created_at)
+            .fetch_one(&mut *tx)
+            .await?
+            .get("id");
+
+        let query = "INSERT INTO transactions (payer_id, receiver_id, amount, currency, date, status, group_id, plan_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id";
+        let mut transactions = Vec::new();
+        for transfer in transfers {
+            let mut transaction = Transaction {
+                status: Status::Pending,
+                group_id,
+                plan_id: Some(plan_id),
+                ..transfer.clone()
+            };
+            let id = sqlx::query(query)
+                .bind(transaction.payer_id)
+                .bind(transaction.receiver_id)
+                .bind(transaction.amount.minor_units)
+                .bind(transaction.amount.currency.clone())
+                .bind(transaction.date.clone())
+                .bind(transaction.status.to_string())
+                .bind(group_id)
+                .bind(plan_id)
+                .fetch_one(&mut *tx)
+                .await?
+                .get("id");
+            transaction.id = Some(id);
+            transactions.push(transaction);
+        }
+        tx.commit().await?;
+
+        Ok(SettlementPlan {
+            id: plan_id,
+            group_id,
+            version,
+            created_by,
+            created_at,
+            transactions,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_latest_settlement_plan(
+        &self,
+        group_id: u32,
+    ) -> Result<SettlementPlan, sqlx::Error> {
+        let query =
+            "SELECT * FROM settlement_plans WHERE group_id = ? ORDER BY version DESC LIMIT 1";
+        let row = sqlx::query(query)
+            .bind(group_id)
+            .fetch_one(&self.pool)
+            .await?;
+        let plan_id: u32 = row.get("id");
+        let rows = sqlx::query("SELECT * FROM transactions WHERE plan_id = ? ORDER BY id")
+            .bind(plan_id)
+            .fetch_all(&self.pool)
+            .await?;
+        let transactions = rows
+            .into_iter()
+            .map(transaction_from_row)
+            .collect::<Result<_, _>>()?;
+        Ok(settlement_plan_from_row(row, transactions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::{
+        db::tests::{backend_tests, fail_on},
+        models::{group::Group, money::Money, user::User},
+    };
+
+    async fn test_new_plan_supersedes_pending_transfers(db: Database) {
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            alice,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, alice).await.unwrap();
+        db.add_user_to_group(group_id, bob).await.unwrap();
+        let transfer = |minor_units| Transaction {
+            id: None,
+            payer_id: bob,
+            receiver_id: alice,
+            amount: Money::new(minor_units, "EUR"),
+            date: "2024-01-01".to_string(),
+            status: Status::Pending,
+            group_id,
+            plan_id: None,
+        };
+        let completed = db
+            .create_transaction(&Transaction {
+                status: Status::Completed,
+                ..transfer(500)
+            })
+            .await
+            .unwrap();
+
+        let first = db
+            .create_settlement_plan(group_id, alice, &[transfer(1000)])
+            .await
+            .unwrap();
+        assert_eq!(first.version, 1);
+        let second = db
+            .create_settlement_plan(group_id, bob, &[transfer(800)])
+            .await
+            .unwrap();
+        assert_eq!(second.version, 2);
+
+        let superseded = db
+            .get_transaction(first.transactions[0].id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(superseded.status, Status::Superseded);
+        let completed = db.get_transaction(completed).await.unwrap();
+        assert_eq!(completed.status, Status::Completed);
+
+        let latest = db.get_latest_settlement_plan(group_id).await.unwrap();
+        assert_eq!(latest.id, second.id);
+        assert_eq!(latest.created_by, bob);
+        assert_eq!(latest.transactions.len(), 1);
+        assert_eq!(latest.transactions[0].amount, Money::new(800, "EUR"));
+        assert_eq!(latest.transactions[0].status, Status::Pending);
+        assert_eq!(latest.transactions[0].plan_id, Some(second.id));
+
+        // A plan that fails halfway keeps the previous one in force
+        fail_on(&db, "INSERT", "transactions").await;
+        assert!(db
+            .create_settlement_plan(group_id, alice, &[transfer(300)])
+            .await
+            .is_err());
+        let latest = db.get_latest_settlement_plan(group_id).await.unwrap();
+        assert_eq!(latest.id, second.id);
+        assert_eq!(latest.transactions[0].status, Status::Pending);
+    }
+
+    async fn test_no_settlement_plan(db: Database) {
+        assert!(matches!(
+            db.get_latest_settlement_plan(1).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+
+    backend_tests!(
+        test_new_plan_supersedes_pending_transfers,
+        test_no_settlement_plan
+    );
+}
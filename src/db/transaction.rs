@@ -1,42 +1,60 @@
-use sqlx::Row;
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+use tracing::instrument;
 
 use crate::{
-    db::Database,
-    models::expenses::{Status, Transaction},
+    db::{repository::TransactionRepository, SqliteRepository},
+    models::{
+        expenses::{Status, Transaction},
+        money::Money,
+    },
 };
 
-impl Database {
-    pub async fn create_transaction(&self, transaction: &Transaction) -> Result<u32, sqlx::Error> {
-        let query = "INSERT INTO transactions (payer_id, receiver_id, amount, date, status, group_id) VALUES (?, ?, ?, ?, ?, ?) RETURNING id";
+pub(crate) fn transaction_from_row(row: SqliteRow) -> Result<Transaction, sqlx::Error> {
+    Ok(Transaction {
+        id: Some(row.get("id")),
+        payer_id: row.get("payer_id"),
+        receiver_id: row.get("receiver_id"),
+        amount: Money::new(row.get("amount"), row.get("currency")),
+        date: row.get("date"),
+        status: row
+            .get::<String, _>("status")
+            .parse()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        group_id: row.get("group_id"),
+        plan_id: row.get("plan_id"),
+    })
+}
+
+#[async_trait]
+impl TransactionRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_transaction(&self, transaction: &Transaction) -> Result<u32, sqlx::Error> {
+        let query = "INSERT INTO transactions (payer_id, receiver_id, amount, currency, date, status, group_id, plan_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id";
         let row = sqlx::query(query)
             .bind(transaction.payer_id)
             .bind(transaction.receiver_id)
-            .bind(transaction.amount)
+            .bind(transaction.amount.minor_units)
+            .bind(transaction.amount.currency.clone())
             .bind(transaction.date.clone())
             .bind(transaction.status.to_string())
             .bind(transaction.group_id)
+            .bind(transaction.plan_id)
             .fetch_one(&self.pool)
             .await?;
         let id = row.get("id");
         Ok(id)
     }
 
-    pub async fn get_transaction(&self, id: u32) -> Result<Transaction, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_transaction(&self, id: u32) -> Result<Transaction, sqlx::Error> {
         let query = "SELECT * FROM transactions WHERE id = ?";
         let row = sqlx::query(query).bind(id).fetch_one(&self.pool).await?;
-        let transaction = Transaction {
-            id: Some(row.get("id")),
-            payer_id: row.get("payer_id"),
-            receiver_id: row.get("receiver_id"),
-            amount: row.get("amount"),
-            date: row.get("date"),
-            status: Status::from_string(row.get("status")),
-            group_id: row.get("group_id"),
-        };
-        Ok(transaction)
+        transaction_from_row(row)
     }
 
-    pub async fn get_transactions_by_payer_id(
+    #[instrument(level = "debug", skip_all)]
+    async fn get_transactions_by_payer_id(
         &self,
         payer_id: u32,
     ) -> Result<Vec<Transaction>, sqlx::Error> {
@@ -45,51 +63,131 @@ impl Database {
             .bind(payer_id)
             .fetch_all(&self.pool)
             .await?;
-        let transactions = rows
-            .into_iter()
-            .map(|row| Transaction {
-                id: Some(row.get("id")),
-                payer_id: row.get("payer_id"),
-                receiver_id: row.get("receiver_id"),
-                amount: row.get("amount"),
-                date: row.get("date"),
-                status: Status::from_string(row.get("status")),
-                group_id: row.get("group_id"),
-            })
-            .collect();
-        Ok(transactions)
+        rows.into_iter().map(transaction_from_row).collect()
     }
 
-    pub async fn delete_transactions_by_group_id(&self, group_id: u32) -> Result<(), sqlx::Error> {
-        let query = "DELETE FROM transactions WHERE group_id = ?";
-        sqlx::query(query)
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_transactions_by_status(
+        &self,
+        group_id: u32,
+        status: Status,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        Self::fetch_group_transactions_by_status(&mut *self.pool.acquire().await?, group_id, status)
+            .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_group_transactions(&self, group_id: u32) -> Result<Vec<Transaction>, sqlx::Error> {
+        let query = "SELECT * FROM transactions WHERE group_id = ? ORDER BY id";
+        let rows = sqlx::query(query)
             .bind(group_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(transaction_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_payment(
+        &self,
+        payment: &Transaction,
+        planned_id: Option<u32>,
+    ) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(planned_id) = planned_id {
+            let query = "UPDATE transactions SET status = ?, date = ? WHERE id = ? AND status = ? AND amount = ?";
+            let result = sqlx::query(query)
+                .bind(Status::Paid.to_string())
+                .bind(payment.date.clone())
+                .bind(planned_id)
+                .bind(Status::Pending.to_string())
+                .bind(payment.amount.minor_units)
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() == 1 {
+                tx.commit().await?;
+                return Ok(planned_id);
+            }
+            let query = "UPDATE transactions SET amount = amount - ? WHERE id = ? AND status = ? AND amount > ?";
+            let result = sqlx::query(query)
+                .bind(payment.amount.minor_units)
+                .bind(planned_id)
+                .bind(Status::Pending.to_string())
+                .bind(payment.amount.minor_units)
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() == 0 {
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+        let query = "INSERT INTO transactions (payer_id, receiver_id, amount, currency, date, status, group_id, plan_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id";
+        let id = sqlx::query(query)
+            .bind(payment.payer_id)
+            .bind(payment.receiver_id)
+            .bind(payment.amount.minor_units)
+            .bind(payment.amount.currency.clone())
+            .bind(payment.date.clone())
+            .bind(Status::Paid.to_string())
+            .bind(payment.group_id)
+            .bind(payment.plan_id)
+            .fetch_one(&mut *tx)
+            .await?
+            .get("id");
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn complete_transaction(&self, id: u32) -> Result<(), sqlx::Error> {
+        let query = "UPDATE transactions SET status = ? WHERE id = ? AND status IN (?, ?)";
+        let result = sqlx::query(query)
+            .bind(Status::Completed.to_string())
+            .bind(id)
+            .bind(Status::Pending.to_string())
+            .bind(Status::Paid.to_string())
             .execute(&self.pool)
             .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
         Ok(())
     }
 }
 
+impl SqliteRepository {
+    /// `get_group_transactions_by_status` on `conn`, which may be a transaction
+    /// of the caller.
+    pub async fn fetch_group_transactions_by_status(
+        conn: &mut SqliteConnection,
+        group_id: u32,
+        status: Status,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        let query = "SELECT * FROM transactions WHERE group_id = ? AND status = ? ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(group_id)
+            .bind(status.to_string())
+            .fetch_all(conn)
+            .await?;
+        rows.into_iter().map(transaction_from_row).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::Database;
     use crate::{
-        db::tests::IN_MEMORY_DB,
+        db::tests::backend_tests,
         models::{group::Group, user::User},
     };
     use chrono::Utc;
 
-    #[tokio::test]
-    async fn test_create_and_get_transaction() {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
-
+    async fn test_create_and_get_transaction(db: Database) {
         // Create two users for the transaction
         let user1 = User::new("Payer User", "payer@example.com", "password");
         let user2 = User::new("Receiver User", "receiver@example.com", "password");
 
-        let payer_id = db.create_user(&user1, "token1").await.unwrap();
-        let receiver_id = db.create_user(&user2, "token2").await.unwrap();
+        let payer_id = db.create_user(&user1).await.unwrap();
+        let receiver_id = db.create_user(&user2).await.unwrap();
 
         let group = Group::new(
             "group1",
@@ -109,10 +207,11 @@ mod tests {
             id: None,
             payer_id,
             receiver_id,
-            amount: 100.0,
+            amount: Money::new(10000, "EUR"),
             date: time.clone(),
             status: Status::Pending,
             group_id: 1,
+            plan_id: None,
         };
 
         // Test creating transaction
@@ -123,16 +222,12 @@ mod tests {
         let fetched_transaction = db.get_transaction(transaction_id).await.unwrap();
         assert_eq!(fetched_transaction.payer_id, payer_id);
         assert_eq!(fetched_transaction.receiver_id, receiver_id);
-        assert_eq!(fetched_transaction.amount, 100.0);
+        assert_eq!(fetched_transaction.amount, Money::new(10000, "EUR"));
         assert_eq!(fetched_transaction.date, time);
         assert_eq!(fetched_transaction.status, Status::Pending);
     }
 
-    #[tokio::test]
-    async fn test_get_transactions_by_payer() {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
-
+    async fn test_get_transactions_by_payer(db: Database) {
         // Create users
         let payer = User::new("Payer", "payer@example.com", "password");
         let receiver1 = User::new("Receiver1", "receiver1@example.com", "password");
@@ -145,9 +240,9 @@ mod tests {
             "Group1".to_string(),
             "ads".to_string(),
         );
-        let payer_id = db.create_user(&payer, "token_payer").await.unwrap();
-        let receiver1_id = db.create_user(&receiver1, "token_receiver1").await.unwrap();
-        let receiver2_id = db.create_user(&receiver2, "token_receiver2").await.unwrap();
+        let payer_id = db.create_user(&payer).await.unwrap();
+        let receiver1_id = db.create_user(&receiver1).await.unwrap();
+        let receiver2_id = db.create_user(&receiver2).await.unwrap();
 
         let group_id = db.create_group(&group).await.unwrap();
 
@@ -162,20 +257,22 @@ mod tests {
             id: None,
             payer_id,
             receiver_id: receiver1_id,
-            amount: 50.0,
+            amount: Money::new(5000, "EUR"),
             date: time.clone(),
             status: Status::Pending,
             group_id: 1,
+            plan_id: None,
         };
 
         let transaction2 = Transaction {
             id: None,
             payer_id,
             receiver_id: receiver2_id,
-            amount: 75.0,
+            amount: Money::new(7500, "EUR"),
             date: time.clone(),
             status: Status::Completed,
             group_id: 1,
+            plan_id: None,
         };
 
         // Create transactions
@@ -185,17 +282,94 @@ mod tests {
         // Test getting transactions by payer
         let payer_transactions = db.get_transactions_by_payer_id(payer_id).await.unwrap();
         assert_eq!(payer_transactions.len(), 2);
-        assert_eq!(payer_transactions[0].amount, 50.0);
-        assert_eq!(payer_transactions[1].amount, 75.0);
+        assert_eq!(payer_transactions[0].amount, Money::new(5000, "EUR"));
+        assert_eq!(payer_transactions[1].amount, Money::new(7500, "EUR"));
     }
 
-    #[tokio::test]
-    async fn test_transaction_not_found() {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
+    async fn test_record_and_complete_payments(db: Database) {
+        let payer_id = db
+            .create_user(&User::new("Payer", "payer@example.com", "password"))
+            .await
+            .unwrap();
+        let receiver_id = db
+            .create_user(&User::new("Receiver", "receiver@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "group1",
+            payer_id,
+            Utc::now(),
+            Utc::now(),
+            "Group1".to_string(),
+            "ads".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, payer_id).await.unwrap();
+        db.add_user_to_group(group_id, receiver_id).await.unwrap();
+        let transfer = |minor_units| Transaction {
+            id: None,
+            payer_id,
+            receiver_id,
+            amount: Money::new(minor_units, "EUR"),
+            date: "2024-01-01".to_string(),
+            status: Status::Pending,
+            group_id,
+            plan_id: None,
+        };
+        let planned = db.create_transaction(&transfer(3000)).await.unwrap();
+
+        // A partial payment is split off the planned transfer
+        let partial = db
+            .record_payment(&transfer(1000), Some(planned))
+            .await
+            .unwrap();
+        assert_ne!(partial, planned);
+        assert_eq!(
+            db.get_transaction(planned).await.unwrap().amount,
+            Money::new(2000, "EUR")
+        );
+        assert_eq!(
+            db.get_transaction(partial).await.unwrap().status,
+            Status::Paid
+        );
+        // Paying more than what is left is rejected
+        assert!(matches!(
+            db.record_payment(&transfer(2500), Some(planned)).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        // Paying the rest settles the planned transfer itself
+        let rest = db
+            .record_payment(&transfer(2000), Some(planned))
+            .await
+            .unwrap();
+        assert_eq!(rest, planned);
+        assert_eq!(
+            db.get_transaction(planned).await.unwrap().status,
+            Status::Paid
+        );
+
+        db.complete_transaction(planned).await.unwrap();
+        assert_eq!(
+            db.get_transaction(planned).await.unwrap().status,
+            Status::Completed
+        );
+        assert!(matches!(
+            db.complete_transaction(planned).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        assert_eq!(db.get_group_transactions(group_id).await.unwrap().len(), 2);
+    }
 
+    async fn test_transaction_not_found(db: Database) {
         // Try to get non-existent transaction
         let result = db.get_transaction(999).await;
         assert!(result.is_err());
     }
+
+    backend_tests!(
+        test_create_and_get_transaction,
+        test_get_transactions_by_payer,
+        test_record_and_complete_payments,
+        test_transaction_not_found
+    );
 }
@@ -1,26 +1,37 @@
-use crate::{db::Database, models::user::User};
-use sqlx::Row;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use sqlx::{Row, SqliteConnection};
+use tracing::instrument;
 
-impl Database {
-    pub async fn create_user(&self, user: &User, token: &str) -> Result<u32, sqlx::Error> {
-        let query = "INSERT INTO users (name, email, password) VALUES (?, ?, ?) RETURNING id";
-        let row = sqlx::query(query)
-            .bind(user.name.clone())
-            .bind(user.email.clone())
-            .bind(user.password.clone())
-            .fetch_one(&self.pool)
-            .await?;
-        let id = row.get("id");
-        let query = "INSERT INTO api_tokens (token, user_id) VALUES (?, ?)";
-        sqlx::query(query)
-            .bind(token)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-        Ok(id)
+use crate::{
+    auth::{
+        password::hash_password,
+        utils::{generate_token, hash_token},
+    },
+    db::{repository::UserRepository, SqliteRepository},
+    models::user::{Session, User},
+};
+
+#[async_trait]
+impl UserRepository for SqliteRepository {
+    #[instrument(level = "debug", skip_all)]
+    async fn create_user(&self, user: &User) -> Result<u32, sqlx::Error> {
+        Self::insert_user(&mut *self.pool.acquire().await?, user).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn register_user(&self, user: &User, guest_id: Option<u32>) -> Result<u32, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let user_id = Self::insert_user(&mut tx, user).await?;
+        if let Some(guest_id) = guest_id {
+            Self::merge_guest_into(&mut tx, guest_id, user_id).await?;
+        }
+        tx.commit().await?;
+        Ok(user_id)
     }
 
-    pub async fn get_user(&self, user_id: u32) -> Result<User, sqlx::Error> {
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user(&self, user_id: u32) -> Result<User, sqlx::Error> {
         let query = "SELECT * FROM users WHERE id = ?";
         let row = sqlx::query(query)
             .bind(user_id)
@@ -35,45 +46,344 @@ impl Database {
         Ok(user)
     }
 
-    pub async fn get_user_id_by_token(&self, token: &str) -> Result<u32, sqlx::Error> {
-        let query = "SELECT * FROM api_tokens WHERE token = ?";
-        let row = sqlx::query(query).bind(token).fetch_one(&self.pool).await?;
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_by_email(&self, email: &str) -> Result<User, sqlx::Error> {
+        let query = "SELECT * FROM users WHERE email = ?";
+        let row = sqlx::query(query).bind(email).fetch_one(&self.pool).await?;
+        let user = User {
+            id: row.get("id"),
+            name: row.get("name"),
+            email: row.get("email"),
+            password: row.get("password"),
+        };
+        Ok(user)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_user_password(&self, user_id: u32, password: &str) -> Result<(), sqlx::Error> {
+        let password_hash =
+            hash_password(password).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        let query = "UPDATE users SET password = ? WHERE id = ?";
+        let result = sqlx::query(query)
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        sqlx::query("DELETE FROM api_tokens WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_api_token(
+        &self,
+        user_id: u32,
+        device_label: Option<&str>,
+        ttl: TimeDelta,
+    ) -> Result<String, sqlx::Error> {
+        let token = generate_token();
+        let now = Utc::now();
+        let query = "INSERT INTO api_tokens (token, user_id, device_label, created_at, last_used_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)";
+        sqlx::query(query)
+            .bind(hash_token(&token))
+            .bind(user_id)
+            .bind(device_label)
+            .bind(now)
+            .bind(now)
+            .bind(now + ttl)
+            .execute(&self.pool)
+            .await?;
+        Ok(token)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_id_by_token(&self, token: &str) -> Result<u32, sqlx::Error> {
+        let token_hash = hash_token(token);
+        let query = "SELECT user_id, expires_at FROM api_tokens WHERE token = ?";
+        let row = sqlx::query(query)
+            .bind(&token_hash)
+            .fetch_one(&self.pool)
+            .await?;
         let user_id = row.get("user_id");
+        let expires_at: DateTime<Utc> = row.get("expires_at");
+
+        let now = Utc::now();
+        if expires_at <= now {
+            self.delete_api_token(token).await?;
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let query = "UPDATE api_tokens SET last_used_at = ? WHERE token = ?";
+        sqlx::query(query)
+            .bind(now)
+            .bind(&token_hash)
+            .execute(&self.pool)
+            .await?;
         Ok(user_id)
     }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_api_token(&self, token: &str) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM api_tokens WHERE token = ?";
+        sqlx::query(query)
+            .bind(hash_token(token))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_user_sessions(
+        &self,
+        user_id: u32,
+        current_token: &str,
+    ) -> Result<Vec<Session>, sqlx::Error> {
+        let current_hash = hash_token(current_token);
+        let query = "SELECT * FROM api_tokens WHERE user_id = ? AND expires_at > ? ORDER BY id";
+        let rows = sqlx::query(query)
+            .bind(user_id)
+            .bind(Utc::now())
+            .fetch_all(&self.pool)
+            .await?;
+        let sessions = rows
+            .into_iter()
+            .map(|row| Session {
+                id: row.get("id"),
+                device_label: row.get("device_label"),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+                expires_at: row.get("expires_at"),
+                current: row.get::<String, _>("token") == current_hash,
+            })
+            .collect();
+        Ok(sessions)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user_session(&self, user_id: u32, session_id: u32) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM api_tokens WHERE id = ? AND user_id = ?";
+        let result = sqlx::query(query)
+            .bind(session_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user_tokens(&self, user_id: u32) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM api_tokens WHERE user_id = ? OR expires_at <= ?";
+        sqlx::query(query)
+            .bind(user_id)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+impl SqliteRepository {
+    /// `create_user` on `conn`, which may be a transaction of the caller.
+    pub async fn insert_user(conn: &mut SqliteConnection, user: &User) -> Result<u32, sqlx::Error> {
+        let password_hash =
+            hash_password(&user.password).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let query = "INSERT INTO users (name, email, password) VALUES (?, ?, ?) RETURNING id";
+        let row = sqlx::query(query)
+            .bind(user.name.clone())
+            .bind(user.email.clone())
+            .bind(password_hash)
+            .fetch_one(conn)
+            .await?;
+        let id = row.get("id");
+        Ok(id)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::db::tests::IN_MEMORY_DB;
+    use crate::{
+        auth::{password::verify_password, utils::TOKEN_TTL},
+        db::tests::{backend_tests, execute, fail_on, fetch_scalar},
+    };
 
     use super::*;
+    use crate::db::Database;
 
-    #[tokio::test]
-    async fn test_create_user() {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
-
+    async fn test_create_user(db: Database) {
         let user = User::new("Test User", "test@example.com", "password");
-        let id = db.create_user(&user, "token").await.unwrap();
-        let token_id = db.get_user_id_by_token("token").await.unwrap();
+        let id = db.create_user(&user).await.unwrap();
+        let token = db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+        let token_id = db.get_user_id_by_token(&token).await.unwrap();
         assert_eq!(id, token_id);
         let user = db.get_user(id).await.unwrap();
         assert_eq!(user.name, "Test User");
         assert_eq!(user.email, "test@example.com");
-        assert_eq!(user.password, "password");
+        assert_ne!(user.password, "password");
+        assert!(verify_password("password", &user.password));
     }
 
-    #[tokio::test]
-    async fn test_unique_user_mail() {
-        let db = Database::new(IN_MEMORY_DB).await.unwrap();
-        db.init().await.unwrap();
+    async fn test_unique_user_mail(db: Database) {
         let user = User::new("Test User", "test@example.com", "password");
         let user2 = User::new("Test User 2", "test@example.com", "password2");
-        db.create_user(&user, "token").await.unwrap();
+        db.create_user(&user).await.unwrap();
         assert!(
-            db.create_user(&user2, "token2").await.is_err(),
+            db.create_user(&user2).await.is_err(),
             "User with the same email should not be created"
         );
     }
+
+    async fn test_tokens_are_random_and_hashed(db: Database) {
+        let user = User::new("Test User", "test@example.com", "password");
+        let id = db.create_user(&user).await.unwrap();
+
+        let first = db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+        let second = db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(db.get_user_id_by_token(&first).await.unwrap(), id);
+        assert_eq!(db.get_user_id_by_token(&second).await.unwrap(), id);
+
+        let stored: String = fetch_scalar(&db, "SELECT token FROM api_tokens LIMIT 1").await;
+        assert_ne!(stored, first);
+        assert!(db.get_user_id_by_token(&stored).await.is_err());
+    }
+
+    async fn test_expired_token_is_rejected(db: Database) {
+        let user = User::new("Test User", "test@example.com", "password");
+        let id = db.create_user(&user).await.unwrap();
+        let token = db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+
+        execute(
+            &db,
+            "UPDATE api_tokens SET expires_at = '2000-01-01T00:00:00Z'",
+        )
+        .await;
+
+        assert!(db.get_user_id_by_token(&token).await.is_err());
+        let remaining: i64 = fetch_scalar(&db, "SELECT COUNT(*) FROM api_tokens").await;
+        assert_eq!(remaining, 0);
+    }
+
+    async fn test_sessions(db: Database) {
+        let user = User::new("Test User", "test@example.com", "password");
+        let other = User::new("Other User", "other@example.com", "password");
+        let id = db.create_user(&user).await.unwrap();
+        let other_id = db.create_user(&other).await.unwrap();
+        let phone = db
+            .create_api_token(id, Some("phone"), TOKEN_TTL)
+            .await
+            .unwrap();
+        let laptop = db
+            .create_api_token(id, Some("laptop"), TOKEN_TTL)
+            .await
+            .unwrap();
+        db.create_api_token(other_id, None, TOKEN_TTL)
+            .await
+            .unwrap();
+        db.create_api_token(id, Some("old tablet"), TOKEN_TTL)
+            .await
+            .unwrap();
+        execute(
+            &db,
+            "UPDATE api_tokens SET expires_at = '2000-01-01T00:00:00Z' WHERE device_label = 'old tablet'",
+        )
+        .await;
+
+        let sessions = db.get_user_sessions(id, &laptop).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].device_label.as_deref(), Some("phone"));
+        assert!(!sessions[0].current);
+        assert!(sessions[1].current);
+
+        // Sessions of other users cannot be revoked
+        assert!(db
+            .delete_user_session(other_id, sessions[0].id)
+            .await
+            .is_err());
+
+        db.delete_user_session(id, sessions[0].id).await.unwrap();
+        assert!(db.get_user_id_by_token(&phone).await.is_err());
+        assert_eq!(db.get_user_id_by_token(&laptop).await.unwrap(), id);
+
+        db.delete_api_token(&laptop).await.unwrap();
+        assert!(db.get_user_id_by_token(&laptop).await.is_err());
+        assert!(db.get_user_sessions(id, &laptop).await.unwrap().is_empty());
+    }
+
+    async fn test_delete_user_tokens(db: Database) {
+        let user = User::new("Test User", "test@example.com", "password");
+        let other = User::new("Other User", "other@example.com", "password");
+        let id = db.create_user(&user).await.unwrap();
+        let other_id = db.create_user(&other).await.unwrap();
+        db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+        db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+        let other_token = db
+            .create_api_token(other_id, None, TOKEN_TTL)
+            .await
+            .unwrap();
+        db.create_api_token(other_id, Some("expired"), TOKEN_TTL)
+            .await
+            .unwrap();
+        execute(
+            &db,
+            "UPDATE api_tokens SET expires_at = '2000-01-01T00:00:00Z' WHERE device_label = 'expired'",
+        )
+        .await;
+
+        db.delete_user_tokens(id).await.unwrap();
+
+        // Expired sessions of other users are cleared out along the way
+        let remaining: i64 = fetch_scalar(&db, "SELECT COUNT(*) FROM api_tokens").await;
+        assert_eq!(remaining, 1);
+        assert_eq!(
+            db.get_user_id_by_token(&other_token).await.unwrap(),
+            other_id
+        );
+    }
+
+    async fn test_password_change_revokes_tokens(db: Database) {
+        let user = User::new("Test User", "test@example.com", "password");
+        let id = db.create_user(&user).await.unwrap();
+        let token = db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+
+        db.update_user_password(id, "new password").await.unwrap();
+
+        assert!(db.get_user_id_by_token(&token).await.is_err());
+        let user = db.get_user(id).await.unwrap();
+        assert!(verify_password("new password", &user.password));
+        assert!(!verify_password("password", &user.password));
+    }
+
+    async fn test_failed_password_change_keeps_password(db: Database) {
+        let user = User::new("Test User", "test@example.com", "password");
+        let id = db.create_user(&user).await.unwrap();
+        let token = db.create_api_token(id, None, TOKEN_TTL).await.unwrap();
+        fail_on(&db, "DELETE", "api_tokens").await;
+
+        assert!(db.update_user_password(id, "new password").await.is_err());
+
+        // The password only changes together with revoking the sessions
+        let user = db.get_user(id).await.unwrap();
+        assert!(verify_password("password", &user.password));
+        assert_eq!(db.get_user_id_by_token(&token).await.unwrap(), id);
+    }
+
+    backend_tests!(
+        test_create_user,
+        test_unique_user_mail,
+        test_tokens_are_random_and_hashed,
+        test_expired_token_is_rejected,
+        test_sessions,
+        test_delete_user_tokens,
+        test_password_change_revokes_tokens,
+        test_failed_password_change_keeps_password
+    );
 }
@@ -0,0 +1,218 @@
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::{
+    expense::split::SplitError,
+    models::{exchange_rate::MissingRate, money::AmountOverflow},
+};
+
+/// Error returned by every handler of the API.
+///
+/// It is rendered as an [`ErrorResponse`] with a status code matching the variant,
+/// so clients can branch on the stable `code` field instead of parsing messages.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    Unprocessable {
+        message: String,
+        details: Option<Value>,
+    },
+    Internal(String),
+}
+
+/// JSON body of every error response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Machine readable error code, e.g. `not_found` or `conflict`.
+    pub code: String,
+    /// Human readable description of the error.
+    pub message: String,
+    /// Optional structured context, e.g. which fields failed validation.
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::Unprocessable {
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unprocessable { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Unprocessable { .. } => "unprocessable_entity",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn into_body(self) -> ErrorResponse {
+        let code = self.code().to_string();
+        let (message, details) = match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message) => (message, None),
+            ApiError::Unprocessable { message, details } => (message, details),
+            // Internal details stay in the server log, not in the response.
+            ApiError::Internal(_) => ("Internal server error".to_string(), None),
+        };
+        ErrorResponse {
+            code,
+            message,
+            details,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unprocessable { message, .. }
+            | ApiError::Internal(message) => write!(f, "{}: {}", self.code(), message),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        match &self {
+            ApiError::Internal(_) => tracing::error!(error = %self, "request failed"),
+            _ => tracing::debug!(error = %self, "request rejected"),
+        }
+        (status, Json(self.into_body())).into_response()
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => ApiError::NotFound("Resource not found".to_string()),
+            sqlx::Error::Database(db_error) => {
+                let message = db_error.message().to_string();
+                match db_error.kind() {
+                    sqlx::error::ErrorKind::UniqueViolation => ApiError::Conflict(message),
+                    sqlx::error::ErrorKind::ForeignKeyViolation => ApiError::Unprocessable {
+                        message: "Referenced resource does not exist".to_string(),
+                        details: Some(Value::String(message)),
+                    },
+                    sqlx::error::ErrorKind::NotNullViolation
+                    | sqlx::error::ErrorKind::CheckViolation => ApiError::Unprocessable {
+                        message: "Invalid value".to_string(),
+                        details: Some(Value::String(message)),
+                    },
+                    _ => ApiError::Internal(error.to_string()),
+                }
+            }
+            // Stored data the balances of a group cannot be computed from
+            sqlx::Error::Decode(source)
+                if source.is::<MissingRate>()
+                    || source.is::<SplitError>()
+                    || source.is::<AmountOverflow>() =>
+            {
+                ApiError::unprocessable(source.to_string())
+            }
+            _ => ApiError::Internal(error.to_string()),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            // Well-formed JSON that does not fit the expected body
+            JsonRejection::JsonDataError(_) => ApiError::unprocessable(rejection.body_text()),
+            _ => ApiError::BadRequest(rejection.body_text()),
+        }
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        match rejection {
+            PathRejection::FailedToDeserializePathParams(_) => {
+                ApiError::BadRequest(rejection.body_text())
+            }
+            // A route without the parameters its handler expects
+            _ => ApiError::Internal(rejection.body_text()),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<SplitError> for ApiError {
+    fn from(error: SplitError) -> Self {
+        ApiError::unprocessable(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::tests::IN_MEMORY_DB, db::Database, models::user::User};
+
+    #[tokio::test]
+    async fn test_sqlx_error_mapping() {
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let user = User::new("Test User", "test@example.com", "password");
+        let user_id = db.create_user(&user).await.unwrap();
+
+        let err = ApiError::from(db.create_user(&user).await.unwrap_err());
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+
+        let err = ApiError::from(db.add_user_to_group(999, user_id).await.unwrap_err());
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let err = ApiError::from(db.get_group(999).await.unwrap_err());
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[test]
+    fn test_internal_error_hides_message() {
+        let body = ApiError::Internal("secret".to_string()).into_body();
+        assert_eq!(body.code, "internal_error");
+        assert!(!body.message.contains("secret"));
+    }
+}
@@ -1,11 +1,11 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::extract::State;
 
 use crate::{
-    auth::utils::extract_user_id_from_headers, models::expenses::ExpenseAddRequest,
+    auth::{extractor::AuthUser, policy::ensure_expense_members},
+    error::{ApiError, ErrorResponse},
+    expense::{split::build_splits, validation::validate_amount},
+    extract::Json,
+    models::expenses::ExpenseAddRequest,
     server::AppState,
 };
 
@@ -14,30 +14,37 @@ use crate::{
     path = "/add_expense",
     request_body = ExpenseAddRequest,
     responses(
-        (status = 200, description = "Expense added to group successfully", body = bool)
+        (status = 200, description = "Expense added to group successfully", body = bool),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller, payer or a participant is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 422, description = "Invalid amount or split values that do not add up", body = ErrorResponse)
     ),
-    security(("api_key" = []))
+    security(("api_key" = []), ("bearer_auth" = []))
 )]
 pub async fn add_expense(
     State(app_state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Json(payload): Json<ExpenseAddRequest>,
-) -> Result<Json<bool>, (StatusCode, String)> {
-    match extract_user_id_from_headers(&headers, &app_state).await {
-        Ok(_) => (),
-        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string())),
-    };
-    match app_state.db.create_expense(&payload.expense).await {
-        Ok(expense_id) => {
-            match app_state
-                .db
-                .add_participants_to_expense(expense_id, payload.participants_ids)
-                .await
-            {
-                Ok(_) => Ok(Json(true)),
-                Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
-            }
-        }
-        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
-    }
+) -> Result<Json<bool>, ApiError> {
+    ensure_expense_members(
+        &app_state.db,
+        payload.expense.group_id,
+        auth.user_id,
+        payload.expense.payer_id,
+        &payload.participants_ids,
+    )
+    .await?;
+    validate_amount(&app_state.db, &payload.expense).await?;
+    let splits = build_splits(
+        &payload.expense.amount,
+        &payload.expense.split_type,
+        &payload.participants_ids,
+        &payload.split_values,
+    )?;
+    app_state
+        .db
+        .create_expense_with_splits(&payload.expense, &splits)
+        .await?;
+    Ok(Json(true))
 }
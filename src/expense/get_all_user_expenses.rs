@@ -1,11 +1,9 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode};
 
 use crate::{
-    auth::utils::extract_user_id_from_headers,
+    auth::{extractor::AuthUser, policy::ensure_self},
+    error::{ApiError, ErrorResponse},
+    extract::Json,
     models::expenses::{Expense, GetExpensesByUserIdRequest},
     server::AppState,
 };
@@ -15,28 +13,19 @@ use crate::{
     path = "/get_all_user_expenses",
     request_body = GetExpensesByUserIdRequest,
     responses(
-        (status = 200, description = "Expenses fetched successfully", body = Vec<Expense>)
+        (status = 200, description = "Expenses fetched successfully", body = Vec<Expense>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Expenses of another user were requested", body = ErrorResponse)
     ),
-    security(("api_key" = []))
+    security(("api_key" = []), ("bearer_auth" = []))
 )]
 pub async fn get_all_user_expenses(
     State(app_state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Json(payload): Json<GetExpensesByUserIdRequest>,
-) -> Result<(StatusCode, Json<Vec<Expense>>), (StatusCode, String)> {
-    match extract_user_id_from_headers(&headers, &app_state).await {
-        Ok(_) => (),
-        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string())),
-    };
-    let expenses_ids = app_state
-        .db
-        .get_all_user_expenses(payload.user_id)
-        .await
-        .unwrap();
-    let expenses = app_state
-        .db
-        .get_expenses_by_ids(expenses_ids)
-        .await
-        .unwrap();
+) -> Result<(StatusCode, Json<Vec<Expense>>), ApiError> {
+    ensure_self(auth.user_id, payload.user_id)?;
+    let expenses_ids = app_state.db.get_all_user_expenses(payload.user_id).await?;
+    let expenses = app_state.db.get_expenses_by_ids(expenses_ids).await?;
     Ok((StatusCode::OK, Json(expenses)))
 }
@@ -1,11 +1,9 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode};
 
 use crate::{
-    auth::utils::extract_user_id_from_headers,
+    auth::{extractor::AuthUser, policy::ensure_group_member},
+    error::{ApiError, ErrorResponse},
+    extract::Json,
     models::expenses::{Expense, GetExpensesByGroupIdRequest},
     server::AppState,
 };
@@ -15,25 +13,22 @@ use crate::{
     path = "/get_group_expenses",
     request_body = GetExpensesByGroupIdRequest,
     responses(
-        (status = 200, description = "Expenses fetched successfully", body = Vec<Expense>)
+        (status = 200, description = "Expenses fetched successfully", body = Vec<Expense>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
     ),
-    security(("api_key" = []))
+    security(("api_key" = []), ("bearer_auth" = []))
 )]
 pub async fn get_group_expenses(
     State(app_state): State<AppState>,
-    headers: HeaderMap,
+    auth: AuthUser,
     Json(payload): Json<GetExpensesByGroupIdRequest>,
-) -> Result<(StatusCode, Json<Vec<Expense>>), (StatusCode, String)> {
-    match extract_user_id_from_headers(&headers, &app_state).await {
-        Ok(_) => (),
-        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string())),
-    };
-    match app_state
+) -> Result<(StatusCode, Json<Vec<Expense>>), ApiError> {
+    ensure_group_member(&app_state.db, payload.group_id, auth.user_id).await?;
+    let expenses = app_state
         .db
         .get_expenses_by_group_id(payload.group_id)
-        .await
-    {
-        Ok(expenses) => Ok((StatusCode::OK, Json(expenses))),
-        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
-    }
+        .await?;
+    Ok((StatusCode::OK, Json(expenses)))
 }
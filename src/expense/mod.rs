@@ -3,6 +3,9 @@ use utoipa::OpenApi;
 
 use crate::server::AppState;
 
+pub mod split;
+pub mod validation;
+
 mod add_expense;
 use add_expense::__path_add_expense;
 use add_expense::add_expense;
@@ -0,0 +1,419 @@
+//! How the amount of an expense is divided between its participants.
+//!
+//! Every participant row stores a `split_value` whose meaning depends on the
+//! expense's [`SplitType`]. The owed amounts are derived from those values when
+//! balances are computed, so editing an expense never leaves stale shares behind.
+
+use std::collections::HashSet;
+
+use crate::models::{
+    expenses::{ParticipantSplit, SplitType},
+    money::Money,
+};
+
+/// Percentages are given in basis points, so 100% is 10 000.
+pub const FULL_PERCENTAGE: i64 = 10_000;
+
+#[derive(Debug, PartialEq)]
+pub enum SplitError {
+    NoParticipants,
+    DuplicateParticipant(u32),
+    MissingValue(u32),
+    UnknownParticipant(u32),
+    NegativeValue(u32),
+    ExactSumMismatch {
+        expected: Money,
+        actual: Money,
+    },
+    PercentageSumMismatch(i64),
+    NoShares,
+    AdjustmentsExceedAmount,
+    /// Split values whose sum does not fit in 64 bits.
+    Overflow,
+}
+
+impl std::fmt::Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitError::NoParticipants => write!(f, "An expense needs at least one participant"),
+            SplitError::DuplicateParticipant(id) => {
+                write!(f, "Participant {} is listed more than once", id)
+            }
+            SplitError::MissingValue(id) => write!(f, "Missing split value for participant {}", id),
+            SplitError::UnknownParticipant(id) => {
+                write!(f, "Split value given for non-participant {}", id)
+            }
+            SplitError::NegativeValue(id) => {
+                write!(f, "Split value of participant {} must not be negative", id)
+            }
+            SplitError::ExactSumMismatch { expected, actual } => write!(
+                f,
+                "Exact amounts sum to {} but the expense is {}",
+                actual, expected
+            ),
+            SplitError::PercentageSumMismatch(actual) => write!(
+                f,
+                "Percentages sum to {} basis points instead of {}",
+                actual, FULL_PERCENTAGE
+            ),
+            SplitError::NoShares => write!(f, "At least one participant needs a share"),
+            SplitError::AdjustmentsExceedAmount => write!(
+                f,
+                "Adjustments would make a participant owe a negative amount"
+            ),
+            SplitError::Overflow => write!(f, "Split values are too large"),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+/// Pairs each participant with its split value from a request.
+///
+/// Equal splits need no values; every other split type needs exactly one value
+/// per participant.
+pub fn resolve_splits(
+    participants_ids: &[u32],
+    split_type: &SplitType,
+    split_values: &[ParticipantSplit],
+) -> Result<Vec<ParticipantSplit>, SplitError> {
+    let mut seen = HashSet::new();
+    for id in participants_ids {
+        if !seen.insert(*id) {
+            return Err(SplitError::DuplicateParticipant(*id));
+        }
+    }
+    if let Some(split) = split_values
+        .iter()
+        .find(|split| !seen.contains(&split.user_id))
+    {
+        return Err(SplitError::UnknownParticipant(split.user_id));
+    }
+
+    participants_ids
+        .iter()
+        .map(|&user_id| {
+            let value = split_values
+                .iter()
+                .find(|split| split.user_id == user_id)
+                .map(|split| split.value);
+            match (split_type, value) {
+                (SplitType::Equal, _) => Ok(ParticipantSplit { user_id, value: 0 }),
+                (_, Some(value)) => Ok(ParticipantSplit { user_id, value }),
+                (_, None) => Err(SplitError::MissingValue(user_id)),
+            }
+        })
+        .collect()
+}
+
+/// Resolves the split values of a request and checks that they add up for `amount`.
+pub fn build_splits(
+    amount: &Money,
+    split_type: &SplitType,
+    participants_ids: &[u32],
+    split_values: &[ParticipantSplit],
+) -> Result<Vec<ParticipantSplit>, SplitError> {
+    let splits = resolve_splits(participants_ids, split_type, split_values)?;
+    owed_amounts(amount, split_type, &splits)?;
+    Ok(splits)
+}
+
+/// Computes how much each participant owes for an expense of `amount`.
+///
+/// The owed amounts always sum to exactly `amount`; indivisible minor units are
+/// handed out with [`Money::allocate`].
+pub fn owed_amounts(
+    amount: &Money,
+    split_type: &SplitType,
+    splits: &[ParticipantSplit],
+) -> Result<Vec<(u32, Money)>, SplitError> {
+    if splits.is_empty() {
+        return Err(SplitError::NoParticipants);
+    }
+    if *split_type != SplitType::Adjustment {
+        if let Some(split) = splits.iter().find(|split| split.value < 0) {
+            return Err(SplitError::NegativeValue(split.user_id));
+        }
+    }
+    let total = splits
+        .iter()
+        .try_fold(0i64, |total, split| total.checked_add(split.value))
+        .ok_or(SplitError::Overflow)?;
+    let weights: Vec<(u32, i64)> = splits
+        .iter()
+        .map(|split| (split.user_id, split.value))
+        .collect();
+    let equal_weights: Vec<(u32, i64)> = splits.iter().map(|split| (split.user_id, 1)).collect();
+
+    let owed = match split_type {
+        SplitType::Equal => amount.allocate(&equal_weights),
+        SplitType::Exact => {
+            if total != amount.minor_units {
+                return Err(SplitError::ExactSumMismatch {
+                    expected: amount.clone(),
+                    actual: Money::new(total, &amount.currency),
+                });
+            }
+            splits
+                .iter()
+                .map(|split| (split.user_id, Money::new(split.value, &amount.currency)))
+                .collect()
+        }
+        SplitType::Percentage => {
+            if total != FULL_PERCENTAGE {
+                return Err(SplitError::PercentageSumMismatch(total));
+            }
+            amount.allocate(&weights)
+        }
+        SplitType::Shares => {
+            if total <= 0 {
+                return Err(SplitError::NoShares);
+            }
+            amount.allocate(&weights)
+        }
+        SplitType::Adjustment => {
+            // Everyone pays an equal part of what is left after the adjustments,
+            // plus their own adjustment.
+            let remaining = amount
+                .minor_units
+                .checked_sub(total)
+                .ok_or(SplitError::Overflow)?;
+            let owed: Vec<(u32, Money)> = Money::new(remaining, &amount.currency)
+                .allocate(&equal_weights)
+                .into_iter()
+                .zip(splits)
+                .map(|((user_id, base), split)| {
+                    let owed = base.minor_units.checked_add(split.value)?;
+                    Some((user_id, Money::new(owed, &amount.currency)))
+                })
+                .collect::<Option<_>>()
+                .ok_or(SplitError::Overflow)?;
+            if owed.iter().any(|(_, owed)| owed.minor_units < 0) {
+                return Err(SplitError::AdjustmentsExceedAmount);
+            }
+            owed
+        }
+    };
+    Ok(owed)
+}
+
+/// Splits of an expense once `from` has handed their part over to `to`.
+///
+/// The result is an exact split of what everyone owed before, with the part of
+/// `from` added to that of `to`. Adding up the split values instead would only
+/// be right for exact splits: it changes the head count of an equal split, the
+/// base of an adjustment split and the rounding of shares and percentages.
+pub fn merge_participant(
+    amount: &Money,
+    split_type: &SplitType,
+    splits: &[ParticipantSplit],
+    from: u32,
+    to: u32,
+) -> Result<Vec<ParticipantSplit>, SplitError> {
+    let owed = owed_amounts(amount, split_type, splits)?;
+    let from_owed: i64 = owed
+        .iter()
+        .filter(|(user_id, _)| *user_id == from)
+        .map(|(_, owed)| owed.minor_units)
+        .sum();
+    let mut merged: Vec<ParticipantSplit> = owed
+        .into_iter()
+        .filter(|(user_id, _)| *user_id != from)
+        .map(|(user_id, owed)| ParticipantSplit {
+            user_id,
+            value: owed.minor_units,
+        })
+        .collect();
+    match merged.iter_mut().find(|split| split.user_id == to) {
+        Some(split) => split.value += from_owed,
+        None => merged.push(ParticipantSplit {
+            user_id: to,
+            value: from_owed,
+        }),
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splits(values: &[(u32, i64)]) -> Vec<ParticipantSplit> {
+        values
+            .iter()
+            .map(|&(user_id, value)| ParticipantSplit { user_id, value })
+            .collect()
+    }
+
+    fn owed(amount: i64, split_type: SplitType, values: &[(u32, i64)]) -> Vec<(u32, i64)> {
+        owed_amounts(&Money::new(amount, "EUR"), &split_type, &splits(values))
+            .unwrap()
+            .into_iter()
+            .map(|(user_id, money)| (user_id, money.minor_units))
+            .collect()
+    }
+
+    fn error(amount: i64, split_type: SplitType, values: &[(u32, i64)]) -> SplitError {
+        owed_amounts(&Money::new(amount, "EUR"), &split_type, &splits(values)).unwrap_err()
+    }
+
+    #[test]
+    fn test_equal_split() {
+        assert_eq!(
+            owed(9000, SplitType::Equal, &[(1, 0), (2, 0), (3, 0)]),
+            vec![(1, 3000), (2, 3000), (3, 3000)]
+        );
+        // 100.00 split three ways still sums to 100.00
+        assert_eq!(
+            owed(10000, SplitType::Equal, &[(1, 0), (2, 0), (3, 0)]),
+            vec![(1, 3334), (2, 3333), (3, 3333)]
+        );
+        assert_eq!(
+            error(9000, SplitType::Equal, &[]),
+            SplitError::NoParticipants
+        );
+    }
+
+    #[test]
+    fn test_exact_split() {
+        assert_eq!(
+            owed(10000, SplitType::Exact, &[(1, 4000), (2, 6000)]),
+            vec![(1, 4000), (2, 6000)]
+        );
+        assert!(matches!(
+            error(10000, SplitType::Exact, &[(1, 4000), (2, 5000)]),
+            SplitError::ExactSumMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_percentage_split() {
+        assert_eq!(
+            owed(20000, SplitType::Percentage, &[(1, 2500), (2, 7500)]),
+            vec![(1, 5000), (2, 15000)]
+        );
+        assert_eq!(
+            owed(
+                100,
+                SplitType::Percentage,
+                &[(1, 3333), (2, 3333), (3, 3334)]
+            ),
+            vec![(1, 33), (2, 33), (3, 34)]
+        );
+        assert_eq!(
+            error(20000, SplitType::Percentage, &[(1, 2500), (2, 7000)]),
+            SplitError::PercentageSumMismatch(9500)
+        );
+    }
+
+    #[test]
+    fn test_shares_split() {
+        assert_eq!(
+            owed(9000, SplitType::Shares, &[(1, 2), (2, 1)]),
+            vec![(1, 6000), (2, 3000)]
+        );
+        assert_eq!(
+            error(9000, SplitType::Shares, &[(1, 0), (2, 0)]),
+            SplitError::NoShares
+        );
+        assert_eq!(
+            error(9000, SplitType::Shares, &[(1, -1), (2, 2)]),
+            SplitError::NegativeValue(1)
+        );
+    }
+
+    #[test]
+    fn test_adjustment_split() {
+        // Participant 1 had an extra 10.00 drink, the rest is shared equally
+        assert_eq!(
+            owed(7000, SplitType::Adjustment, &[(1, 1000), (2, 0)]),
+            vec![(1, 4000), (2, 3000)]
+        );
+        assert_eq!(
+            owed(1001, SplitType::Adjustment, &[(1, 0), (2, 0)]),
+            vec![(1, 501), (2, 500)]
+        );
+        assert_eq!(
+            error(1000, SplitType::Adjustment, &[(1, 3000), (2, 0)]),
+            SplitError::AdjustmentsExceedAmount
+        );
+    }
+
+    #[test]
+    fn test_split_overflow() {
+        assert_eq!(
+            error(9000, SplitType::Shares, &[(1, i64::MAX), (2, 1)]),
+            SplitError::Overflow
+        );
+        assert_eq!(
+            error(9000, SplitType::Exact, &[(1, i64::MAX), (2, i64::MAX)]),
+            SplitError::Overflow
+        );
+        assert_eq!(
+            error(9000, SplitType::Adjustment, &[(1, -i64::MAX), (2, -10)]),
+            SplitError::Overflow
+        );
+        assert_eq!(
+            error(i64::MAX, SplitType::Adjustment, &[(1, -1), (2, 0)]),
+            SplitError::Overflow
+        );
+    }
+
+    #[test]
+    fn test_owed_amounts_sum_to_total() {
+        for amount in [99, 100, 10000, 12345] {
+            for split_type in [SplitType::Equal, SplitType::Shares, SplitType::Adjustment] {
+                let owed = owed(amount, split_type, &[(1, 1), (2, 2), (3, 4)]);
+                assert_eq!(owed.iter().map(|(_, owed)| owed).sum::<i64>(), amount);
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_participant() {
+        let merged = |split_type, values: &[(u32, i64)]| {
+            merge_participant(&Money::new(9000, "EUR"), &split_type, &splits(values), 3, 2).unwrap()
+        };
+        // Everyone else keeps owing what they did, whatever the split type
+        assert_eq!(
+            merged(SplitType::Equal, &[(1, 0), (2, 0), (3, 0)]),
+            splits(&[(1, 3000), (2, 6000)])
+        );
+        assert_eq!(
+            merged(SplitType::Adjustment, &[(1, 1500), (2, 0), (3, 0)]),
+            splits(&[(1, 4000), (2, 5000)])
+        );
+        assert_eq!(
+            merged(SplitType::Shares, &[(1, 1), (2, 1), (3, 1)]),
+            splits(&[(1, 3000), (2, 6000)])
+        );
+        // Taking over an expense `to` was not part of
+        assert_eq!(
+            merged(SplitType::Equal, &[(1, 0), (3, 0)]),
+            splits(&[(1, 4500), (2, 4500)])
+        );
+    }
+
+    #[test]
+    fn test_resolve_splits() {
+        let resolved = resolve_splits(&[1, 2], &SplitType::Equal, &[]).unwrap();
+        assert_eq!(resolved, splits(&[(1, 0), (2, 0)]));
+
+        let resolved =
+            resolve_splits(&[1, 2], &SplitType::Exact, &splits(&[(2, 6000), (1, 4000)])).unwrap();
+        assert_eq!(resolved, splits(&[(1, 4000), (2, 6000)]));
+
+        assert_eq!(
+            resolve_splits(&[1, 2], &SplitType::Exact, &splits(&[(1, 4000)])),
+            Err(SplitError::MissingValue(2))
+        );
+        assert_eq!(
+            resolve_splits(&[1], &SplitType::Exact, &splits(&[(1, 4000), (3, 6000)])),
+            Err(SplitError::UnknownParticipant(3))
+        );
+        assert_eq!(
+            resolve_splits(&[1, 1], &SplitType::Equal, &[]),
+            Err(SplitError::DuplicateParticipant(1))
+        );
+    }
+}
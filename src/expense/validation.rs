@@ -0,0 +1,52 @@
+use crate::{
+    db::Database,
+    error::ApiError,
+    models::{expenses::Expense, money::is_valid_currency},
+};
+
+/// Checks that `currency` looks like an ISO 4217 code.
+pub fn validate_currency(currency: &str) -> Result<(), ApiError> {
+    if !is_valid_currency(currency) {
+        return Err(ApiError::unprocessable(format!(
+            "Invalid currency code {:?}",
+            currency
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the amount of a new or edited expense: it must be positive, in a valid
+/// currency, and convertible to the base currency of its group at the expense
+/// date so that balances can be added up.
+pub async fn validate_amount(db: &Database, expense: &Expense) -> Result<(), ApiError> {
+    let amount = &expense.amount;
+    if amount.minor_units <= 0 {
+        return Err(ApiError::unprocessable("Amount must be positive"));
+    }
+    validate_currency(&amount.currency)?;
+    let group = db.get_group(expense.group_id).await?;
+    if amount.currency == group.base_currency {
+        return Ok(());
+    }
+    let Some(day) = expense.day() else {
+        return Err(ApiError::unprocessable(format!(
+            "Invalid expense date {:?}, expected YYYY-MM-DD",
+            expense.date
+        )));
+    };
+    let rate = db
+        .find_exchange_rate(
+            group.id.unwrap(),
+            &amount.currency,
+            &group.base_currency,
+            day,
+        )
+        .await?;
+    if rate.is_none() {
+        return Err(ApiError::unprocessable(format!(
+            "No exchange rate from {} to {} on {}",
+            amount.currency, group.base_currency, day
+        )));
+    }
+    Ok(())
+}
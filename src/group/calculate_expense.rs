@@ -1,11 +1,10 @@
 use axum::{
     extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+    http::{HeaderMap, StatusCode}};
 
 use crate::{
     auth::utils::extract_user_id_from_headers,
+    extract::Json,
     models::group::{get_group_summary, GroupRequest, GroupSummary},
     server::AppState,
 };
@@ -1,38 +1,34 @@
-use axum::{extract::State, http::HeaderMap, response::Response, Json};
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use axum::extract::State;
 
-use crate::{auth::utils::extract_user_id_from_headers, models::group::Group, server::AppState};
-
-#[derive(Deserialize, Serialize, ToSchema)]
-pub struct CreateGroupRequest {
-    pub name: String,
-    pub group_start_date: DateTime<Utc>,
-    pub group_end_date: DateTime<Utc>,
-    pub description: String,
-    pub location: String,
-}
+use crate::{
+    auth::extractor::AuthUser,
+    error::{ApiError, ErrorResponse},
+    expense::validation::validate_currency,
+    extract::Json,
+    models::group::{CreateGroupRequest, Group},
+    server::AppState,
+};
 
 #[utoipa::path(
     post,
     path = "/create_group",
     request_body = CreateGroupRequest,
     responses(
-        (status = 200, description = "Group created successfully", body = String)
+        (status = 200, description = "Group created successfully", body = String),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 422, description = "Invalid group data", body = ErrorResponse)
     ),
-    security(("api_key" = []))
+    security(("api_key" = []), ("bearer_auth" = []))
 )]
 pub async fn create_group(
     State(app_state): State<AppState>,
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     Json(group): Json<CreateGroupRequest>,
-) -> Response<String> {
-    let user_id = match extract_user_id_from_headers(&headers, &app_state).await {
-        Ok(id) => id,
-        Err(msg) => return Response::new(msg),
-    };
-    let group = Group::new(
+) -> Result<String, ApiError> {
+    validate_currency(&group.base_currency)?;
+    let base_currency = group.base_currency;
+    let settlement_strategy = group.settlement_strategy;
+    let mut group = Group::new(
         group.name.as_str(),
         user_id,
         group.group_start_date,
@@ -40,12 +36,9 @@ pub async fn create_group(
         group.description,
         group.location,
     );
+    group.base_currency = base_currency;
+    group.settlement_strategy = settlement_strategy;
 
-    match app_state.db.create_group(&group).await {
-        Ok(id) => {
-            app_state.db.add_user_to_group(id, user_id).await.unwrap();
-            Response::new(format!("Group created succesfully: {:?}", id))
-        }
-        Err(e) => Response::new(e.to_string()),
-    }
+    let id = app_state.db.create_group_with_owner(&group).await?;
+    Ok(format!("Group created succesfully: {:?}", id))
 }
@@ -1,23 +1,27 @@
-use crate::{auth::utils::extract_user_id_from_headers, models::group::Group, server::AppState};
-use axum::{extract::State, http::HeaderMap, Json};
+use crate::{
+    auth::extractor::AuthUser,
+    error::{ApiError, ErrorResponse},
+    extract::Json,
+    models::group::Group,
+    server::AppState,
+};
+use axum::extract::State;
 
 #[utoipa::path(
     post,
     path = "/get_user_owned_groups",
     responses(
-        (status = 200, description = "Groups fetched successfully", body = Vec<Group>)
+        (status = 200, description = "Groups fetched successfully; archived groups are left out", body = Vec<Group>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
     ),
-    security(("api_key" = []))
+    security(("api_key" = []), ("bearer_auth" = []))
 )]
 
 pub async fn get_user_owned_groups(
     State(app_state): State<AppState>,
-    headers: HeaderMap,
-) -> Json<Vec<Group>> {
-    let user_id = match extract_user_id_from_headers(&headers, &app_state).await {
-        Ok(id) => id,
-        Err(_) => return Json(vec![]),
-    };
-    let groups = app_state.db.get_groups_by_owner_id(user_id).await.unwrap();
-    Json(groups)
+    AuthUser { user_id, .. }: AuthUser,
+) -> Result<Json<Vec<Group>>, ApiError> {
+    let mut groups = app_state.db.get_groups_by_owner_id(user_id).await?;
+    groups.retain(|group| group.archived_at.is_none());
+    Ok(Json(groups))
 }
@@ -1,11 +1,11 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::extract::State;
 
 use crate::{
-    auth::utils::extract_user_id_from_headers, models::group::JoinGroupRequest, server::AppState,
+    auth::{extractor::AuthUser, policy::join_with_code},
+    error::{ApiError, ErrorResponse},
+    extract::Json,
+    models::{group::JoinGroupRequest, invitation::JoinOutcome},
+    server::AppState,
 };
 
 #[utoipa::path(
@@ -13,25 +13,19 @@ use crate::{
     path = "/join_group",
     request_body = JoinGroupRequest,
     responses(
-        (status = 200, description = "User added to group successfully", body = bool)
+        (status = 200, description = "True if the user joined the group, false if the join request waits for the owner's approval", body = bool),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Invalid or expired invitation code", body = ErrorResponse),
+        (status = 404, description = "Group does not exist", body = ErrorResponse),
+        (status = 409, description = "User is already a member of the group", body = ErrorResponse)
     ),
-    security(("api_key" = []))
+    security(("api_key" = []), ("bearer_auth" = []))
 )]
 pub async fn join_group(
     State(app_state): State<AppState>,
-    headers: HeaderMap,
+    AuthUser { user_id, .. }: AuthUser,
     Json(payload): Json<JoinGroupRequest>,
-) -> Result<Json<bool>, (StatusCode, String)> {
-    let user_id = match extract_user_id_from_headers(&headers, &app_state).await {
-        Ok(id) => id,
-        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string())),
-    };
-    match app_state
-        .db
-        .add_user_to_group(payload.group_id, user_id)
-        .await
-    {
-        Ok(_) => Ok(Json(true)),
-        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
-    }
+) -> Result<Json<bool>, ApiError> {
+    let outcome = join_with_code(&app_state.db, payload.group_id, &payload.code, user_id).await?;
+    Ok(Json(outcome == JoinOutcome::Joined))
 }
@@ -1,7 +1,12 @@
 pub mod auth;
+pub mod config;
 pub mod db;
+pub mod error;
 pub mod expense;
+pub mod extract;
 pub mod group;
+pub mod logging;
 pub mod models;
 pub mod server;
 pub mod summary;
+pub mod v1;
@@ -0,0 +1,90 @@
+use std::{any::Any, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{self, Request},
+    response::{IntoResponse, Response},
+};
+use tracing::{field::Empty, level_filters::LevelFilter, Span};
+use tracing_subscriber::{
+    filter::Targets, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+use crate::{
+    config::{Config, LogFormat, LogLevel},
+    error::ApiError,
+};
+
+/// Header carrying the id of a request, taken from the client or generated.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Writes the logs of the server and the libraries it is built on to standard
+/// output. Closed spans are logged with their duration, which times requests
+/// and database calls.
+pub fn init(config: &Config) {
+    let level = LevelFilter::from(config.log_level);
+    // Other crates only get to warn, or they drown out the server at debug
+    let filter = Targets::new()
+        .with_default(level.min(LevelFilter::WARN))
+        .with_target("trip_split", level)
+        .with_target("tower_http", level)
+        .with_target("sqlx", level);
+    let layer = tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE);
+    let registry = tracing_subscriber::registry().with(filter);
+    match config.log_format {
+        LogFormat::Text => registry.with(layer).init(),
+        LogFormat::Json => registry.with(layer.json()).init(),
+    }
+}
+
+/// Span of a request. `user_id` is recorded once the caller is authenticated.
+pub fn request_span(request: &Request<Body>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok());
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        uri = %request.uri(),
+        request_id,
+        user_id = Empty,
+    )
+}
+
+pub fn log_response<B>(response: &http::Response<B>, latency: Duration, _span: &Span) {
+    tracing::info!(
+        status = response.status().as_u16(),
+        latency_ms = latency.as_millis() as u64,
+        "finished request"
+    );
+}
+
+/// Answers a request whose handler panicked with a 500, instead of dropping
+/// the connection.
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    ApiError::Internal(format!("handler panicked: {}", message)).into_response()
+}
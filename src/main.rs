@@ -1,12 +1,107 @@
+use clap::{Parser, Subcommand};
+
+use config::{Config, ConfigArgs};
+use db::{migration::MigrationError, Database};
+
 pub mod auth;
+pub mod config;
 pub mod db;
+pub mod error;
 pub mod expense;
+pub mod extract;
 pub mod group;
+pub mod logging;
 pub mod models;
 pub mod server;
 pub mod summary;
+pub mod v1;
+
+/// Trip Split server. Without a command it serves the API.
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the API, after applying the pending migrations
+    Serve,
+    /// Apply the pending migrations
+    Migrate,
+    /// Roll back the most recently applied migrations. Migrations without a
+    /// down script, which today are all of them, are roll-forward only: the
+    /// command then fails without changing the database.
+    Rollback {
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// List the migrations and when they were applied
+    Status,
+}
 
 #[tokio::main]
 async fn main() {
-    server::start().await;
+    let cli = Cli::parse();
+    let config = match Config::load(cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init(&config);
+    let command = cli.command.unwrap_or(Command::Serve);
+    if let Command::Serve = command {
+        if let Err(e) = server::start(config).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Err(e) = run_migrations(command, &config).await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run_migrations(command: Command, config: &Config) -> Result<(), MigrationError> {
+    let db = Database::connect(&config.database_url, config.max_connections).await?;
+    match command {
+        Command::Serve => unreachable!(),
+        Command::Migrate => {
+            let applied = db.migrate().await?;
+            if applied.is_empty() {
+                println!("Database is up to date");
+            }
+            for migration in applied {
+                println!(
+                    "Applied migration {} ({})",
+                    migration.version, migration.name
+                );
+            }
+        }
+        Command::Rollback { steps } => {
+            for migration in db.rollback(steps).await? {
+                println!(
+                    "Rolled back migration {} ({})",
+                    migration.version, migration.name
+                );
+            }
+        }
+        Command::Status => {
+            for migration in db.migration_status().await? {
+                match migration.applied_at {
+                    Some(applied_at) => println!(
+                        "{:>4} {:<24} applied {}",
+                        migration.version, migration.name, applied_at
+                    ),
+                    None => println!("{:>4} {:<24} pending", migration.version, migration.name),
+                }
+            }
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,146 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::money::{is_valid_currency, MAX_RATE_MICROS, RATE_SCALE};
+
+/// One unit of `from_currency` is worth `rate_micros / 1 000 000` units of
+/// `to_currency` from `effective_date` on, until a newer rate takes over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ExchangeRate {
+    pub id: Option<u32>,
+    /// Group the rate was entered for; `None` for rates loaded from the rates file.
+    pub group_id: Option<u32>,
+    pub from_currency: String,
+    pub to_currency: String,
+    pub rate_micros: i64,
+    pub effective_date: NaiveDate,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ExchangeRateRequest {
+    pub from_currency: String,
+    /// Defaults to the base currency of the group.
+    pub to_currency: Option<String>,
+    pub rate_micros: i64,
+    pub effective_date: NaiveDate,
+}
+
+impl ExchangeRate {
+    /// The rate converting `from` into the other currency of this rate, in
+    /// millionths; a rate stored for the opposite direction is inverted.
+    pub fn micros_from(&self, from: &str) -> i64 {
+        if self.from_currency == from {
+            return self.rate_micros;
+        }
+        let scale = RATE_SCALE as i128 * RATE_SCALE as i128;
+        let rate_micros = self.rate_micros as i128;
+        ((scale + rate_micros / 2) / rate_micros) as i64
+    }
+
+    /// Parses a rates file with one `date,from,to,rate` line per rate, e.g.
+    /// `2024-07-01,USD,EUR,0.9213`. Empty lines, `#` comments and a header line
+    /// starting with `date` are skipped.
+    pub fn parse_csv(contents: &str) -> Result<Vec<ExchangeRate>, String> {
+        let mut rates = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("date") {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [date, from, to, rate] = fields[..] else {
+                return Err(error("expected date,from,to,rate"));
+            };
+            let effective_date =
+                NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| error("invalid date"))?;
+            if !is_valid_currency(from) || !is_valid_currency(to) {
+                return Err(error("invalid currency code"));
+            }
+            let rate_micros = parse_rate(rate).ok_or_else(|| error("invalid rate"))?;
+            rates.push(ExchangeRate {
+                id: None,
+                group_id: None,
+                from_currency: from.to_string(),
+                to_currency: to.to_string(),
+                rate_micros,
+                effective_date,
+            });
+        }
+        Ok(rates)
+    }
+}
+
+/// No exchange rate converts the amount of an expense into the base currency
+/// of its group on the day of the expense.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingRate {
+    pub from_currency: String,
+    pub to_currency: String,
+    pub expense_id: Option<u32>,
+}
+
+impl std::fmt::Display for MissingRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Missing exchange rate for {}→{}",
+            self.from_currency, self.to_currency
+        )?;
+        match self.expense_id {
+            Some(id) => write!(f, " on the date of expense {}", id),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for MissingRate {}
+
+/// Parses a positive decimal rate such as `0.9213` into millionths.
+fn parse_rate(rate: &str) -> Option<i64> {
+    let (whole, fraction) = rate.split_once('.').unwrap_or((rate, ""));
+    if fraction.len() > 6 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let whole: i64 = whole.parse().ok()?;
+    let fraction: i64 = format!("{:0<6}", fraction).parse().ok()?;
+    let micros = whole.checked_mul(RATE_SCALE)?.checked_add(fraction)?;
+    (1..=MAX_RATE_MICROS).contains(&micros).then_some(micros)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("0.9213"), Some(921_300));
+        assert_eq!(parse_rate("161"), Some(161_000_000));
+        assert_eq!(parse_rate("1.000001"), Some(1_000_001));
+        assert_eq!(parse_rate("1.0000001"), None);
+        assert_eq!(parse_rate("0"), None);
+        assert_eq!(parse_rate("1000000000000"), Some(MAX_RATE_MICROS));
+        assert_eq!(parse_rate("1000000000000.000001"), None);
+        assert_eq!(parse_rate("-1.5"), None);
+        assert_eq!(parse_rate("abc"), None);
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let contents = "date,from,to,rate\n# daily rates\n2024-07-01,USD,EUR,0.9213\n\n2024-07-01, JPY , EUR, 0.0058\n";
+        let rates = ExchangeRate::parse_csv(contents).unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates[0].from_currency, "USD");
+        assert_eq!(rates[0].rate_micros, 921_300);
+        assert_eq!(rates[1].from_currency, "JPY");
+        assert_eq!(
+            rates[1].effective_date,
+            NaiveDate::from_ymd_opt(2024, 7, 1).unwrap()
+        );
+
+        assert!(ExchangeRate::parse_csv("2024-07-01,USD,EUR").is_err());
+        assert!(ExchangeRate::parse_csv("2024-13-01,USD,EUR,1").is_err());
+        assert!(ExchangeRate::parse_csv("2024-07-01,usd,EUR,1").is_err());
+    }
+}
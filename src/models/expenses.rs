@@ -1,17 +1,110 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::models::{money::Money, InvalidVariant};
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Expense {
     #[serde(skip_deserializing)]
     pub id: Option<u32>,
     pub description: String,
-    pub amount: f64,
+    pub amount: Money,
     pub payer_id: u32,
+    #[serde(default)]
     pub group_id: u32,
     pub date: String,
+    #[serde(default)]
+    pub split_type: SplitType,
+}
+
+impl Expense {
+    /// Day the expense was made, taken from the `YYYY-MM-DD` prefix of `date`.
+    pub fn day(&self) -> Option<NaiveDate> {
+        let day = self.date.get(..10)?;
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()
+    }
+}
+
+/// How the amount of an expense is divided between its participants.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitType {
+    /// Everyone owes the same part.
+    #[default]
+    Equal,
+    /// Split values are the amounts owed in minor units and must sum to the total.
+    Exact,
+    /// Split values are percentages in basis points and must sum to 10 000.
+    Percentage,
+    /// Split values are weights, e.g. 2 shares for a couple.
+    Shares,
+    /// Split values in minor units are added to an equal part of the remaining amount.
+    Adjustment,
+}
+
+impl Display for SplitType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitType::Equal => write!(f, "equal"),
+            SplitType::Exact => write!(f, "exact"),
+            SplitType::Percentage => write!(f, "percentage"),
+            SplitType::Shares => write!(f, "shares"),
+            SplitType::Adjustment => write!(f, "adjustment"),
+        }
+    }
+}
+
+impl FromStr for SplitType {
+    type Err = InvalidVariant;
+
+    fn from_str(split_type: &str) -> Result<Self, Self::Err> {
+        match split_type {
+            "equal" => Ok(SplitType::Equal),
+            "exact" => Ok(SplitType::Exact),
+            "percentage" => Ok(SplitType::Percentage),
+            "shares" => Ok(SplitType::Shares),
+            "adjustment" => Ok(SplitType::Adjustment),
+            _ => Err(InvalidVariant {
+                kind: "split type",
+                value: split_type.to_string(),
+            }),
+        }
+    }
+}
+
+/// Split value of one participant, interpreted according to the expense's [`SplitType`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ParticipantSplit {
+    pub user_id: u32,
+    pub value: i64,
+}
+
+/// An expense together with the users it is split between.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExpenseDetail {
+    pub expense: Expense,
+    pub participants_ids: Vec<u32>,
+    /// Per-participant split values; empty for equal splits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub split_values: Vec<ParticipantSplit>,
+}
+
+impl ExpenseDetail {
+    pub fn new(expense: Expense, splits: Vec<ParticipantSplit>) -> Self {
+        let participants_ids = splits.iter().map(|split| split.user_id).collect();
+        let split_values = match expense.split_type {
+            SplitType::Equal => Vec::new(),
+            _ => splits,
+        };
+        ExpenseDetail {
+            expense,
+            participants_ids,
+            split_values,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -19,42 +112,86 @@ pub struct Transaction {
     pub id: Option<u32>,
     pub payer_id: u32,
     pub receiver_id: u32,
-    pub amount: f64,
+    pub amount: Money,
     pub date: String,
     pub status: Status,
     pub group_id: u32,
+    /// Settlement plan that proposed the transaction, if any.
+    #[serde(default)]
+    pub plan_id: Option<u32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum Status {
+    /// Proposed by a settlement plan and not paid yet.
     Pending,
+    /// Recorded by the payer, waiting for the receiver to confirm it.
+    Paid,
+    /// Confirmed by the receiver; counts towards the balances.
     Completed,
+    /// Proposed by a settlement plan that a newer plan replaced.
+    Superseded,
 }
 
 impl Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Status::Pending => write!(f, "pending"),
+            Status::Paid => write!(f, "paid"),
             Status::Completed => write!(f, "completed"),
+            Status::Superseded => write!(f, "superseded"),
         }
     }
 }
 
-impl Status {
-    pub fn from_string(status: String) -> Self {
-        match status.as_str() {
-            "pending" => Status::Pending,
-            "completed" => Status::Completed,
-            _ => panic!("Invalid status"),
+impl FromStr for Status {
+    type Err = InvalidVariant;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(Status::Pending),
+            "paid" => Ok(Status::Paid),
+            "completed" => Ok(Status::Completed),
+            "superseded" => Ok(Status::Superseded),
+            _ => Err(InvalidVariant {
+                kind: "transaction status",
+                value: status.to_string(),
+            }),
         }
     }
 }
 
+/// A payment the caller made to another member of the group.
+#[derive(Deserialize, ToSchema)]
+pub struct PaymentRequest {
+    pub receiver_id: u32,
+    pub amount: Money,
+    /// Pending transfer of a settlement plan this payment pays off, fully or in part.
+    #[serde(default)]
+    pub transaction_id: Option<u32>,
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct ExpenseAddRequest {
     pub expense: Expense,
     pub participants_ids: Vec<u32>,
+    /// One value per participant, required unless `expense.split_type` is `equal`.
+    #[serde(default)]
+    pub split_values: Vec<ParticipantSplit>,
 }
+/// Partial update of an expense; omitted fields keep their current value.
+/// `participants_ids`, when present, replaces the whole participant list.
+#[derive(Deserialize, ToSchema)]
+pub struct ExpenseUpdateRequest {
+    pub description: Option<String>,
+    pub amount: Option<Money>,
+    pub payer_id: Option<u32>,
+    pub date: Option<String>,
+    pub participants_ids: Option<Vec<u32>>,
+    pub split_type: Option<SplitType>,
+    pub split_values: Option<Vec<ParticipantSplit>>,
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct GetExpensesByGroupIdRequest {
     pub group_id: u32,
@@ -64,3 +201,41 @@ pub struct GetExpensesByGroupIdRequest {
 pub struct GetExpensesByUserIdRequest {
     pub user_id: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_type_round_trip() {
+        for split_type in [
+            SplitType::Equal,
+            SplitType::Exact,
+            SplitType::Percentage,
+            SplitType::Shares,
+            SplitType::Adjustment,
+        ] {
+            assert_eq!(split_type.to_string().parse(), Ok(split_type));
+        }
+        assert_eq!(
+            "thirds".parse::<SplitType>(),
+            Err(InvalidVariant {
+                kind: "split type",
+                value: "thirds".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [
+            Status::Pending,
+            Status::Paid,
+            Status::Completed,
+            Status::Superseded,
+        ] {
+            assert_eq!(status.to_string().parse(), Ok(status));
+        }
+        assert!("refunded".parse::<Status>().is_err());
+    }
+}
@@ -1,10 +1,17 @@
-use core::fmt;
+use core::{fmt, str::FromStr};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::models::expenses::{Expense, Transaction};
+use crate::models::{
+    expenses::{Expense, ParticipantSplit, Transaction},
+    money::{default_currency, Money},
+    role::{GroupRole, Permission},
+    settlement_constraint::SettlementConstraint,
+    user::UserProfile,
+    InvalidVariant,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Group {
@@ -16,7 +23,111 @@ pub struct Group {
     pub group_end_date: DateTime<Utc>,
     pub description: String,
     pub location: String,
+    /// Currency balances and settlements of the group are expressed in.
+    #[serde(default = "default_currency")]
+    pub base_currency: String,
+    #[serde(default)]
+    pub settlement_strategy: SettlementStrategy,
+    /// When the group was archived; archived groups are read-only.
+    #[serde(skip_deserializing)]
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// A group as its members see it, with the caller's role in it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupDetail {
+    #[serde(flatten)]
+    pub group: Group,
+    pub role: GroupRole,
+    /// What the role lets the caller do in the group.
+    pub permissions: Vec<Permission>,
+}
+
+/// A member of a group with their role.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupMember {
+    #[serde(flatten)]
+    pub user: UserProfile,
+    pub role: GroupRole,
+}
+
+/// How the transfers settling a group are chosen. Every strategy zeroes all balances.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementStrategy {
+    /// As few transfers as possible. Exact for groups of up to 15 people with a
+    /// non-zero balance, greedy above that.
+    #[default]
+    MinimizeTransfers,
+    /// No simplification: members only pay the people they owe money to.
+    Direct,
+    /// Debtors only pay creditors, so no money moves more than once; the
+    /// largest debts are matched first.
+    MinimizeAmount,
+}
+
+impl fmt::Display for SettlementStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettlementStrategy::MinimizeTransfers => write!(f, "minimize_transfers"),
+            SettlementStrategy::Direct => write!(f, "direct"),
+            SettlementStrategy::MinimizeAmount => write!(f, "minimize_amount"),
+        }
+    }
+}
+
+impl FromStr for SettlementStrategy {
+    type Err = InvalidVariant;
+
+    fn from_str(strategy: &str) -> Result<Self, Self::Err> {
+        match strategy {
+            "minimize_transfers" => Ok(SettlementStrategy::MinimizeTransfers),
+            "direct" => Ok(SettlementStrategy::Direct),
+            "minimize_amount" => Ok(SettlementStrategy::MinimizeAmount),
+            _ => Err(InvalidVariant {
+                kind: "settlement strategy",
+                value: strategy.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct CreateGroupRequest {
+    pub name: String,
+    pub group_start_date: DateTime<Utc>,
+    pub group_end_date: DateTime<Utc>,
+    pub description: String,
+    pub location: String,
+    #[serde(default = "default_currency")]
+    pub base_currency: String,
+    #[serde(default)]
+    pub settlement_strategy: SettlementStrategy,
+}
+
+/// Body of `PATCH /groups/{id}`; fields that are left out keep their value.
+#[derive(Deserialize, ToSchema)]
+pub struct GroupUpdateRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub group_start_date: Option<DateTime<Utc>>,
+    pub group_end_date: Option<DateTime<Utc>>,
+}
+
+/// Body of `PUT /groups/{id}/settlement-strategy`.
+#[derive(Deserialize, ToSchema)]
+pub struct SettlementStrategyRequest {
+    pub settlement_strategy: SettlementStrategy,
+}
+
+/// Body of `PUT /groups/{id}/owner`.
+#[derive(Deserialize, ToSchema)]
+pub struct TransferOwnershipRequest {
+    /// Member who becomes the new owner.
+    pub user_id: u32,
 }
+
 #[derive(Deserialize, ToSchema)]
 pub struct GroupRequest {
     pub group_id: u32,
@@ -24,12 +135,14 @@ pub struct GroupRequest {
 #[derive(Deserialize, ToSchema)]
 pub struct JoinGroupRequest {
     pub group_id: u32,
+    /// Invitation code from the owner of the group.
+    pub code: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct GroupSummary {
     pub group: Group,
-    pub total_spent: f64,
+    pub total_spent: Money,
     pub expenses: Vec<Expense>,
     pub transactions: Vec<Transaction>,
 }
@@ -43,6 +156,37 @@ impl fmt::Display for GroupSummary {
     }
 }
 
+/// Everything the balances of a group are computed from, read at one point
+/// in time.
+#[derive(Debug)]
+pub struct GroupLedger {
+    pub group: Group,
+    /// Current members; former members only show up in what they recorded.
+    pub members: Vec<u32>,
+    pub expenses: Vec<LedgerExpense>,
+    /// Completed payments between members.
+    pub payments: Vec<Transaction>,
+    pub constraint: Option<SettlementConstraint>,
+}
+
+/// An expense of a [`GroupLedger`] with its splits.
+#[derive(Debug)]
+pub struct LedgerExpense {
+    pub expense: Expense,
+    /// Rate converting the amount to the base currency, if there is one.
+    pub rate_micros: Option<i64>,
+    pub splits: Vec<ParticipantSplit>,
+}
+
+/// What removing a member from a group did.
+#[derive(Debug, PartialEq)]
+pub enum MemberRemoval {
+    /// The user is now a former member of the group.
+    Removed,
+    /// Nothing changed: the user still has this balance in the group.
+    OutstandingBalance(Money),
+}
+
 impl Group {
     pub fn new(
         name: &str,
@@ -60,6 +204,9 @@ impl Group {
             group_end_date,
             description,
             location,
+            base_currency: default_currency(),
+            settlement_strategy: SettlementStrategy::default(),
+            archived_at: None,
         }
     }
 }
@@ -94,3 +241,20 @@ impl fmt::Display for Group {
         write!(f, "Group: {}", self.name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settlement_strategy_round_trip() {
+        for strategy in [
+            SettlementStrategy::MinimizeTransfers,
+            SettlementStrategy::Direct,
+            SettlementStrategy::MinimizeAmount,
+        ] {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        assert!("random".parse::<SettlementStrategy>().is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Guests are stored as users whose email is in this reserved domain, so they
+/// can take part in expenses and settlements like any other member. Nobody can
+/// register or log in with such an address.
+pub const GUEST_EMAIL_DOMAIN: &str = "guest.invalid";
+
+/// Whether `email` is the placeholder address of a guest.
+pub fn is_guest_email(email: &str) -> bool {
+    email
+        .rsplit_once('@')
+        .is_some_and(|(_, domain)| domain.eq_ignore_ascii_case(GUEST_EMAIL_DOMAIN))
+}
+
+/// A member of a group without an account, added by name by the group owner.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Guest {
+    /// Id the guest appears under in expenses, balances and transactions.
+    pub user_id: u32,
+    pub group_id: u32,
+    pub name: String,
+    pub created_by: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GuestRequest {
+    pub name: String,
+}
+
+/// A new guest with the token that lets the person behind it claim it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GuestInvitation {
+    pub guest: Guest,
+    /// Secret to send to the guest, e.g. as `/auth/register?claim=<token>`.
+    /// Only returned once.
+    pub claim_token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ClaimGuestRequest {
+    pub claim_token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_guest_email() {
+        assert!(is_guest_email("guest-abc@guest.invalid"));
+        assert!(is_guest_email("someone@GUEST.invalid"));
+        assert!(!is_guest_email("someone@example.com"));
+        assert!(!is_guest_email("guest.invalid"));
+    }
+}
@@ -0,0 +1,122 @@
+use core::{fmt, str::FromStr};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::InvalidVariant;
+
+/// How long an invitation stays valid when the request does not say.
+pub const DEFAULT_INVITATION_TTL: TimeDelta = TimeDelta::days(7);
+
+/// A code the owner of a group hands out to let people join it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Invitation {
+    pub id: u32,
+    pub group_id: u32,
+    pub created_by: u32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// How many people may join with the code; `None` for no limit.
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    /// Whether joining with the code only files a request the owner has to approve.
+    pub require_approval: bool,
+    pub revoked: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct InvitationRequest {
+    /// Hours until the code expires, 168 (a week) by default.
+    pub expires_in_hours: Option<i64>,
+    /// Limit on the number of uses, e.g. 1 for a single-use code.
+    pub max_uses: Option<u32>,
+    #[serde(default)]
+    pub require_approval: bool,
+}
+
+/// A new invitation with its code, which is only returned once.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NewInvitation {
+    pub invitation: Invitation,
+    /// Secret to share, e.g. as a link to `POST /groups/{id}/members`.
+    pub code: String,
+}
+
+/// Body for joining a group.
+#[derive(Deserialize, ToSchema)]
+pub struct JoinWithCodeRequest {
+    pub code: String,
+}
+
+/// A request to join a group through an invitation that requires approval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct JoinRequest {
+    pub id: u32,
+    pub group_id: u32,
+    pub user_id: u32,
+    pub invitation_id: u32,
+    pub status: JoinRequestStatus,
+    pub created_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl fmt::Display for JoinRequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinRequestStatus::Pending => write!(f, "pending"),
+            JoinRequestStatus::Approved => write!(f, "approved"),
+            JoinRequestStatus::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+impl FromStr for JoinRequestStatus {
+    type Err = InvalidVariant;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(JoinRequestStatus::Pending),
+            "approved" => Ok(JoinRequestStatus::Approved),
+            "rejected" => Ok(JoinRequestStatus::Rejected),
+            _ => Err(InvalidVariant {
+                kind: "join request status",
+                value: status.to_string(),
+            }),
+        }
+    }
+}
+
+/// What redeeming an invitation code did.
+#[derive(Debug, PartialEq)]
+pub enum JoinOutcome {
+    /// The user is now a member of the group.
+    Joined,
+    /// The invitation requires approval; the owner has to accept this request.
+    Requested(JoinRequest),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_request_status_round_trip() {
+        for status in [
+            JoinRequestStatus::Pending,
+            JoinRequestStatus::Approved,
+            JoinRequestStatus::Rejected,
+        ] {
+            assert_eq!(status.to_string().parse(), Ok(status));
+        }
+        assert!("withdrawn".parse::<JoinRequestStatus>().is_err());
+    }
+}
@@ -1,3 +1,29 @@
+pub mod exchange_rate;
 pub mod expenses;
 pub mod group;
+pub mod guest;
+pub mod invitation;
+pub mod money;
+pub mod role;
+pub mod settlement_constraint;
+pub mod settlement_plan;
 pub mod user;
+
+use std::fmt;
+
+/// A string that names none of the variants of one of the model enums, e.g.
+/// an unknown split type read from the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidVariant {
+    /// What the string should have named, e.g. "split type".
+    pub kind: &'static str,
+    pub value: String,
+}
+
+impl fmt::Display for InvalidVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {} {:?}", self.kind, self.value)
+    }
+}
+
+impl std::error::Error for InvalidVariant {}
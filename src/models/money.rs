@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Currency used when a request does not name one.
+pub const DEFAULT_CURRENCY: &str = "EUR";
+
+/// Exchange rates are stored as integers scaled by this factor, so a rate of
+/// 1.085 is stored as 1 085 000.
+pub const RATE_SCALE: i64 = 1_000_000;
+
+/// Largest rate accepted for an exchange rate: one unit worth a trillion
+/// units of the other currency.
+pub const MAX_RATE_MICROS: i64 = 1_000_000_000_000 * RATE_SCALE;
+
+pub fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+/// Whether `code` looks like an ISO 4217 currency code.
+pub fn is_valid_currency(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// Number of decimal places of the minor unit of `currency`.
+fn currency_exponent(currency: &str) -> u32 {
+    match currency {
+        "JPY" | "KRW" | "ISK" | "CLP" | "VND" => 0,
+        "BHD" | "KWD" | "OMR" | "JOD" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// An exact amount of money in the smallest unit of its currency, e.g. cents for EUR.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Money {
+    pub minor_units: i64,
+    /// ISO 4217 currency code.
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(minor_units: i64, currency: &str) -> Self {
+        Money {
+            minor_units,
+            currency: currency.to_string(),
+        }
+    }
+
+    pub fn zero(currency: &str) -> Self {
+        Money::new(0, currency)
+    }
+
+    /// Whether the currency looks like an ISO 4217 code.
+    pub fn has_valid_currency(&self) -> bool {
+        is_valid_currency(&self.currency)
+    }
+
+    /// Number of decimal places of the currency's minor unit.
+    pub fn exponent(&self) -> u32 {
+        currency_exponent(&self.currency)
+    }
+
+    /// Converts to `currency`, where one unit of this currency is worth
+    /// `rate_micros / RATE_SCALE` units of the target. Rounds half away from zero,
+    /// and fails with [`AmountOverflow`] when the result does not fit in an `i64`.
+    pub fn convert(&self, currency: &str, rate_micros: i64) -> Result<Money, AmountOverflow> {
+        let overflow = || AmountOverflow {
+            amount: self.clone(),
+            currency: currency.to_string(),
+        };
+        let numerator = (self.minor_units as i128)
+            .checked_mul(rate_micros as i128)
+            .and_then(|n| n.checked_mul(10i128.pow(currency_exponent(currency))))
+            .ok_or_else(overflow)?;
+        let denominator = RATE_SCALE as i128 * 10i128.pow(self.exponent());
+        let rounded = (numerator.abs() + denominator / 2) / denominator;
+        let minor_units = i64::try_from(numerator.signum() * rounded).map_err(|_| overflow())?;
+        Ok(Money::new(minor_units, currency))
+    }
+
+    /// Splits the amount proportionally to `weights` without losing a minor unit.
+    ///
+    /// Every entry first gets the rounded-down proportional part; the units left
+    /// over go one by one to the entries with the largest remainders, ties going
+    /// to the lowest id. The parts always sum to the original amount and the
+    /// result does not depend on the order of `weights`.
+    pub fn allocate(&self, weights: &[(u32, i64)]) -> Vec<(u32, Money)> {
+        let total_weight: i128 = weights.iter().map(|(_, weight)| *weight as i128).sum();
+        if total_weight <= 0 {
+            return weights
+                .iter()
+                .map(|(id, _)| (*id, Money::zero(&self.currency)))
+                .collect();
+        }
+        // Allocate the magnitude so negative amounts round the same way as positive ones.
+        let sign = self.minor_units.signum() as i128;
+        let amount = (self.minor_units as i128).abs();
+
+        let mut parts: Vec<(u32, i128, i128)> = weights
+            .iter()
+            .map(|(id, weight)| {
+                let exact = amount * *weight as i128;
+                (*id, exact / total_weight, exact % total_weight)
+            })
+            .collect();
+        let allocated: i128 = parts.iter().map(|(_, part, _)| part).sum();
+
+        let mut order: Vec<usize> = (0..parts.len()).collect();
+        order.sort_by(|&a, &b| {
+            parts[b]
+                .2
+                .cmp(&parts[a].2)
+                .then(parts[a].0.cmp(&parts[b].0))
+        });
+        for &index in order.iter().take((amount - allocated) as usize) {
+            parts[index].1 += 1;
+        }
+
+        parts
+            .into_iter()
+            .map(|(id, part, _)| (id, Money::new((sign * part) as i64, &self.currency)))
+            .collect()
+    }
+}
+
+/// Converting an amount into another currency gives more minor units than an
+/// `i64` holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmountOverflow {
+    pub amount: Money,
+    pub currency: String,
+}
+
+impl std::fmt::Display for AmountOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is too large to convert to {}",
+            self.amount, self.currency
+        )
+    }
+}
+
+impl std::error::Error for AmountOverflow {}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let exponent = self.exponent();
+        if exponent == 0 {
+            return write!(f, "{} {}", self.minor_units, self.currency);
+        }
+        let scale = 10i64.pow(exponent);
+        let sign = if self.minor_units < 0 { "-" } else { "" };
+        let units = self.minor_units.unsigned_abs();
+        write!(
+            f,
+            "{}{}.{:0width$} {}",
+            sign,
+            units / scale as u64,
+            units % scale as u64,
+            self.currency,
+            width = exponent as usize
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minor_units(parts: Vec<(u32, Money)>) -> Vec<(u32, i64)> {
+        parts
+            .into_iter()
+            .map(|(id, money)| (id, money.minor_units))
+            .collect()
+    }
+
+    #[test]
+    fn test_allocate_equal_parts_sum_to_total() {
+        let parts = Money::new(10000, "EUR").allocate(&[(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(minor_units(parts), vec![(1, 3334), (2, 3333), (3, 3333)]);
+    }
+
+    #[test]
+    fn test_allocate_is_stable_by_id() {
+        let forward = Money::new(100, "EUR").allocate(&[(1, 1), (2, 1), (3, 1)]);
+        let backward = Money::new(100, "EUR").allocate(&[(3, 1), (2, 1), (1, 1)]);
+        assert_eq!(minor_units(forward), vec![(1, 34), (2, 33), (3, 33)]);
+        assert_eq!(minor_units(backward), vec![(3, 33), (2, 33), (1, 34)]);
+    }
+
+    #[test]
+    fn test_allocate_largest_remainder() {
+        // 1000 * 1/6 = 166.67, 1000 * 2/6 = 333.33, 1000 * 3/6 = 500
+        let parts = Money::new(1000, "EUR").allocate(&[(1, 1), (2, 2), (3, 3)]);
+        assert_eq!(minor_units(parts), vec![(1, 167), (2, 333), (3, 500)]);
+    }
+
+    #[test]
+    fn test_allocate_negative_and_zero_weights() {
+        let parts = Money::new(-100, "EUR").allocate(&[(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(minor_units(parts), vec![(1, -34), (2, -33), (3, -33)]);
+        let parts = Money::new(100, "EUR").allocate(&[(1, 0), (2, 0)]);
+        assert_eq!(minor_units(parts), vec![(1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Money::new(12345, "EUR").to_string(), "123.45 EUR");
+        assert_eq!(Money::new(-5, "EUR").to_string(), "-0.05 EUR");
+        assert_eq!(Money::new(500, "JPY").to_string(), "500 JPY");
+        assert_eq!(Money::new(1500, "KWD").to_string(), "1.500 KWD");
+    }
+
+    #[test]
+    fn test_convert() {
+        let usd = Money::new(10000, "USD");
+        assert_eq!(
+            usd.convert("EUR", 920_000).unwrap(),
+            Money::new(9200, "EUR")
+        );
+        // 0.5 cent rounds away from zero
+        assert_eq!(
+            Money::new(1, "USD").convert("EUR", 500_000).unwrap(),
+            Money::new(1, "EUR")
+        );
+        assert_eq!(
+            Money::new(-1, "USD").convert("EUR", 500_000).unwrap(),
+            Money::new(-1, "EUR")
+        );
+        // Different minor units: 1000 JPY at 0.0062 EUR
+        assert_eq!(
+            Money::new(1000, "JPY").convert("EUR", 6_200).unwrap(),
+            Money::new(620, "EUR")
+        );
+        assert_eq!(
+            Money::new(620, "EUR").convert("JPY", 161_290_323).unwrap(),
+            Money::new(1000, "JPY")
+        );
+        // Overflowing the intermediate product or the result
+        assert!(Money::new(i64::MAX, "USD")
+            .convert("EUR", i64::MAX)
+            .is_err());
+        assert!(Money::new(i64::MAX, "USD")
+            .convert("EUR", 2 * RATE_SCALE)
+            .is_err());
+    }
+
+    #[test]
+    fn test_currency_validation() {
+        assert!(Money::new(1, "EUR").has_valid_currency());
+        assert!(!Money::new(1, "eur").has_valid_currency());
+        assert!(!Money::new(1, "EURO").has_valid_currency());
+    }
+}
@@ -0,0 +1,141 @@
+use core::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::InvalidVariant;
+
+/// Role of a member in a group. The owner is whoever `owner_id` of the group
+/// names; the other roles are stored on the membership.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupRole {
+    Owner,
+    /// Helps the owner run the group: everything but transferring ownership
+    /// and changing roles.
+    Admin,
+    /// Adds the expenses they paid and edits their own.
+    #[default]
+    Member,
+    /// Can only look at the group.
+    Viewer,
+}
+
+/// Something a member may or may not do in a group, depending on their role.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Add and edit expenses they paid, record payments and generate settlement plans.
+    AddExpenses,
+    /// Add expenses paid by another member.
+    AddExpensesForOthers,
+    /// Edit and delete expenses other members paid.
+    EditOthersExpenses,
+    /// Invite people, handle join requests, add guests and remove members.
+    ManageMembers,
+    /// Change the settlement strategy and constraint and the exchange rates.
+    ChangeSettings,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::AddExpenses,
+        Permission::AddExpensesForOthers,
+        Permission::EditOthersExpenses,
+        Permission::ManageMembers,
+        Permission::ChangeSettings,
+    ];
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::AddExpenses => write!(f, "add expenses"),
+            Permission::AddExpensesForOthers => write!(f, "add expenses for others"),
+            Permission::EditOthersExpenses => write!(f, "edit the expenses of others"),
+            Permission::ManageMembers => write!(f, "manage members"),
+            Permission::ChangeSettings => write!(f, "change the settings"),
+        }
+    }
+}
+
+impl GroupRole {
+    pub fn allows(self, permission: Permission) -> bool {
+        match self {
+            GroupRole::Owner | GroupRole::Admin => true,
+            GroupRole::Member => permission == Permission::AddExpenses,
+            GroupRole::Viewer => false,
+        }
+    }
+
+    /// Everything the role allows, for clients to decide what to show.
+    pub fn permissions(self) -> Vec<Permission> {
+        Permission::ALL
+            .into_iter()
+            .filter(|permission| self.allows(*permission))
+            .collect()
+    }
+}
+
+impl fmt::Display for GroupRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupRole::Owner => write!(f, "owner"),
+            GroupRole::Admin => write!(f, "admin"),
+            GroupRole::Member => write!(f, "member"),
+            GroupRole::Viewer => write!(f, "viewer"),
+        }
+    }
+}
+
+impl FromStr for GroupRole {
+    type Err = InvalidVariant;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "owner" => Ok(GroupRole::Owner),
+            "admin" => Ok(GroupRole::Admin),
+            "member" => Ok(GroupRole::Member),
+            "viewer" => Ok(GroupRole::Viewer),
+            _ => Err(InvalidVariant {
+                kind: "group role",
+                value: role.to_string(),
+            }),
+        }
+    }
+}
+
+/// Body of `PUT /groups/{id}/members/{user_id}/role`.
+#[derive(Deserialize, ToSchema)]
+pub struct RoleRequest {
+    pub role: GroupRole,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_permissions() {
+        assert_eq!(GroupRole::Owner.permissions(), Permission::ALL.to_vec());
+        assert_eq!(GroupRole::Admin.permissions(), Permission::ALL.to_vec());
+        assert_eq!(
+            GroupRole::Member.permissions(),
+            vec![Permission::AddExpenses]
+        );
+        assert!(GroupRole::Viewer.permissions().is_empty());
+    }
+
+    #[test]
+    fn test_role_round_trip() {
+        for role in [
+            GroupRole::Owner,
+            GroupRole::Admin,
+            GroupRole::Member,
+            GroupRole::Viewer,
+        ] {
+            assert_eq!(role.to_string().parse(), Ok(role));
+        }
+        assert!("guest".parse::<GroupRole>().is_err());
+    }
+}
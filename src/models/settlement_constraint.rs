@@ -0,0 +1,216 @@
+use core::{fmt, str::FromStr};
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::InvalidVariant;
+
+/// Two members of a group, in no particular order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct MemberPair {
+    pub first_user_id: u32,
+    pub second_user_id: u32,
+}
+
+impl MemberPair {
+    pub fn new(first_user_id: u32, second_user_id: u32) -> Self {
+        MemberPair {
+            first_user_id,
+            second_user_id,
+        }
+    }
+
+    /// Whether the pair consists of `a` and `b`, in either order.
+    fn joins(&self, a: u32, b: u32) -> bool {
+        (self.first_user_id, self.second_user_id) == (a, b)
+            || (self.first_user_id, self.second_user_id) == (b, a)
+    }
+}
+
+/// Limits on who may pay whom when a group settles up. The settlement still
+/// zeroes every balance, routing money through other members where needed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SettlementConstraint {
+    /// Members only pay each other along these pairs, in either direction.
+    AllowedPairs { pairs: Vec<MemberPair> },
+    /// Any two members may pay each other except along these pairs.
+    ForbiddenPairs { pairs: Vec<MemberPair> },
+    /// Every transfer goes to or from this member.
+    Hub { user_id: u32 },
+    /// Every transfer goes to or from the owner of the group.
+    ThroughOwner,
+}
+
+/// The variant of a [`SettlementConstraint`] without its data, as stored in
+/// the `kind` column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstraintKind {
+    AllowedPairs,
+    ForbiddenPairs,
+    Hub,
+    ThroughOwner,
+}
+
+impl fmt::Display for ConstraintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstraintKind::AllowedPairs => write!(f, "allowed_pairs"),
+            ConstraintKind::ForbiddenPairs => write!(f, "forbidden_pairs"),
+            ConstraintKind::Hub => write!(f, "hub"),
+            ConstraintKind::ThroughOwner => write!(f, "through_owner"),
+        }
+    }
+}
+
+impl FromStr for ConstraintKind {
+    type Err = InvalidVariant;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "allowed_pairs" => Ok(ConstraintKind::AllowedPairs),
+            "forbidden_pairs" => Ok(ConstraintKind::ForbiddenPairs),
+            "hub" => Ok(ConstraintKind::Hub),
+            "through_owner" => Ok(ConstraintKind::ThroughOwner),
+            _ => Err(InvalidVariant {
+                kind: "settlement constraint",
+                value: kind.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for SettlementConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+impl SettlementConstraint {
+    /// Which kind of constraint this is, without its data.
+    pub fn kind(&self) -> ConstraintKind {
+        match self {
+            SettlementConstraint::AllowedPairs { .. } => ConstraintKind::AllowedPairs,
+            SettlementConstraint::ForbiddenPairs { .. } => ConstraintKind::ForbiddenPairs,
+            SettlementConstraint::Hub { .. } => ConstraintKind::Hub,
+            SettlementConstraint::ThroughOwner => ConstraintKind::ThroughOwner,
+        }
+    }
+
+    /// Whether `payer` may pay `receiver` directly in a group owned by `owner_id`.
+    pub fn allows(&self, owner_id: u32, payer: u32, receiver: u32) -> bool {
+        match self {
+            SettlementConstraint::AllowedPairs { pairs } => {
+                pairs.iter().any(|pair| pair.joins(payer, receiver))
+            }
+            SettlementConstraint::ForbiddenPairs { pairs } => {
+                !pairs.iter().any(|pair| pair.joins(payer, receiver))
+            }
+            SettlementConstraint::Hub { user_id } => payer == *user_id || receiver == *user_id,
+            SettlementConstraint::ThroughOwner => payer == owner_id || receiver == owner_id,
+        }
+    }
+
+    /// Members the constraint refers to, which must all belong to the group.
+    pub fn user_ids(&self) -> Vec<u32> {
+        let ids: BTreeSet<u32> = match self {
+            SettlementConstraint::AllowedPairs { pairs }
+            | SettlementConstraint::ForbiddenPairs { pairs } => pairs
+                .iter()
+                .flat_map(|pair| [pair.first_user_id, pair.second_user_id])
+                .collect(),
+            SettlementConstraint::Hub { user_id } => [*user_id].into(),
+            SettlementConstraint::ThroughOwner => BTreeSet::new(),
+        };
+        ids.into_iter().collect()
+    }
+
+    /// Members that cannot reach the first of `members` through a chain of
+    /// allowed payments. Any balances can be settled when this is empty.
+    pub fn unreachable_members(&self, owner_id: u32, members: &[u32]) -> Vec<u32> {
+        let Some(&first) = members.first() else {
+            return Vec::new();
+        };
+        let mut reached = BTreeSet::from([first]);
+        let mut queue = vec![first];
+        while let Some(user) = queue.pop() {
+            for &other in members {
+                if !reached.contains(&other) && self.allows(owner_id, user, other) {
+                    reached.insert(other);
+                    queue.push(other);
+                }
+            }
+        }
+        members
+            .iter()
+            .copied()
+            .filter(|member| !reached.contains(member))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows() {
+        let allowed = SettlementConstraint::AllowedPairs {
+            pairs: vec![MemberPair::new(1, 2)],
+        };
+        assert!(allowed.allows(9, 1, 2));
+        assert!(allowed.allows(9, 2, 1));
+        assert!(!allowed.allows(9, 1, 3));
+
+        let forbidden = SettlementConstraint::ForbiddenPairs {
+            pairs: vec![MemberPair::new(1, 2)],
+        };
+        assert!(!forbidden.allows(9, 2, 1));
+        assert!(forbidden.allows(9, 1, 3));
+
+        let hub = SettlementConstraint::Hub { user_id: 3 };
+        assert!(hub.allows(9, 1, 3));
+        assert!(!hub.allows(9, 1, 2));
+        assert!(SettlementConstraint::ThroughOwner.allows(9, 9, 2));
+        assert!(!SettlementConstraint::ThroughOwner.allows(9, 1, 2));
+    }
+
+    #[test]
+    fn test_unreachable_members() {
+        let chain = SettlementConstraint::AllowedPairs {
+            pairs: vec![MemberPair::new(1, 2), MemberPair::new(3, 2)],
+        };
+        assert!(chain.unreachable_members(1, &[1, 2, 3]).is_empty());
+        assert_eq!(chain.unreachable_members(1, &[1, 2, 3, 4]), vec![4]);
+
+        let forbidden = SettlementConstraint::ForbiddenPairs {
+            pairs: vec![MemberPair::new(1, 2)],
+        };
+        assert_eq!(forbidden.unreachable_members(1, &[1, 2]), vec![2]);
+        assert!(forbidden.unreachable_members(1, &[1, 2, 3]).is_empty());
+    }
+
+    #[test]
+    fn test_kind_round_trip() {
+        for kind in [
+            ConstraintKind::AllowedPairs,
+            ConstraintKind::ForbiddenPairs,
+            ConstraintKind::Hub,
+            ConstraintKind::ThroughOwner,
+        ] {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+        }
+        assert!("nobody".parse::<ConstraintKind>().is_err());
+    }
+
+    #[test]
+    fn test_serialization() {
+        let hub: SettlementConstraint =
+            serde_json::from_str(r#"{"kind": "hub", "user_id": 4}"#).unwrap();
+        assert_eq!(hub, SettlementConstraint::Hub { user_id: 4 });
+        let owner: SettlementConstraint =
+            serde_json::from_str(r#"{"kind": "through_owner"}"#).unwrap();
+        assert_eq!(owner, SettlementConstraint::ThroughOwner);
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::expenses::Transaction;
+
+/// A versioned set of transfers proposed to settle the outstanding balances of
+/// a group. Generating a new plan supersedes the pending transfers of the
+/// previous one; completed transfers are kept and already accounted for.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SettlementPlan {
+    pub id: u32,
+    pub group_id: u32,
+    /// Starts at 1 and increases with every plan generated for the group.
+    pub version: u32,
+    pub created_by: u32,
+    pub created_at: DateTime<Utc>,
+    pub transactions: Vec<Transaction>,
+}
@@ -1,6 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::models::guest::is_guest_email;
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct User {
     #[serde(skip_deserializing)]
@@ -24,3 +27,38 @@ impl std::fmt::Display for User {
         write!(f, "User: {}\nEmail: {}", self.name, self.email)
     }
 }
+
+/// Public view of a user, without credentials.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserProfile {
+    pub id: u32,
+    pub name: String,
+    /// Empty for guests.
+    pub email: String,
+    /// Whether this is a guest added by name, without an account.
+    pub guest: bool,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        let guest = is_guest_email(&user.email);
+        UserProfile {
+            id: user.id.unwrap_or_default(),
+            name: user.name,
+            email: if guest { String::new() } else { user.email },
+            guest,
+        }
+    }
+}
+
+/// An active login of a user, as listed by `/auth/sessions`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Session {
+    pub id: u32,
+    pub device_label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
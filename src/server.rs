@@ -1,15 +1,34 @@
-use std::fs;
-use std::net::SocketAddr;
-use std::path::Path;
+use std::{fmt, fs, io, net::SocketAddr, path::PathBuf, sync::Arc};
 
-use axum::{routing::get, Router};
-use tower_http::cors::CorsLayer;
+use axum::{
+    http::{header::HeaderName, HeaderValue},
+    middleware::map_response,
+    response::Response,
+    routing::get,
+    Router,
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
 
-use crate::{auth, db::Database, expense, group, summary};
+use crate::{
+    auth,
+    config::Config,
+    db::{migration::MigrationError, Database},
+    error::ErrorResponse,
+    expense, group,
+    logging::{self, REQUEST_ID_HEADER},
+    models::exchange_rate::ExchangeRate,
+    summary, v1,
+};
 use axum::serve;
 use tokio::net::TcpListener;
-use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
-use utoipa::openapi::Info;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{Deprecated, Info};
 use utoipa::Modify;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
@@ -17,6 +36,7 @@ use utoipa_swagger_ui::SwaggerUi;
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
+    pub config: Arc<Config>,
 }
 #[derive(OpenApi)]
 #[openapi(
@@ -25,42 +45,148 @@ pub struct AppState {
         (path = "/auth", api = auth::AuthApi),
         (path = "/expense", api = expense::ExpenseApi),
         (path = "/summary", api = summary::SummaryApi),
+        (path = "/api/v1", api = v1::V1Api),
     ),
     paths(
         ok_handler
     ),
-    modifiers(&SecurityAddon)
+    components(schemas(ErrorResponse)),
+    modifiers(&SecurityAddon, &LegacyRoutesAddon)
 )]
 struct ApiDoc;
 
+/// Prefixes of the RPC-style routes superseded by `/api/v1`.
+const LEGACY_PREFIXES: [&str; 3] = ["/group/", "/expense/", "/summary/"];
+
+/// Marks the operations of the legacy routes as deprecated in the OpenAPI document.
+struct LegacyRoutesAddon;
+impl Modify for LegacyRoutesAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if !LEGACY_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+            {
+                continue;
+            }
+            for operation in [
+                &mut item.get,
+                &mut item.post,
+                &mut item.put,
+                &mut item.patch,
+                &mut item.delete,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                operation.deprecated = Some(Deprecated::True);
+            }
+        }
+    }
+}
+
+/// Tells clients of the legacy routes to move to `/api/v1`.
+async fn deprecation_headers(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_static("true"),
+    );
+    headers.insert(
+        HeaderName::from_static("link"),
+        HeaderValue::from_static("</api/v1>; rel=\"successor-version\""),
+    );
+    response
+}
+
 struct SecurityAddon;
 impl Modify for SecurityAddon {
     fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
         if let Some(components) = openapi.components.as_mut() {
             components.add_security_scheme(
                 "api_key",
-                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("todo_apikey"))),
-            )
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "todo_apikey",
+                    "Legacy header carrying the session token",
+                ))),
+            );
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some(
+                            "Session token from /auth/login sent as `Authorization: Bearer <token>`",
+                        ))
+                        .build(),
+                ),
+            );
         }
     }
 }
-pub async fn app() -> Router {
-    let db_path = "sqlite.db";
+/// Why the server could not start.
+#[derive(Debug)]
+pub enum StartupError {
+    /// The database at `database_url` could not be opened.
+    Connect(sqlx::Error),
+    Migrate(MigrationError),
+    /// The exchange rates file exists but could not be read or loaded.
+    ExchangeRates {
+        path: PathBuf,
+        reason: String,
+    },
+    /// Nothing can listen on `bind_address`, e.g. because it is in use.
+    Bind {
+        address: SocketAddr,
+        error: io::Error,
+    },
+    Serve(io::Error),
+}
 
-    let db_exists = Path::new(db_path).exists();
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::Connect(e) => write!(f, "Cannot connect to the database: {}", e),
+            StartupError::Migrate(e) => write!(f, "Cannot migrate the database: {}", e),
+            StartupError::ExchangeRates { path, reason } => {
+                write!(
+                    f,
+                    "Invalid exchange rates file {}: {}",
+                    path.display(),
+                    reason
+                )
+            }
+            StartupError::Bind { address, error } => {
+                write!(f, "Cannot listen on {}: {}", address, error)
+            }
+            StartupError::Serve(e) => write!(f, "Server error: {}", e),
+        }
+    }
+}
 
-    let db = if !db_exists {
-        fs::write(db_path, "").unwrap();
-        let db = Database::new(db_path).await.unwrap();
-        db.init().await.unwrap();
-        db
-    } else {
-        Database::new(db_path).await.unwrap()
-    };
+impl std::error::Error for StartupError {}
 
-    let app_state = AppState { db };
+pub async fn app(config: Config) -> Result<Router, StartupError> {
+    let db = Database::connect(&config.database_url, config.max_connections)
+        .await
+        .map_err(StartupError::Connect)?;
+    for migration in db.migrate().await.map_err(StartupError::Migrate)? {
+        tracing::info!(
+            version = migration.version,
+            name = migration.name,
+            "applied migration"
+        );
+    }
+
+    load_exchange_rates(&db, &config).await?;
+
+    let cors = cors_layer(&config);
+    let features = config.features.clone();
+    let app_state = AppState {
+        db,
+        config: Arc::new(config),
+    };
 
-    let cors = CorsLayer::permissive();
     let mut doc = ApiDoc::openapi();
     doc.info = Info::builder()
         .title("Trip Split")
@@ -70,24 +196,106 @@ pub async fn app() -> Router {
         ))
         .build();
 
-    Router::new()
-        .merge(SwaggerUi::new("/swagger-ui").url("/api/openapi.json", doc))
-        .nest("/group", group::router(app_state.clone()))
+    let mut router = Router::new();
+    if features.legacy_routes {
+        router = router
+            .nest(
+                "/group",
+                group::router(app_state.clone()).layer(map_response(deprecation_headers)),
+            )
+            .nest(
+                "/expense",
+                expense::router(app_state.clone()).layer(map_response(deprecation_headers)),
+            )
+            .nest(
+                "/summary",
+                summary::router(app_state.clone()).layer(map_response(deprecation_headers)),
+            );
+    } else {
+        doc.paths.paths.retain(|path, _| {
+            !LEGACY_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+        });
+    }
+    if features.swagger_ui {
+        router = router.merge(SwaggerUi::new("/swagger-ui").url("/api/openapi.json", doc));
+    }
+    let router = router
         .nest("/auth", auth::router(app_state.clone()))
-        .nest("/expense", expense::router(app_state.clone()))
-        .nest("/summary", summary::router(app_state.clone()))
+        .nest("/api/v1", v1::router(app_state.clone()))
         .route("/ok", get(ok_handler))
         .fallback(ok_handler)
-        .layer(cors)
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::new(
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                    MakeRequestUuid,
+                ))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(logging::request_span)
+                        .on_response(logging::log_response),
+                )
+                .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+                    REQUEST_ID_HEADER,
+                )))
+                .layer(CatchPanicLayer::custom(logging::panic_response))
+                .layer(cors),
+        );
+    Ok(router)
+}
+
+/// Allows the configured origins, or any origin if they include `*`.
+fn cors_layer(config: &Config) -> CorsLayer {
+    if config.allows_any_origin() {
+        return CorsLayer::permissive();
+    }
+    let origins = config
+        .cors_allowed_origins
+        .iter()
+        // Checked by `Config::validate`
+        .map(|origin| HeaderValue::from_str(origin).unwrap());
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(Any)
+        .allow_headers(Any)
 }
 
-pub async fn start() {
-    let addr: SocketAddr = format!("{}:{}", "0.0.0.0", 3000).parse().unwrap();
+/// Replaces the stored global exchange rates with the configured rates file,
+/// if it exists.
+async fn load_exchange_rates(db: &Database, config: &Config) -> Result<(), StartupError> {
+    let path = &config.exchange_rates_file;
+    let invalid = |reason: String| StartupError::ExchangeRates {
+        path: path.clone(),
+        reason,
+    };
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+    let rates = ExchangeRate::parse_csv(&contents).map_err(invalid)?;
+    db.replace_global_exchange_rates(&rates)
+        .await
+        .map_err(|e| invalid(e.to_string()))?;
+    tracing::info!(
+        count = rates.len(),
+        path = %path.display(),
+        "loaded exchange rates"
+    );
+    Ok(())
+}
 
-    println!("Listening on http://{}", addr);
-    let listener = TcpListener::bind(addr).await.unwrap();
+pub async fn start(config: Config) -> Result<(), StartupError> {
+    let address = config.bind_address;
+    let app = app(config).await?;
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|error| StartupError::Bind { address, error })?;
 
-    serve(listener, app().await).await.unwrap();
+    tracing::info!("listening on http://{}", address);
+    serve(listener, app).await.map_err(StartupError::Serve)
 }
 
 #[utoipa::path(
@@ -1,250 +1,829 @@
 use crate::{
     db::Database,
-    models::expenses::{Expense, Transaction as DetailedTransaction},
+    expense::split::owed_amounts,
+    models::{
+        exchange_rate::MissingRate,
+        expenses::{Expense, ParticipantSplit, Status, Transaction as DetailedTransaction},
+        group::GroupLedger,
+        money::{AmountOverflow, Money},
+        settlement_plan::SettlementPlan,
+    },
+    summary::solver::{constrain, settle},
 };
 use chrono::Utc;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use utoipa::ToSchema;
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct UserBalance {
     pub user_id: u32,
-    pub total_paid: f64,
-    pub total_owed: f64,
-    pub net_balance: f64,
+    /// Sum of the expenses the user paid.
+    pub total_paid: Money,
+    /// Sum of the user's shares of expenses.
+    pub total_owed: Money,
+    /// Completed settlement payments the user made to other members.
+    pub payments_sent: Money,
+    /// Completed settlement payments the user received from other members.
+    pub payments_received: Money,
+    pub net_balance: Money,
+}
+
+impl UserBalance {
+    fn new(user_id: u32, currency: &str) -> Self {
+        UserBalance {
+            user_id,
+            total_paid: Money::zero(currency),
+            total_owed: Money::zero(currency),
+            payments_sent: Money::zero(currency),
+            payments_received: Money::zero(currency),
+            net_balance: Money::zero(currency),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct GroupSummary {
     pub group_id: u32,
-    pub total_expenses: f64,
+    /// Currency every total and transaction of the summary is expressed in.
+    pub base_currency: String,
+    pub total_expenses: Money,
+    pub expenses: Vec<ConvertedExpense>,
     pub transactions_needed: Vec<Transaction>,
 }
 
+/// Balance of a user in one of their groups.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupPosition {
+    pub group_id: u32,
+    pub group_name: String,
+    pub balance: UserBalance,
+}
+
+/// Net position of a user across every group they belong to.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserPosition {
+    pub user_id: u32,
+    pub groups: Vec<GroupPosition>,
+    /// Net balance over all groups, one entry per base currency. Positive
+    /// means the user is owed money overall.
+    pub net_balances: Vec<Money>,
+    /// Groups left out of `groups` and `net_balances` because their balances
+    /// cannot be computed, e.g. for lack of an exchange rate.
+    pub incomplete_groups: Vec<IncompleteGroup>,
+}
+
+/// A group whose balances cannot be computed, and why.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncompleteGroup {
+    pub group_id: u32,
+    pub group_name: String,
+    pub reason: String,
+}
+
+/// An expense with its amount as entered and converted to the base currency of the group.
 #[derive(Debug, Serialize, ToSchema)]
+pub struct ConvertedExpense {
+    pub expense_id: u32,
+    pub description: String,
+    pub date: String,
+    pub original_amount: Money,
+    pub converted_amount: Money,
+    /// Rate used for the conversion, in millionths.
+    pub rate_micros: i64,
+}
+
+/// A transfer settling a debt: `from_user_id` pays `amount` to `to_user_id`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Transaction {
     pub id: Option<u32>,
     pub from_user_id: u32,
     pub to_user_id: u32,
-    pub amount: f64,
+    pub amount: Money,
 }
 
 impl Database {
+    /// Summary of the group; see [`group_summary`].
     pub async fn get_group_summary(&self, group_id: u32) -> Result<GroupSummary, sqlx::Error> {
-        // Get all expenses for the group
-        let expenses = self.get_expenses_by_group_id(group_id).await?;
+        group_summary(group_id, &self.get_group_ledger(group_id).await?)
+    }
 
-        let mut total_expenses = 0.0;
+    /// Balances of the members of the group; see [`group_balances`].
+    pub async fn get_group_balances(&self, group_id: u32) -> Result<Vec<UserBalance>, sqlx::Error> {
+        group_balances(&self.get_group_ledger(group_id).await?)
+    }
 
-        // Calculate expenses and splits
-        for expense in &expenses {
-            total_expenses += expense.amount;
+    /// Balance of the user in each of their groups, plus the net totals per currency.
+    pub async fn get_user_position(&self, user_id: u32) -> Result<UserPosition, sqlx::Error> {
+        let mut groups = Vec::new();
+        let mut incomplete_groups = Vec::new();
+        let mut totals: BTreeMap<String, i64> = BTreeMap::new();
+        let mut group_ids = self.get_user_groups(user_id).await?;
+        group_ids.sort();
+        for group_id in group_ids {
+            let group = self.get_group(group_id).await?;
+            let balances = match self.get_group_balances(group_id).await {
+                Ok(balances) => balances,
+                // One group missing a rate should not hide the others
+                Err(sqlx::Error::Decode(error))
+                    if error.is::<MissingRate>() || error.is::<AmountOverflow>() =>
+                {
+                    incomplete_groups.push(IncompleteGroup {
+                        group_id,
+                        group_name: group.name,
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let Some(balance) = balances
+                .into_iter()
+                .find(|balance| balance.user_id == user_id)
+            else {
+                continue;
+            };
+            *totals
+                .entry(balance.net_balance.currency.clone())
+                .or_insert(0) += balance.net_balance.minor_units;
+            groups.push(GroupPosition {
+                group_id,
+                group_name: group.name,
+                balance,
+            });
         }
-        let rounded = (total_expenses * 100.0).round() / 100.0; // Calculate needed transactions
-        let transactions_needed = self
-            .calculate_optimal_transactions(expenses, group_id)
-            .await;
-
-        Ok(GroupSummary {
-            group_id,
-            total_expenses: rounded,
-            transactions_needed,
+        Ok(UserPosition {
+            user_id,
+            groups,
+            net_balances: totals
+                .into_iter()
+                .map(|(currency, minor_units)| Money::new(minor_units, &currency))
+                .collect(),
+            incomplete_groups,
         })
     }
-    async fn calculate_optimal_transactions(
+
+    /// Stores the transfers currently needed to settle the group as a new
+    /// version of its settlement plan, replacing the pending transfers of the
+    /// previous version. Completed payments are kept and already deducted.
+    pub async fn generate_settlement_plan(
         &self,
-        expenses: Vec<Expense>,
         group_id: u32,
-    ) -> Vec<Transaction> {
-        //TODO: remove previous transactions, and create new ones
-        self.delete_transactions_by_group_id(group_id)
+        created_by: u32,
+    ) -> Result<SettlementPlan, sqlx::Error> {
+        let ledger = self.get_group_ledger(group_id).await?;
+        let date = Utc::now().naive_utc().to_string();
+        let transfers: Vec<DetailedTransaction> = optimal_transactions(&ledger)?
+            .into_iter()
+            .map(|transfer| DetailedTransaction {
+                id: None,
+                payer_id: transfer.from_user_id,
+                receiver_id: transfer.to_user_id,
+                amount: transfer.amount,
+                date: date.clone(),
+                status: Status::Pending,
+                group_id,
+                plan_id: None,
+            })
+            .collect();
+        self.create_settlement_plan(group_id, created_by, &transfers)
             .await
-            .unwrap();
-        let mut transactions = vec![];
-        for expense in expenses {
-            let payer = expense.payer_id;
-            let participants = self
-                .get_expense_participants(expense.id.unwrap())
-                .await
-                .unwrap();
-            let amount = expense.amount;
-            let amount_per_participant = amount / participants.len() as f64;
-            for participant in participants {
-                if participant == payer {
-                    continue;
-                }
-                transactions.push(Transaction {
-                    id: None,
-                    from_user_id: payer,
-                    to_user_id: participant,
-                    amount: amount_per_participant,
-                });
+    }
+}
+
+/// Expenses of the group converted to its base currency, with the transfers
+/// that would settle what is still outstanding.
+pub fn group_summary(group_id: u32, ledger: &GroupLedger) -> Result<GroupSummary, sqlx::Error> {
+    let currency = &ledger.group.base_currency;
+    let expenses = ledger
+        .expenses
+        .iter()
+        .map(|entry| convert_expense(&entry.expense, currency, entry.rate_micros))
+        .collect::<Result<Vec<_>, _>>()?;
+    let total_expenses = Money::new(
+        expenses
+            .iter()
+            .map(|converted| converted.converted_amount.minor_units)
+            .sum(),
+        currency,
+    );
+    Ok(GroupSummary {
+        group_id,
+        base_currency: currency.clone(),
+        total_expenses,
+        expenses,
+        transactions_needed: optimal_transactions(ledger)?,
+    })
+}
+
+/// Transfers that would settle what is still outstanding in the group: the
+/// debts from every expense, minus the payments already completed, simplified
+/// according to the group's settlement strategy and rerouted to respect its
+/// settlement constraint, if any, and to leave out former members. Nothing is
+/// stored; see [`Database::generate_settlement_plan`].
+fn optimal_transactions(ledger: &GroupLedger) -> Result<Vec<Transaction>, sqlx::Error> {
+    let group = &ledger.group;
+    let mut transactions = vec![];
+    for entry in &ledger.expenses {
+        let expense = &entry.expense;
+        let converted = convert_expense(expense, &group.base_currency, entry.rate_micros)?;
+        let payer = expense.payer_id;
+        for (participant, owed) in
+            converted_owed_amounts(expense, &converted.converted_amount, &entry.splits)?
+        {
+            if participant == payer {
+                continue;
             }
+            // Every participant owes their part to the payer
+            transactions.push(Transaction {
+                id: None,
+                from_user_id: participant,
+                to_user_id: payer,
+                amount: owed,
+            });
         }
-        transactions = minimize(transactions);
-        for transaction in &mut transactions {
-            let id = self
-                .create_transaction(&DetailedTransaction {
-                    payer_id: transaction.from_user_id,
-                    receiver_id: transaction.to_user_id,
-                    amount: transaction.amount,
-                    id: None,
-                    date: Utc::now().naive_utc().to_string(),
-                    status: crate::models::expenses::Status::Pending,
-                    group_id,
-                })
-                .await
-                .unwrap();
-            transaction.id = Some(id);
+    }
+    for payment in &ledger.payments {
+        // A completed payment cancels the same amount of debt
+        transactions.push(Transaction {
+            id: None,
+            from_user_id: payment.receiver_id,
+            to_user_id: payment.payer_id,
+            amount: payment.amount.clone(),
+        });
+    }
+    let transfers = settle(transactions, group.settlement_strategy);
+    // Former members only keep the balance they had when they left, which
+    // was zero, so nobody should be asked to pay them or through them.
+    let members = &ledger.members;
+    Ok(constrain(transfers, members, |payer, receiver| {
+        members.contains(&payer)
+            && members.contains(&receiver)
+            && ledger
+                .constraint
+                .as_ref()
+                .is_none_or(|c| c.allows(group.owner_id, payer, receiver))
+    }))
+}
+
+/// Computes how much each member of the group paid and owes according to the
+/// split of every expense and the completed payments between members, in the
+/// base currency of the group. A positive `net_balance` means the member is
+/// still owed money.
+pub fn group_balances(ledger: &GroupLedger) -> Result<Vec<UserBalance>, sqlx::Error> {
+    let currency = &ledger.group.base_currency;
+    let mut balances: HashMap<u32, UserBalance> = HashMap::new();
+    for &member in &ledger.members {
+        balances.insert(member, UserBalance::new(member, currency));
+    }
+    for entry in &ledger.expenses {
+        let expense = &entry.expense;
+        let converted = convert_expense(expense, currency, entry.rate_micros)?;
+        balances
+            .entry(expense.payer_id)
+            .or_insert_with(|| UserBalance::new(expense.payer_id, currency))
+            .total_paid
+            .minor_units += converted.converted_amount.minor_units;
+        for (participant, owed) in
+            converted_owed_amounts(expense, &converted.converted_amount, &entry.splits)?
+        {
+            balances
+                .entry(participant)
+                .or_insert_with(|| UserBalance::new(participant, currency))
+                .total_owed
+                .minor_units += owed.minor_units;
         }
-        transactions
     }
+    for payment in &ledger.payments {
+        balances
+            .entry(payment.payer_id)
+            .or_insert_with(|| UserBalance::new(payment.payer_id, currency))
+            .payments_sent
+            .minor_units += payment.amount.minor_units;
+        balances
+            .entry(payment.receiver_id)
+            .or_insert_with(|| UserBalance::new(payment.receiver_id, currency))
+            .payments_received
+            .minor_units += payment.amount.minor_units;
+    }
+    let mut balances: Vec<UserBalance> = balances
+        .into_values()
+        .map(|mut balance| {
+            balance.net_balance.minor_units = balance.total_paid.minor_units
+                - balance.total_owed.minor_units
+                + balance.payments_sent.minor_units
+                - balance.payments_received.minor_units;
+            balance
+        })
+        .collect();
+    balances.sort_by_key(|balance| balance.user_id);
+    Ok(balances)
 }
 
-fn minimize(transactions: Vec<Transaction>) -> Vec<Transaction> {
-    let mut saldo: HashMap<u32, f64> = HashMap::new();
+/// The expense converted to `base_currency` with `rate_micros`, the rate
+/// effective at the expense date. Without one this fails with a [`MissingRate`],
+/// and with an [`AmountOverflow`] when the converted amount is out of range;
+/// the API reports both as 422.
+fn convert_expense(
+    expense: &Expense,
+    base_currency: &str,
+    rate_micros: Option<i64>,
+) -> Result<ConvertedExpense, sqlx::Error> {
+    let original_amount = expense.amount.clone();
+    let Some(rate_micros) = rate_micros else {
+        return Err(sqlx::Error::Decode(Box::new(MissingRate {
+            from_currency: original_amount.currency,
+            to_currency: base_currency.to_string(),
+            expense_id: expense.id,
+        })));
+    };
+    let expense_id = expense
+        .id
+        .ok_or_else(|| sqlx::Error::Protocol("Stored expense without an id".to_string()))?;
+    Ok(ConvertedExpense {
+        expense_id,
+        description: expense.description.clone(),
+        date: expense.date.clone(),
+        converted_amount: original_amount
+            .convert(base_currency, rate_micros)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        original_amount,
+        rate_micros,
+    })
+}
 
-    for t in &transactions {
-        *saldo.entry(t.from_user_id).or_insert(0.0) -= t.amount;
-        *saldo.entry(t.to_user_id).or_insert(0.0) += t.amount;
+/// Owed amount of every participant of an expense, in the currency of
+/// `converted_amount`. The converted amount is divided in proportion to the
+/// owed amounts in the original currency, so the parts still add up exactly.
+fn converted_owed_amounts(
+    expense: &Expense,
+    converted_amount: &Money,
+    splits: &[ParticipantSplit],
+) -> Result<Vec<(u32, Money)>, sqlx::Error> {
+    if splits.is_empty() {
+        return Ok(Vec::new());
     }
-
-    // Osobno dłużnicy i wierzyciele
-    let mut creditors: Vec<(u32, f64)> = saldo
+    let owed = owed_amounts(&expense.amount, &expense.split_type, splits)
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    if converted_amount.currency == expense.amount.currency {
+        return Ok(owed);
+    }
+    let weights: Vec<(u32, i64)> = owed
         .iter()
-        .filter(|(_, &v)| v > 1e-6)
-        .map(|(&k, &v)| (k, v))
+        .map(|(user_id, amount)| (*user_id, amount.minor_units))
         .collect();
+    Ok(converted_amount.allocate(&weights))
+}
 
-    let mut debtors: Vec<(u32, f64)> = saldo
-        .iter()
-        .filter(|(_, &v)| v < -1e-6)
-        .map(|(&k, &v)| (k, v))
-        .collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    creditors.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    debtors.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    #[tokio::test]
+    async fn test_group_balances() {
+        use crate::{
+            db::tests::IN_MEMORY_DB,
+            models::{
+                expenses::{ParticipantSplit, SplitType},
+                group::Group,
+                user::User,
+            },
+        };
 
-    let mut result = Vec::new();
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            alice,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, alice).await.unwrap();
+        db.add_user_to_group(group_id, bob).await.unwrap();
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Dinner".to_string(),
+                amount: Money::new(3000, "EUR"),
+                payer_id: alice,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type: SplitType::Equal,
+            })
+            .await
+            .unwrap();
+        db.add_participants_to_expense(expense_id, vec![alice, bob])
+            .await
+            .unwrap();
 
-    while !creditors.is_empty() && !debtors.is_empty() {
-        let (creditor_id, mut credit_amount) = creditors.pop().unwrap();
-        let (debtor_id, mut debt_amount) = debtors.pop().unwrap();
+        let balances = db.get_group_balances(group_id).await.unwrap();
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[0].user_id, alice);
+        assert_eq!(balances[0].total_paid, Money::new(3000, "EUR"));
+        assert_eq!(balances[0].net_balance, Money::new(1500, "EUR"));
+        assert_eq!(balances[1].user_id, bob);
+        assert_eq!(balances[1].total_owed, Money::new(1500, "EUR"));
+        assert_eq!(balances[1].net_balance, Money::new(-1500, "EUR"));
 
-        debt_amount = -debt_amount;
-        let amount = credit_amount.min(debt_amount);
+        // Bob pays a 90.00 hotel where Alice has two shares and Bob one
+        let expense_id = db
+            .create_expense(&Expense {
+                id: None,
+                description: "Hotel".to_string(),
+                amount: Money::new(9000, "EUR"),
+                payer_id: bob,
+                group_id,
+                date: "2024-01-02".to_string(),
+                split_type: SplitType::Shares,
+            })
+            .await
+            .unwrap();
+        db.add_expense_splits(
+            expense_id,
+            &[
+                ParticipantSplit {
+                    user_id: alice,
+                    value: 2,
+                },
+                ParticipantSplit {
+                    user_id: bob,
+                    value: 1,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let balances = db.get_group_balances(group_id).await.unwrap();
+        assert_eq!(balances[0].total_owed, Money::new(7500, "EUR"));
+        assert_eq!(balances[0].net_balance, Money::new(-4500, "EUR"));
+        assert_eq!(balances[1].net_balance, Money::new(4500, "EUR"));
+        let summary = db.get_group_summary(group_id).await.unwrap();
+        assert_eq!(summary.transactions_needed.len(), 1);
+        assert_eq!(
+            summary.transactions_needed[0].amount,
+            Money::new(4500, "EUR")
+        );
+        // The debtor pays the creditor
+        assert_eq!(summary.transactions_needed[0].from_user_id, alice);
+        assert_eq!(summary.transactions_needed[0].to_user_id, bob);
+        // Viewing the summary stores nothing
+        assert_eq!(summary.transactions_needed[0].id, None);
+        assert!(db
+            .get_transactions_by_payer_id(alice)
+            .await
+            .unwrap()
+            .is_empty());
 
-        result.push(Transaction {
+        let plan = db.generate_settlement_plan(group_id, bob).await.unwrap();
+        assert_eq!(plan.version, 1);
+        let stored = db
+            .get_transaction(plan.transactions[0].id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(stored.payer_id, alice);
+        assert_eq!(stored.receiver_id, bob);
+        assert_eq!(stored.plan_id, Some(plan.id));
+
+        // Once Alice paid part of it, only the rest is proposed
+        db.create_transaction(&DetailedTransaction {
             id: None,
-            from_user_id: debtor_id,
-            to_user_id: creditor_id,
-            amount: (amount * 100.0).round() / 100.0,
-        });
+            payer_id: alice,
+            receiver_id: bob,
+            amount: Money::new(2000, "EUR"),
+            date: "2024-01-03".to_string(),
+            status: Status::Completed,
+            group_id,
+            plan_id: None,
+        })
+        .await
+        .unwrap();
+        let summary = db.get_group_summary(group_id).await.unwrap();
+        assert_eq!(
+            summary.transactions_needed[0].amount,
+            Money::new(2500, "EUR")
+        );
+        let balances = db.get_group_balances(group_id).await.unwrap();
+        assert_eq!(balances[0].payments_sent, Money::new(2000, "EUR"));
+        assert_eq!(balances[0].net_balance, Money::new(-2500, "EUR"));
+        assert_eq!(balances[1].payments_received, Money::new(2000, "EUR"));
+        let plan = db.generate_settlement_plan(group_id, bob).await.unwrap();
+        assert_eq!(plan.version, 2);
+        assert_eq!(plan.transactions[0].amount, Money::new(2500, "EUR"));
+        let statuses: Vec<Status> = db
+            .get_transactions_by_payer_id(alice)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|transaction| transaction.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![Status::Superseded, Status::Completed, Status::Pending]
+        );
+    }
 
-        credit_amount -= amount;
-        debt_amount -= amount;
+    #[tokio::test]
+    async fn test_user_position_across_groups() {
+        use crate::{
+            db::tests::IN_MEMORY_DB,
+            models::{expenses::SplitType, group::Group, user::User},
+        };
 
-        if credit_amount > 1e-6 {
-            creditors.push((creditor_id, credit_amount));
-            creditors.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        // Alice is owed 10.00 EUR in two groups and owes 5 CHF in a third
+        for (currency, payer_id) in [("EUR", alice), ("EUR", alice), ("CHF", bob)] {
+            let mut group = Group::new(
+                "Trip",
+                alice,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            );
+            group.base_currency = currency.to_string();
+            let group_id = db.create_group(&group).await.unwrap();
+            db.add_user_to_group(group_id, alice).await.unwrap();
+            db.add_user_to_group(group_id, bob).await.unwrap();
+            let amount = if currency == "EUR" { 2000 } else { 1000 };
+            let expense_id = db
+                .create_expense(&Expense {
+                    id: None,
+                    description: "Dinner".to_string(),
+                    amount: Money::new(amount, currency),
+                    payer_id,
+                    group_id,
+                    date: "2024-01-01".to_string(),
+                    split_type: SplitType::Equal,
+                })
+                .await
+                .unwrap();
+            db.add_participants_to_expense(expense_id, vec![alice, bob])
+                .await
+                .unwrap();
         }
 
-        if debt_amount > 1e-6 {
-            debtors.push((debtor_id, -debt_amount));
-            debtors.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-        }
+        let position = db.get_user_position(alice).await.unwrap();
+        assert_eq!(position.groups.len(), 3);
+        assert_eq!(
+            position.groups[2].balance.net_balance,
+            Money::new(-500, "CHF")
+        );
+        assert_eq!(
+            position.net_balances,
+            vec![Money::new(-500, "CHF"), Money::new(2000, "EUR")]
+        );
+        let position = db.get_user_position(bob).await.unwrap();
+        assert_eq!(
+            position.net_balances,
+            vec![Money::new(500, "CHF"), Money::new(-2000, "EUR")]
+        );
     }
 
-    result
-}
+    #[tokio::test]
+    async fn test_user_position_skips_group_without_rate() {
+        use crate::{
+            db::tests::IN_MEMORY_DB,
+            error::ApiError,
+            models::{expenses::SplitType, group::Group, user::User},
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_simple_case() {
-        let transactions = vec![
-            Transaction {
-                id: None,
-                from_user_id: 2,
-                to_user_id: 1,
-                amount: 2.5,
-            },
-            Transaction {
-                id: None,
-                from_user_id: 1,
-                to_user_id: 2,
-                amount: 12.5,
-            },
-        ];
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        // Alice pays 20.00 EUR in one group and 20.00 USD in another, for
+        // which no rate to EUR exists
+        let mut group_ids = Vec::new();
+        for (name, currency) in [("Trip", "EUR"), ("Detour", "USD")] {
+            let group = Group::new(
+                name,
+                alice,
+                Utc::now(),
+                Utc::now(),
+                "Description".to_string(),
+                "Location".to_string(),
+            );
+            let group_id = db.create_group(&group).await.unwrap();
+            db.add_user_to_group(group_id, alice).await.unwrap();
+            db.add_user_to_group(group_id, bob).await.unwrap();
+            let expense_id = db
+                .create_expense(&Expense {
+                    id: None,
+                    description: "Dinner".to_string(),
+                    amount: Money::new(2000, currency),
+                    payer_id: alice,
+                    group_id,
+                    date: "2024-01-01".to_string(),
+                    split_type: SplitType::Equal,
+                })
+                .await
+                .unwrap();
+            db.add_participants_to_expense(expense_id, vec![alice, bob])
+                .await
+                .unwrap();
+            group_ids.push(group_id);
+        }
 
-        let result = minimize(transactions);
+        let error = ApiError::from(db.get_group_balances(group_ids[1]).await.unwrap_err());
+        assert_eq!(error.status(), axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(error.to_string().contains("USD→EUR"));
 
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].from_user_id, 1);
-        assert_eq!(result[0].to_user_id, 2);
-        assert_eq!(result[0].amount, 10.0);
+        let position = db.get_user_position(alice).await.unwrap();
+        assert_eq!(position.groups.len(), 1);
+        assert_eq!(position.net_balances, vec![Money::new(1000, "EUR")]);
+        assert_eq!(position.incomplete_groups.len(), 1);
+        assert_eq!(position.incomplete_groups[0].group_id, group_ids[1]);
+        assert_eq!(position.incomplete_groups[0].group_name, "Detour");
     }
 
-    #[test]
-    fn test_three_users() {
-        let transactions = vec![
-            Transaction {
-                id: None,
-                from_user_id: 1,
-                to_user_id: 2,
-                amount: 10.0,
-            },
-            Transaction {
-                id: None,
-                from_user_id: 2,
-                to_user_id: 3,
-                amount: 10.0,
-            },
-            Transaction {
+    #[tokio::test]
+    async fn test_summary_converts_to_base_currency() {
+        use crate::{
+            db::tests::IN_MEMORY_DB,
+            models::{exchange_rate::ExchangeRate, expenses::SplitType, group::Group, user::User},
+        };
+
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let alice = db
+            .create_user(&User::new("Alice", "alice@example.com", "password"))
+            .await
+            .unwrap();
+        let bob = db
+            .create_user(&User::new("Bob", "bob@example.com", "password"))
+            .await
+            .unwrap();
+        let group = Group::new(
+            "Trip",
+            alice,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        db.add_user_to_group(group_id, alice).await.unwrap();
+        db.add_user_to_group(group_id, bob).await.unwrap();
+        for (rate_micros, day) in [(900_000, "2024-01-01"), (950_000, "2024-02-01")] {
+            db.create_exchange_rate(&ExchangeRate {
                 id: None,
-                from_user_id: 3,
-                to_user_id: 1,
-                amount: 10.0,
-            },
-        ];
+                group_id: Some(group_id),
+                from_currency: "USD".to_string(),
+                to_currency: "EUR".to_string(),
+                rate_micros,
+                effective_date: day.parse().unwrap(),
+            })
+            .await
+            .unwrap();
+        }
+        // Alice pays 100.00 USD in January, Bob 30.00 EUR in February
+        for (amount, payer_id, date) in [
+            (Money::new(10000, "USD"), alice, "2024-01-10"),
+            (Money::new(3000, "EUR"), bob, "2024-02-05"),
+        ] {
+            let expense_id = db
+                .create_expense(&Expense {
+                    id: None,
+                    description: "Dinner".to_string(),
+                    amount,
+                    payer_id,
+                    group_id,
+                    date: date.to_string(),
+                    split_type: SplitType::Equal,
+                })
+                .await
+                .unwrap();
+            db.add_participants_to_expense(expense_id, vec![alice, bob])
+                .await
+                .unwrap();
+        }
 
-        let result = minimize(transactions);
+        let summary = db.get_group_summary(group_id).await.unwrap();
+        assert_eq!(summary.base_currency, "EUR");
+        assert_eq!(summary.total_expenses, Money::new(12000, "EUR"));
+        assert_eq!(
+            summary.expenses[0].original_amount,
+            Money::new(10000, "USD")
+        );
+        // The January rate applies, not the later one
+        assert_eq!(summary.expenses[0].rate_micros, 900_000);
+        assert_eq!(
+            summary.expenses[0].converted_amount,
+            Money::new(9000, "EUR")
+        );
+        assert_eq!(
+            summary.expenses[1].converted_amount,
+            Money::new(3000, "EUR")
+        );
+        assert_eq!(summary.transactions_needed.len(), 1);
+        assert_eq!(summary.transactions_needed[0].from_user_id, bob);
+        assert_eq!(
+            summary.transactions_needed[0].amount,
+            Money::new(3000, "EUR")
+        );
 
-        assert_eq!(result.len(), 0);
+        let balances = db.get_group_balances(group_id).await.unwrap();
+        assert_eq!(balances[0].total_paid, Money::new(9000, "EUR"));
+        assert_eq!(balances[0].net_balance, Money::new(3000, "EUR"));
     }
-    #[test]
-    fn test_four_users() {
-        let transactions = vec![
-            Transaction {
-                id: None,
-                from_user_id: 1,
-                to_user_id: 2,
-                amount: 10.0,
-            },
-            Transaction {
-                id: None,
-                from_user_id: 1,
-                to_user_id: 3,
-                amount: 10.0,
-            },
-            Transaction {
-                id: None,
-                from_user_id: 1,
-                to_user_id: 4,
-                amount: 10.0,
+
+    #[tokio::test]
+    async fn test_summary_respects_settlement_constraint() {
+        use crate::{
+            db::tests::IN_MEMORY_DB,
+            models::{
+                expenses::SplitType, group::Group, settlement_constraint::SettlementConstraint,
+                user::User,
             },
-            Transaction {
+        };
+
+        let db = Database::new(IN_MEMORY_DB).await.unwrap();
+        db.migrate().await.unwrap();
+        let mut users = Vec::new();
+        for name in ["Alice", "Bob", "Carol"] {
+            let email = format!("{}@example.com", name.to_lowercase());
+            users.push(
+                db.create_user(&User::new(name, &email, "password"))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let (alice, bob, carol) = (users[0], users[1], users[2]);
+        let group = Group::new(
+            "Trip",
+            alice,
+            Utc::now(),
+            Utc::now(),
+            "Description".to_string(),
+            "Location".to_string(),
+        );
+        let group_id = db.create_group(&group).await.unwrap();
+        for user in &users {
+            db.add_user_to_group(group_id, *user).await.unwrap();
+        }
+        // Bob pays for Carol and himself, so Carol owes Bob 20.00
+        let expense_id = db
+            .create_expense(&Expense {
                 id: None,
-                from_user_id: 2,
-                to_user_id: 1,
-                amount: 10.0,
-            },
-        ];
+                description: "Taxi".to_string(),
+                amount: Money::new(4000, "EUR"),
+                payer_id: bob,
+                group_id,
+                date: "2024-01-01".to_string(),
+                split_type: SplitType::Equal,
+            })
+            .await
+            .unwrap();
+        db.add_participants_to_expense(expense_id, vec![bob, carol])
+            .await
+            .unwrap();
+
+        db.set_settlement_constraint(group_id, &SettlementConstraint::ThroughOwner)
+            .await
+            .unwrap();
+        let summary = db.get_group_summary(group_id).await.unwrap();
+        let transfers: Vec<(u32, u32, i64)> = summary
+            .transactions_needed
+            .iter()
+            .map(|t| (t.from_user_id, t.to_user_id, t.amount.minor_units))
+            .collect();
+        let mut expected = vec![(carol, alice, 2000), (alice, bob, 2000)];
+        expected.sort();
+        assert_eq!(transfers, expected);
 
-        let result = minimize(transactions);
-        assert_eq!(result.len(), 2);
+        // The stored plan follows the same route
+        let plan = db.generate_settlement_plan(group_id, carol).await.unwrap();
+        assert_eq!(plan.transactions.len(), 2);
+        assert!(plan
+            .transactions
+            .iter()
+            .all(|t| t.payer_id == alice || t.receiver_id == alice));
     }
 }
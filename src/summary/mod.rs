@@ -1,21 +1,33 @@
-use axum::{
-    extract::{Path, State},
-    response::Response,
-    routing::get,
-    Router,
-};
+use axum::{extract::State, routing::get, Router};
 use utoipa::OpenApi;
 
-use crate::server::AppState;
+use crate::{
+    auth::{extractor::AuthUser, policy::ensure_group_member},
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    server::AppState,
+};
 
 mod get_summary;
+pub mod solver;
 
-pub use get_summary::{GroupSummary, Transaction, UserBalance};
+pub use get_summary::{
+    group_balances, ConvertedExpense, GroupPosition, GroupSummary, IncompleteGroup, Transaction,
+    UserBalance, UserPosition,
+};
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(get_group_summary),
-    components(schemas(GroupSummary, Transaction, UserBalance))
+    paths(get_group_summary, get_group_balances, get_my_position),
+    components(schemas(
+        ConvertedExpense,
+        GroupPosition,
+        GroupSummary,
+        IncompleteGroup,
+        Transaction,
+        UserBalance,
+        UserPosition
+    ))
 )]
 pub struct SummaryApi;
 
@@ -27,25 +39,79 @@ pub struct SummaryApi;
     ),
     responses(
         (status = 200, description = "Group summary retrieved successfully", body = GroupSummary),
-        (status = 404, description = "Group not found"),
-        (status = 500, description = "Internal server error")
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 422, description = "An expense has no exchange rate to the base currency of the group", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
-        ("api_key" = [])
+        ("api_key" = []),
+        ("bearer_auth" = [])
     )
 )]
 pub async fn get_group_summary(
     State(state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<GroupSummary>, ApiError> {
+    ensure_group_member(&state.db, group_id, auth.user_id).await?;
+    let summary = state.db.get_group_summary(group_id).await?;
+    Ok(Json(summary))
+}
+
+#[utoipa::path(
+    get,
+    path = "/group/{id}/balances",
+    params(
+        ("id" = u32, Path, description = "Group ID to get balances for")
+    ),
+    responses(
+        (status = 200, description = "Paid, owed, settled and net amount of every member", body = Vec<UserBalance>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 422, description = "An expense has no exchange rate to the base currency of the group", body = ErrorResponse)
+    ),
+    security(
+        ("api_key" = []),
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_group_balances(
+    State(state): State<AppState>,
+    auth: AuthUser,
     Path(group_id): Path<u32>,
-) -> Response<String> {
-    match state.db.get_group_summary(group_id).await {
-        Ok(summary) => Response::new(serde_json::to_string(&summary).unwrap()),
-        Err(e) => Response::new(e.to_string()),
-    }
+) -> Result<Json<Vec<UserBalance>>, ApiError> {
+    ensure_group_member(&state.db, group_id, auth.user_id).await?;
+    let balances = state.db.get_group_balances(group_id).await?;
+    Ok(Json(balances))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me",
+    responses(
+        (status = 200, description = "Balance of the caller in each of their groups and overall", body = UserPosition),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(
+        ("api_key" = []),
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_my_position(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<UserPosition>, ApiError> {
+    let position = state.db.get_user_position(auth.user_id).await?;
+    Ok(Json(position))
 }
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
         .route("/group/{id}", get(get_group_summary))
+        .route("/group/{id}/balances", get(get_group_balances))
+        .route("/me", get(get_my_position))
         .with_state(app_state)
 }
@@ -0,0 +1,553 @@
+//! Chooses the transfers that settle the debts of a group.
+//!
+//! Every strategy turns the same debts into transfers that leave every member
+//! with a zero balance; they differ in how many transfers they need and who
+//! ends up paying whom.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::Transaction;
+use crate::models::{group::SettlementStrategy, money::Money};
+
+/// Most non-zero balances [`minimize_transfers`] solves exactly. The exact
+/// search is exponential in this number; larger groups fall back to [`greedy`].
+pub const EXACT_SOLVER_LIMIT: usize = 15;
+
+/// Replaces `debts`, all in the same currency, with the transfers `strategy` picks.
+pub fn settle(debts: Vec<Transaction>, strategy: SettlementStrategy) -> Vec<Transaction> {
+    match strategy {
+        SettlementStrategy::MinimizeTransfers => minimize_transfers(debts),
+        SettlementStrategy::Direct => direct(debts),
+        SettlementStrategy::MinimizeAmount => minimize(debts),
+    }
+}
+
+/// Net balance of every user in `debts`; positive means the user is owed money.
+/// Users whose debts cancel out are left out.
+fn balances(debts: &[Transaction]) -> BTreeMap<u32, i64> {
+    let mut saldo: BTreeMap<u32, i64> = BTreeMap::new();
+    for t in debts {
+        *saldo.entry(t.from_user_id).or_insert(0) -= t.amount.minor_units;
+        *saldo.entry(t.to_user_id).or_insert(0) += t.amount.minor_units;
+    }
+    saldo.retain(|_, balance| *balance != 0);
+    saldo
+}
+
+/// Settles `debts` with the fewest transfers possible.
+///
+/// A group whose balances can be split into `k` subsets that each sum to zero
+/// needs exactly `n - k` transfers, so the search looks for the largest number
+/// of such subsets with a dynamic program over all subsets of members, then
+/// settles each subset on its own. Above [`EXACT_SOLVER_LIMIT`] members with a
+/// non-zero balance it falls back to [`greedy`].
+pub fn minimize_transfers(debts: Vec<Transaction>) -> Vec<Transaction> {
+    let Some(currency) = debts.first().map(|t| t.amount.currency.clone()) else {
+        return Vec::new();
+    };
+    let saldo: Vec<(u32, i64)> = balances(&debts).into_iter().collect();
+    if saldo.len() > EXACT_SOLVER_LIMIT {
+        return greedy(&saldo.into_iter().collect(), &currency);
+    }
+
+    let n = saldo.len();
+    let full = (1usize << n) - 1;
+    // sum[mask]: total balance of the members in mask.
+    // best[mask]: most zero-sum subsets the members of mask can be split into,
+    // counting a non-zero remainder as no subset.
+    let mut sum = vec![0i64; full + 1];
+    let mut best = vec![0u32; full + 1];
+    for mask in 1..=full {
+        let lowest = mask.trailing_zeros() as usize;
+        sum[mask] = sum[mask & (mask - 1)] + saldo[lowest].1;
+        let previous = (0..n)
+            .filter(|i| mask & (1 << i) != 0)
+            .map(|i| best[mask ^ (1 << i)])
+            .max()
+            .unwrap_or(0);
+        best[mask] = previous + u32::from(sum[mask] == 0);
+    }
+
+    // Walk back from the full set, removing one member at a time along an
+    // optimal path; every zero-sum set reached closes a subset.
+    let mut transfers = Vec::new();
+    let mut subset = BTreeMap::new();
+    let mut mask = full;
+    while mask != 0 {
+        let closes = u32::from(sum[mask] == 0);
+        let i = (0..n)
+            .find(|&i| mask & (1 << i) != 0 && best[mask ^ (1 << i)] + closes == best[mask])
+            .expect("an optimal predecessor always exists");
+        subset.insert(saldo[i].0, saldo[i].1);
+        mask ^= 1 << i;
+        if sum[mask] == 0 {
+            transfers.extend(greedy(&subset, &currency));
+            subset.clear();
+        }
+    }
+    transfers.sort_by_key(|t| (t.from_user_id, t.to_user_id));
+    transfers
+}
+
+/// No simplification: for every pair of users, whoever owes the other more
+/// pays the difference. Nobody pays a person they do not owe money to.
+pub fn direct(debts: Vec<Transaction>) -> Vec<Transaction> {
+    let Some(currency) = debts.first().map(|t| t.amount.currency.clone()) else {
+        return Vec::new();
+    };
+    // Keyed by (lower id, higher id); positive means the lower id owes the higher one.
+    let mut pairs: BTreeMap<(u32, u32), i64> = BTreeMap::new();
+    for t in &debts {
+        if t.from_user_id == t.to_user_id {
+            continue;
+        }
+        if t.from_user_id < t.to_user_id {
+            *pairs.entry((t.from_user_id, t.to_user_id)).or_insert(0) += t.amount.minor_units;
+        } else {
+            *pairs.entry((t.to_user_id, t.from_user_id)).or_insert(0) -= t.amount.minor_units;
+        }
+    }
+    pairs
+        .into_iter()
+        .filter(|(_, amount)| *amount != 0)
+        .map(|((low, high), amount)| {
+            let (from_user_id, to_user_id) = if amount > 0 { (low, high) } else { (high, low) };
+            Transaction {
+                id: None,
+                from_user_id,
+                to_user_id,
+                amount: Money::new(amount.abs(), &currency),
+            }
+        })
+        .collect()
+}
+
+/// Reroutes `transfers` so that every payment is one `allows` accepts, leaving
+/// every balance as it was. Transfers that already comply are kept as they are.
+///
+/// Otherwise what the debtors owe is pushed to the creditors as a maximum flow
+/// over the allowed payments between `members` (Edmonds-Karp, so short routes are
+/// used first), which may pass money through members with no balance of their
+/// own. Payments in opposite directions between two members cancel out. Any
+/// balance the allowed payments cannot reach is settled without constraints, so
+/// the result always zeroes every balance.
+pub fn constrain(
+    transfers: Vec<Transaction>,
+    members: &[u32],
+    allows: impl Fn(u32, u32) -> bool,
+) -> Vec<Transaction> {
+    if transfers
+        .iter()
+        .all(|t| allows(t.from_user_id, t.to_user_id))
+    {
+        return transfers;
+    }
+    let currency = transfers[0].amount.currency.clone();
+    let saldo = balances(&transfers);
+    let mut users: Vec<u32> = members.iter().chain(saldo.keys()).copied().collect();
+    users.sort();
+    users.dedup();
+
+    // Nodes are the users followed by a source feeding every debtor and a sink
+    // draining every creditor.
+    let n = users.len() + 2;
+    let (source, sink) = (n - 2, n - 1);
+    let unlimited: i64 = saldo.values().filter(|balance| **balance > 0).sum();
+    let mut capacity = vec![vec![0i64; n]; n];
+    for (i, &payer) in users.iter().enumerate() {
+        for (j, &receiver) in users.iter().enumerate() {
+            if i != j && allows(payer, receiver) {
+                capacity[i][j] = unlimited;
+            }
+        }
+        match saldo.get(&payer) {
+            Some(&balance) if balance < 0 => capacity[source][i] = -balance,
+            Some(&balance) if balance > 0 => capacity[i][sink] = balance,
+            _ => {}
+        }
+    }
+
+    // flow[u][v] == -flow[v][u], so payments both ways are netted as they are found
+    let mut flow = vec![vec![0i64; n]; n];
+    loop {
+        let mut parent = vec![None; n];
+        parent[source] = Some(source);
+        let mut queue = VecDeque::from([source]);
+        while let Some(u) = queue.pop_front() {
+            for v in 0..n {
+                if parent[v].is_none() && capacity[u][v] > flow[u][v] {
+                    parent[v] = Some(u);
+                    queue.push_back(v);
+                }
+            }
+        }
+        if parent[sink].is_none() {
+            break;
+        }
+        let mut path = Vec::new();
+        let mut v = sink;
+        while v != source {
+            let u = parent[v].expect("every node on the path has a parent");
+            path.push((u, v));
+            v = u;
+        }
+        let amount = path
+            .iter()
+            .map(|&(u, v)| capacity[u][v] - flow[u][v])
+            .min()
+            .unwrap_or(0);
+        for (u, v) in path {
+            flow[u][v] += amount;
+            flow[v][u] -= amount;
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut unreached = BTreeMap::new();
+    for (i, &payer) in users.iter().enumerate() {
+        for (j, &receiver) in users.iter().enumerate() {
+            if flow[i][j] > 0 {
+                result.push(Transaction {
+                    id: None,
+                    from_user_id: payer,
+                    to_user_id: receiver,
+                    amount: Money::new(flow[i][j], &currency),
+                });
+            }
+        }
+        let left = (capacity[i][sink] - flow[i][sink]) - (capacity[source][i] - flow[source][i]);
+        if left != 0 {
+            unreached.insert(payer, left);
+        }
+    }
+    result.extend(greedy(&unreached, &currency));
+    result.sort_by_key(|t| (t.from_user_id, t.to_user_id));
+    result
+}
+
+/// Replaces a list of debts with as few transfers as the greedy matching of the
+/// largest debtors and creditors finds. All debts must be in the same currency.
+pub fn minimize(transactions: Vec<Transaction>) -> Vec<Transaction> {
+    let Some(currency) = transactions.first().map(|t| t.amount.currency.clone()) else {
+        return Vec::new();
+    };
+    greedy(&balances(&transactions), &currency)
+}
+
+/// Matches the largest debtors and creditors of `saldo` until every balance is zero.
+fn greedy(saldo: &BTreeMap<u32, i64>, currency: &str) -> Vec<Transaction> {
+    // Osobno dłużnicy i wierzyciele
+    let mut creditors: Vec<(u32, i64)> = saldo
+        .iter()
+        .filter(|(_, &v)| v > 0)
+        .map(|(&k, &v)| (k, v))
+        .collect();
+
+    let mut debtors: Vec<(u32, i64)> = saldo
+        .iter()
+        .filter(|(_, &v)| v < 0)
+        .map(|(&k, &v)| (k, -v))
+        .collect();
+
+    // Largest amounts are popped first; ties go to the lowest user id so the
+    // result does not depend on hash map order.
+    let by_amount = |a: &(u32, i64), b: &(u32, i64)| a.1.cmp(&b.1).then(b.0.cmp(&a.0));
+    creditors.sort_by(by_amount);
+    debtors.sort_by(by_amount);
+
+    let mut result = Vec::new();
+
+    while let (Some((creditor_id, credit_amount)), Some((debtor_id, debt_amount))) =
+        (creditors.pop(), debtors.pop())
+    {
+        let amount = credit_amount.min(debt_amount);
+
+        result.push(Transaction {
+            id: None,
+            from_user_id: debtor_id,
+            to_user_id: creditor_id,
+            amount: Money::new(amount, currency),
+        });
+
+        if credit_amount > amount {
+            creditors.push((creditor_id, credit_amount - amount));
+            creditors.sort_by(by_amount);
+        }
+
+        if debt_amount > amount {
+            debtors.push((debtor_id, debt_amount - amount));
+            debtors.sort_by(by_amount);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_simple_case() {
+        let transactions = vec![
+            Transaction {
+                id: None,
+                from_user_id: 2,
+                to_user_id: 1,
+                amount: Money::new(250, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 1,
+                to_user_id: 2,
+                amount: Money::new(1250, "EUR"),
+            },
+        ];
+
+        let result = minimize(transactions);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].from_user_id, 1);
+        assert_eq!(result[0].to_user_id, 2);
+        assert_eq!(result[0].amount, Money::new(1000, "EUR"));
+    }
+
+    #[test]
+    fn test_three_users() {
+        let transactions = vec![
+            Transaction {
+                id: None,
+                from_user_id: 1,
+                to_user_id: 2,
+                amount: Money::new(1000, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 2,
+                to_user_id: 3,
+                amount: Money::new(1000, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 3,
+                to_user_id: 1,
+                amount: Money::new(1000, "EUR"),
+            },
+        ];
+
+        let result = minimize(transactions);
+
+        assert_eq!(result.len(), 0);
+    }
+    #[test]
+    fn test_four_users() {
+        let transactions = vec![
+            Transaction {
+                id: None,
+                from_user_id: 1,
+                to_user_id: 2,
+                amount: Money::new(1000, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 1,
+                to_user_id: 3,
+                amount: Money::new(1000, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 1,
+                to_user_id: 4,
+                amount: Money::new(1000, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 2,
+                to_user_id: 1,
+                amount: Money::new(1000, "EUR"),
+            },
+        ];
+
+        let result = minimize(transactions);
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn test_three_way_split_settles_to_the_cent() {
+        // 100.00 paid by user 1 and split equally: 33.34 + 33.33 + 33.33
+        let transactions = vec![
+            Transaction {
+                id: None,
+                from_user_id: 2,
+                to_user_id: 1,
+                amount: Money::new(3333, "EUR"),
+            },
+            Transaction {
+                id: None,
+                from_user_id: 3,
+                to_user_id: 1,
+                amount: Money::new(3333, "EUR"),
+            },
+        ];
+
+        let result = minimize(transactions);
+        let settled: i64 = result.iter().map(|t| t.amount.minor_units).sum();
+        assert_eq!(settled, 6666);
+        assert!(result.iter().all(|t| t.to_user_id == 1));
+    }
+
+    fn debt(from_user_id: u32, to_user_id: u32, minor_units: i64) -> Transaction {
+        Transaction {
+            id: None,
+            from_user_id,
+            to_user_id,
+            amount: Money::new(minor_units, "EUR"),
+        }
+    }
+
+    /// Debts leaving users 1 to 6 with balances -8, 6, -2, 3, 4 and -3.
+    fn two_cycles() -> Vec<Transaction> {
+        vec![debt(1, 2, 6), debt(1, 5, 2), debt(3, 5, 2), debt(6, 4, 3)]
+    }
+
+    #[test]
+    fn test_exact_solver_beats_greedy() {
+        // Greedy matches 6 with -8 first and needs 5 transfers; splitting into
+        // {-8, 6, -2, 4} and {3, -3} needs 4.
+        let debts = two_cycles();
+        assert_eq!(minimize(debts.clone()).len(), 5);
+        let result = minimize_transfers(debts);
+        assert_eq!(result.len(), 4);
+        assert!(result
+            .iter()
+            .any(|t| t.from_user_id == 6 && t.to_user_id == 4 && t.amount.minor_units == 3));
+    }
+
+    #[test]
+    fn test_direct_only_pays_creditors_owed() {
+        // 1 owes 2, 2 owes 3: simplification would have 1 pay 3 directly
+        let debts = vec![debt(1, 2, 500), debt(2, 3, 500), debt(2, 1, 200)];
+        let result = direct(debts);
+        assert_eq!(result.len(), 2);
+        assert_eq!((result[0].from_user_id, result[0].to_user_id), (1, 2));
+        assert_eq!(result[0].amount.minor_units, 300);
+        assert_eq!((result[1].from_user_id, result[1].to_user_id), (2, 3));
+        assert_eq!(settle(two_cycles(), SettlementStrategy::Direct).len(), 4);
+    }
+
+    #[test]
+    fn test_large_groups_fall_back_to_greedy() {
+        let debts: Vec<Transaction> = (2..=20).map(|user| debt(1, user, 100)).collect();
+        let result = minimize_transfers(debts);
+        assert_eq!(result.len(), 19);
+        assert!(result.iter().all(|t| t.from_user_id == 1));
+    }
+
+    #[test]
+    fn test_constrain_keeps_compliant_transfers() {
+        let transfers = minimize_transfers(two_cycles());
+        let result = constrain(transfers, &[1, 2, 3, 4, 5, 6], |_, _| true);
+        assert_eq!(result.len(), 4);
+    }
+
+    #[test]
+    fn test_constrain_routes_through_hub() {
+        // 1 and 2 owe 3, but everything has to go through 4, who owes nothing
+        let debts = vec![debt(1, 3, 500), debt(2, 3, 300)];
+        let hub = |payer, receiver| payer == 4 || receiver == 4;
+        let result = constrain(minimize_transfers(debts.clone()), &[1, 2, 3, 4], hub);
+        assert_settles(&debts, &result);
+        assert!(result.iter().all(|t| hub(t.from_user_id, t.to_user_id)));
+        assert_eq!(result.len(), 3);
+        let paid_on: i64 = result
+            .iter()
+            .filter(|t| t.from_user_id == 4)
+            .map(|t| t.amount.minor_units)
+            .sum();
+        assert_eq!(paid_on, 800);
+    }
+
+    #[test]
+    fn test_constrain_avoids_forbidden_pair() {
+        // 1 owes 2, but they cannot pay each other; 3 can reach both
+        let debts = vec![debt(1, 2, 1000)];
+        let allowed = |payer, receiver| (payer, receiver) != (1, 2) && (payer, receiver) != (2, 1);
+        let result = constrain(direct(debts.clone()), &[1, 2, 3], allowed);
+        assert_settles(&debts, &result);
+        assert_eq!(result.len(), 2);
+        assert_eq!((result[0].from_user_id, result[0].to_user_id), (1, 3));
+        assert_eq!((result[1].from_user_id, result[1].to_user_id), (3, 2));
+    }
+
+    #[test]
+    fn test_constrain_settles_unreachable_balances_anyway() {
+        let debts = vec![debt(1, 2, 1000)];
+        let result = constrain(direct(debts.clone()), &[1, 2], |_, _| false);
+        assert_settles(&debts, &result);
+        assert_eq!(result.len(), 1);
+    }
+
+    fn assert_settles(debts: &[Transaction], transfers: &[Transaction]) {
+        let mut remaining = balances(debts);
+        for t in transfers {
+            assert!(t.amount.minor_units > 0);
+            *remaining.entry(t.from_user_id).or_insert(0) += t.amount.minor_units;
+            *remaining.entry(t.to_user_id).or_insert(0) -= t.amount.minor_units;
+        }
+        assert!(remaining.values().all(|balance| *balance == 0));
+    }
+
+    fn arbitrary_debts(max_users: u32) -> impl Strategy<Value = Vec<Transaction>> {
+        prop::collection::vec((1..=max_users, 1..=max_users, 1..10_000i64), 1..40).prop_map(
+            |debts| {
+                debts
+                    .into_iter()
+                    .map(|(from, to, amount)| debt(from, to, amount))
+                    .collect()
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_every_strategy_zeroes_all_balances(debts in arbitrary_debts(12)) {
+            for strategy in [
+                SettlementStrategy::MinimizeTransfers,
+                SettlementStrategy::Direct,
+                SettlementStrategy::MinimizeAmount,
+            ] {
+                assert_settles(&debts, &settle(debts.clone(), strategy));
+            }
+        }
+
+        #[test]
+        fn prop_fallback_zeroes_all_balances(debts in arbitrary_debts(40)) {
+            assert_settles(&debts, &minimize_transfers(debts.clone()));
+        }
+
+        #[test]
+        fn prop_constrained_transfers_only_use_allowed_pairs(
+            debts in arbitrary_debts(8),
+            hub in 1..=8u32,
+        ) {
+            let members: Vec<u32> = (1..=8).collect();
+            // Everyone may pay their neighbours in id order, and the hub anyone
+            let allowed = |payer: u32, receiver: u32| {
+                payer.abs_diff(receiver) == 1 || payer == hub || receiver == hub
+            };
+            let result = constrain(minimize_transfers(debts.clone()), &members, allowed);
+            assert_settles(&debts, &result);
+            prop_assert!(result.iter().all(|t| allowed(t.from_user_id, t.to_user_id)));
+        }
+
+        #[test]
+        fn prop_exact_solver_never_needs_more_transfers(debts in arbitrary_debts(10)) {
+            let exact = minimize_transfers(debts.clone());
+            let greedy = minimize(debts.clone());
+            prop_assert!(exact.len() <= greedy.len());
+            // Never more than one transfer less than the number of non-zero balances
+            prop_assert!(exact.len() <= balances(&debts).len().saturating_sub(1));
+        }
+    }
+}
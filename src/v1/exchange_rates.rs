@@ -0,0 +1,98 @@
+use axum::{extract::State, http::StatusCode};
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{ensure_group_member, ensure_permission, find_group},
+    },
+    error::{ApiError, ErrorResponse},
+    expense::validation::validate_currency,
+    extract::{Json, Path},
+    models::{
+        exchange_rate::{ExchangeRate, ExchangeRateRequest},
+        money::MAX_RATE_MICROS,
+        role::Permission,
+    },
+    server::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/exchange-rates",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Exchange rates entered for the group, newest first", body = Vec<ExchangeRate>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_exchange_rates(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<ExchangeRate>>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let rates = app_state.db.get_group_exchange_rates(group_id).await?;
+    Ok(Json(rates))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/exchange-rates",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = ExchangeRateRequest,
+    responses(
+        (status = 201, description = "Exchange rate stored; it overrides the rates file for this group", body = ExchangeRate),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow changing the settings", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Invalid currency or non-positive rate", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn create_exchange_rate(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<ExchangeRateRequest>,
+) -> Result<(StatusCode, Json<ExchangeRate>), ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ChangeSettings,
+    )
+    .await?;
+    let group = find_group(&app_state.db, group_id).await?;
+    let to_currency = request.to_currency.unwrap_or(group.base_currency);
+    validate_currency(&request.from_currency)?;
+    validate_currency(&to_currency)?;
+    if request.from_currency == to_currency {
+        return Err(ApiError::unprocessable(
+            "An exchange rate needs two different currencies",
+        ));
+    }
+    if !(1..=MAX_RATE_MICROS).contains(&request.rate_micros) {
+        return Err(ApiError::unprocessable(format!(
+            "Rate must be between 1 and {} millionths",
+            MAX_RATE_MICROS
+        )));
+    }
+    let mut rate = ExchangeRate {
+        id: None,
+        group_id: Some(group_id),
+        from_currency: request.from_currency,
+        to_currency,
+        rate_micros: request.rate_micros,
+        effective_date: request.effective_date,
+    };
+    rate.id = Some(app_state.db.create_exchange_rate(&rate).await?);
+    Ok((StatusCode::CREATED, Json(rate)))
+}
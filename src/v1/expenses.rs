@@ -0,0 +1,153 @@
+use axum::{extract::State, http::StatusCode};
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{ensure_expense_editor, ensure_expense_members, ensure_group_member},
+    },
+    db::Database,
+    error::{ApiError, ErrorResponse},
+    expense::{split::build_splits, validation::validate_amount},
+    extract::{Json, Path},
+    models::expenses::{Expense, ExpenseDetail, ExpenseUpdateRequest},
+    server::AppState,
+};
+
+/// Loads an expense, or 404 if it does not exist.
+async fn find_expense(db: &Database, expense_id: u32) -> Result<Expense, ApiError> {
+    match db.get_expense_by_id(expense_id).await {
+        Ok(expense) => Ok(expense),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::NotFound(format!(
+            "Expense {} not found",
+            expense_id
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/expenses/{id}",
+    params(
+        ("id" = u32, Path, description = "Expense ID")
+    ),
+    responses(
+        (status = 200, description = "Expense with its participants", body = ExpenseDetail),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the expense's group", body = ErrorResponse),
+        (status = 404, description = "Expense not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_expense(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(expense_id): Path<u32>,
+) -> Result<Json<ExpenseDetail>, ApiError> {
+    let expense = find_expense(&app_state.db, expense_id).await?;
+    ensure_group_member(&app_state.db, expense.group_id, auth.user_id).await?;
+    let splits = app_state.db.get_expense_splits(expense_id).await?;
+    Ok(Json(ExpenseDetail::new(expense, splits)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/expenses/{id}",
+    params(
+        ("id" = u32, Path, description = "Expense ID")
+    ),
+    request_body = ExpenseUpdateRequest,
+    responses(
+        (status = 200, description = "Expense updated", body = ExpenseDetail),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is neither the payer nor the group owner, or a new payer or participant is not a member", body = ErrorResponse),
+        (status = 404, description = "Expense not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Invalid amount, split values that do not add up or no participants left", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn update_expense(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(expense_id): Path<u32>,
+    Json(payload): Json<ExpenseUpdateRequest>,
+) -> Result<Json<ExpenseDetail>, ApiError> {
+    let mut expense = find_expense(&app_state.db, expense_id).await?;
+    ensure_expense_editor(&app_state.db, &expense, auth.user_id).await?;
+
+    if let Some(description) = payload.description {
+        expense.description = description;
+    }
+    if let Some(amount) = payload.amount {
+        expense.amount = amount;
+    }
+    if let Some(payer_id) = payload.payer_id {
+        expense.payer_id = payer_id;
+    }
+    if let Some(date) = payload.date {
+        expense.date = date;
+    }
+    if let Some(split_type) = payload.split_type {
+        expense.split_type = split_type;
+    }
+    let current_splits = app_state.db.get_expense_splits(expense_id).await?;
+    let participants_ids = match payload.participants_ids {
+        Some(participants_ids) => participants_ids,
+        None => current_splits.iter().map(|split| split.user_id).collect(),
+    };
+    // Values of removed participants are dropped, those of the others are kept
+    let split_values = payload.split_values.unwrap_or_else(|| {
+        current_splits
+            .into_iter()
+            .filter(|split| participants_ids.contains(&split.user_id))
+            .collect()
+    });
+    ensure_expense_members(
+        &app_state.db,
+        expense.group_id,
+        auth.user_id,
+        expense.payer_id,
+        &participants_ids,
+    )
+    .await?;
+    validate_amount(&app_state.db, &expense).await?;
+    let splits = build_splits(
+        &expense.amount,
+        &expense.split_type,
+        &participants_ids,
+        &split_values,
+    )?;
+
+    app_state
+        .db
+        .update_expense(expense_id, &expense, &splits)
+        .await?;
+    Ok(Json(ExpenseDetail::new(expense, splits)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/expenses/{id}",
+    params(
+        ("id" = u32, Path, description = "Expense ID")
+    ),
+    responses(
+        (status = 204, description = "Expense deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is neither the payer nor the group owner", body = ErrorResponse),
+        (status = 404, description = "Expense not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn delete_expense(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(expense_id): Path<u32>,
+) -> Result<StatusCode, ApiError> {
+    let expense = find_expense(&app_state.db, expense_id).await?;
+    ensure_expense_editor(&app_state.db, &expense, auth.user_id).await?;
+    app_state.db.delete_expense(expense_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
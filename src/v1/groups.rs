@@ -0,0 +1,623 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{
+            change_member_role, ensure_expense_members, ensure_group_member, ensure_group_owner,
+            ensure_not_archived, ensure_permission, join_with_code, remove_member,
+            transfer_ownership,
+        },
+    },
+    error::{ApiError, ErrorResponse},
+    expense::{
+        split::build_splits,
+        validation::{validate_amount, validate_currency},
+    },
+    extract::{Json, Path, Query},
+    models::{
+        expenses::{Expense, ExpenseAddRequest, ExpenseDetail},
+        group::{
+            CreateGroupRequest, Group, GroupDetail, GroupMember, GroupUpdateRequest,
+            SettlementStrategyRequest, TransferOwnershipRequest,
+        },
+        invitation::{JoinOutcome, JoinRequest, JoinWithCodeRequest},
+        role::{Permission, RoleRequest},
+    },
+    server::AppState,
+    summary::{GroupSummary, UserBalance},
+};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListGroupsQuery {
+    /// Only return groups owned by the caller instead of every group they belong to.
+    #[serde(default)]
+    pub owned: bool,
+    /// Also return archived groups, which are left out by default.
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+/// Rejects a group that would end before it starts.
+fn validate_group_dates(group: &Group) -> Result<(), ApiError> {
+    if group.group_start_date > group.group_end_date {
+        return Err(ApiError::unprocessable(
+            "A group cannot end before it starts",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RemoveMemberQuery {
+    /// Member who takes over the expenses, shares and payments of the removed
+    /// member, and with them their balance. Only the owner can reassign.
+    pub reassign_to: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups",
+    params(ListGroupsQuery),
+    responses(
+        (status = 200, description = "Groups of the authenticated user", body = Vec<Group>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_groups(
+    State(app_state): State<AppState>,
+    AuthUser { user_id, .. }: AuthUser,
+    Query(query): Query<ListGroupsQuery>,
+) -> Result<Json<Vec<Group>>, ApiError> {
+    let mut groups = Vec::new();
+    if query.owned {
+        groups = app_state.db.get_groups_by_owner_id(user_id).await?;
+    } else {
+        for group_id in app_state.db.get_user_groups(user_id).await? {
+            groups.push(app_state.db.get_group(group_id).await?);
+        }
+    }
+    if !query.include_archived {
+        groups.retain(|group| group.archived_at.is_none());
+    }
+    Ok(Json(groups))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups",
+    request_body = CreateGroupRequest,
+    responses(
+        (status = 201, description = "Group created, the caller is its owner and first member", body = Group),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 422, description = "Invalid group data", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn create_group(
+    State(app_state): State<AppState>,
+    AuthUser { user_id, .. }: AuthUser,
+    Json(request): Json<CreateGroupRequest>,
+) -> Result<(StatusCode, Json<Group>), ApiError> {
+    validate_currency(&request.base_currency)?;
+    let mut group = Group::new(
+        request.name.as_str(),
+        user_id,
+        request.group_start_date,
+        request.group_end_date,
+        request.description,
+        request.location,
+    );
+    group.base_currency = request.base_currency;
+    group.settlement_strategy = request.settlement_strategy;
+    validate_group_dates(&group)?;
+    let id = app_state.db.create_group_with_owner(&group).await?;
+    let group = app_state.db.get_group(id).await?;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Group details with the caller's role and what it allows", body = GroupDetail),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_group(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<GroupDetail>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let group = app_state.db.get_group(group_id).await?;
+    let role = app_state.db.get_member_role(group_id, auth.user_id).await?;
+    Ok(Json(GroupDetail {
+        group,
+        role,
+        permissions: role.permissions(),
+    }))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/groups/{id}",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = GroupUpdateRequest,
+    responses(
+        (status = 200, description = "Group updated", body = Group),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow changing the settings", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Empty name or a group that ends before it starts", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn update_group(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<GroupUpdateRequest>,
+) -> Result<Json<Group>, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ChangeSettings,
+    )
+    .await?;
+    let mut group = app_state.db.get_group(group_id).await?;
+    if let Some(name) = request.name {
+        if name.trim().is_empty() {
+            return Err(ApiError::unprocessable("A group needs a name"));
+        }
+        group.name = name;
+    }
+    if let Some(description) = request.description {
+        group.description = description;
+    }
+    if let Some(location) = request.location {
+        group.location = location;
+    }
+    if let Some(start) = request.group_start_date {
+        group.group_start_date = start;
+    }
+    if let Some(end) = request.group_end_date {
+        group.group_end_date = end;
+    }
+    validate_group_dates(&group)?;
+    app_state.db.update_group(&group).await?;
+    Ok(Json(group))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 204, description = "Group deleted with its expenses, transactions, memberships and guests"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not the owner of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn delete_group(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<StatusCode, ApiError> {
+    ensure_group_owner(&app_state.db, group_id, auth.user_id).await?;
+    app_state.db.delete_group(group_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/archive",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Group archived; it is read-only and left out of listings until reopened", body = Group),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not the owner of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is already archived or not settled yet", body = ErrorResponse),
+        (status = 422, description = "An expense has no exchange rate to the base currency of the group", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn archive_group(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Group>, ApiError> {
+    let group = ensure_group_owner(&app_state.db, group_id, auth.user_id).await?;
+    ensure_not_archived(&group)?;
+    let unsettled: Vec<u32> = app_state
+        .db
+        .get_group_balances(group_id)
+        .await?
+        .into_iter()
+        .filter(|balance| balance.net_balance.minor_units != 0)
+        .map(|balance| balance.user_id)
+        .collect();
+    if !unsettled.is_empty() {
+        return Err(ApiError::Conflict(format!(
+            "Group {} is not settled yet; members {:?} still have a balance",
+            group_id, unsettled
+        )));
+    }
+    app_state
+        .db
+        .set_group_archived(group_id, Some(Utc::now()))
+        .await?;
+    let group = app_state.db.get_group(group_id).await?;
+    Ok(Json(group))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}/archive",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Group reopened", body = Group),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not the owner of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is not archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn reopen_group(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Group>, ApiError> {
+    let group = ensure_group_owner(&app_state.db, group_id, auth.user_id).await?;
+    if group.archived_at.is_none() {
+        return Err(ApiError::Conflict(format!(
+            "Group {} is not archived",
+            group_id
+        )));
+    }
+    app_state.db.set_group_archived(group_id, None).await?;
+    let group = app_state.db.get_group(group_id).await?;
+    Ok(Json(group))
+}
+
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/settlement-strategy",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = SettlementStrategyRequest,
+    responses(
+        (status = 200, description = "Settlement strategy changed; it applies to the summary and to new settlement plans", body = Group),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow changing the settings", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn set_settlement_strategy(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<SettlementStrategyRequest>,
+) -> Result<Json<Group>, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ChangeSettings,
+    )
+    .await?;
+    app_state
+        .db
+        .update_settlement_strategy(group_id, request.settlement_strategy)
+        .await?;
+    let group = app_state.db.get_group(group_id).await?;
+    Ok(Json(group))
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/members",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Members of the group with their roles", body = Vec<GroupMember>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_group_members(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<GroupMember>>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let mut members = Vec::new();
+    for (member_id, role) in app_state.db.get_group_member_roles(group_id).await? {
+        members.push(GroupMember {
+            user: app_state.db.get_user(member_id).await?.into(),
+            role,
+        });
+    }
+    Ok(Json(members))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/members",
+    params(
+        ("id" = u32, Path, description = "Group ID to join")
+    ),
+    request_body = JoinWithCodeRequest,
+    responses(
+        (status = 204, description = "Caller joined the group"),
+        (status = 202, description = "The invitation requires approval; the owner has to accept this request", body = JoinRequest),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Invalid or expired invitation code", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Caller is already a member of the group or already asked to join, or the group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn join_group(
+    State(app_state): State<AppState>,
+    AuthUser { user_id, .. }: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<JoinWithCodeRequest>,
+) -> Result<Response, ApiError> {
+    match join_with_code(&app_state.db, group_id, &request.code, user_id).await? {
+        JoinOutcome::Joined => Ok(StatusCode::NO_CONTENT.into_response()),
+        JoinOutcome::Requested(request) => {
+            Ok((StatusCode::ACCEPTED, Json(request)).into_response())
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}/members/{user_id}",
+    params(
+        ("id" = u32, Path, description = "Group ID"),
+        ("user_id" = u32, Path, description = "Member to remove; the caller's own ID to leave the group"),
+        RemoveMemberQuery
+    ),
+    responses(
+        (status = 204, description = "Member removed; their expenses and payments stay in the group's history"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member, or removes someone else without a role that allows managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found or user is not a member", body = ErrorResponse),
+        (status = 409, description = "Member still has a balance, is the owner or is part of the settlement constraint, or the group is archived", body = ErrorResponse),
+        (status = 422, description = "Reassigning to someone who is not another member, or an expense has no exchange rate to the base currency", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn remove_group_member(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path((group_id, user_id)): Path<(u32, u32)>,
+    Query(query): Query<RemoveMemberQuery>,
+) -> Result<StatusCode, ApiError> {
+    remove_member(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        user_id,
+        query.reassign_to,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/owner",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = TransferOwnershipRequest,
+    responses(
+        (status = 200, description = "Ownership transferred; the previous owner stays a member", body = Group),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not the owner of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 422, description = "New owner is not a member or is a guest", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn transfer_group_ownership(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<Json<Group>, ApiError> {
+    let group = transfer_ownership(&app_state.db, group_id, auth.user_id, request.user_id).await?;
+    Ok(Json(group))
+}
+
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/members/{user_id}/role",
+    params(
+        ("id" = u32, Path, description = "Group ID"),
+        ("user_id" = u32, Path, description = "Member whose role changes")
+    ),
+    request_body = RoleRequest,
+    responses(
+        (status = 204, description = "Role changed"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not the owner of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found or user is not a member", body = ErrorResponse),
+        (status = 422, description = "Making someone owner or changing the owner's role; transfer ownership instead", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn set_member_role(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path((group_id, user_id)): Path<(u32, u32)>,
+    Json(request): Json<RoleRequest>,
+) -> Result<StatusCode, ApiError> {
+    change_member_role(&app_state.db, group_id, auth.user_id, user_id, request.role).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/expenses",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Expenses of the group", body = Vec<Expense>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_group_expenses(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<Expense>>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let expenses = app_state.db.get_expenses_by_group_id(group_id).await?;
+    Ok(Json(expenses))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/expenses",
+    params(
+        ("id" = u32, Path, description = "Group ID the expense belongs to")
+    ),
+    request_body = ExpenseAddRequest,
+    responses(
+        (status = 201, description = "Expense created", body = ExpenseDetail),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller, payer or a participant is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Invalid amount or split values that do not add up", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn create_group_expense(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(payload): Json<ExpenseAddRequest>,
+) -> Result<(StatusCode, Json<ExpenseDetail>), ApiError> {
+    // The group comes from the path; any `group_id` in the body is ignored.
+    let mut expense = payload.expense;
+    expense.group_id = group_id;
+    ensure_expense_members(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        expense.payer_id,
+        &payload.participants_ids,
+    )
+    .await?;
+    validate_amount(&app_state.db, &expense).await?;
+    let splits = build_splits(
+        &expense.amount,
+        &expense.split_type,
+        &payload.participants_ids,
+        &payload.split_values,
+    )?;
+    let expense_id = app_state
+        .db
+        .create_expense_with_splits(&expense, &splits)
+        .await?;
+    expense.id = Some(expense_id);
+    Ok((
+        StatusCode::CREATED,
+        Json(ExpenseDetail::new(expense, splits)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/balances",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Paid, owed and net amount of every member", body = Vec<UserBalance>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 422, description = "An expense has no exchange rate to the base currency of the group", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_group_balances(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<UserBalance>>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let balances = app_state.db.get_group_balances(group_id).await?;
+    Ok(Json(balances))
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/summary",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Group summary with the transfers still needed to settle up; nothing is stored", body = GroupSummary),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 422, description = "An expense has no exchange rate to the base currency of the group", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_group_summary(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<GroupSummary>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let summary = app_state.db.get_group_summary(group_id).await?;
+    Ok(Json(summary))
+}
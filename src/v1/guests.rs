@@ -0,0 +1,105 @@
+use axum::{extract::State, http::StatusCode};
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{ensure_group_member, ensure_permission, find_guest},
+    },
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    models::{
+        guest::{ClaimGuestRequest, Guest, GuestInvitation, GuestRequest},
+        role::Permission,
+    },
+    server::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/guests",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Guests of the group nobody has claimed yet", body = Vec<Guest>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_guests(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<Guest>>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let guests = app_state.db.get_group_guests(group_id).await?;
+    Ok(Json(guests))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/guests",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = GuestRequest,
+    responses(
+        (status = 201, description = "Guest added as a member; it can be used in expenses right away", body = GuestInvitation),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Empty name", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn create_guest(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<GuestRequest>,
+) -> Result<(StatusCode, Json<GuestInvitation>), ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::unprocessable("A guest needs a name"));
+    }
+    let invitation = app_state
+        .db
+        .create_guest(group_id, name, auth.user_id)
+        .await?;
+    Ok((StatusCode::CREATED, Json(invitation)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/guests/claim",
+    request_body = ClaimGuestRequest,
+    responses(
+        (status = 204, description = "Guest merged into the caller, who takes over its groups, expenses and transactions"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Unknown or already claimed token", body = ErrorResponse),
+        (status = 409, description = "Group of the guest is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn claim_guest(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Json(request): Json<ClaimGuestRequest>,
+) -> Result<StatusCode, ApiError> {
+    let guest = find_guest(&app_state.db, &request.claim_token).await?;
+    app_state
+        .db
+        .merge_guest(guest.user_id, auth.user_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,241 @@
+use axum::{extract::State, http::StatusCode};
+use chrono::{TimeDelta, Utc};
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{ensure_permission, ensure_role},
+    },
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    models::{
+        invitation::{
+            Invitation, InvitationRequest, JoinRequest, NewInvitation, DEFAULT_INVITATION_TTL,
+        },
+        role::Permission,
+    },
+    server::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/invitations",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Invitations of the group, newest first; codes are not shown", body = Vec<Invitation>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_invitations(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<Invitation>>, ApiError> {
+    ensure_role(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    let invitations = app_state.db.get_group_invitations(group_id).await?;
+    Ok(Json(invitations))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/invitations",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = InvitationRequest,
+    responses(
+        (status = 201, description = "Invitation created; the code is only returned here", body = NewInvitation),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Non-positive lifetime or number of uses", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn create_invitation(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<InvitationRequest>,
+) -> Result<(StatusCode, Json<NewInvitation>), ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    let ttl = match request.expires_in_hours {
+        Some(hours) if hours > 0 => TimeDelta::try_hours(hours)
+            .ok_or_else(|| ApiError::unprocessable("Invitation lifetime is too long"))?,
+        Some(_) => {
+            return Err(ApiError::unprocessable(
+                "Invitation lifetime must be positive",
+            ))
+        }
+        None => DEFAULT_INVITATION_TTL,
+    };
+    if request.max_uses == Some(0) {
+        return Err(ApiError::unprocessable(
+            "An invitation must allow at least one use",
+        ));
+    }
+    let invitation = app_state
+        .db
+        .create_invitation(
+            group_id,
+            auth.user_id,
+            Utc::now() + ttl,
+            request.max_uses,
+            request.require_approval,
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(invitation)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}/invitations/{invitation_id}",
+    params(
+        ("id" = u32, Path, description = "Group ID"),
+        ("invitation_id" = u32, Path, description = "Invitation ID")
+    ),
+    responses(
+        (status = 204, description = "Invitation revoked; its code no longer works"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group or invitation not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn revoke_invitation(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path((group_id, invitation_id)): Path<(u32, u32)>,
+) -> Result<StatusCode, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    app_state
+        .db
+        .revoke_invitation(group_id, invitation_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/join-requests",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Join requests waiting for approval, oldest first", body = Vec<JoinRequest>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_join_requests(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<JoinRequest>>, ApiError> {
+    ensure_role(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    let requests = app_state.db.get_pending_join_requests(group_id).await?;
+    Ok(Json(requests))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/join-requests/{request_id}/approve",
+    params(
+        ("id" = u32, Path, description = "Group ID"),
+        ("request_id" = u32, Path, description = "Join request ID")
+    ),
+    responses(
+        (status = 200, description = "Request approved; the user is now a member", body = JoinRequest),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found or no such pending request", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn approve_join_request(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path((group_id, request_id)): Path<(u32, u32)>,
+) -> Result<Json<JoinRequest>, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    let request = app_state
+        .db
+        .decide_join_request(group_id, request_id, true)
+        .await?;
+    Ok(Json(request))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/join-requests/{request_id}/reject",
+    params(
+        ("id" = u32, Path, description = "Group ID"),
+        ("request_id" = u32, Path, description = "Join request ID")
+    ),
+    responses(
+        (status = 200, description = "Request rejected", body = JoinRequest),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow managing members", body = ErrorResponse),
+        (status = 404, description = "Group not found or no such pending request", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn reject_join_request(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path((group_id, request_id)): Path<(u32, u32)>,
+) -> Result<Json<JoinRequest>, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ManageMembers,
+    )
+    .await?;
+    let request = app_state
+        .db
+        .decide_join_request(group_id, request_id, false)
+        .await?;
+    Ok(Json(request))
+}
@@ -0,0 +1,62 @@
+use axum::extract::State;
+
+use crate::{
+    auth::extractor::AuthUser,
+    error::{ApiError, ErrorResponse},
+    extract::Json,
+    models::{expenses::Expense, user::UserProfile},
+    server::AppState,
+    summary::UserPosition,
+};
+
+#[utoipa::path(
+    get,
+    path = "/me",
+    responses(
+        (status = 200, description = "Profile of the authenticated user", body = UserProfile),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_me(
+    State(app_state): State<AppState>,
+    AuthUser { user_id, .. }: AuthUser,
+) -> Result<Json<UserProfile>, ApiError> {
+    let user = app_state.db.get_user(user_id).await?;
+    Ok(Json(user.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/expenses",
+    responses(
+        (status = 200, description = "Expenses the authenticated user participates in", body = Vec<Expense>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_my_expenses(
+    State(app_state): State<AppState>,
+    AuthUser { user_id, .. }: AuthUser,
+) -> Result<Json<Vec<Expense>>, ApiError> {
+    let expenses_ids = app_state.db.get_all_user_expenses(user_id).await?;
+    let expenses = app_state.db.get_expenses_by_ids(expenses_ids).await?;
+    Ok(Json(expenses))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/balances",
+    responses(
+        (status = 200, description = "Balance of the authenticated user in each of their groups and overall", body = UserPosition),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_my_balances(
+    State(app_state): State<AppState>,
+    AuthUser { user_id, .. }: AuthUser,
+) -> Result<Json<UserPosition>, ApiError> {
+    let position = app_state.db.get_user_position(user_id).await?;
+    Ok(Json(position))
+}
@@ -0,0 +1,222 @@
+//! Resource-oriented REST API mounted under `/api/v1`.
+//!
+//! Resources are addressed by path and manipulated with HTTP verbs. The
+//! RPC-style routes under `/group`, `/expense` and `/summary` are kept for
+//! existing clients but answer with a `Deprecation` header pointing here.
+
+use axum::{
+    routing::{delete, get, post, put},
+    Router,
+};
+use utoipa::OpenApi;
+
+use crate::server::AppState;
+
+mod exchange_rates;
+use exchange_rates::__path_create_exchange_rate;
+use exchange_rates::__path_list_exchange_rates;
+use exchange_rates::{create_exchange_rate, list_exchange_rates};
+
+mod expenses;
+use expenses::__path_delete_expense;
+use expenses::__path_get_expense;
+use expenses::__path_update_expense;
+use expenses::{delete_expense, get_expense, update_expense};
+
+mod groups;
+use groups::__path_archive_group;
+use groups::__path_create_group;
+use groups::__path_create_group_expense;
+use groups::__path_delete_group;
+use groups::__path_get_group;
+use groups::__path_get_group_balances;
+use groups::__path_get_group_summary;
+use groups::__path_join_group;
+use groups::__path_list_group_expenses;
+use groups::__path_list_group_members;
+use groups::__path_list_groups;
+use groups::__path_remove_group_member;
+use groups::__path_reopen_group;
+use groups::__path_set_member_role;
+use groups::__path_set_settlement_strategy;
+use groups::__path_transfer_group_ownership;
+use groups::__path_update_group;
+use groups::{
+    archive_group, create_group, create_group_expense, delete_group, get_group, get_group_balances,
+    get_group_summary, join_group, list_group_expenses, list_group_members, list_groups,
+    remove_group_member, reopen_group, set_member_role, set_settlement_strategy,
+    transfer_group_ownership, update_group,
+};
+
+mod guests;
+use guests::__path_claim_guest;
+use guests::__path_create_guest;
+use guests::__path_list_guests;
+use guests::{claim_guest, create_guest, list_guests};
+
+mod invitations;
+use invitations::__path_approve_join_request;
+use invitations::__path_create_invitation;
+use invitations::__path_list_invitations;
+use invitations::__path_list_join_requests;
+use invitations::__path_reject_join_request;
+use invitations::__path_revoke_invitation;
+use invitations::{
+    approve_join_request, create_invitation, list_invitations, list_join_requests,
+    reject_join_request, revoke_invitation,
+};
+
+mod me;
+use me::__path_get_me;
+use me::__path_get_my_balances;
+use me::__path_get_my_expenses;
+use me::{get_me, get_my_balances, get_my_expenses};
+
+mod settlement_constraints;
+use settlement_constraints::__path_delete_settlement_constraint;
+use settlement_constraints::__path_get_settlement_constraint;
+use settlement_constraints::__path_set_settlement_constraint;
+use settlement_constraints::{
+    delete_settlement_constraint, get_settlement_constraint, set_settlement_constraint,
+};
+
+mod settlement_plans;
+use settlement_plans::__path_create_settlement_plan;
+use settlement_plans::__path_get_latest_settlement_plan;
+use settlement_plans::{create_settlement_plan, get_latest_settlement_plan};
+
+mod transactions;
+use transactions::__path_confirm_transaction;
+use transactions::__path_list_group_transactions;
+use transactions::__path_record_payment;
+use transactions::{confirm_transaction, list_group_transactions, record_payment};
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_me,
+    get_my_expenses,
+    get_my_balances,
+    list_groups,
+    create_group,
+    get_group,
+    update_group,
+    delete_group,
+    archive_group,
+    reopen_group,
+    set_settlement_strategy,
+    get_settlement_constraint,
+    set_settlement_constraint,
+    delete_settlement_constraint,
+    transfer_group_ownership,
+    list_group_members,
+    join_group,
+    remove_group_member,
+    set_member_role,
+    list_invitations,
+    create_invitation,
+    revoke_invitation,
+    list_join_requests,
+    approve_join_request,
+    reject_join_request,
+    list_guests,
+    create_guest,
+    claim_guest,
+    list_group_expenses,
+    create_group_expense,
+    get_group_balances,
+    get_group_summary,
+    list_exchange_rates,
+    create_exchange_rate,
+    create_settlement_plan,
+    get_latest_settlement_plan,
+    list_group_transactions,
+    record_payment,
+    confirm_transaction,
+    get_expense,
+    update_expense,
+    delete_expense,
+))]
+pub struct V1Api;
+
+pub fn router(app_state: AppState) -> Router {
+    Router::new()
+        .route("/me", get(get_me))
+        .route("/me/expenses", get(get_my_expenses))
+        .route("/me/balances", get(get_my_balances))
+        .route("/groups", get(list_groups).post(create_group))
+        .route(
+            "/groups/{id}",
+            get(get_group).patch(update_group).delete(delete_group),
+        )
+        .route(
+            "/groups/{id}/archive",
+            post(archive_group).delete(reopen_group),
+        )
+        .route(
+            "/groups/{id}/settlement-strategy",
+            put(set_settlement_strategy),
+        )
+        .route(
+            "/groups/{id}/settlement-constraint",
+            get(get_settlement_constraint)
+                .put(set_settlement_constraint)
+                .delete(delete_settlement_constraint),
+        )
+        .route("/groups/{id}/owner", put(transfer_group_ownership))
+        .route(
+            "/groups/{id}/members",
+            get(list_group_members).post(join_group),
+        )
+        .route(
+            "/groups/{id}/members/{user_id}",
+            delete(remove_group_member),
+        )
+        .route("/groups/{id}/members/{user_id}/role", put(set_member_role))
+        .route(
+            "/groups/{id}/invitations",
+            get(list_invitations).post(create_invitation),
+        )
+        .route(
+            "/groups/{id}/invitations/{invitation_id}",
+            delete(revoke_invitation),
+        )
+        .route("/groups/{id}/join-requests", get(list_join_requests))
+        .route(
+            "/groups/{id}/join-requests/{request_id}/approve",
+            post(approve_join_request),
+        )
+        .route(
+            "/groups/{id}/join-requests/{request_id}/reject",
+            post(reject_join_request),
+        )
+        .route("/groups/{id}/guests", get(list_guests).post(create_guest))
+        .route("/guests/claim", post(claim_guest))
+        .route(
+            "/groups/{id}/expenses",
+            get(list_group_expenses).post(create_group_expense),
+        )
+        .route("/groups/{id}/balances", get(get_group_balances))
+        .route("/groups/{id}/summary", get(get_group_summary))
+        .route(
+            "/groups/{id}/exchange-rates",
+            get(list_exchange_rates).post(create_exchange_rate),
+        )
+        .route(
+            "/groups/{id}/settlement-plans",
+            post(create_settlement_plan),
+        )
+        .route(
+            "/groups/{id}/settlement-plans/latest",
+            get(get_latest_settlement_plan),
+        )
+        .route("/groups/{id}/transactions", get(list_group_transactions))
+        .route("/groups/{id}/payments", post(record_payment))
+        .route("/transactions/{id}/confirm", post(confirm_transaction))
+        .route(
+            "/expenses/{id}",
+            get(get_expense)
+                .patch(update_expense)
+                .delete(delete_expense),
+        )
+        .with_state(app_state)
+}
@@ -0,0 +1,142 @@
+use axum::{extract::State, http::StatusCode};
+use serde_json::json;
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{ensure_group_member, ensure_permission},
+    },
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    models::{role::Permission, settlement_constraint::SettlementConstraint},
+    server::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/settlement-constraint",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Constraint on who may pay whom when the group settles up", body = SettlementConstraint),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found or it has no constraint", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_settlement_constraint(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<SettlementConstraint>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let constraint = app_state
+        .db
+        .get_settlement_constraint(group_id)
+        .await?
+        .ok_or_else(|| {
+            ApiError::NotFound(format!("Group {} has no settlement constraint", group_id))
+        })?;
+    Ok(Json(constraint))
+}
+
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/settlement-constraint",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = SettlementConstraint,
+    responses(
+        (status = 200, description = "Constraint replaced; the summary and new settlement plans route payments accordingly", body = SettlementConstraint),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow changing the settings", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "Constraint names non-members or leaves members unable to settle", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn set_settlement_constraint(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(constraint): Json<SettlementConstraint>,
+) -> Result<Json<SettlementConstraint>, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ChangeSettings,
+    )
+    .await?;
+    let group = app_state.db.get_group(group_id).await?;
+    let members = app_state.db.get_group_members(group_id).await?;
+    if let Some(user_id) = constraint
+        .user_ids()
+        .into_iter()
+        .find(|user_id| !members.contains(user_id))
+    {
+        return Err(ApiError::unprocessable(format!(
+            "User {} is not a member of group {}",
+            user_id, group_id
+        )));
+    }
+    if let SettlementConstraint::AllowedPairs { pairs }
+    | SettlementConstraint::ForbiddenPairs { pairs } = &constraint
+    {
+        if pairs
+            .iter()
+            .any(|pair| pair.first_user_id == pair.second_user_id)
+        {
+            return Err(ApiError::unprocessable(
+                "A pair needs two different members",
+            ));
+        }
+    }
+    let unreachable = constraint.unreachable_members(group.owner_id, &members);
+    if !unreachable.is_empty() {
+        return Err(ApiError::Unprocessable {
+            message: "Some members could not settle with the rest of the group".to_string(),
+            details: Some(json!({ "unreachable_user_ids": unreachable })),
+        });
+    }
+    app_state
+        .db
+        .set_settlement_constraint(group_id, &constraint)
+        .await?;
+    Ok(Json(constraint))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}/settlement-constraint",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 204, description = "Constraint removed; anyone may pay anyone again"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller's role does not allow changing the settings", body = ErrorResponse),
+        (status = 404, description = "Group not found or it has no constraint", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn delete_settlement_constraint(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<StatusCode, ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::ChangeSettings,
+    )
+    .await?;
+    app_state.db.delete_settlement_constraint(group_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,77 @@
+use axum::{extract::State, http::StatusCode};
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{ensure_group_member, ensure_permission},
+    },
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    models::{role::Permission, settlement_plan::SettlementPlan},
+    server::AppState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/settlement-plans",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 201, description = "New plan version proposing transfers for the outstanding balances; pending transfers of the previous plan are superseded", body = SettlementPlan),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group or only a viewer", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse),
+        (status = 409, description = "Group is archived", body = ErrorResponse),
+        (status = 422, description = "An expense has no exchange rate to the base currency of the group", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn create_settlement_plan(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<(StatusCode, Json<SettlementPlan>), ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::AddExpenses,
+    )
+    .await?;
+    let plan = app_state
+        .db
+        .generate_settlement_plan(group_id, auth.user_id)
+        .await?;
+    Ok((StatusCode::CREATED, Json(plan)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/settlement-plans/latest",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Most recent settlement plan of the group", body = SettlementPlan),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found or no plan generated yet", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn get_latest_settlement_plan(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<SettlementPlan>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    match app_state.db.get_latest_settlement_plan(group_id).await {
+        Ok(plan) => Ok(Json(plan)),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::NotFound(format!(
+            "No settlement plan for group {}",
+            group_id
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
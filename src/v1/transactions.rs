@@ -0,0 +1,202 @@
+use axum::{extract::State, http::StatusCode};
+use chrono::Utc;
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        policy::{
+            ensure_group_member, ensure_not_archived, ensure_permission,
+            ensure_transaction_receiver, find_group,
+        },
+    },
+    db::Database,
+    error::{ApiError, ErrorResponse},
+    extract::{Json, Path},
+    models::{
+        expenses::{PaymentRequest, Status, Transaction},
+        role::Permission,
+    },
+    server::AppState,
+};
+
+/// Loads a transaction, or 404 if it does not exist.
+async fn find_transaction(db: &Database, transaction_id: u32) -> Result<Transaction, ApiError> {
+    match db.get_transaction(transaction_id).await {
+        Ok(transaction) => Ok(transaction),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::NotFound(format!(
+            "Transaction {} not found",
+            transaction_id
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/transactions",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Planned transfers and recorded payments of the group, oldest first", body = Vec<Transaction>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not a member of the group", body = ErrorResponse),
+        (status = 404, description = "Group not found", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn list_group_transactions(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+) -> Result<Json<Vec<Transaction>>, ApiError> {
+    ensure_group_member(&app_state.db, group_id, auth.user_id).await?;
+    let transactions = app_state.db.get_group_transactions(group_id).await?;
+    Ok(Json(transactions))
+}
+
+#[utoipa::path(
+    post,
+    path = "/groups/{id}/payments",
+    params(
+        ("id" = u32, Path, description = "Group ID")
+    ),
+    request_body = PaymentRequest,
+    responses(
+        (status = 201, description = "Payment recorded, waiting for the receiver to confirm it", body = Transaction),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller or receiver is not a member of the group, or the caller is only a viewer", body = ErrorResponse),
+        (status = 404, description = "Group or planned transfer not found", body = ErrorResponse),
+        (status = 409, description = "Planned transfer is no longer pending, or the group is archived", body = ErrorResponse),
+        (status = 422, description = "Invalid amount, or a payment that does not match the planned transfer", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn record_payment(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(group_id): Path<u32>,
+    Json(request): Json<PaymentRequest>,
+) -> Result<(StatusCode, Json<Transaction>), ApiError> {
+    ensure_permission(
+        &app_state.db,
+        group_id,
+        auth.user_id,
+        Permission::AddExpenses,
+    )
+    .await?;
+    let group = find_group(&app_state.db, group_id).await?;
+    let members = app_state.db.get_group_members(group_id).await?;
+    if !members.contains(&request.receiver_id) {
+        return Err(ApiError::Forbidden(format!(
+            "Receiver {} is not a member of group {}",
+            request.receiver_id, group_id
+        )));
+    }
+    if request.receiver_id == auth.user_id {
+        return Err(ApiError::unprocessable("You cannot pay yourself"));
+    }
+    if request.amount.minor_units <= 0 {
+        return Err(ApiError::unprocessable("Amount must be positive"));
+    }
+    if request.amount.currency != group.base_currency {
+        return Err(ApiError::unprocessable(format!(
+            "Payments in group {} must be in {}",
+            group_id, group.base_currency
+        )));
+    }
+
+    let mut plan_id = None;
+    if let Some(transaction_id) = request.transaction_id {
+        let planned = find_transaction(&app_state.db, transaction_id).await?;
+        if planned.group_id != group_id {
+            return Err(ApiError::NotFound(format!(
+                "Transaction {} not found",
+                transaction_id
+            )));
+        }
+        if planned.status != Status::Pending {
+            return Err(ApiError::Conflict(format!(
+                "Transaction {} is no longer pending",
+                transaction_id
+            )));
+        }
+        if planned.payer_id != auth.user_id || planned.receiver_id != request.receiver_id {
+            return Err(ApiError::unprocessable(format!(
+                "Transaction {} is not a transfer from you to user {}",
+                transaction_id, request.receiver_id
+            )));
+        }
+        if request.amount.minor_units > planned.amount.minor_units {
+            return Err(ApiError::unprocessable(format!(
+                "Payment exceeds the {} left on transaction {}",
+                planned.amount, transaction_id
+            )));
+        }
+        plan_id = planned.plan_id;
+    }
+
+    let payment = Transaction {
+        id: None,
+        payer_id: auth.user_id,
+        receiver_id: request.receiver_id,
+        amount: request.amount,
+        date: Utc::now().naive_utc().to_string(),
+        status: Status::Paid,
+        group_id,
+        plan_id,
+    };
+    let id = match app_state
+        .db
+        .record_payment(&payment, request.transaction_id)
+        .await
+    {
+        Ok(id) => id,
+        // The planned transfer changed since it was checked above
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(ApiError::Conflict(
+                "The planned transfer changed, please retry".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let payment = app_state.db.get_transaction(id).await?;
+    Ok((StatusCode::CREATED, Json(payment)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/transactions/{id}/confirm",
+    params(
+        ("id" = u32, Path, description = "Transaction ID")
+    ),
+    responses(
+        (status = 200, description = "Receipt confirmed; the transaction is completed and counts towards the balances", body = Transaction),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not the receiver of the transaction", body = ErrorResponse),
+        (status = 404, description = "Transaction not found", body = ErrorResponse),
+        (status = 409, description = "Transaction is already completed or superseded, or the group is archived", body = ErrorResponse)
+    ),
+    security(("api_key" = []), ("bearer_auth" = []))
+)]
+pub async fn confirm_transaction(
+    State(app_state): State<AppState>,
+    auth: AuthUser,
+    Path(transaction_id): Path<u32>,
+) -> Result<Json<Transaction>, ApiError> {
+    let transaction = find_transaction(&app_state.db, transaction_id).await?;
+    ensure_transaction_receiver(&transaction, auth.user_id)?;
+    ensure_not_archived(&find_group(&app_state.db, transaction.group_id).await?)?;
+    match app_state.db.complete_transaction(transaction_id).await {
+        Ok(()) => {}
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(ApiError::Conflict(format!(
+                "Transaction {} is already {}",
+                transaction_id, transaction.status
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let transaction = app_state.db.get_transaction(transaction_id).await?;
+    Ok(Json(transaction))
+}
@@ -1,8 +1,9 @@
 use chrono::Utc;
 use reqwest::Client;
 use trip_split::models::{
-    expenses::{Expense, Status, Transaction},
-    group::Group,
+    expenses::{Expense, SplitType, Status, Transaction},
+    group::{Group, SettlementStrategy},
+    money::Money,
     user::User,
 };
 
@@ -34,14 +35,39 @@ impl Sdk {
         };
         let resp = self
             .client
-            .post(&format!("{}/auth/register", self.base_url))
+            .post(format!("{}/auth/register", self.base_url))
             .json(&user)
             .send()
             .await?;
         resp.text().await
     }
 
+    pub async fn login(&self, email: &str, password: &str) -> reqwest::Result<reqwest::Response> {
+        self.client
+            .post(format!("{}/auth/login", self.base_url))
+            .json(&serde_json::json!({ "email": email, "password": password }))
+            .send()
+            .await
+    }
+
+    pub async fn logout(&self, api_token: &str) -> reqwest::Result<reqwest::Response> {
+        self.client
+            .post(format!("{}/auth/logout", self.base_url))
+            .header("todo_apikey", api_token)
+            .send()
+            .await
+    }
+
+    pub async fn get_sessions(&self, api_token: &str) -> reqwest::Result<reqwest::Response> {
+        self.client
+            .get(format!("{}/auth/sessions", self.base_url))
+            .header("todo_apikey", api_token)
+            .send()
+            .await
+    }
+
     // Group endpoints
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_group(
         &self,
         name: &str,
@@ -60,10 +86,13 @@ impl Sdk {
             group_end_date: end_date,
             description: description.to_string(),
             location: location.to_string(),
+            base_currency: "EUR".to_string(),
+            settlement_strategy: SettlementStrategy::MinimizeTransfers,
+            archived_at: None,
         };
         let resp = self
             .client
-            .post(&format!("{}/group/create_group", self.base_url))
+            .post(format!("{}/group/create_group", self.base_url))
             .header("todo_apikey", api_token)
             .json(&group)
             .send()
@@ -71,10 +100,10 @@ impl Sdk {
         resp.text().await
     }
 
-    pub async fn get_groups(&self, owner: u32, api_token: &str) -> reqwest::Result<Vec<Group>> {
+    pub async fn get_groups(&self, _owner: u32, api_token: &str) -> reqwest::Result<Vec<Group>> {
         let resp = self
             .client
-            .post(&format!("{}/group/get_user_owned_groups", self.base_url))
+            .post(format!("{}/group/get_user_owned_groups", self.base_url))
             .header("todo_apikey", api_token)
             .send()
             .await?;
@@ -89,7 +118,7 @@ impl Sdk {
     ) -> reqwest::Result<()> {
         let resp = self
             .client
-            .post(&format!("{}/group/{}/add_users", self.base_url, group_id))
+            .post(format!("{}/group/{}/add_users", self.base_url, group_id))
             .header("todo_apikey", api_token)
             .json(&user_ids)
             .send()
@@ -105,7 +134,7 @@ impl Sdk {
     ) -> reqwest::Result<Vec<u32>> {
         let resp = self
             .client
-            .get(&format!("{}/group/{}/members", self.base_url, group_id))
+            .get(format!("{}/group/{}/members", self.base_url, group_id))
             .header("todo_apikey", api_token)
             .send()
             .await?;
@@ -116,7 +145,7 @@ impl Sdk {
     pub async fn _create_expense(
         &self,
         description: &str,
-        amount: f64,
+        amount: Money,
         payer_id: u32,
         group_id: u32,
         participant_ids: Vec<u32>,
@@ -129,11 +158,12 @@ impl Sdk {
             payer_id,
             group_id,
             date: Utc::now().to_string(),
+            split_type: SplitType::Equal,
         };
 
         let resp = self
             .client
-            .post(&format!("{}/expense/create", self.base_url))
+            .post(format!("{}/expense/create", self.base_url))
             .header("todo_apikey", api_token)
             .json(&(expense, participant_ids))
             .send()
@@ -148,7 +178,7 @@ impl Sdk {
     ) -> reqwest::Result<Vec<Expense>> {
         let resp = self
             .client
-            .get(&format!("{}/expense/group/{}", self.base_url, group_id))
+            .get(format!("{}/expense/group/{}", self.base_url, group_id))
             .header("todo_apikey", api_token)
             .send()
             .await?;
@@ -158,7 +188,7 @@ impl Sdk {
     pub async fn _get_user_expenses(&self, api_token: &str) -> reqwest::Result<Vec<Expense>> {
         let resp = self
             .client
-            .get(&format!("{}/expense/user", self.base_url))
+            .get(format!("{}/expense/user", self.base_url))
             .header("todo_apikey", api_token)
             .send()
             .await?;
@@ -170,7 +200,7 @@ impl Sdk {
         &self,
         payer_id: u32,
         receiver_id: u32,
-        amount: f64,
+        amount: Money,
         api_token: &str,
         group_id: u32,
     ) -> reqwest::Result<String> {
@@ -182,11 +212,12 @@ impl Sdk {
             date: Utc::now().to_string(),
             status: Status::Pending,
             group_id,
+            plan_id: None,
         };
 
         let resp = self
             .client
-            .post(&format!("{}/transaction/create", self.base_url))
+            .post(format!("{}/transaction/create", self.base_url))
             .header("todo_apikey", api_token)
             .json(&transaction)
             .send()
@@ -201,7 +232,7 @@ impl Sdk {
     ) -> reqwest::Result<Transaction> {
         let resp = self
             .client
-            .get(&format!("{}/transaction/{}", self.base_url, transaction_id))
+            .get(format!("{}/transaction/{}", self.base_url, transaction_id))
             .header("todo_apikey", api_token)
             .send()
             .await?;
@@ -214,7 +245,7 @@ impl Sdk {
     ) -> reqwest::Result<Vec<Transaction>> {
         let resp = self
             .client
-            .get(&format!("{}/transaction/payer", self.base_url))
+            .get(format!("{}/transaction/payer", self.base_url))
             .header("todo_apikey", api_token)
             .send()
             .await?;
@@ -4,26 +4,29 @@ use chrono::Utc;
 use portpicker::pick_unused_port;
 use sdk::Sdk;
 use tokio::net::TcpListener;
-use trip_split::server;
+use trip_split::{
+    config::Config,
+    server::{self, StartupError},
+};
 
 async fn spawn_server_on_random_port() -> u16 {
     let port = pick_unused_port().expect("No ports free");
     let addr = format!("127.0.0.1:{}", port);
     let listener = TcpListener::bind(&addr).await.expect("bind");
-    let app = server::app();
+    let app = server::app(Config {
+        database_url: "sqlite::memory:".to_string(),
+        ..Config::default()
+    })
+    .await
+    .expect("app");
     tokio::spawn(async move {
-        serve(listener, app.await).await.unwrap();
+        serve(listener, app).await.unwrap();
     });
     // Optionally, poll until server is ready
     port
 }
 
-#[tokio::test]
-async fn test_create_and_get_group() {
-    let port = spawn_server_on_random_port().await;
-    let base_url = format!("http://127.0.0.1:{}", port);
-    let sdk = Sdk::new(&base_url);
-    // Wait for server to be ready
+async fn wait_until_ready(base_url: &str) {
     let mut tries = 0;
     loop {
         if let Ok(resp) = reqwest::get(format!("{}/", base_url)).await {
@@ -37,13 +40,81 @@ async fn test_create_and_get_group() {
         }
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
     }
+}
+
+/// Starts a server with its own in-memory database and waits until it answers.
+async fn spawn_app() -> (Sdk, String) {
+    let port = spawn_server_on_random_port().await;
+    let base_url = format!("http://127.0.0.1:{}", port);
+    wait_until_ready(&base_url).await;
+    (Sdk::new(&base_url), base_url)
+}
+
+#[tokio::test]
+async fn test_startup_errors() {
+    let missing_dir = Config {
+        database_url: "sqlite:/nonexistent/trip_split.db".to_string(),
+        ..Config::default()
+    };
+    assert!(matches!(
+        server::app(missing_dir).await,
+        Err(StartupError::Connect(_))
+    ));
+
+    let rates_file =
+        std::env::temp_dir().join(format!("trip_split_rates_{}.csv", std::process::id()));
+    std::fs::write(&rates_file, "not,a,rate\n").unwrap();
+    let bad_rates = Config {
+        database_url: "sqlite::memory:".to_string(),
+        exchange_rates_file: rates_file.clone(),
+        ..Config::default()
+    };
+    let result = server::app(bad_rates).await;
+    std::fs::remove_file(&rates_file).unwrap();
+    assert!(matches!(result, Err(StartupError::ExchangeRates { .. })));
+
+    let taken = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let busy_port = Config {
+        database_url: "sqlite::memory:".to_string(),
+        bind_address: taken.local_addr().unwrap(),
+        ..Config::default()
+    };
+    assert!(matches!(
+        server::start(busy_port).await,
+        Err(StartupError::Bind { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_create_and_get_group() {
+    let (sdk, _) = spawn_app().await;
     // Create a user
     let api_token = sdk
         .create_user("IntegrationTestUser", "integration@test.com", "password123")
         .await
         .expect("create user");
     println!("API token: {}", api_token);
-    assert!(api_token.contains("xjX3pNg9FOkRQMsVX+UjgRir5atax3WhD0tERHPMzQA="));
+    assert_eq!(api_token.len(), 43);
+
+    // Logging in mints a fresh session token instead of recomputing the same one
+    let resp = sdk
+        .login("integration@test.com", "password123")
+        .await
+        .expect("login");
+    assert!(resp.status().is_success());
+    let login_token = resp.text().await.expect("login token");
+    assert_ne!(login_token, api_token);
+
+    let resp = sdk
+        .login("integration@test.com", "wrong password")
+        .await
+        .expect("login");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let resp = sdk
+        .login("nobody@test.com", "password123")
+        .await
+        .expect("login");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
 
     // Create a group
     let resp = sdk
@@ -71,3 +142,1824 @@ async fn test_create_and_get_group() {
     assert_eq!(group.name, "IntegrationTestGroup");
     assert_eq!(group.owner_id, 1);
 }
+
+#[tokio::test]
+async fn test_sessions_and_logout() {
+    let (sdk, _) = spawn_app().await;
+    let first_token = sdk
+        .create_user("SessionTestUser", "sessions@test.com", "password123")
+        .await
+        .expect("create user");
+    let second_token = sdk
+        .login("sessions@test.com", "password123")
+        .await
+        .expect("login")
+        .text()
+        .await
+        .expect("login token");
+
+    let sessions: Vec<serde_json::Value> = sdk
+        .get_sessions(&second_token)
+        .await
+        .expect("sessions")
+        .json()
+        .await
+        .expect("sessions json");
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[1]["current"], true);
+
+    let resp = sdk.logout(&first_token).await.expect("logout");
+    assert!(resp.status().is_success());
+    let resp = sdk.get_sessions(&first_token).await.expect("sessions");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let resp = sdk.get_sessions(&second_token).await.expect("sessions");
+    assert!(resp.status().is_success());
+}
+
+#[tokio::test]
+async fn test_bearer_authentication() {
+    let (sdk, base_url) = spawn_app().await;
+    let api_token = sdk
+        .create_user("BearerTestUser", "bearer@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{}/group/get_user_joined_groups", base_url))
+        .bearer_auth(&api_token)
+        .send()
+        .await
+        .expect("joined groups");
+    assert!(resp.status().is_success());
+
+    let resp = client
+        .get(format!("{}/summary/group/1", base_url))
+        .send()
+        .await
+        .expect("summary");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = resp.json().await.expect("error json");
+    assert_eq!(body["code"], "unauthorized");
+
+    let resp = client
+        .post(format!("{}/group/get_user_joined_groups", base_url))
+        .bearer_auth("not a token")
+        .send()
+        .await
+        .expect("joined groups");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_group_authorization() {
+    let (sdk, base_url) = spawn_app().await;
+    let owner_token = sdk
+        .create_user("OwnerTestUser", "owner@test.com", "password123")
+        .await
+        .expect("create user");
+    let outsider_token = sdk
+        .create_user("OutsiderTestUser", "outsider@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let owner_id = v1_user_id(&api, &owner_token).await;
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&owner_token)
+        .json(&serde_json::json!({
+            "name": "AuthorizationGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+
+    let resp = client
+        .get(format!("{}/summary/group/{}", base_url, group_id))
+        .bearer_auth(&owner_token)
+        .send()
+        .await
+        .expect("summary");
+    assert!(resp.status().is_success());
+
+    let resp = client
+        .get(format!("{}/summary/group/{}", base_url, group_id))
+        .bearer_auth(&outsider_token)
+        .send()
+        .await
+        .expect("summary");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .post(format!("{}/expense/get_group_expenses", base_url))
+        .bearer_auth(&outsider_token)
+        .json(&serde_json::json!({ "group_id": 999 }))
+        .send()
+        .await
+        .expect("group expenses");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let resp = client
+        .post(format!("{}/expense/get_all_user_expenses", base_url))
+        .bearer_auth(&outsider_token)
+        .json(&serde_json::json!({ "user_id": owner_id }))
+        .send()
+        .await
+        .expect("user expenses");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_error_responses() {
+    let (sdk, base_url) = spawn_app().await;
+    sdk.create_user("IntegrationTestUser", "integration@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{}/auth/register", base_url))
+        .json(&serde_json::json!({
+            "name": "Duplicate",
+            "email": "integration@test.com",
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .expect("register");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+    let body: serde_json::Value = resp.json().await.expect("error json");
+    assert_eq!(body["code"], "conflict");
+    assert!(body.get("details").is_some());
+
+    // Bodies the JSON extractor rejects get the same error format
+    let resp = client
+        .post(format!("{}/auth/register", base_url))
+        .header("content-type", "application/json")
+        .body("{\"name\": \"Broken\",")
+        .send()
+        .await
+        .expect("register");
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = resp.json().await.expect("error json");
+    assert_eq!(body["code"], "bad_request");
+
+    let resp = client
+        .post(format!("{}/auth/register", base_url))
+        .json(&serde_json::json!({ "name": "No email" }))
+        .send()
+        .await
+        .expect("register");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = resp.json().await.expect("error json");
+    assert_eq!(body["code"], "unprocessable_entity");
+}
+
+#[tokio::test]
+async fn test_v1_api() {
+    let (sdk, base_url) = spawn_app().await;
+    let token = sdk
+        .create_user("V1TestUser", "v1@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+
+    let me: serde_json::Value = client
+        .get(format!("{}/me", api))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("me")
+        .json()
+        .await
+        .expect("me json");
+    assert_eq!(me["email"], "v1@test.com");
+    assert!(me.get("password").is_none());
+    let user_id = me["id"].as_u64().unwrap();
+
+    let resp = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "name": "V1Group",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    assert!(resp.headers().get("deprecation").is_none());
+    let group: serde_json::Value = resp.json().await.expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "expense": {
+                "description": "Dinner",
+                "amount": { "minor_units": 2000, "currency": "EUR" },
+                "payer_id": user_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [user_id]
+        }))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let expense: serde_json::Value = resp.json().await.expect("expense json");
+    assert_eq!(expense["expense"]["group_id"], group_id);
+    let expense_id = expense["expense"]["id"].as_u64().unwrap();
+
+    let resp = client
+        .get(format!("{}/expenses/{}", api, expense_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get expense");
+    assert!(resp.status().is_success());
+
+    let groups: Vec<serde_json::Value> = client
+        .get(format!("{}/groups?owned=true", api))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("groups")
+        .json()
+        .await
+        .expect("groups json");
+    assert_eq!(groups.len(), 1);
+
+    let balances: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/balances", api, group_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("balances")
+        .json()
+        .await
+        .expect("balances json");
+    assert_eq!(balances.len(), 1);
+    assert_eq!(balances[0]["net_balance"]["minor_units"], 0);
+
+    let resp = client
+        .get(format!("{}/groups/999", api))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("missing group");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // Legacy routes keep working but announce their successor
+    let resp = client
+        .post(format!("{}/group/get_user_owned_groups", base_url))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("legacy groups");
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers()["deprecation"], "true");
+    assert!(resp.headers()["link"].to_str().unwrap().contains("/api/v1"));
+
+    let doc: serde_json::Value = client
+        .get(format!("{}/api/openapi.json", base_url))
+        .send()
+        .await
+        .expect("openapi")
+        .json()
+        .await
+        .expect("openapi json");
+    assert!(doc["paths"]["/api/v1/groups/{id}/balances"]["get"].is_object());
+    assert_eq!(
+        doc["paths"]["/group/get_user_owned_groups"]["post"]["deprecated"],
+        true
+    );
+}
+
+#[tokio::test]
+async fn test_expense_editing() {
+    let (sdk, base_url) = spawn_app().await;
+    let owner_token = sdk
+        .create_user("EditOwner", "edit-owner@test.com", "password123")
+        .await
+        .expect("create user");
+    let member_token = sdk
+        .create_user("EditMember", "edit-member@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let owner_id = v1_user_id(&api, &owner_token).await;
+    let member_id = v1_user_id(&api, &member_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&owner_token)
+        .json(&serde_json::json!({
+            "name": "EditGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    let resp = v1_join_group(&api, group_id, &owner_token, &member_token).await;
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+
+    let expense: serde_json::Value = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&member_token)
+        .json(&serde_json::json!({
+            "expense": {
+                "description": "Taxi",
+                "amount": { "minor_units": 4000, "currency": "EUR" },
+                "payer_id": member_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [owner_id, member_id]
+        }))
+        .send()
+        .await
+        .expect("create expense")
+        .json()
+        .await
+        .expect("expense json");
+    let expense_id = expense["expense"]["id"].as_u64().unwrap();
+    assert_eq!(v1_summary_total(&api, group_id, &owner_token).await, 4000);
+
+    // The group owner may edit an expense someone else paid
+    let resp = client
+        .patch(format!("{}/expenses/{}", api, expense_id))
+        .bearer_auth(&owner_token)
+        .json(&serde_json::json!({ "amount": { "minor_units": 3000, "currency": "EUR" }, "participants_ids": [owner_id] }))
+        .send()
+        .await
+        .expect("update expense");
+    assert!(resp.status().is_success());
+    let updated: serde_json::Value = resp.json().await.expect("expense json");
+    assert_eq!(updated["expense"]["description"], "Taxi");
+    assert_eq!(updated["participants_ids"], serde_json::json!([owner_id]));
+    assert_eq!(v1_summary_total(&api, group_id, &owner_token).await, 3000);
+
+    let resp = client
+        .patch(format!("{}/expenses/{}", api, expense_id))
+        .bearer_auth(&member_token)
+        .json(&serde_json::json!({ "participants_ids": [] }))
+        .send()
+        .await
+        .expect("update expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let outsider_token = sdk
+        .create_user("EditOutsider", "edit-outsider@test.com", "password123")
+        .await
+        .expect("create user");
+    let resp = client
+        .delete(format!("{}/expenses/{}", api, expense_id))
+        .bearer_auth(&outsider_token)
+        .send()
+        .await
+        .expect("delete expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .delete(format!("{}/expenses/{}", api, expense_id))
+        .bearer_auth(&member_token)
+        .send()
+        .await
+        .expect("delete expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(v1_summary_total(&api, group_id, &owner_token).await, 0);
+    let resp = client
+        .get(format!("{}/expenses/{}", api, expense_id))
+        .bearer_auth(&member_token)
+        .send()
+        .await
+        .expect("get expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+async fn v1_user_id(api: &str, token: &str) -> u64 {
+    let me: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}/me", api))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("me")
+        .json()
+        .await
+        .expect("me json");
+    me["id"].as_u64().unwrap()
+}
+
+/// Has the owner invite the user into the group and joins with the code.
+async fn v1_join_group(
+    api: &str,
+    group_id: u64,
+    owner_token: &str,
+    token: &str,
+) -> reqwest::Response {
+    let client = reqwest::Client::new();
+    let invitation: serde_json::Value = client
+        .post(format!("{}/groups/{}/invitations", api, group_id))
+        .bearer_auth(owner_token)
+        .json(&serde_json::json!({ "max_uses": 1 }))
+        .send()
+        .await
+        .expect("create invitation")
+        .json()
+        .await
+        .expect("invitation json");
+    client
+        .post(format!("{}/groups/{}/members", api, group_id))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "code": invitation["code"] }))
+        .send()
+        .await
+        .expect("join group")
+}
+
+async fn v1_summary_total(api: &str, group_id: u64, token: &str) -> i64 {
+    let summary: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("summary")
+        .json()
+        .await
+        .expect("summary json");
+    summary["total_expenses"]["minor_units"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn test_split_modes() {
+    let (sdk, base_url) = spawn_app().await;
+    let anna_token = sdk
+        .create_user("Anna", "anna@test.com", "password123")
+        .await
+        .expect("create user");
+    let ben_token = sdk
+        .create_user("Ben", "ben@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let anna_id = v1_user_id(&api, &anna_token).await;
+    let ben_id = v1_user_id(&api, &ben_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&anna_token)
+        .json(&serde_json::json!({
+            "name": "SplitGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    v1_join_group(&api, group_id, &anna_token, &ben_token).await;
+
+    let exact_expense = |values: [i64; 2]| {
+        serde_json::json!({
+            "expense": {
+                "description": "Groceries",
+                "amount": { "minor_units": 10000, "currency": "EUR" },
+                "payer_id": anna_id,
+                "date": "2024-01-01",
+                "split_type": "exact"
+            },
+            "participants_ids": [anna_id, ben_id],
+            "split_values": [
+                { "user_id": anna_id, "value": values[0] },
+                { "user_id": ben_id, "value": values[1] }
+            ]
+        })
+    };
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&anna_token)
+        .json(&exact_expense([4000, 5000]))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&anna_token)
+        .json(&exact_expense([4000, 6000]))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let expense: serde_json::Value = resp.json().await.expect("expense json");
+    assert_eq!(expense["split_values"][1]["value"], 6000);
+
+    let balances: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/balances", api, group_id))
+        .bearer_auth(&ben_token)
+        .send()
+        .await
+        .expect("balances")
+        .json()
+        .await
+        .expect("balances json");
+    let ben = balances
+        .iter()
+        .find(|balance| balance["user_id"] == ben_id)
+        .expect("ben balance");
+    assert_eq!(ben["net_balance"]["minor_units"], -6000);
+}
+
+#[tokio::test]
+async fn test_multi_currency() {
+    let (sdk, base_url) = spawn_app().await;
+    let token = sdk
+        .create_user("Carla", "carla@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let carla_id = v1_user_id(&api, &token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "name": "Roadtrip",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location",
+            "base_currency": "CHF"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    assert_eq!(group["base_currency"], "CHF");
+    let group_id = group["id"].as_u64().unwrap();
+
+    let usd_expense = serde_json::json!({
+        "expense": {
+            "description": "Fuel",
+            "amount": { "minor_units": 5000, "currency": "USD" },
+            "payer_id": carla_id,
+            "date": "2024-03-10"
+        },
+        "participants_ids": [carla_id]
+    });
+    // Without a rate the expense cannot be converted to CHF
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&token)
+        .json(&usd_expense)
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    // A rate beyond the cap would overflow later conversions
+    let resp = client
+        .post(format!("{}/groups/{}/exchange-rates", api, group_id))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "from_currency": "USD",
+            "rate_micros": i64::MAX,
+            "effective_date": "2024-03-01"
+        }))
+        .send()
+        .await
+        .expect("create rate");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let resp = client
+        .post(format!("{}/groups/{}/exchange-rates", api, group_id))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "from_currency": "USD",
+            "rate_micros": 880_000,
+            "effective_date": "2024-03-01"
+        }))
+        .send()
+        .await
+        .expect("create rate");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let rate: serde_json::Value = resp.json().await.expect("rate json");
+    assert_eq!(rate["to_currency"], "CHF");
+
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&token)
+        .json(&usd_expense)
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+
+    let summary: serde_json::Value = client
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("summary")
+        .json()
+        .await
+        .expect("summary json");
+    assert_eq!(summary["base_currency"], "CHF");
+    assert_eq!(summary["total_expenses"]["minor_units"], 4400);
+    let expense = &summary["expenses"][0];
+    assert_eq!(expense["original_amount"]["currency"], "USD");
+    assert_eq!(expense["original_amount"]["minor_units"], 5000);
+    assert_eq!(expense["converted_amount"]["minor_units"], 4400);
+    assert_eq!(expense["rate_micros"], 880_000);
+}
+
+#[tokio::test]
+async fn test_settlement_plans() {
+    let (sdk, base_url) = spawn_app().await;
+    let dana_token = sdk
+        .create_user("Dana", "dana@test.com", "password123")
+        .await
+        .expect("create user");
+    let eli_token = sdk
+        .create_user("Eli", "eli@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let dana_id = v1_user_id(&api, &dana_token).await;
+    let eli_id = v1_user_id(&api, &eli_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&dana_token)
+        .json(&serde_json::json!({
+            "name": "PlanGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    v1_join_group(&api, group_id, &dana_token, &eli_token).await;
+    let resp = client
+        .get(format!(
+            "{}/groups/{}/settlement-plans/latest",
+            api, group_id
+        ))
+        .bearer_auth(&dana_token)
+        .send()
+        .await
+        .expect("latest plan");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&dana_token)
+        .json(&serde_json::json!({
+            "expense": {
+                "description": "Tickets",
+                "amount": { "minor_units": 5000, "currency": "EUR" },
+                "payer_id": dana_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [dana_id, eli_id]
+        }))
+        .send()
+        .await
+        .expect("create expense");
+
+    for _ in 0..2 {
+        let summary: serde_json::Value = client
+            .get(format!("{}/groups/{}/summary", api, group_id))
+            .bearer_auth(&eli_token)
+            .send()
+            .await
+            .expect("summary")
+            .json()
+            .await
+            .expect("summary json");
+        assert!(summary["transactions_needed"][0]["id"].is_null());
+    }
+
+    for version in 1..=2 {
+        let resp = client
+            .post(format!("{}/groups/{}/settlement-plans", api, group_id))
+            .bearer_auth(&eli_token)
+            .send()
+            .await
+            .expect("create plan");
+        assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+        let plan: serde_json::Value = resp.json().await.expect("plan json");
+        assert_eq!(plan["version"], version);
+    }
+    let plan: serde_json::Value = client
+        .get(format!(
+            "{}/groups/{}/settlement-plans/latest",
+            api, group_id
+        ))
+        .bearer_auth(&dana_token)
+        .send()
+        .await
+        .expect("latest plan")
+        .json()
+        .await
+        .expect("plan json");
+    assert_eq!(plan["version"], 2);
+    let transactions = plan["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0]["payer_id"], eli_id);
+    assert_eq!(transactions[0]["amount"]["minor_units"], 2500);
+    assert_eq!(transactions[0]["status"], "Pending");
+
+    // Eli pays 10.00 of the 25.00 and Dana confirms it
+    let planned_id = transactions[0]["id"].as_u64().unwrap();
+    let pay = |minor_units: i64| {
+        serde_json::json!({
+            "receiver_id": dana_id,
+            "amount": { "minor_units": minor_units, "currency": "EUR" },
+            "transaction_id": planned_id
+        })
+    };
+    let resp = client
+        .post(format!("{}/groups/{}/payments", api, group_id))
+        .bearer_auth(&eli_token)
+        .json(&pay(3000))
+        .send()
+        .await
+        .expect("record payment");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let resp = client
+        .post(format!("{}/groups/{}/payments", api, group_id))
+        .bearer_auth(&eli_token)
+        .json(&pay(1000))
+        .send()
+        .await
+        .expect("record payment");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let payment: serde_json::Value = resp.json().await.expect("payment json");
+    assert_eq!(payment["status"], "Paid");
+    let payment_id = payment["id"].as_u64().unwrap();
+
+    let confirm = |token: &str| {
+        client
+            .post(format!("{}/transactions/{}/confirm", api, payment_id))
+            .bearer_auth(token.to_string())
+            .send()
+    };
+    let resp = confirm(&eli_token).await.expect("confirm");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = confirm(&dana_token).await.expect("confirm");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = confirm(&dana_token).await.expect("confirm");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+
+    let summary: serde_json::Value = client
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(&eli_token)
+        .send()
+        .await
+        .expect("summary")
+        .json()
+        .await
+        .expect("summary json");
+    assert_eq!(
+        summary["transactions_needed"][0]["amount"]["minor_units"],
+        1500
+    );
+    let balances: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/balances", api, group_id))
+        .bearer_auth(&eli_token)
+        .send()
+        .await
+        .expect("balances")
+        .json()
+        .await
+        .expect("balances json");
+    let eli = balances
+        .iter()
+        .find(|balance| balance["user_id"] == eli_id)
+        .expect("eli balance");
+    assert_eq!(eli["payments_sent"]["minor_units"], 1000);
+    assert_eq!(eli["net_balance"]["minor_units"], -1500);
+}
+
+#[tokio::test]
+async fn test_balance_endpoints() {
+    let (sdk, base_url) = spawn_app().await;
+    let fay_token = sdk
+        .create_user("Fay", "fay@test.com", "password123")
+        .await
+        .expect("create user");
+    let gus_token = sdk
+        .create_user("Gus", "gus@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let fay_id = v1_user_id(&api, &fay_token).await;
+    let gus_id = v1_user_id(&api, &gus_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&fay_token)
+        .json(&serde_json::json!({
+            "name": "BalanceGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    v1_join_group(&api, group_id, &fay_token, &gus_token).await;
+    client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&fay_token)
+        .json(&serde_json::json!({
+            "expense": {
+                "description": "Museum",
+                "amount": { "minor_units": 4000, "currency": "EUR" },
+                "payer_id": fay_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [fay_id, gus_id]
+        }))
+        .send()
+        .await
+        .expect("create expense");
+
+    let resp = client
+        .get(format!("{}/summary/group/{}/balances", base_url, group_id))
+        .bearer_auth(&gus_token)
+        .send()
+        .await
+        .expect("balances");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let balances: Vec<serde_json::Value> = resp.json().await.expect("balances json");
+    assert_eq!(balances.len(), 2);
+    let gus = balances
+        .iter()
+        .find(|balance| balance["user_id"] == gus_id)
+        .expect("gus balance");
+    assert_eq!(gus["total_owed"]["minor_units"], 2000);
+    assert_eq!(gus["net_balance"]["minor_units"], -2000);
+
+    for url in [
+        format!("{}/summary/me", base_url),
+        format!("{}/me/balances", api),
+    ] {
+        let position: serde_json::Value = client
+            .get(url)
+            .bearer_auth(&fay_token)
+            .send()
+            .await
+            .expect("position")
+            .json()
+            .await
+            .expect("position json");
+        assert_eq!(position["user_id"], fay_id);
+        assert_eq!(position["groups"][0]["group_name"], "BalanceGroup");
+        assert_eq!(position["net_balances"][0]["minor_units"], 2000);
+    }
+
+    assert_eq!(group["settlement_strategy"], "minimize_transfers");
+    let strategy_url = format!("{}/groups/{}/settlement-strategy", api, group_id);
+    let resp = client
+        .put(&strategy_url)
+        .bearer_auth(&gus_token)
+        .json(&serde_json::json!({ "settlement_strategy": "direct" }))
+        .send()
+        .await
+        .expect("set strategy");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .put(&strategy_url)
+        .bearer_auth(&fay_token)
+        .json(&serde_json::json!({ "settlement_strategy": "direct" }))
+        .send()
+        .await
+        .expect("set strategy");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let group: serde_json::Value = resp.json().await.expect("group json");
+    assert_eq!(group["settlement_strategy"], "direct");
+    let summary: serde_json::Value = client
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(&gus_token)
+        .send()
+        .await
+        .expect("summary")
+        .json()
+        .await
+        .expect("summary json");
+    let transfers = summary["transactions_needed"].as_array().unwrap();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0]["from_user_id"], gus_id);
+    assert_eq!(transfers[0]["amount"]["minor_units"], 2000);
+
+    let constraint_url = format!("{}/groups/{}/settlement-constraint", api, group_id);
+    let resp = client
+        .put(&constraint_url)
+        .bearer_auth(&fay_token)
+        .json(&serde_json::json!({
+            "kind": "forbidden_pairs",
+            "pairs": [{ "first_user_id": fay_id, "second_user_id": gus_id }]
+        }))
+        .send()
+        .await
+        .expect("set constraint");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = resp.json().await.expect("error json");
+    assert_eq!(body["details"]["unreachable_user_ids"][0], gus_id);
+    let resp = client
+        .put(&constraint_url)
+        .bearer_auth(&fay_token)
+        .json(&serde_json::json!({ "kind": "through_owner" }))
+        .send()
+        .await
+        .expect("set constraint");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let constraint: serde_json::Value = client
+        .get(&constraint_url)
+        .bearer_auth(&gus_token)
+        .send()
+        .await
+        .expect("get constraint")
+        .json()
+        .await
+        .expect("constraint json");
+    assert_eq!(constraint["kind"], "through_owner");
+    let resp = client
+        .delete(&constraint_url)
+        .bearer_auth(&fay_token)
+        .send()
+        .await
+        .expect("delete constraint");
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    let resp = client
+        .get(&constraint_url)
+        .bearer_auth(&gus_token)
+        .send()
+        .await
+        .expect("get constraint");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_guests() {
+    let (sdk, base_url) = spawn_app().await;
+    let ida_token = sdk
+        .create_user("Ida", "ida@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let ida_id = v1_user_id(&api, &ida_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&ida_token)
+        .json(&serde_json::json!({
+            "name": "GuestGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    let resp = client
+        .post(format!("{}/groups/{}/guests", api, group_id))
+        .bearer_auth(&ida_token)
+        .json(&serde_json::json!({ "name": "Jon" }))
+        .send()
+        .await
+        .expect("create guest");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let invitation: serde_json::Value = resp.json().await.expect("guest json");
+    let guest_id = invitation["guest"]["user_id"].as_u64().unwrap();
+    let claim_token = invitation["claim_token"].as_str().unwrap().to_string();
+
+    let members: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/members", api, group_id))
+        .bearer_auth(&ida_token)
+        .send()
+        .await
+        .expect("members")
+        .json()
+        .await
+        .expect("members json");
+    let guest = members
+        .iter()
+        .find(|member| member["id"] == guest_id)
+        .expect("guest member");
+    assert_eq!(guest["guest"], true);
+    assert_eq!(guest["email"], "");
+
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&ida_token)
+        .json(&serde_json::json!({
+            "expense": {
+                "description": "Groceries",
+                "amount": { "minor_units": 3000, "currency": "EUR" },
+                "payer_id": ida_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [ida_id, guest_id]
+        }))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+
+    // Registering through the claim link takes over the guest's debt
+    let resp = client
+        .post(format!("{}/auth/register?claim={}", base_url, claim_token))
+        .json(&serde_json::json!({
+            "name": "Jon",
+            "email": "jon@test.com",
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .expect("register");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let jon_token = resp.text().await.expect("token");
+    let position: serde_json::Value = client
+        .get(format!("{}/me/balances", api))
+        .bearer_auth(&jon_token)
+        .send()
+        .await
+        .expect("position")
+        .json()
+        .await
+        .expect("position json");
+    assert_eq!(position["groups"][0]["group_id"], group_id);
+    assert_eq!(position["net_balances"][0]["minor_units"], -1500);
+
+    let resp = client
+        .post(format!("{}/guests/claim", api))
+        .bearer_auth(&jon_token)
+        .json(&serde_json::json!({ "claim_token": claim_token }))
+        .send()
+        .await
+        .expect("claim guest");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_invitations() {
+    let (sdk, base_url) = spawn_app().await;
+    let kim_token = sdk
+        .create_user("Kim", "kim@test.com", "password123")
+        .await
+        .expect("create user");
+    let lou_token = sdk
+        .create_user("Lou", "lou@test.com", "password123")
+        .await
+        .expect("create user");
+    let max_token = sdk
+        .create_user("Max", "max@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let lou_id = v1_user_id(&api, &lou_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&kim_token)
+        .json(&serde_json::json!({
+            "name": "InviteGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    let members_url = format!("{}/groups/{}/members", api, group_id);
+    let invitations_url = format!("{}/groups/{}/invitations", api, group_id);
+
+    // Knowing the group id is no longer enough
+    let resp = client
+        .post(&members_url)
+        .bearer_auth(&lou_token)
+        .json(&serde_json::json!({ "code": "guessed" }))
+        .send()
+        .await
+        .expect("join group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .post(format!("{}/group/join_group", base_url))
+        .bearer_auth(&lou_token)
+        .json(&serde_json::json!({ "group_id": group_id, "code": "guessed" }))
+        .send()
+        .await
+        .expect("legacy join group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .post(&invitations_url)
+        .bearer_auth(&lou_token)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .expect("create invitation");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .post(&invitations_url)
+        .bearer_auth(&kim_token)
+        .json(&serde_json::json!({ "require_approval": true, "expires_in_hours": 2 }))
+        .send()
+        .await
+        .expect("create invitation");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let invitation: serde_json::Value = resp.json().await.expect("invitation json");
+    let code = invitation["code"].as_str().unwrap().to_string();
+    let invitation_id = invitation["invitation"]["id"].as_u64().unwrap();
+
+    let resp = client
+        .post(&members_url)
+        .bearer_auth(&lou_token)
+        .json(&serde_json::json!({ "code": code }))
+        .send()
+        .await
+        .expect("join group");
+    assert_eq!(resp.status(), reqwest::StatusCode::ACCEPTED);
+    let request: serde_json::Value = resp.json().await.expect("join request json");
+    assert_eq!(request["status"], "pending");
+    let resp = client
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(&lou_token)
+        .send()
+        .await
+        .expect("summary");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let requests: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/join-requests", api, group_id))
+        .bearer_auth(&kim_token)
+        .send()
+        .await
+        .expect("join requests")
+        .json()
+        .await
+        .expect("join requests json");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["user_id"], lou_id);
+    let resp = client
+        .post(format!(
+            "{}/groups/{}/join-requests/{}/approve",
+            api, group_id, requests[0]["id"]
+        ))
+        .bearer_auth(&kim_token)
+        .send()
+        .await
+        .expect("approve");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(&lou_token)
+        .send()
+        .await
+        .expect("summary");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    // A revoked code stops working
+    let resp = client
+        .delete(format!("{}/{}", invitations_url, invitation_id))
+        .bearer_auth(&kim_token)
+        .send()
+        .await
+        .expect("revoke invitation");
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    let resp = client
+        .post(&members_url)
+        .bearer_auth(&max_token)
+        .json(&serde_json::json!({ "code": code }))
+        .send()
+        .await
+        .expect("join group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let invitations: Vec<serde_json::Value> = client
+        .get(&invitations_url)
+        .bearer_auth(&kim_token)
+        .send()
+        .await
+        .expect("invitations")
+        .json()
+        .await
+        .expect("invitations json");
+    assert_eq!(invitations[0]["revoked"], true);
+    assert_eq!(invitations[0]["uses"], 1);
+}
+
+#[tokio::test]
+async fn test_member_management() {
+    let (sdk, base_url) = spawn_app().await;
+    let ned_token = sdk
+        .create_user("Ned", "ned@test.com", "password123")
+        .await
+        .expect("create user");
+    let ola_token = sdk
+        .create_user("Ola", "ola@test.com", "password123")
+        .await
+        .expect("create user");
+    let pia_token = sdk
+        .create_user("Pia", "pia@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let ned_id = v1_user_id(&api, &ned_token).await;
+    let ola_id = v1_user_id(&api, &ola_token).await;
+    let pia_id = v1_user_id(&api, &pia_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&ned_token)
+        .json(&serde_json::json!({
+            "name": "MemberGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    for token in [&ola_token, &pia_token] {
+        let resp = v1_join_group(&api, group_id, &ned_token, token).await;
+        assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    }
+    let member_url = |user_id: u64| format!("{}/groups/{}/members/{}", api, group_id, user_id);
+
+    // Ola owes Ned 10.00
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&ned_token)
+        .json(&serde_json::json!({
+            "expense": {
+                "description": "Museum",
+                "amount": { "minor_units": 2000, "currency": "EUR" },
+                "payer_id": ned_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [ned_id, ola_id]
+        }))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+
+    let resp = client
+        .delete(member_url(ola_id))
+        .bearer_auth(&ola_token)
+        .send()
+        .await
+        .expect("leave group");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+    let resp = client
+        .delete(member_url(ola_id))
+        .bearer_auth(&pia_token)
+        .send()
+        .await
+        .expect("remove member");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .delete(member_url(ned_id))
+        .bearer_auth(&ned_token)
+        .send()
+        .await
+        .expect("owner leaves");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+
+    // Pia has no balance and may leave on her own
+    let resp = client
+        .delete(member_url(pia_id))
+        .bearer_auth(&pia_token)
+        .send()
+        .await
+        .expect("leave group");
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    let resp = client
+        .get(format!("{}/groups/{}", api, group_id))
+        .bearer_auth(&pia_token)
+        .send()
+        .await
+        .expect("get group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // Ownership goes to Ola, then Ned can leave once his balance moved to her
+    let resp = client
+        .put(format!("{}/groups/{}/owner", api, group_id))
+        .bearer_auth(&ola_token)
+        .json(&serde_json::json!({ "user_id": ola_id }))
+        .send()
+        .await
+        .expect("transfer ownership");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .put(format!("{}/groups/{}/owner", api, group_id))
+        .bearer_auth(&ned_token)
+        .json(&serde_json::json!({ "user_id": ola_id }))
+        .send()
+        .await
+        .expect("transfer ownership");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let group: serde_json::Value = resp.json().await.expect("group json");
+    assert_eq!(group["owner_id"].as_u64(), Some(ola_id));
+
+    let resp = client
+        .delete(format!("{}?reassign_to={}", member_url(ned_id), ola_id))
+        .bearer_auth(&ola_token)
+        .send()
+        .await
+        .expect("remove member");
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    let members: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/members", api, group_id))
+        .bearer_auth(&ola_token)
+        .send()
+        .await
+        .expect("list members")
+        .json()
+        .await
+        .expect("members json");
+    assert_eq!(members.len(), 1);
+    let balances: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/balances", api, group_id))
+        .bearer_auth(&ola_token)
+        .send()
+        .await
+        .expect("balances")
+        .json()
+        .await
+        .expect("balances json");
+    assert!(balances
+        .iter()
+        .all(|balance| balance["net_balance"]["minor_units"] == 0));
+}
+
+#[tokio::test]
+async fn test_roles() {
+    let (sdk, base_url) = spawn_app().await;
+    let quinn_token = sdk
+        .create_user("Quinn", "quinn@test.com", "password123")
+        .await
+        .expect("create user");
+    let rae_token = sdk
+        .create_user("Rae", "rae@test.com", "password123")
+        .await
+        .expect("create user");
+    let sid_token = sdk
+        .create_user("Sid", "sid@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let quinn_id = v1_user_id(&api, &quinn_token).await;
+    let rae_id = v1_user_id(&api, &rae_token).await;
+    let sid_id = v1_user_id(&api, &sid_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&quinn_token)
+        .json(&serde_json::json!({
+            "name": "RoleGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    for token in [&rae_token, &sid_token] {
+        v1_join_group(&api, group_id, &quinn_token, token).await;
+    }
+    let role_url = |user_id: u64| format!("{}/groups/{}/members/{}/role", api, group_id, user_id);
+    let expense = |payer_id: u64| {
+        serde_json::json!({
+            "expense": {
+                "description": "Snacks",
+                "amount": { "minor_units": 900, "currency": "EUR" },
+                "payer_id": payer_id,
+                "date": "2024-01-01"
+            },
+            "participants_ids": [quinn_id, rae_id, sid_id]
+        })
+    };
+
+    let detail: serde_json::Value = client
+        .get(format!("{}/groups/{}", api, group_id))
+        .bearer_auth(&rae_token)
+        .send()
+        .await
+        .expect("get group")
+        .json()
+        .await
+        .expect("group json");
+    assert_eq!(detail["name"], "RoleGroup");
+    assert_eq!(detail["role"], "member");
+    assert_eq!(detail["permissions"], serde_json::json!(["add_expenses"]));
+
+    // A member only adds what they paid themselves
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&rae_token)
+        .json(&expense(quinn_id))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .post(format!("{}/groups/{}/invitations", api, group_id))
+        .bearer_auth(&rae_token)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .expect("create invitation");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .put(role_url(rae_id))
+        .bearer_auth(&rae_token)
+        .json(&serde_json::json!({ "role": "admin" }))
+        .send()
+        .await
+        .expect("set role");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    for (user_id, role) in [(rae_id, "admin"), (sid_id, "viewer")] {
+        let resp = client
+            .put(role_url(user_id))
+            .bearer_auth(&quinn_token)
+            .json(&serde_json::json!({ "role": role }))
+            .send()
+            .await
+            .expect("set role");
+        assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    }
+    let resp = client
+        .put(role_url(rae_id))
+        .bearer_auth(&quinn_token)
+        .json(&serde_json::json!({ "role": "owner" }))
+        .send()
+        .await
+        .expect("set role");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    let members: Vec<serde_json::Value> = client
+        .get(format!("{}/groups/{}/members", api, group_id))
+        .bearer_auth(&sid_token)
+        .send()
+        .await
+        .expect("list members")
+        .json()
+        .await
+        .expect("members json");
+    let roles: Vec<&str> = members
+        .iter()
+        .map(|member| member["role"].as_str().unwrap())
+        .collect();
+    assert_eq!(roles, ["owner", "admin", "viewer"]);
+
+    // Admins act for others and change settings; viewers only look
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&rae_token)
+        .json(&expense(quinn_id))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let resp = client
+        .put(format!("{}/groups/{}/settlement-strategy", api, group_id))
+        .bearer_auth(&rae_token)
+        .json(&serde_json::json!({ "settlement_strategy": "direct" }))
+        .send()
+        .await
+        .expect("set strategy");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .post(format!("{}/groups/{}/expenses", api, group_id))
+        .bearer_auth(&sid_token)
+        .json(&expense(sid_id))
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .get(format!("{}/groups/{}/summary", api, group_id))
+        .bearer_auth(&sid_token)
+        .send()
+        .await
+        .expect("summary");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_group_lifecycle() {
+    let (sdk, base_url) = spawn_app().await;
+    let uma_token = sdk
+        .create_user("Uma", "uma@test.com", "password123")
+        .await
+        .expect("create user");
+    let val_token = sdk
+        .create_user("Val", "val@test.com", "password123")
+        .await
+        .expect("create user");
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1", base_url);
+    let uma_id = v1_user_id(&api, &uma_token).await;
+    let val_id = v1_user_id(&api, &val_token).await;
+
+    let group: serde_json::Value = client
+        .post(format!("{}/groups", api))
+        .bearer_auth(&uma_token)
+        .json(&serde_json::json!({
+            "name": "LifecycleGroup",
+            "group_start_date": Utc::now(),
+            "group_end_date": Utc::now(),
+            "description": "Description",
+            "location": "Location"
+        }))
+        .send()
+        .await
+        .expect("create group")
+        .json()
+        .await
+        .expect("group json");
+    let group_id = group["id"].as_u64().unwrap();
+    v1_join_group(&api, group_id, &uma_token, &val_token).await;
+    let group_url = format!("{}/groups/{}", api, group_id);
+    let archive_url = format!("{}/archive", group_url);
+    let expense = serde_json::json!({
+        "expense": {
+            "description": "Tickets",
+            "amount": { "minor_units": 1000, "currency": "EUR" },
+            "payer_id": uma_id,
+            "date": "2024-01-01"
+        },
+        "participants_ids": [uma_id, val_id]
+    });
+    let listed = |include_archived: bool| {
+        let client = client.clone();
+        let url = format!("{}/groups?include_archived={}", api, include_archived);
+        let token = uma_token.clone();
+        async move {
+            let groups: Vec<serde_json::Value> = client
+                .get(url)
+                .bearer_auth(token)
+                .send()
+                .await
+                .expect("list groups")
+                .json()
+                .await
+                .expect("groups json");
+            groups
+                .iter()
+                .any(|group| group["id"].as_u64() == Some(group_id))
+        }
+    };
+
+    let resp = client
+        .patch(&group_url)
+        .bearer_auth(&uma_token)
+        .json(&serde_json::json!({
+            "name": "Festival",
+            "group_start_date": "2024-06-10T00:00:00Z",
+            "group_end_date": "2024-06-01T00:00:00Z"
+        }))
+        .send()
+        .await
+        .expect("update group");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let resp = client
+        .patch(&group_url)
+        .bearer_auth(&val_token)
+        .json(&serde_json::json!({ "name": "Festival" }))
+        .send()
+        .await
+        .expect("update group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let updated: serde_json::Value = client
+        .patch(&group_url)
+        .bearer_auth(&uma_token)
+        .json(&serde_json::json!({ "name": "Festival", "location": "Ghent" }))
+        .send()
+        .await
+        .expect("update group")
+        .json()
+        .await
+        .expect("group json");
+    assert_eq!(updated["name"], "Festival");
+    assert_eq!(updated["location"], "Ghent");
+    assert_eq!(updated["description"], "Description");
+
+    // Only a settled group can be archived
+    let resp = client
+        .post(format!("{}/expenses", group_url))
+        .bearer_auth(&uma_token)
+        .json(&expense)
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+    let resp = client
+        .post(&archive_url)
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("archive group");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+    let payment: serde_json::Value = client
+        .post(format!("{}/payments", group_url))
+        .bearer_auth(&val_token)
+        .json(&serde_json::json!({
+            "receiver_id": uma_id,
+            "amount": { "minor_units": 500, "currency": "EUR" }
+        }))
+        .send()
+        .await
+        .expect("record payment")
+        .json()
+        .await
+        .expect("payment json");
+    let resp = client
+        .post(format!(
+            "{}/transactions/{}/confirm",
+            api,
+            payment["id"].as_u64().unwrap()
+        ))
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("confirm payment");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let resp = client
+        .post(&archive_url)
+        .bearer_auth(&val_token)
+        .send()
+        .await
+        .expect("archive group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let archived: serde_json::Value = client
+        .post(&archive_url)
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("archive group")
+        .json()
+        .await
+        .expect("group json");
+    assert!(archived["archived_at"].is_string());
+    assert!(!listed(false).await);
+    assert!(listed(true).await);
+
+    // Archived groups are read-only until reopened
+    let resp = client
+        .post(format!("{}/expenses", group_url))
+        .bearer_auth(&uma_token)
+        .json(&expense)
+        .send()
+        .await
+        .expect("create expense");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+    let resp = client
+        .get(format!("{}/summary", group_url))
+        .bearer_auth(&val_token)
+        .send()
+        .await
+        .expect("summary");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .delete(&archive_url)
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("reopen group");
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert!(listed(false).await);
+    let resp = client
+        .delete(&archive_url)
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("reopen group");
+    assert_eq!(resp.status(), reqwest::StatusCode::CONFLICT);
+
+    let resp = client
+        .delete(&group_url)
+        .bearer_auth(&val_token)
+        .send()
+        .await
+        .expect("delete group");
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let resp = client
+        .delete(&group_url)
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("delete group");
+    assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    let resp = client
+        .get(&group_url)
+        .bearer_auth(&uma_token)
+        .send()
+        .await
+        .expect("get group");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_request_ids() {
+    let (_, base_url) = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!("{}/ok", base_url))
+        .send()
+        .await
+        .expect("ok");
+    let generated = resp
+        .headers()
+        .get("x-request-id")
+        .expect("generated request id")
+        .to_str()
+        .unwrap();
+    assert_eq!(generated.len(), 36);
+
+    // Errors carry the id too, and one sent by the client is kept
+    let resp = client
+        .get(format!("{}/api/v1/groups/1", base_url))
+        .header("x-request-id", "client-chosen-id")
+        .send()
+        .await
+        .expect("get group");
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers()["x-request-id"], "client-chosen-id");
+}